@@ -1,3 +1,4 @@
+use crate::core::metadata::LoudnessSeries;
 use serde::{Deserialize, Serialize};
 
 pub type FloSample = f32;
@@ -15,6 +16,67 @@ pub struct LoudnessMetrics {
     pub sample_peak_dbfs: f64,
 }
 
+/// ReplayGain 2.0 reference loudness, track and album gains bring audio to it
+pub const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+impl LoudnessMetrics {
+    /// ReplayGain 2.0 gain in dB, -18 LUFS minus the integrated loudness
+    pub fn replay_gain_db(&self) -> f64 {
+        REPLAYGAIN_REFERENCE_LUFS - self.integrated_lufs
+    }
+}
+
+/// Loudness of several tracks played as one, for album gain
+///
+/// Every track adds its 400 ms blocks and the gates run over all of them
+/// together, so the album loudness is not the average of the track loudnesses:
+/// a quiet interlude counts for as long as it lasts.
+#[derive(Debug, Clone)]
+pub struct AlbumLoudness {
+    block_energies: Vec<f64>,
+    true_peak_dbtp: f64,
+    sample_peak_dbfs: f64,
+}
+
+impl Default for AlbumLoudness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlbumLoudness {
+    pub fn new() -> Self {
+        Self {
+            block_energies: Vec::new(),
+            true_peak_dbtp: -150.0,
+            sample_peak_dbfs: -150.0,
+        }
+    }
+
+    /// Measure a track and add it to the album, returns the track's own metrics
+    pub fn add_track(
+        &mut self,
+        samples: &[FloSample],
+        channels: u8,
+        sample_rate: u32,
+    ) -> LoudnessMetrics {
+        let (metrics, block_energies) = measure(samples, channels, sample_rate);
+        self.block_energies.extend(block_energies);
+        self.true_peak_dbtp = self.true_peak_dbtp.max(metrics.true_peak_dbtp);
+        self.sample_peak_dbfs = self.sample_peak_dbfs.max(metrics.sample_peak_dbfs);
+        metrics
+    }
+
+    /// Metrics of every track added so far, as one programme
+    pub fn metrics(&self) -> LoudnessMetrics {
+        gated_metrics(
+            &self.block_energies,
+            self.true_peak_dbtp,
+            self.sample_peak_dbfs,
+        )
+    }
+}
+
 #[derive(Clone)]
 struct Biquad {
     b0: f64,
@@ -141,18 +203,9 @@ fn compute_true_peak(samples: &[FloSample], channels: u8, sample_rate: u32) -> f
 
     let mut max_peak = 0.0f64;
 
+    let len = samples.len() / channels as usize;
     for ch in 0..channels as usize {
-        let channel_samples: Vec<f64> = samples
-            .iter()
-            .skip(ch)
-            .step_by(channels as usize)
-            .map(|&s| s as f64)
-            .collect();
-
-        let len = channel_samples.len();
-        if len == 0 {
-            continue;
-        }
+        let sample_at = |i: usize| samples[i * channels as usize + ch] as f64;
 
         for i in 0..len {
             for sub in 0..factor {
@@ -162,7 +215,7 @@ fn compute_true_peak(samples: &[FloSample], channels: u8, sample_rate: u32) -> f
                 for (k, &h) in coeffs.iter().enumerate() {
                     let src = pos - center + k as f64;
                     if src >= 0.0 && src < len as f64 {
-                        acc += channel_samples[src as usize] * h;
+                        acc += sample_at(src as usize) * h;
                     }
                 }
 
@@ -184,90 +237,255 @@ pub fn compute_ebu_r128_loudness(
     channels: u8,
     sample_rate: u32,
 ) -> LoudnessMetrics {
-    if samples.is_empty() || channels == 0 {
-        return LoudnessMetrics {
-            integrated_lufs: -23.0,
-            loudness_range_lu: 0.0,
-            true_peak_dbtp: -150.0,
-            sample_peak_dbfs: -150.0,
-        };
-    }
+    measure(samples, channels, sample_rate).0
+}
 
-    let sr = sample_rate as f64;
-    let hop_100ms = (sr * 0.1).round() as usize; // 100 ms hop
-    let block_400ms = hop_100ms * 4; // 400 ms window
+/// Momentary (400 ms) and short-term (3 s) loudness over time
+///
+/// Each point is the loudness of the window ending where the next point starts,
+/// so point `i` covers up to `(i + 1) / points_per_second` seconds. Windows are
+/// shorter at the very start. Digital silence is -150 LUFS.
+pub fn compute_loudness_series(
+    samples: &[FloSample],
+    channels: u8,
+    sample_rate: u32,
+    points_per_second: u32,
+) -> LoudnessSeries {
+    let points_per_second = points_per_second.max(1);
+    let windows = SeriesWindows::new(samples, channels, sample_rate, points_per_second);
+    let scan = Scan::new(samples, channels, sample_rate, windows.bounds());
+    windows.series(&scan)
+}
 
-    // De‑interleave
-    let frames = samples.len() / channels as usize;
-    let mut per_channel: Vec<Vec<f64>> = Vec::with_capacity(channels as usize);
-    for ch in 0..channels as usize {
-        let mut v = Vec::with_capacity(frames);
-        for i in 0..frames {
-            v.push(samples[i * channels as usize + ch] as f64);
+/// Metrics and loudness series from one K-weighting pass
+///
+/// The same as `compute_ebu_r128_loudness` and `compute_loudness_series`
+/// together, for encoders that store both.
+pub fn compute_loudness(
+    samples: &[FloSample],
+    channels: u8,
+    sample_rate: u32,
+    points_per_second: u32,
+) -> (LoudnessMetrics, LoudnessSeries) {
+    let points_per_second = points_per_second.max(1);
+    let windows = SeriesWindows::new(samples, channels, sample_rate, points_per_second);
+    let mut bounds = windows.bounds();
+    bounds.extend(block_bounds(samples, channels, sample_rate));
+
+    let scan = Scan::new(samples, channels, sample_rate, bounds);
+    let (metrics, _) = scan_metrics(&scan, samples, channels, sample_rate);
+    (metrics, windows.series(&scan))
+}
+
+/// Internal: K-weighted energy, summed across channels, between consecutive
+/// sample-frame positions
+///
+/// Only the positions windows start or end at are kept, so memory follows
+/// the number of windows and not the length of the signal.
+struct Scan {
+    bounds: Vec<usize>,
+    /// `energy[i]` covers `bounds[i]..bounds[i + 1]`
+    energy: Vec<f64>,
+    peak: f64,
+}
+
+impl Scan {
+    fn new(samples: &[FloSample], channels: u8, sample_rate: u32, mut bounds: Vec<usize>) -> Self {
+        bounds.push(0);
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let mut energy = Vec::with_capacity(bounds.len());
+        let mut peak = 0.0f64;
+        if channels > 0 && sample_rate > 0 {
+            let mut kf = KWeighting::new(sample_rate as f64, channels);
+            let mut next = bounds.iter().skip(1).peekable();
+            let mut sum = 0.0f64;
+            for (i, frame) in samples.chunks_exact(channels as usize).enumerate() {
+                while next.next_if(|&&b| b <= i).is_some() {
+                    energy.push(std::mem::take(&mut sum));
+                }
+                for (c, &s) in frame.iter().enumerate() {
+                    let x = s as f64;
+                    peak = peak.max(x.abs());
+                    let y = kf.process(x, c);
+                    sum += y * y;
+                }
+            }
+            for _ in next {
+                energy.push(std::mem::take(&mut sum));
+            }
         }
-        per_channel.push(v);
-    }
+        energy.resize(bounds.len().saturating_sub(1), 0.0);
 
-    // Sample peak
-    let mut sample_peak_dbfs = -150.0f64;
-    for ch in 0..channels as usize {
-        let peak = per_channel[ch].iter().fold(0.0f64, |m, &x| m.max(x.abs()));
-        if peak > 1e-6 {
-            sample_peak_dbfs = sample_peak_dbfs.max(20.0 * peak.log10());
+        Self {
+            bounds,
+            energy,
+            peak,
         }
     }
 
-    // K‑weighting
-    let mut kf = KWeighting::new(sr, channels);
+    /// energy from `start` to `end`, both have to be bounds
+    fn sum(&self, start: usize, end: usize) -> f64 {
+        let at = |pos: usize| self.bounds.binary_search(&pos).unwrap_or_else(|i| i);
+        self.energy[at(start)..at(end)].iter().sum()
+    }
+}
 
-    // K‑weighted per‑channel
-    let mut kw: Vec<Vec<f64>> = Vec::with_capacity(channels as usize);
-    for ch in 0..channels as usize {
-        let mut out = Vec::with_capacity(frames);
-        for &s in &per_channel[ch] {
-            out.push(kf.process(s, ch));
+/// Internal: where the points of a loudness series and their windows lie
+struct SeriesWindows {
+    points_per_second: u32,
+    /// end of each point's windows, in sample-frames
+    ends: Vec<usize>,
+    momentary_len: usize,
+    short_term_len: usize,
+}
+
+impl SeriesWindows {
+    fn new(samples: &[FloSample], channels: u8, sample_rate: u32, points_per_second: u32) -> Self {
+        let frames = match channels {
+            0 => 0,
+            ch => samples.len() / ch as usize,
+        };
+        let ends = if frames == 0 || sample_rate == 0 {
+            Vec::new()
+        } else {
+            let points =
+                (frames as u64 * points_per_second as u64).div_ceil(sample_rate as u64) as usize;
+            (0..points as u64)
+                .map(|i| {
+                    let end = (i + 1) * sample_rate as u64 / points_per_second as u64;
+                    (end as usize).clamp(1, frames)
+                })
+                .collect()
+        };
+
+        Self {
+            points_per_second,
+            ends,
+            momentary_len: (sample_rate as usize * 4).div_ceil(10),
+            short_term_len: sample_rate as usize * 3,
         }
-        kw.push(out);
     }
 
-    // Block energies (400 ms, 100 ms hop), summed across channels
-    let mut block_energies = Vec::<f64>::new();
-    let mut block_loudness = Vec::<f64>::new();
+    fn bounds(&self) -> Vec<usize> {
+        self.ends
+            .iter()
+            .flat_map(|&end| {
+                [
+                    end,
+                    end.saturating_sub(self.momentary_len),
+                    end.saturating_sub(self.short_term_len),
+                ]
+            })
+            .collect()
+    }
 
-    let mut start = 0usize;
-    while start < frames {
-        let end = (start + block_400ms).min(frames);
-        if end <= start {
-            break;
+    fn series(&self, scan: &Scan) -> LoudnessSeries {
+        let window_lufs = |end: usize, len: usize| {
+            let start = end.saturating_sub(len);
+            let mean = scan.sum(start, end).max(0.0) / (end - start) as f64;
+            let lufs = if mean > 0.0 {
+                (-0.691 + 10.0 * mean.log10()).max(-150.0) as f32
+            } else {
+                -150.0
+            };
+            LoudnessSeries::from_lufs(lufs)
+        };
+
+        LoudnessSeries {
+            points_per_second: self.points_per_second,
+            momentary: self
+                .ends
+                .iter()
+                .map(|&end| window_lufs(end, self.momentary_len))
+                .collect(),
+            short_term: self
+                .ends
+                .iter()
+                .map(|&end| window_lufs(end, self.short_term_len))
+                .collect(),
         }
+    }
+}
 
-        let mut energy = 0.0f64;
-        let len = end - start;
+/// Internal: hop of the 400 ms gating blocks
+fn block_hop(sample_rate: u32) -> usize {
+    (sample_rate as f64 * 0.1).round() as usize
+}
 
-        for ch in 0..channels as usize {
-            let slice = &kw[ch][start..end];
-            let mut sum_sq = 0.0;
-            for &y in slice {
-                sum_sq += y * y;
-            }
-            energy += sum_sq / len as f64;
-        }
+/// Internal: where the 400 ms blocks (100 ms hop) start and end
+fn block_bounds(samples: &[FloSample], channels: u8, sample_rate: u32) -> Vec<usize> {
+    let hop = block_hop(sample_rate);
+    if channels == 0 || hop == 0 {
+        return Vec::new();
+    }
+    let frames = samples.len() / channels as usize;
+    let mut bounds: Vec<usize> = (0..frames).step_by(hop).collect();
+    bounds.push(frames);
+    bounds
+}
 
-        block_energies.push(energy);
-        if energy > 0.0 {
-            block_loudness.push(-0.691 + 10.0 * energy.log10());
-        } else {
-            block_loudness.push(-150.0);
-        }
+/// Internal: metrics and the 400 ms block energies they were gated from
+fn measure(samples: &[FloSample], channels: u8, sample_rate: u32) -> (LoudnessMetrics, Vec<f64>) {
+    let bounds = block_bounds(samples, channels, sample_rate);
+    let scan = Scan::new(samples, channels, sample_rate, bounds);
+    scan_metrics(&scan, samples, channels, sample_rate)
+}
+
+/// Internal: metrics from a scan that includes the block bounds
+fn scan_metrics(
+    scan: &Scan,
+    samples: &[FloSample],
+    channels: u8,
+    sample_rate: u32,
+) -> (LoudnessMetrics, Vec<f64>) {
+    if samples.is_empty() || channels == 0 {
+        return (
+            LoudnessMetrics {
+                integrated_lufs: -23.0,
+                loudness_range_lu: 0.0,
+                true_peak_dbtp: -150.0,
+                sample_peak_dbfs: -150.0,
+            },
+            Vec::new(),
+        );
+    }
+
+    let sample_peak_dbfs = if scan.peak > 1e-6 {
+        20.0 * scan.peak.log10()
+    } else {
+        -150.0
+    };
 
-        if end == frames {
-            break;
+    // Block energies (400 ms, 100 ms hop), summed across channels
+    let frames = samples.len() / channels as usize;
+    let hop = block_hop(sample_rate);
+    let mut block_energies = Vec::<f64>::new();
+    if hop > 0 {
+        let mut start = 0usize;
+        while start < frames {
+            let end = (start + hop * 4).min(frames);
+            block_energies.push(scan.sum(start, end) / (end - start) as f64);
+            if end == frames {
+                break;
+            }
+            start += hop;
         }
-        start += hop_100ms;
     }
 
+    let true_peak_dbtp = compute_true_peak(samples, channels, sample_rate);
+    let metrics = gated_metrics(&block_energies, true_peak_dbtp, sample_peak_dbfs);
+    (metrics, block_energies)
+}
+
+/// Internal: integrated loudness and LRA of 400 ms block energies
+fn gated_metrics(
+    block_energies: &[f64],
+    true_peak_dbtp: f64,
+    sample_peak_dbfs: f64,
+) -> LoudnessMetrics {
     if block_energies.is_empty() {
-        let true_peak_dbtp = compute_true_peak(samples, channels, sample_rate);
         return LoudnessMetrics {
             integrated_lufs: -23.0,
             loudness_range_lu: 0.0,
@@ -287,7 +505,6 @@ pub fn compute_ebu_r128_loudness(
         .collect();
 
     if gated_indices.is_empty() {
-        let true_peak_dbtp = compute_true_peak(samples, channels, sample_rate);
         return LoudnessMetrics {
             integrated_lufs: -23.0,
             loudness_range_lu: 0.0,
@@ -322,7 +539,10 @@ pub fn compute_ebu_r128_loudness(
     let loudness_range_lu = if final_indices.len() < 2 {
         0.0
     } else {
-        let mut vals: Vec<f64> = final_indices.iter().map(|&i| block_loudness[i]).collect();
+        let mut vals: Vec<f64> = final_indices
+            .iter()
+            .map(|&i| -0.691 + 10.0 * block_energies[i].log10())
+            .collect();
         vals.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
         let n = vals.len() as f64;
@@ -344,8 +564,6 @@ pub fn compute_ebu_r128_loudness(
         p95 - p10
     };
 
-    let true_peak_dbtp = compute_true_peak(samples, channels, sample_rate);
-
     LoudnessMetrics {
         integrated_lufs,
         loudness_range_lu,
@@ -0,0 +1,406 @@
+//! header parsing, serialization and validation
+//!
+//! Since 1.3 the fixed header fields are followed by a CRC32. It covers every
+//! header byte after the magic except the CRC itself, so fields a newer minor
+//! version appends after it are protected too.
+//!
+//! Compatibility rules:
+//! - a different major version is rejected
+//! - a newer minor version is read as far as we understand it, anything it
+//!   adds to the header is skipped using `header_size`
+//! - 1.0 - 1.2 files have no header CRC and are read as before
+//!
+//! Header extension (after the CRC):
+//! - `early_meta_size: u64` when `FLAG_EARLY_META` is set
+//! - `encoder_delay: u32, padding: u32` when `FLAG_GAPLESS` is set
+
+use std::fmt;
+
+use super::crc32;
+use super::toc::TOC_ENTRY_SIZE;
+use super::types::{Header, HEADER_SIZE, MAGIC, VERSION_MAJOR, VERSION_MINOR};
+
+/// size of the header fields shared by every version (excludes magic)
+pub const BASE_HEADER_SIZE: u64 = 66;
+
+/// first minor version with a header crc
+pub const HEADER_CRC_MINOR: u8 = 3;
+
+/// first minor version with sample positions in the TOC
+pub const TOC_SAMPLES_MINOR: u8 = 4;
+
+/// size of the early metadata field in the header extension
+pub const EARLY_META_FIELD_SIZE: u64 = 8;
+
+/// size of the encoder delay and padding fields in the header extension
+pub const GAPLESS_FIELD_SIZE: u64 = 8;
+
+/// lowest sample rate we accept
+pub const MIN_SAMPLE_RATE: u32 = 1_000;
+
+/// highest sample rate we accept
+pub const MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// most channels we accept
+pub const MAX_CHANNELS: u8 = 32;
+
+/// bit depths we accept
+pub const SUPPORTED_BIT_DEPTHS: [u8; 4] = [8, 16, 24, 32];
+
+/// largest header a future minor version may use
+pub const MAX_HEADER_SIZE: u64 = 4096;
+
+/// most TOC entries we accept for any stream, longer ones may have more
+pub const MAX_TOC_ENTRIES: u64 = 100_000;
+
+/// most frames per second the encoders write (2.5 ms low latency frames)
+pub const MAX_FRAMES_PER_SECOND: u64 = 400;
+
+/// why a header was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    /// fewer bytes than the header needs
+    TooShort { needed: u64, available: u64 },
+    /// magic is not "FLO!"
+    BadMagic,
+    /// major version we don't know how to read
+    UnsupportedVersion { major: u8, minor: u8 },
+    /// header bytes don't match the header crc
+    CrcMismatch { stored: u32, computed: u32 },
+    /// header_size too small for the version or unreasonably large
+    InvalidHeaderSize(u64),
+    /// sample rate outside MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE
+    InvalidSampleRate(u32),
+    /// zero channels or more than MAX_CHANNELS
+    InvalidChannels(u8),
+    /// bit depth not in SUPPORTED_BIT_DEPTHS
+    InvalidBitDepth(u8),
+    /// toc_size doesn't hold a whole number of entries, or too many of them
+    InvalidTocSize(u64),
+    /// early metadata bigger than the DATA chunk holding it
+    InvalidEarlyMetaSize(u64),
+    /// encoder delay and padding longer than the frames they're part of
+    InvalidGapless { encoder_delay: u32, padding: u32 },
+    /// chunk sizes add up to more than fits in a u64
+    ChunkSizeOverflow,
+    /// a chunk ends past the end of the file
+    ChunkPastEnd {
+        chunk: &'static str,
+        end: u64,
+        file_size: u64,
+    },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::TooShort { needed, available } => write!(
+                f,
+                "Invalid flo file: header needs {} bytes, got {}",
+                needed, available
+            ),
+            HeaderError::BadMagic => write!(f, "Invalid flo file: bad magic"),
+            HeaderError::UnsupportedVersion { major, minor } => write!(
+                f,
+                "Unsupported flo version {}.{} (this build reads {}.x)",
+                major, minor, VERSION_MAJOR
+            ),
+            HeaderError::CrcMismatch { stored, computed } => write!(
+                f,
+                "Header CRC mismatch: stored {:08x}, computed {:08x}",
+                stored, computed
+            ),
+            HeaderError::InvalidHeaderSize(size) => write!(f, "Invalid header size: {}", size),
+            HeaderError::InvalidSampleRate(rate) => write!(
+                f,
+                "Invalid sample rate: {} Hz (expected {}-{})",
+                rate, MIN_SAMPLE_RATE, MAX_SAMPLE_RATE
+            ),
+            HeaderError::InvalidChannels(channels) => write!(
+                f,
+                "Invalid channel count: {} (expected 1-{})",
+                channels, MAX_CHANNELS
+            ),
+            HeaderError::InvalidBitDepth(bits) => write!(
+                f,
+                "Invalid bit depth: {} (expected one of {:?})",
+                bits, SUPPORTED_BIT_DEPTHS
+            ),
+            HeaderError::InvalidTocSize(size) => write!(f, "Invalid TOC size: {}", size),
+            HeaderError::InvalidEarlyMetaSize(size) => {
+                write!(f, "Invalid early metadata size: {}", size)
+            }
+            HeaderError::InvalidGapless {
+                encoder_delay,
+                padding,
+            } => write!(
+                f,
+                "Invalid gapless info: delay {} and padding {} exceed the total samples",
+                encoder_delay, padding
+            ),
+            HeaderError::ChunkSizeOverflow => write!(f, "Invalid chunk sizes: total overflows"),
+            HeaderError::ChunkPastEnd {
+                chunk,
+                end,
+                file_size,
+            } => write!(
+                f,
+                "{} chunk ends at byte {}, past the end of the file ({} bytes)",
+                chunk, end, file_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+impl From<HeaderError> for String {
+    fn from(e: HeaderError) -> String {
+        e.to_string()
+    }
+}
+
+impl Header {
+    /// parse and validate a header from the start of a file (magic included)
+    ///
+    /// Only needs the header bytes, chunk sizes are checked against the
+    /// file length separately with `check_file_size`.
+    pub fn parse(data: &[u8]) -> Result<Header, HeaderError> {
+        let fixed_end = 4 + BASE_HEADER_SIZE;
+        if (data.len() as u64) < fixed_end {
+            return Err(HeaderError::TooShort {
+                needed: fixed_end,
+                available: data.len() as u64,
+            });
+        }
+        if data[0..4] != MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+
+        let version_major = data[4];
+        let version_minor = data[5];
+        if version_major != VERSION_MAJOR {
+            return Err(HeaderError::UnsupportedVersion {
+                major: version_major,
+                minor: version_minor,
+            });
+        }
+
+        let mut header = Header {
+            version_major,
+            version_minor,
+            flags: u16::from_le_bytes([data[6], data[7]]),
+            sample_rate: read_u32(data, 8),
+            channels: data[12],
+            bit_depth: data[13],
+            total_samples: read_u64(data, 14),
+            compression_level: data[22],
+            data_crc32: read_u32(data, 26),
+            header_size: read_u64(data, 30),
+            toc_size: read_u64(data, 38),
+            data_size: read_u64(data, 46),
+            extra_size: read_u64(data, 54),
+            meta_size: read_u64(data, 62),
+            header_crc32: 0,
+            early_meta_size: 0,
+            encoder_delay: 0,
+            padding: 0,
+        };
+
+        let min_size = if header.has_header_crc() {
+            header.extended_size()
+        } else {
+            BASE_HEADER_SIZE
+        };
+        if header.header_size < min_size || header.header_size > MAX_HEADER_SIZE {
+            return Err(HeaderError::InvalidHeaderSize(header.header_size));
+        }
+
+        if header.has_header_crc() {
+            let end = 4 + header.header_size;
+            if (data.len() as u64) < end {
+                return Err(HeaderError::TooShort {
+                    needed: end,
+                    available: data.len() as u64,
+                });
+            }
+            let stored = read_u32(data, fixed_end as usize);
+            let computed = header_crc(&data[..end as usize]);
+            if stored != computed {
+                return Err(HeaderError::CrcMismatch { stored, computed });
+            }
+            header.header_crc32 = stored;
+
+            let mut pos = (4 + HEADER_SIZE) as usize;
+            if header.has_early_meta() {
+                header.early_meta_size = read_u64(data, pos);
+                pos += EARLY_META_FIELD_SIZE as usize;
+            }
+            if header.has_gapless() {
+                header.encoder_delay = read_u32(data, pos);
+                header.padding = read_u32(data, pos + 4);
+            }
+        }
+
+        header.validate()?;
+        Ok(header)
+    }
+
+    /// check the stream parameters and chunk sizes are sane
+    pub fn validate(&self) -> Result<(), HeaderError> {
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&self.sample_rate) {
+            return Err(HeaderError::InvalidSampleRate(self.sample_rate));
+        }
+        if self.channels == 0 || self.channels > MAX_CHANNELS {
+            return Err(HeaderError::InvalidChannels(self.channels));
+        }
+        if !SUPPORTED_BIT_DEPTHS.contains(&self.bit_depth) {
+            return Err(HeaderError::InvalidBitDepth(self.bit_depth));
+        }
+
+        // either empty or a count followed by whole rows, the count itself
+        // is checked against the size when the TOC is parsed
+        let toc_ok = self.toc_size == 0
+            || (self.toc_size >= 4
+                && (self.toc_size - 4).is_multiple_of(TOC_ENTRY_SIZE)
+                && self.toc_size <= self.toc_size_for(self.max_toc_entries()));
+        if !toc_ok {
+            return Err(HeaderError::InvalidTocSize(self.toc_size));
+        }
+        if self.early_meta_size > self.data_size {
+            return Err(HeaderError::InvalidEarlyMetaSize(self.early_meta_size));
+        }
+        if self.encoder_delay as u64 + self.padding as u64 > self.total_samples {
+            return Err(HeaderError::InvalidGapless {
+                encoder_delay: self.encoder_delay,
+                padding: self.padding,
+            });
+        }
+
+        self.file_size().ok_or(HeaderError::ChunkSizeOverflow)?;
+        Ok(())
+    }
+
+    /// check every chunk ends inside a file of `file_size` bytes
+    pub fn check_file_size(&self, file_size: u64) -> Result<(), HeaderError> {
+        let chunks = [
+            ("Header", self.header_size),
+            ("TOC", self.toc_size),
+            ("DATA", self.data_size),
+            ("EXTRA", self.extra_size),
+            ("META", self.meta_size),
+        ];
+
+        let mut end = 4u64;
+        for (chunk, size) in chunks {
+            end = end
+                .checked_add(size)
+                .ok_or(HeaderError::ChunkSizeOverflow)?;
+            if end > file_size {
+                return Err(HeaderError::ChunkPastEnd {
+                    chunk,
+                    end,
+                    file_size,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// total file size the header describes, None if it overflows
+    pub fn file_size(&self) -> Option<u64> {
+        [
+            self.header_size,
+            self.toc_size,
+            self.data_size,
+            self.extra_size,
+            self.meta_size,
+        ]
+        .iter()
+        .try_fold(4u64, |acc, &size| acc.checked_add(size))
+    }
+
+    /// does this version carry a header crc?
+    pub fn has_header_crc(&self) -> bool {
+        self.version_minor >= HEADER_CRC_MINOR
+    }
+
+    /// header size of the current version with the extension fields the flags ask for
+    fn extended_size(&self) -> u64 {
+        let mut size = HEADER_SIZE;
+        if self.has_early_meta() {
+            size += EARLY_META_FIELD_SIZE;
+        }
+        if self.has_gapless() {
+            size += GAPLESS_FIELD_SIZE;
+        }
+        size
+    }
+
+    /// serialize as the current version, magic and header crc included
+    ///
+    /// The extension fields are written when their flags are set.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_size = self.extended_size();
+
+        let mut out = Vec::with_capacity(4 + header_size as usize);
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION_MAJOR);
+        out.push(VERSION_MINOR);
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.sample_rate.to_le_bytes());
+        out.push(self.channels);
+        out.push(self.bit_depth);
+        out.extend_from_slice(&self.total_samples.to_le_bytes());
+        out.push(self.compression_level);
+        out.extend_from_slice(&[0, 0, 0]); // reserved
+        out.extend_from_slice(&self.data_crc32.to_le_bytes());
+        out.extend_from_slice(&header_size.to_le_bytes());
+        out.extend_from_slice(&self.toc_size.to_le_bytes());
+        out.extend_from_slice(&self.data_size.to_le_bytes());
+        out.extend_from_slice(&self.extra_size.to_le_bytes());
+        out.extend_from_slice(&self.meta_size.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        if self.has_early_meta() {
+            out.extend_from_slice(&self.early_meta_size.to_le_bytes());
+        }
+        if self.has_gapless() {
+            out.extend_from_slice(&self.encoder_delay.to_le_bytes());
+            out.extend_from_slice(&self.padding.to_le_bytes());
+        }
+        reseal_header(&mut out);
+        out
+    }
+}
+
+/// recompute the header crc of a file in place after patching its header
+///
+/// Does nothing for versions without a header crc.
+pub(crate) fn reseal_header(data: &mut [u8]) {
+    if data.len() < (4 + HEADER_SIZE) as usize || data[5] < HEADER_CRC_MINOR {
+        return;
+    }
+    let header_size = read_u64(data, 30).min(data.len() as u64 - 4);
+    let crc = header_crc(&data[..(4 + header_size) as usize]);
+    let pos = (4 + BASE_HEADER_SIZE) as usize;
+    data[pos..pos + 4].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// crc of the header bytes after the magic, skipping the crc field
+fn header_crc(header: &[u8]) -> u32 {
+    let crc_pos = (4 + BASE_HEADER_SIZE) as usize;
+    let mut bytes = Vec::with_capacity(header.len());
+    bytes.extend_from_slice(&header[4..crc_pos]);
+    bytes.extend_from_slice(&header[crc_pos + 4..]);
+    crc32::compute(&bytes)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn read_u64(data: &[u8], pos: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[pos..pos + 8]);
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,1254 @@
+//! ID3v2 tag import and export
+//!
+//! `FloMetadata` follows ID3v2.4 field by field, this converts it to and from
+//! real tag bytes. Reads v2.3 and v2.4 tags in any of the four text encodings,
+//! with multi-value frames and unsynchronisation. Writes v2.4 as UTF-8, v2.3
+//! as Latin-1 or UTF-16, never unsynchronised. Frames flo™ has no field for
+//! are skipped, as are compressed and encrypted ones.
+
+use wasm_bindgen::prelude::*;
+
+use super::metadata::{
+    Comment, FloMetadata, Lyrics, Picture, PictureType, Popularimeter, SyncedLyrics,
+    SyncedLyricsContentType, SyncedLyricsLine, UserText, UserUrl,
+};
+use super::types::FloResult;
+
+/// ID3v2 version to write
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Id3Version {
+    /// ID3v2.3, what older players and Windows read
+    V23 = 3,
+    /// ID3v2.4, UTF-8 and real multi-value frames
+    #[default]
+    V24 = 4,
+}
+
+/// Size of the ID3v2 tag at the start of `data`, footer included
+///
+/// None if `data` doesn't start with one. Works on whole MP3 files, the
+/// audio starts right after.
+pub fn id3_tag_size(data: &[u8]) -> Option<usize> {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return None;
+    }
+    let size = syncsafe(&data[6..10])? as usize;
+    let footer = if data[3] >= 4 && data[5] & FLAG_FOOTER != 0 {
+        10
+    } else {
+        0
+    };
+    Some(10 + size + footer)
+}
+
+// tag header flags
+const FLAG_UNSYNC: u8 = 0x80;
+const FLAG_EXTENDED: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+
+// text encodings
+const LATIN1: u8 = 0;
+const UTF16: u8 = 1;
+const UTF16BE: u8 = 2;
+const UTF8: u8 = 3;
+
+/// v2.4 frames without a v2.3 equivalent, written to v2.3 as TXXX with the
+/// frame ID as description and read back from there
+const V24_ONLY: [&str; 7] = ["TDEN", "TDOR", "TDRL", "TDTG", "TMOO", "TPRO", "TSST"];
+
+/// tag keys mapping to one string field each
+macro_rules! string_fields {
+    ($fields:ident, $field_mut:ident { $($id:literal => $field:ident,)* }) => {
+        fn $fields(meta: &FloMetadata) -> Vec<(&'static str, &Option<String>)> {
+            vec![$(($id, &meta.$field),)*]
+        }
+
+        fn $field_mut<'a>(meta: &'a mut FloMetadata, id: &str) -> Option<&'a mut Option<String>> {
+            match id {
+                $($id => Some(&mut meta.$field),)*
+                _ => None,
+            }
+        }
+    };
+}
+pub(super) use string_fields;
+
+string_fields!(text_fields, text_field_mut {
+    "TIT1" => content_group,
+    "TIT2" => title,
+    "TIT3" => subtitle,
+    "TALB" => album,
+    "TOAL" => original_album,
+    "TSST" => set_subtitle,
+    "TSRC" => isrc,
+    "TPE1" => artist,
+    "TPE2" => album_artist,
+    "TPE3" => conductor,
+    "TPE4" => remixer,
+    "TOPE" => original_artist,
+    "TCOM" => composer,
+    "TEXT" => lyricist,
+    "TOLY" => original_lyricist,
+    "TENC" => encoded_by,
+    "TMOO" => mood,
+    "TKEY" => key,
+    "TLAN" => language,
+    "TDRL" => release_time,
+    "TDOR" => original_release_time,
+    "TDEN" => encoding_time,
+    "TDTG" => tagging_time,
+    "TCOP" => copyright,
+    "TPRO" => produced_notice,
+    "TPUB" => publisher,
+    "TOWN" => file_owner,
+    "TRSN" => radio_station,
+    "TRSO" => radio_station_owner,
+    "TSOA" => album_sort,
+    "TSOP" => artist_sort,
+    "TSOT" => title_sort,
+    "TOFN" => original_filename,
+    "TSSE" => encoder_settings,
+});
+
+string_fields!(url_fields, url_field_mut {
+    "WCOM" => url_commercial,
+    "WCOP" => url_copyright,
+    "WOAF" => url_audio_file,
+    "WOAR" => url_artist,
+    "WOAS" => url_audio_source,
+    "WORS" => url_radio_station,
+    "WPAY" => url_payment,
+    "WPUB" => url_publisher,
+});
+
+/// APIC picture types in ID3 order
+const PICTURE_TYPES: [PictureType; 21] = [
+    PictureType::Other,
+    PictureType::FileIcon,
+    PictureType::OtherFileIcon,
+    PictureType::CoverFront,
+    PictureType::CoverBack,
+    PictureType::LeafletPage,
+    PictureType::Media,
+    PictureType::LeadArtist,
+    PictureType::Artist,
+    PictureType::Conductor,
+    PictureType::Band,
+    PictureType::Composer,
+    PictureType::Lyricist,
+    PictureType::RecordingLocation,
+    PictureType::DuringRecording,
+    PictureType::DuringPerformance,
+    PictureType::VideoScreenCapture,
+    PictureType::BrightColouredFish,
+    PictureType::Illustration,
+    PictureType::BandLogo,
+    PictureType::PublisherLogo,
+];
+
+/// SYLT content types in ID3 order
+const CONTENT_TYPES: [SyncedLyricsContentType; 9] = [
+    SyncedLyricsContentType::Other,
+    SyncedLyricsContentType::Lyrics,
+    SyncedLyricsContentType::TextTranscription,
+    SyncedLyricsContentType::PartName,
+    SyncedLyricsContentType::Events,
+    SyncedLyricsContentType::Chord,
+    SyncedLyricsContentType::Trivia,
+    SyncedLyricsContentType::WebpageUrl,
+    SyncedLyricsContentType::ImageUrl,
+];
+
+impl FloMetadata {
+    /// Read an ID3v2.3 or v2.4 tag
+    ///
+    /// `tag` may run on past the tag, e.g. a whole MP3 file.
+    pub fn from_id3(tag: &[u8]) -> FloResult<FloMetadata> {
+        let mut meta = FloMetadata::new();
+        meta.apply_id3(tag)?;
+        Ok(meta)
+    }
+
+    /// Set every field an ID3v2.3 or v2.4 tag has, leave the rest alone
+    ///
+    /// Comments, lyrics, pictures and user fields replace the existing list
+    /// when the tag has any of that kind. Values of a v2.4 multi-value frame
+    /// are joined with "/", as v2.3 writes them.
+    pub fn apply_id3(&mut self, tag: &[u8]) -> FloResult<()> {
+        let frames = read_frames(tag)?;
+
+        let has = |id: &str| frames.iter().any(|(frame_id, _)| frame_id == id);
+        if has("COMM") {
+            self.comments.clear();
+        }
+        if has("USLT") {
+            self.lyrics.clear();
+        }
+        if has("SYLT") {
+            self.synced_lyrics.clear();
+        }
+        if has("APIC") {
+            self.pictures.clear();
+        }
+        if has("TXXX") {
+            self.user_text.clear();
+        }
+        if has("WXXX") {
+            self.user_urls.clear();
+        }
+
+        let mut date = DateParts::default();
+        for (id, data) in &frames {
+            // a malformed frame is skipped, the rest of the tag still counts
+            let _ = self.apply_frame(id, data, &mut date);
+        }
+        date.apply(self);
+        Ok(())
+    }
+
+    /// Write the fields with an ID3 frame as an ID3v2 tag, without padding
+    pub fn to_id3(&self, version: Id3Version) -> FloResult<Vec<u8>> {
+        let mut tag = TagWriter::new(version);
+
+        for (id, value) in text_fields(self) {
+            if let Some(value) = value {
+                if version == Id3Version::V23 && V24_ONLY.contains(&id) {
+                    tag.user_text(id, value)?;
+                } else {
+                    tag.text(id, &[value])?;
+                }
+            }
+        }
+
+        let recording_time = self
+            .recording_time
+            .clone()
+            .or_else(|| self.year.map(|year| year.to_string()));
+        if let Some(time) = recording_time {
+            match version {
+                Id3Version::V24 => tag.text("TDRC", &[&time])?,
+                Id3Version::V23 => {
+                    for (id, value) in v23_date_frames(&time) {
+                        tag.text(id, &[&value])?;
+                    }
+                }
+            }
+        }
+
+        if let Some(track) = self.track_number {
+            tag.text("TRCK", &[&count_of(track, self.track_total)])?;
+        }
+        if let Some(disc) = self.disc_number {
+            tag.text("TPOS", &[&count_of(disc, self.disc_total)])?;
+        }
+        if let Some(ref genre) = self.genre {
+            match version {
+                // v2.3 reads a leading "(" as a genre reference
+                Id3Version::V23 if genre.starts_with('(') => {
+                    tag.text("TCON", &[&format!("({}", genre)])?
+                }
+                _ => tag.text("TCON", &[genre])?,
+            }
+        }
+        if let Some(bpm) = self.bpm {
+            tag.text("TBPM", &[&bpm.to_string()])?;
+        }
+        if let Some(length) = self.length_ms {
+            tag.text("TLEN", &[&length.to_string()])?;
+        }
+        if let Some(delay) = self.playlist_delay {
+            tag.text("TDLY", &[&delay.to_string()])?;
+        }
+
+        let people = [
+            ("TIPL", &self.involved_people),
+            ("TMCL", &self.musician_credits),
+        ];
+        match version {
+            Id3Version::V24 => {
+                for (id, pairs) in people {
+                    if let Some(pairs) = pairs {
+                        tag.text(id, &flatten_pairs(pairs))?;
+                    }
+                }
+            }
+            // v2.3 only has the one list for both
+            Id3Version::V23 => {
+                let values: Vec<&str> = people
+                    .iter()
+                    .flat_map(|(_, pairs)| pairs.iter().flat_map(|pairs| flatten_pairs(pairs)))
+                    .collect();
+                if !values.is_empty() {
+                    tag.text("IPLS", &values)?;
+                }
+            }
+        }
+
+        for (id, url) in url_fields(self) {
+            if let Some(url) = url {
+                let mut data = Vec::new();
+                push_latin1(&mut data, url);
+                tag.frame(id, &data)?;
+            }
+        }
+        for url in &self.user_urls {
+            let encoding = tag.encoding(&[&url.description]);
+            let mut data = vec![encoding];
+            push_string(&mut data, encoding, &url.description);
+            push_terminator(&mut data, encoding);
+            push_latin1(&mut data, &url.url);
+            tag.frame("WXXX", &data)?;
+        }
+        for text in &self.user_text {
+            tag.user_text(&text.description, &text.value)?;
+        }
+        for (key, value) in self.replay_gain_tags() {
+            tag.user_text(key, &value)?;
+        }
+
+        for comment in &self.comments {
+            tag.language_frame(
+                "COMM",
+                &comment.language,
+                &comment.description,
+                &comment.text,
+            )?;
+        }
+        for lyrics in &self.lyrics {
+            tag.language_frame("USLT", &lyrics.language, &lyrics.description, &lyrics.text)?;
+        }
+        for synced in &self.synced_lyrics {
+            tag.synced_lyrics(synced)?;
+        }
+        for picture in &self.pictures {
+            tag.picture(picture)?;
+        }
+
+        if let Some(count) = self.play_count {
+            tag.frame("PCNT", &counter_bytes(count))?;
+        }
+        if let Some(ref popm) = self.popularimeter {
+            let mut data = Vec::new();
+            push_latin1(&mut data, popm.email.as_deref().unwrap_or(""));
+            data.extend_from_slice(&[0, popm.rating]);
+            if let Some(count) = popm.play_count {
+                data.extend_from_slice(&counter_bytes(count));
+            }
+            tag.frame("POPM", &data)?;
+        }
+
+        tag.finish()
+    }
+
+    /// Internal: set the field(s) of one frame, None if it's malformed
+    fn apply_frame(&mut self, id: &str, data: &[u8], date: &mut DateParts) -> Option<()> {
+        match id {
+            "TXXX" => {
+                let (encoding, rest) = data.split_first()?;
+                let (description, rest) = take_string(*encoding, rest);
+                let value = join_values(*encoding, rest);
+                if let Some(field) = V24_ONLY
+                    .contains(&description.as_str())
+                    .then(|| text_field_mut(self, &description))
+                    .flatten()
+                {
+                    *field = Some(value);
+                } else if !self.apply_replay_gain_tag(&description.to_ascii_uppercase(), &value) {
+                    self.user_text.push(UserText { description, value });
+                }
+            }
+            "WXXX" => {
+                let (encoding, rest) = data.split_first()?;
+                let (description, rest) = take_string(*encoding, rest);
+                let (url, _) = take_string(LATIN1, rest);
+                self.user_urls.push(UserUrl { description, url });
+            }
+            "TDRC" => date.recording = text_values(data)?.into_iter().next(),
+            "TYER" => date.year = text_values(data)?.into_iter().next(),
+            "TDAT" => date.day = text_values(data)?.into_iter().next(),
+            "TIME" => date.time = text_values(data)?.into_iter().next(),
+            "TORY" if self.original_release_time.is_none() => {
+                self.original_release_time = text_values(data)?.into_iter().next();
+            }
+            "TRCK" => {
+                let (number, total) = parse_count(&text_values(data)?.join("/"));
+                self.track_number = number.or(self.track_number);
+                self.track_total = total.or(self.track_total);
+            }
+            "TPOS" => {
+                let (number, total) = parse_count(&text_values(data)?.join("/"));
+                self.disc_number = number.or(self.disc_number);
+                self.disc_total = total.or(self.disc_total);
+            }
+            "TCON" => {
+                let genres = parse_genres(&text_values(data)?);
+                if !genres.is_empty() {
+                    self.genre = Some(genres.join("/"));
+                }
+            }
+            "TBPM" => {
+                let bpm: f32 = text_values(data)?.first()?.trim().parse().ok()?;
+                self.bpm = Some(bpm.round() as u32);
+            }
+            "TLEN" => self.length_ms = Some(text_values(data)?.first()?.trim().parse().ok()?),
+            "TDLY" => self.playlist_delay = Some(text_values(data)?.first()?.trim().parse().ok()?),
+            "TIPL" | "IPLS" => self.involved_people = Some(pairs_of(text_values(data)?)),
+            "TMCL" => self.musician_credits = Some(pairs_of(text_values(data)?)),
+            "COMM" | "USLT" => {
+                let (language, description, text) = language_frame(data)?;
+                if id == "COMM" {
+                    self.comments.push(Comment {
+                        language,
+                        description,
+                        text,
+                    });
+                } else {
+                    self.lyrics.push(Lyrics {
+                        language,
+                        description,
+                        text,
+                    });
+                }
+            }
+            "SYLT" => self.synced_lyrics.push(synced_lyrics(data)?),
+            "APIC" => self.pictures.push(picture(data)?),
+            "PCNT" => self.play_count = Some(counter(data)?),
+            "POPM" => {
+                let (email, rest) = take_string(LATIN1, data);
+                let (&rating, rest) = rest.split_first()?;
+                self.popularimeter = Some(Popularimeter {
+                    email: non_empty(email),
+                    rating,
+                    play_count: counter(rest),
+                });
+            }
+            _ if id.starts_with('T') => {
+                let field = text_field_mut(self, id)?;
+                let value = text_values(data)?.join("/");
+                if !value.is_empty() {
+                    *field = Some(value);
+                }
+            }
+            _ if id.starts_with('W') => {
+                let (url, _) = take_string(LATIN1, data);
+                let field = url_field_mut(self, id)?;
+                // WCOM and WOAR may repeat, keep the first
+                if field.is_none() && !url.is_empty() {
+                    *field = Some(url);
+                }
+            }
+            _ => {}
+        }
+        Some(())
+    }
+}
+
+// ============================================================================
+// Reading
+// ============================================================================
+
+/// Internal: ID and payload of every frame in the tag, in order
+fn read_frames(tag: &[u8]) -> FloResult<Vec<(String, Vec<u8>)>> {
+    if tag.len() < 10 || &tag[..3] != b"ID3" {
+        return Err("Not an ID3v2 tag".to_string());
+    }
+    let major = tag[3];
+    if major != 3 && major != 4 {
+        return Err(format!("Unsupported ID3v2.{} tag", major));
+    }
+    let flags = tag[5];
+    let size = syncsafe(&tag[6..10]).ok_or("Invalid ID3 tag size")? as usize;
+    let body = tag.get(10..10 + size).ok_or("ID3 tag is truncated")?;
+
+    // v2.3 unsynchronises the whole tag, v2.4 each frame
+    let tag_unsync = flags & FLAG_UNSYNC != 0;
+    let body = if major == 3 && tag_unsync {
+        remove_unsync(body)
+    } else {
+        body.to_vec()
+    };
+
+    let mut pos = 0;
+    if flags & FLAG_EXTENDED != 0 {
+        let size_bytes = body.get(..4).ok_or("ID3 tag is truncated")?;
+        let size = match major {
+            3 => (u32::from_be_bytes(size_bytes.try_into().unwrap()) as usize).checked_add(4),
+            _ => Some(syncsafe(size_bytes).ok_or("Invalid ID3 extended header")? as usize),
+        };
+        // an extended header past the end leaves no frames
+        pos = size.unwrap_or(usize::MAX);
+    }
+
+    let mut frames = Vec::new();
+    while pos.checked_add(10).is_some_and(|end| end <= body.len()) {
+        let id = &body[pos..pos + 4];
+        // padding, or junk after the last frame
+        if !id
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            break;
+        }
+        let size = match major {
+            3 => u32::from_be_bytes(body[pos + 4..pos + 8].try_into().unwrap()) as usize,
+            _ => v24_frame_size(&body, pos),
+        };
+        let format_flags = body[pos + 9];
+        let Some(payload) = body.get(pos + 10..).and_then(|rest| rest.get(..size)) else {
+            break;
+        };
+        pos += 10 + size;
+
+        let payload = match major {
+            3 => {
+                // compressed or encrypted
+                if format_flags & 0xC0 != 0 {
+                    continue;
+                }
+                let grouping = if format_flags & 0x20 != 0 { 1 } else { 0 };
+                payload.get(grouping..).unwrap_or_default().to_vec()
+            }
+            _ => {
+                if format_flags & 0x0C != 0 {
+                    continue;
+                }
+                let mut skip = 0;
+                if format_flags & 0x40 != 0 {
+                    skip += 1;
+                }
+                // data length indicator
+                if format_flags & 0x01 != 0 {
+                    skip += 4;
+                }
+                let payload = payload.get(skip..).unwrap_or_default();
+                if tag_unsync || format_flags & 0x02 != 0 {
+                    remove_unsync(payload)
+                } else {
+                    payload.to_vec()
+                }
+            }
+        };
+        frames.push((String::from_utf8_lossy(id).into_owned(), payload));
+    }
+    Ok(frames)
+}
+
+/// Internal: size of the v2.4 frame at `pos`
+///
+/// Some writers (older iTunes) put plain sizes in v2.4 tags. When the
+/// syncsafe size doesn't end on a frame or padding and the plain one does,
+/// the plain one is right.
+fn v24_frame_size(body: &[u8], pos: usize) -> usize {
+    let bytes = &body[pos + 4..pos + 8];
+    let plain = u32::from_be_bytes(bytes.try_into().unwrap()) as usize;
+    let ends_frame = |size: usize| {
+        (pos + 10)
+            .checked_add(size)
+            .is_some_and(|end| frame_boundary(body, end))
+    };
+    match syncsafe(bytes) {
+        Some(size) if size as usize == plain => plain,
+        Some(size) if ends_frame(size as usize) => size as usize,
+        Some(size) if !ends_frame(plain) => size as usize,
+        _ => plain,
+    }
+}
+
+/// Internal: whether a frame could end at `pos`
+fn frame_boundary(body: &[u8], pos: usize) -> bool {
+    match body.get(pos..) {
+        None => false,
+        Some([]) | Some([0, ..]) => true,
+        Some(rest) => {
+            rest.len() >= 4
+                && rest[..4]
+                    .iter()
+                    .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        }
+    }
+}
+
+/// Internal: 28-bit syncsafe integer, None if a byte has its top bit set
+fn syncsafe(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0u32, |value, &b| {
+        (b & 0x80 == 0).then_some((value << 7) | b as u32)
+    })
+}
+
+/// Internal: undo unsynchronisation, dropping the 0x00 after every 0xFF
+fn remove_unsync(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut after_ff = false;
+    for &b in data {
+        if !(after_ff && b == 0) {
+            out.push(b);
+        }
+        after_ff = b == 0xFF;
+    }
+    out
+}
+
+fn is_wide(encoding: u8) -> bool {
+    encoding == UTF16 || encoding == UTF16BE
+}
+
+/// Internal: first string of `data`, and what follows its terminator
+fn take_string(encoding: u8, data: &[u8]) -> (String, &[u8]) {
+    let (end, terminator) = if is_wide(encoding) {
+        let end = data.chunks_exact(2).position(|unit| unit == [0, 0]);
+        (end.map(|i| i * 2), 2)
+    } else {
+        (data.iter().position(|&b| b == 0), 1)
+    };
+    match end {
+        Some(end) => (
+            decode_string(encoding, &data[..end]),
+            &data[end + terminator..],
+        ),
+        None => (decode_string(encoding, data), &[]),
+    }
+}
+
+fn decode_string(encoding: u8, bytes: &[u8]) -> String {
+    match encoding {
+        LATIN1 => bytes.iter().map(|&b| b as char).collect(),
+        UTF16 | UTF16BE => {
+            let (big_endian, bytes) = match bytes {
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                _ => (true, bytes),
+            };
+            let units = bytes.chunks_exact(2).map(|unit| {
+                let unit = [unit[0], unit[1]];
+                if big_endian {
+                    u16::from_be_bytes(unit)
+                } else {
+                    u16::from_le_bytes(unit)
+                }
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Internal: all values of a text frame, None if it's empty
+fn text_values(data: &[u8]) -> Option<Vec<String>> {
+    let (&encoding, mut rest) = data.split_first()?;
+    let mut values = Vec::new();
+    while !rest.is_empty() {
+        let (value, next) = take_string(encoding, rest);
+        values.push(value);
+        rest = next;
+    }
+    while values.last().is_some_and(|value| value.is_empty()) {
+        values.pop();
+    }
+    Some(values)
+}
+
+/// Internal: the strings left in `data`, joined with "/"
+fn join_values(encoding: u8, data: &[u8]) -> String {
+    let mut frame = vec![encoding];
+    frame.extend_from_slice(data);
+    text_values(&frame).unwrap_or_default().join("/")
+}
+
+/// Internal: language, description and text of COMM/USLT
+fn language_frame(data: &[u8]) -> Option<(Option<String>, Option<String>, String)> {
+    let (&encoding, rest) = data.split_first()?;
+    let language = language_code(rest.get(..3)?);
+    let (description, rest) = take_string(encoding, &rest[3..]);
+    let (text, _) = take_string(encoding, rest);
+    Some((language, non_empty(description), text))
+}
+
+/// Internal: "XXX" and zeros mean no language
+fn language_code(bytes: &[u8]) -> Option<String> {
+    let code = decode_string(LATIN1, bytes);
+    let known = code.chars().all(|c| c.is_ascii_alphabetic()) && !code.eq_ignore_ascii_case("xxx");
+    known.then(|| code.to_ascii_lowercase())
+}
+
+/// Internal: SYLT, only millisecond timestamps can be kept
+fn synced_lyrics(data: &[u8]) -> Option<SyncedLyrics> {
+    let (&encoding, rest) = data.split_first()?;
+    let language = language_code(rest.get(..3)?);
+    let (&format, rest) = rest[3..].split_first()?;
+    if format != 2 {
+        return None;
+    }
+    let (&content_type, rest) = rest.split_first()?;
+    let (description, mut rest) = take_string(encoding, rest);
+
+    let mut lines = Vec::new();
+    while !rest.is_empty() {
+        let (text, next) = take_string(encoding, rest);
+        let timestamp = next.get(..4)?;
+        lines.push(SyncedLyricsLine {
+            timestamp_ms: u32::from_be_bytes(timestamp.try_into().unwrap()) as u64,
+            text,
+        });
+        rest = &next[4..];
+    }
+
+    Some(SyncedLyrics {
+        language,
+        content_type: CONTENT_TYPES
+            .get(content_type as usize)
+            .copied()
+            .unwrap_or_default(),
+        description: non_empty(description),
+        lines,
+    })
+}
+
+fn picture(data: &[u8]) -> Option<Picture> {
+    let (&encoding, rest) = data.split_first()?;
+    let (mime_type, rest) = take_string(LATIN1, rest);
+    let (&picture_type, rest) = rest.split_first()?;
+    let (description, rest) = take_string(encoding, rest);
+    Some(Picture {
+        mime_type: match mime_type.as_str() {
+            // v2.2 style image formats some writers still use
+            "PNG" => "image/png".to_string(),
+            "JPG" => "image/jpeg".to_string(),
+            _ => mime_type,
+        },
+        picture_type: PICTURE_TYPES
+            .get(picture_type as usize)
+            .copied()
+            .unwrap_or(PictureType::Other),
+        description: non_empty(description),
+        data: rest.to_vec(),
+    })
+}
+
+/// Internal: big-endian counter of 4 or more bytes
+fn counter(data: &[u8]) -> Option<u64> {
+    (data.len() >= 4 && data.len() <= 8)
+        .then(|| data.iter().fold(0u64, |count, &b| (count << 8) | b as u64))
+}
+
+/// Internal: "3" or "3/12"
+fn parse_count(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = value.splitn(2, '/');
+    let number = parts.next().and_then(|n| n.trim().parse().ok());
+    let total = parts.next().and_then(|n| n.trim().parse().ok());
+    (number, total)
+}
+
+/// Internal: role/name pairs of TIPL, TMCL and IPLS
+fn pairs_of(values: Vec<String>) -> Vec<(String, String)> {
+    let mut values = values.into_iter();
+    let mut pairs = Vec::new();
+    while let Some(role) = values.next() {
+        pairs.push((role, values.next().unwrap_or_default()));
+    }
+    pairs
+}
+
+/// Internal: genre names of TCON values
+///
+/// v2.4 values may be ID3v1 genre numbers, v2.3 ones start with "(n)"
+/// references and may end in a refinement. "((" escapes a real "(".
+fn parse_genres(values: &[String]) -> Vec<String> {
+    let mut genres: Vec<String> = Vec::new();
+    let mut add = |genre: String| {
+        if !genre.is_empty() && !genres.contains(&genre) {
+            genres.push(genre);
+        }
+    };
+
+    for value in values {
+        let mut rest = value.as_str();
+        while let Some(inner) = rest.strip_prefix('(').filter(|r| !r.starts_with('(')) {
+            let Some(end) = inner.find(')') else {
+                break;
+            };
+            let reference = &inner[..end];
+            let numeric = !reference.is_empty() && reference.bytes().all(|b| b.is_ascii_digit());
+            if !numeric && reference != "RX" && reference != "CR" {
+                break;
+            }
+            add(genre_name(reference));
+            rest = &inner[end + 1..];
+        }
+        let rest = rest
+            .strip_prefix('(')
+            .filter(|r| r.starts_with('('))
+            .unwrap_or(rest);
+        add(genre_name(rest));
+    }
+    genres
+}
+
+fn genre_name(reference: &str) -> String {
+    match reference {
+        "RX" => "Remix".to_string(),
+        "CR" => "Cover".to_string(),
+        _ => match reference.parse::<usize>() {
+            Ok(index) => GENRES.get(index).copied().unwrap_or(reference).to_string(),
+            Err(_) => reference.to_string(),
+        },
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Internal: v2.3 splits the recording time over TYER, TDAT and TIME
+#[derive(Default)]
+struct DateParts {
+    recording: Option<String>,
+    year: Option<String>,
+    /// DDMM
+    day: Option<String>,
+    /// HHMM
+    time: Option<String>,
+}
+
+impl DateParts {
+    fn apply(self, meta: &mut FloMetadata) {
+        let time = self.recording.or_else(|| {
+            let mut time = self.year?;
+            let four_digits = |s: &String| s.len() == 4 && s.bytes().all(|b| b.is_ascii_digit());
+            if let Some(day) = self.day.filter(four_digits) {
+                time += &format!("-{}-{}", &day[2..], &day[..2]);
+                if let Some(hm) = self.time.filter(four_digits) {
+                    time += &format!("T{}:{}", &hm[..2], &hm[2..]);
+                }
+            }
+            Some(time)
+        });
+        let Some(time) = time else {
+            return;
+        };
+
+        if let Some(year) = time.get(..4).and_then(|y| y.parse().ok()) {
+            meta.year = Some(year);
+        }
+        // a bare year is just the year
+        if time.len() > 4 {
+            meta.recording_time = Some(time);
+        }
+    }
+}
+
+// ============================================================================
+// Writing
+// ============================================================================
+
+/// Internal: TYER/TDAT/TIME of an ISO 8601 time
+fn v23_date_frames(time: &str) -> Vec<(&'static str, String)> {
+    let part = |range: std::ops::Range<usize>| time.get(range).map(str::to_string);
+    let mut frames = Vec::new();
+    if let Some(year) = part(0..4) {
+        frames.push(("TYER", year));
+    }
+    if let (Some(month), Some(day)) = (part(5..7), part(8..10)) {
+        frames.push(("TDAT", day + &month));
+        if let (Some(hour), Some(minute)) = (part(11..13), part(14..16)) {
+            frames.push(("TIME", hour + &minute));
+        }
+    }
+    frames
+}
+
+fn count_of(number: u32, total: Option<u32>) -> String {
+    match total {
+        Some(total) => format!("{}/{}", number, total),
+        None => number.to_string(),
+    }
+}
+
+fn flatten_pairs(pairs: &[(String, String)]) -> Vec<&str> {
+    pairs
+        .iter()
+        .flat_map(|(role, name)| [role.as_str(), name.as_str()])
+        .collect()
+}
+
+/// Internal: big-endian, at least 4 bytes
+fn counter_bytes(count: u64) -> Vec<u8> {
+    let bytes = count.to_be_bytes();
+    let skip = (count.leading_zeros() / 8).min(4) as usize;
+    bytes[skip..].to_vec()
+}
+
+fn push_latin1(out: &mut Vec<u8>, text: &str) {
+    out.extend(text.chars().map(|c| u8::try_from(c).unwrap_or(b'?')));
+}
+
+fn push_string(out: &mut Vec<u8>, encoding: u8, text: &str) {
+    match encoding {
+        LATIN1 => push_latin1(out, text),
+        UTF16 => {
+            out.extend_from_slice(&[0xFF, 0xFE]);
+            for unit in text.encode_utf16() {
+                out.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        _ => out.extend_from_slice(text.as_bytes()),
+    }
+}
+
+fn push_terminator(out: &mut Vec<u8>, encoding: u8) {
+    out.extend_from_slice(if is_wide(encoding) { &[0, 0] } else { &[0] });
+}
+
+/// Internal: 3-letter ISO-639-2 code, "XXX" when unknown
+fn push_language(out: &mut Vec<u8>, language: &Option<String>) {
+    match language {
+        Some(code) if code.len() == 3 && code.is_ascii() => out.extend_from_slice(code.as_bytes()),
+        _ => out.extend_from_slice(b"XXX"),
+    }
+}
+
+/// Internal: builds the frames, then the tag around them
+struct TagWriter {
+    version: Id3Version,
+    frames: Vec<u8>,
+}
+
+impl TagWriter {
+    fn new(version: Id3Version) -> Self {
+        Self {
+            version,
+            frames: Vec::new(),
+        }
+    }
+
+    /// UTF-8 in v2.4, Latin-1 in v2.3 if `texts` fit, else UTF-16
+    fn encoding(&self, texts: &[&str]) -> u8 {
+        match self.version {
+            Id3Version::V24 => UTF8,
+            Id3Version::V23 if texts.iter().all(|t| t.chars().all(|c| (c as u32) < 0x100)) => {
+                LATIN1
+            }
+            Id3Version::V23 => UTF16,
+        }
+    }
+
+    fn frame(&mut self, id: &str, data: &[u8]) -> FloResult<()> {
+        let size = u32::try_from(data.len())
+            .ok()
+            .filter(|&size| self.version == Id3Version::V23 || size < 1 << 28)
+            .ok_or_else(|| format!("ID3 frame {} is too large", id))?;
+
+        self.frames.extend_from_slice(id.as_bytes());
+        match self.version {
+            Id3Version::V23 => self.frames.extend_from_slice(&size.to_be_bytes()),
+            Id3Version::V24 => self.frames.extend_from_slice(&to_syncsafe(size)),
+        }
+        self.frames.extend_from_slice(&[0, 0]);
+        self.frames.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// text frame, values separated by terminators
+    fn text(&mut self, id: &str, values: &[&str]) -> FloResult<()> {
+        let encoding = self.encoding(values);
+        let mut data = vec![encoding];
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                push_terminator(&mut data, encoding);
+            }
+            push_string(&mut data, encoding, value);
+        }
+        self.frame(id, &data)
+    }
+
+    /// TXXX
+    fn user_text(&mut self, description: &str, value: &str) -> FloResult<()> {
+        let encoding = self.encoding(&[description, value]);
+        let mut data = vec![encoding];
+        push_string(&mut data, encoding, description);
+        push_terminator(&mut data, encoding);
+        push_string(&mut data, encoding, value);
+        self.frame("TXXX", &data)
+    }
+
+    /// COMM/USLT
+    fn language_frame(
+        &mut self,
+        id: &str,
+        language: &Option<String>,
+        description: &Option<String>,
+        text: &str,
+    ) -> FloResult<()> {
+        let description = description.as_deref().unwrap_or("");
+        let encoding = self.encoding(&[description, text]);
+        let mut data = vec![encoding];
+        push_language(&mut data, language);
+        push_string(&mut data, encoding, description);
+        push_terminator(&mut data, encoding);
+        push_string(&mut data, encoding, text);
+        self.frame(id, &data)
+    }
+
+    fn synced_lyrics(&mut self, synced: &SyncedLyrics) -> FloResult<()> {
+        let description = synced.description.as_deref().unwrap_or("");
+        let mut texts: Vec<&str> = synced.lines.iter().map(|l| l.text.as_str()).collect();
+        texts.push(description);
+        let encoding = self.encoding(&texts);
+
+        let content_type = CONTENT_TYPES
+            .iter()
+            .position(|&t| t == synced.content_type)
+            .unwrap_or(0) as u8;
+        // timestamps in milliseconds
+        let mut data = vec![encoding];
+        push_language(&mut data, &synced.language);
+        data.extend_from_slice(&[2, content_type]);
+        push_string(&mut data, encoding, description);
+        push_terminator(&mut data, encoding);
+        for line in &synced.lines {
+            push_string(&mut data, encoding, &line.text);
+            push_terminator(&mut data, encoding);
+            let timestamp = line.timestamp_ms.min(u32::MAX as u64) as u32;
+            data.extend_from_slice(&timestamp.to_be_bytes());
+        }
+        self.frame("SYLT", &data)
+    }
+
+    fn picture(&mut self, picture: &Picture) -> FloResult<()> {
+        let description = picture.description.as_deref().unwrap_or("");
+        let encoding = self.encoding(&[description]);
+        let picture_type = PICTURE_TYPES
+            .iter()
+            .position(|&t| t == picture.picture_type)
+            .unwrap_or(0) as u8;
+
+        let mut data = vec![encoding];
+        push_latin1(&mut data, &picture.mime_type);
+        data.extend_from_slice(&[0, picture_type]);
+        push_string(&mut data, encoding, description);
+        push_terminator(&mut data, encoding);
+        data.extend_from_slice(&picture.data);
+        self.frame("APIC", &data)
+    }
+
+    fn finish(self) -> FloResult<Vec<u8>> {
+        let size = u32::try_from(self.frames.len())
+            .ok()
+            .filter(|&size| size < 1 << 28)
+            .ok_or("ID3 tag is too large")?;
+
+        let mut tag = Vec::with_capacity(10 + self.frames.len());
+        tag.extend_from_slice(b"ID3");
+        tag.extend_from_slice(&[self.version as u8, 0, 0]);
+        tag.extend_from_slice(&to_syncsafe(size));
+        tag.extend_from_slice(&self.frames);
+        Ok(tag)
+    }
+}
+
+fn to_syncsafe(value: u32) -> [u8; 4] {
+    [
+        (value >> 21) as u8 & 0x7F,
+        (value >> 14) as u8 & 0x7F,
+        (value >> 7) as u8 & 0x7F,
+        value as u8 & 0x7F,
+    ]
+}
+
+/// ID3v1 genres with the Winamp extensions, for numeric TCON references
+const GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "AlternRock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "JPop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
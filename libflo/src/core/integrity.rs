@@ -0,0 +1,172 @@
+//! per-frame integrity checks and concealment of damaged frames
+//!
+//! When the header has `FLAG_FRAME_CRC` set, every frame in the DATA chunk is
+//! followed by a CRC32 of its own bytes. The trailing CRC is counted in the
+//! TOC `frame_size`, so readers that don't know about it just skip it.
+
+use wasm_bindgen::prelude::*;
+
+use super::crc32;
+use crate::lossless::lpc;
+
+/// Size of the trailing per-frame CRC
+pub const FRAME_CRC_SIZE: usize = 4;
+
+/// Sample-frames before a gap that concealment looks at
+pub(crate) const CONCEAL_HISTORY: usize = 2048;
+
+/// Pitch periods `RepeatFade` looks for, in samples
+const MIN_PERIOD: usize = 32;
+const MAX_PERIOD: usize = 1024;
+/// Samples compared when looking for the pitch period
+const MATCH_WINDOW: usize = 256;
+/// Samples for a `RepeatFade` repeat to drop by half, 20 ms at 48 kHz
+const REPEAT_HALF_LIFE: f32 = 960.0;
+
+/// LPC order for `Spectral` extrapolation of lossless gaps
+const EXTRAPOLATION_ORDER: usize = 16;
+/// Per-sample damping on the LPC continuation, so it can't ring on forever
+const EXTRAPOLATION_DAMPING: f32 = 0.9995;
+
+/// How to fill in a frame that failed its integrity check or never arrived
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Concealment {
+    /// Replace the frame with silence
+    #[default]
+    Silence = 0,
+    /// Ramp between the neighbouring frames (lossless) or repeat the
+    /// previous frame's spectrum (lossy)
+    Interpolate = 1,
+    /// Repeat the last pitch period (lossless) or the previous spectrum
+    /// (lossy), fading out
+    RepeatFade = 2,
+    /// Continue the spectral envelope: LPC extrapolation (lossless), or the
+    /// previous band energies with noise-like fine structure (lossy),
+    /// fading out slowly
+    Spectral = 3,
+}
+
+/// Append the CRC of `buffer[frame_start..]` to `buffer`
+pub(crate) fn append_frame_crc(buffer: &mut Vec<u8>, frame_start: usize) {
+    let crc = crc32::compute(&buffer[frame_start..]);
+    buffer.extend_from_slice(&crc.to_le_bytes());
+}
+
+/// Check a frame that ends with its CRC32
+///
+/// `frame` is the whole TOC-sized frame, trailing CRC included.
+pub fn verify_frame_crc(frame: &[u8]) -> bool {
+    if frame.len() < FRAME_CRC_SIZE {
+        return false;
+    }
+    let (body, stored) = frame.split_at(frame.len() - FRAME_CRC_SIZE);
+    crc32::compute(body) == u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]])
+}
+
+/// Fill `len` sample-frames starting at `start` in interleaved `samples`
+///
+/// With `Interpolate` the gap becomes a straight line from the last sample
+/// before it to the first sample after it (zero where there is no neighbour),
+/// which avoids the clicks silence would leave at both edges. `RepeatFade`
+/// and `Spectral` carry on the audio before the gap (up to `CONCEAL_HISTORY`
+/// sample-frames of it), bent so the end meets the sample after the gap.
+pub fn conceal_in_place(
+    samples: &mut [f32],
+    channels: usize,
+    start: usize,
+    len: usize,
+    mode: Concealment,
+) {
+    let total = samples.len() / channels.max(1);
+    let end = (start + len).min(total);
+    if channels == 0 || start >= end {
+        return;
+    }
+    let len = end - start;
+
+    for ch in 0..channels {
+        let from = match start {
+            0 => 0.0,
+            _ => samples[(start - 1) * channels + ch],
+        };
+        let to = (end < total).then(|| samples[end * channels + ch]);
+
+        let history: Vec<f32> = (start.saturating_sub(CONCEAL_HISTORY)..start)
+            .map(|i| samples[i * channels + ch])
+            .collect();
+        // one sample longer, to see how far off the continuation ends up
+        let continuation = match mode {
+            Concealment::RepeatFade => repeat_pitch(&history, len + 1).map(|mut c| {
+                for (i, s) in c.iter_mut().enumerate() {
+                    *s *= 0.5f32.powf(i as f32 / REPEAT_HALF_LIFE);
+                }
+                c
+            }),
+            Concealment::Spectral => extrapolate_lpc(&history, len + 1),
+            _ => None,
+        };
+
+        let span = (len + 1) as f32;
+        for i in 0..len {
+            let t = (i + 1) as f32 / span;
+            samples[(start + i) * channels + ch] = match (mode, &continuation) {
+                (Concealment::Silence, _) => 0.0,
+                (_, Some(c)) => c[i] + to.map_or(0.0, |to| to - c[len]) * t,
+                (_, None) => from + (to.unwrap_or(0.0) - from) * t,
+            };
+        }
+    }
+}
+
+/// Repeat the last pitch period of `history` for `len` samples
+///
+/// The period is the lag that best matches the most recent samples, so the
+/// repeat carries on where the audio left off. None if there's too little
+/// history to find one.
+fn repeat_pitch(history: &[f32], len: usize) -> Option<Vec<f32>> {
+    let n = history.len();
+    let window = MATCH_WINDOW.min(n / 2);
+    if window < MIN_PERIOD {
+        return None;
+    }
+    let recent = &history[n - window..];
+
+    let score = |period: usize| {
+        let past = &history[n - window - period..n - period];
+        let dot: f32 = recent.iter().zip(past).map(|(a, b)| a * b).sum();
+        let energy: f32 = past.iter().map(|x| x * x).sum();
+        dot / (energy + 1e-9).sqrt()
+    };
+    let period =
+        (MIN_PERIOD..=MAX_PERIOD.min(n - window)).max_by(|&a, &b| score(a).total_cmp(&score(b)))?;
+
+    let cycle = &history[n - period..];
+    Some((0..len).map(|i| cycle[i % period]).collect())
+}
+
+/// Run an LPC model of `history` on past its end for `len` samples
+///
+/// Levinson-Durbin keeps the reflection coefficients inside the unit circle,
+/// so the filter is stable. None if there's too little history or it's silent.
+fn extrapolate_lpc(history: &[f32], len: usize) -> Option<Vec<f32>> {
+    if history.len() < 4 * EXTRAPOLATION_ORDER {
+        return None;
+    }
+    let autocorr = lpc::autocorrelation(history, EXTRAPOLATION_ORDER);
+    if autocorr[0] <= 1e-9 {
+        return None;
+    }
+    let coeffs = lpc::levinson_durbin(&autocorr, EXTRAPOLATION_ORDER);
+
+    let mut past = history[history.len() - EXTRAPOLATION_ORDER..].to_vec();
+    for _ in 0..len {
+        let prediction: f32 = coeffs
+            .iter()
+            .zip(past.iter().rev())
+            .map(|(c, s)| c * s)
+            .sum();
+        past.push((prediction * EXTRAPOLATION_DAMPING).clamp(-1.0, 1.0));
+    }
+    Some(past.split_off(EXTRAPOLATION_ORDER))
+}
@@ -5,6 +5,9 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use super::ebu_r128::LoudnessMetrics;
 
 // ============================================================================
 // Picture Types (ID3v2.4 APIC)
@@ -207,6 +210,31 @@ pub struct SectionMarker {
     pub label: Option<String>,
 }
 
+/// Chapter or track in a file holding a whole album or audiobook
+///
+/// A chapter runs from `start_sample` to the next chapter's start, the last
+/// one to the end of the file. Fields left unset fall back to the file's own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Chapter {
+    /// First sample-frame (per channel) of the chapter
+    pub start_sample: u64,
+    /// Chapter/track title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Performer of this track
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// Track number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_number: Option<u32>,
+    /// ISRC code of this track
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isrc: Option<String>,
+    /// Any other per-track fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub user_text: Vec<UserText>,
+}
+
 /// BPM change point for tempo mapping
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BpmChange {
@@ -234,6 +262,72 @@ pub struct LoudnessPoint {
     pub lufs: f32,
 }
 
+/// Momentary and short-term loudness over time
+///
+/// Both series have one value per `1 / points_per_second` seconds, see
+/// [`compute_loudness_series`](crate::core::ebu_r128::compute_loudness_series).
+/// Values are stored in steps of 0.01 LU, so -2314 is -23.14 LUFS.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoudnessSeries {
+    /// Number of points per second of audio; each value is LUFS * 100
+    pub points_per_second: u32,
+    /// Momentary loudness (400 ms window), in 0.01 LU
+    pub momentary: Vec<i16>,
+    /// Short-term loudness (3 s window), in 0.01 LU
+    pub short_term: Vec<i16>,
+}
+
+impl LoudnessSeries {
+    /// Stored value of a loudness in LUFS
+    pub fn from_lufs(lufs: f32) -> i16 {
+        (lufs * 100.0)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+
+    /// Loudness in LUFS of a stored value
+    pub fn to_lufs(value: i16) -> f32 {
+        value as f32 / 100.0
+    }
+
+    /// Timestamp of a point in milliseconds
+    pub fn timestamp_ms(&self, index: usize) -> u64 {
+        index as u64 * 1000 / self.points_per_second.max(1) as u64
+    }
+
+    /// Momentary values in LUFS
+    pub fn momentary_lufs(&self) -> Vec<f32> {
+        self.momentary.iter().map(|&v| Self::to_lufs(v)).collect()
+    }
+
+    /// Short-term values in LUFS
+    pub fn short_term_lufs(&self) -> Vec<f32> {
+        self.short_term.iter().map(|&v| Self::to_lufs(v)).collect()
+    }
+
+    /// Loudest momentary value in LUFS, "max momentary" in meters
+    pub fn max_momentary(&self) -> Option<f32> {
+        self.momentary.iter().copied().max().map(Self::to_lufs)
+    }
+
+    /// Loudest short-term value in LUFS, "max short-term" in meters
+    pub fn max_short_term(&self) -> Option<f32> {
+        self.short_term.iter().copied().max().map(Self::to_lufs)
+    }
+
+    /// Short-term values as timestamped points
+    pub fn short_term_points(&self) -> Vec<LoudnessPoint> {
+        self.short_term
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| LoudnessPoint {
+                timestamp_ms: self.timestamp_ms(i),
+                lufs: Self::to_lufs(value),
+            })
+            .collect()
+    }
+}
+
 /// Creator/producer note with optional timestamp
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreatorNote {
@@ -320,6 +414,30 @@ pub struct CoverVariant {
     pub description: Option<String>,
 }
 
+// ============================================================================
+// Placement
+// ============================================================================
+
+/// Where a writer puts the metadata
+///
+/// Early metadata sits at the start of the DATA chunk ahead of the first
+/// frame, so progressive readers see it right after the TOC. Readers that
+/// don't know about it skip it, frames are always found through the TOC.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataPlacement {
+    /// Everything in the META chunk at the end of the file
+    #[default]
+    End = 0,
+    /// A small `preview()` up front, everything in the META chunk as usual
+    Preview = 1,
+    /// Everything up front, the META chunk stays empty (older readers won't see it)
+    Start = 2,
+}
+
+/// Largest front cover kept in a preview
+pub const PREVIEW_MAX_PICTURE_SIZE: usize = 64 * 1024;
+
 // ============================================================================
 // Main Metadata Structure
 // ============================================================================
@@ -602,10 +720,14 @@ pub struct FloMetadata {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub key_changes: Vec<KeyChange>,
 
-    /// Frame-by-frame loudness profile (ReplayGain dynamic)
+    /// Loudness at chosen points, e.g. hand-picked from a mastering session
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub loudness_profile: Vec<LoudnessPoint>,
 
+    /// Measured momentary and short-term loudness, written by the encoder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness_series: Option<LoudnessSeries>,
+
     /// Integrated loudness (LUFS): EBU R128
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub integrated_loudness_lufs: Option<f32>,
@@ -618,10 +740,30 @@ pub struct FloMetadata {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub true_peak_dbtp: Option<f32>,
 
+    /// Track gain (dB): ReplayGain 2.0, to -18 LUFS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_gain_db: Option<f32>,
+
+    /// Album gain (dB): ReplayGain 2.0, the whole album measured as one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album_gain_db: Option<f32>,
+
+    /// Integrated loudness of the whole album (LUFS)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album_loudness_lufs: Option<f32>,
+
+    /// True peak of the whole album (dBTP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album_true_peak_dbtp: Option<f32>,
+
     /// Section markers (intro/verse/chorus/etc.)
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub section_markers: Vec<SectionMarker>,
 
+    /// Chapter/track table, sorted by start sample
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chapters: Vec<Chapter>,
+
     // ==================== CREATOR INFO (flo™-unique) ====================
     /// Producer commentary with timestamps
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -715,6 +857,39 @@ impl FloMetadata {
         rmp_serde::from_slice(data)
     }
 
+    /// Lightweight subset for showing a track before it has downloaded
+    ///
+    /// Titles, numbering, length, a front cover up to `PREVIEW_MAX_PICTURE_SIZE`,
+    /// waveform, synced lyrics, chapters and section markers.
+    pub fn preview(&self) -> FloMetadata {
+        let cover = self
+            .front_cover()
+            .filter(|p| p.data.len() <= PREVIEW_MAX_PICTURE_SIZE);
+
+        FloMetadata {
+            title: self.title.clone(),
+            subtitle: self.subtitle.clone(),
+            album: self.album.clone(),
+            artist: self.artist.clone(),
+            album_artist: self.album_artist.clone(),
+            track_number: self.track_number,
+            track_total: self.track_total,
+            disc_number: self.disc_number,
+            disc_total: self.disc_total,
+            genre: self.genre.clone(),
+            year: self.year,
+            length_ms: self.length_ms,
+            track_gain_db: self.track_gain_db,
+            album_gain_db: self.album_gain_db,
+            pictures: cover.into_iter().cloned().collect(),
+            waveform_data: self.waveform_data.clone(),
+            synced_lyrics: self.synced_lyrics.clone(),
+            section_markers: self.section_markers.clone(),
+            chapters: self.chapters.clone(),
+            ..Default::default()
+        }
+    }
+
     /// Check if metadata is empty (no significant fields set)
     pub fn is_empty(&self) -> bool {
         self.title.is_none()
@@ -824,6 +999,39 @@ impl FloMetadata {
         });
     }
 
+    /// Add a chapter starting at `start_sample`, keeping the table sorted
+    pub fn add_chapter(&mut self, start_sample: u64, title: Option<&str>) -> &mut Chapter {
+        let index = self
+            .chapters
+            .partition_point(|c| c.start_sample <= start_sample);
+        self.chapters.insert(
+            index,
+            Chapter {
+                start_sample,
+                title: title.map(|s| s.to_string()),
+                ..Chapter::default()
+            },
+        );
+        &mut self.chapters[index]
+    }
+
+    /// Index of the chapter playing at `sample`
+    pub fn chapter_at(&self, sample: u64) -> Option<usize> {
+        self.chapters
+            .partition_point(|c| c.start_sample <= sample)
+            .checked_sub(1)
+    }
+
+    /// Sample range `start..end` of a chapter in a file of `total_samples`
+    pub fn chapter_range(&self, index: usize, total_samples: u64) -> Option<(u64, u64)> {
+        let chapter = self.chapters.get(index)?;
+        let end = self
+            .chapters
+            .get(index + 1)
+            .map_or(total_samples, |next| next.start_sample);
+        Some((chapter.start_sample, end.max(chapter.start_sample)))
+    }
+
     /// Add a BPM change point
     pub fn add_bpm_change(&mut self, timestamp_ms: u64, bpm: f32) {
         self.bpm_map.push(BpmChange { timestamp_ms, bpm });
@@ -845,6 +1053,23 @@ impl FloMetadata {
         });
     }
 
+    // ==================== LOUDNESS HELPERS ====================
+
+    /// Set integrated loudness, range, true peak and track gain from a measurement
+    pub fn set_track_loudness(&mut self, metrics: &LoudnessMetrics) {
+        self.integrated_loudness_lufs = Some(metrics.integrated_lufs as f32);
+        self.loudness_range_lu = Some(metrics.loudness_range_lu as f32);
+        self.true_peak_dbtp = Some(metrics.true_peak_dbtp as f32);
+        self.track_gain_db = Some(metrics.replay_gain_db() as f32);
+    }
+
+    /// Set the album fields from a measurement of the whole album
+    pub fn set_album_loudness(&mut self, metrics: &LoudnessMetrics) {
+        self.album_loudness_lufs = Some(metrics.integrated_lufs as f32);
+        self.album_true_peak_dbtp = Some(metrics.true_peak_dbtp as f32);
+        self.album_gain_db = Some(metrics.replay_gain_db() as f32);
+    }
+
     /// Add collaboration credit
     pub fn add_collaboration(&mut self, role: &str, name: &str, timestamp_ms: Option<u64>) {
         self.collaboration_credits.push(CollaborationCredit {
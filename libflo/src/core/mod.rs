@@ -2,13 +2,24 @@ pub mod analysis;
 pub mod audio_constants;
 pub mod crc32;
 pub mod ebu_r128;
+pub mod header;
+pub mod id3;
+pub mod integrity;
 pub mod metadata;
 pub mod rice;
+pub mod toc;
 pub mod types;
+pub mod vorbis;
 
 pub use analysis::*;
 pub use audio_constants::*;
 pub use crc32::compute as compute_crc32;
+pub use header::{
+    HeaderError, MAX_CHANNELS, MAX_HEADER_SIZE, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE,
+    SUPPORTED_BIT_DEPTHS,
+};
+pub(crate) use integrity::CONCEAL_HISTORY;
+pub use integrity::{conceal_in_place, verify_frame_crc, Concealment, FRAME_CRC_SIZE};
 
 pub use rice::{
     decode as rice_decode, decode_i32 as rice_decode_i32, encode as rice_encode,
@@ -16,13 +27,16 @@ pub use rice::{
     BitWriter,
 };
 
+pub use id3::{id3_tag_size, Id3Version};
+pub use toc::{toc_size, TOC_ENTRY_SIZE};
 pub use types::*;
 
 pub use metadata::{
-    AnimatedCover, BpmChange, CollaborationCredit, Comment, CoverVariant, CoverVariantType,
-    CreatorNote, FloMetadata, KeyChange, LoudnessPoint, Lyrics, Picture, PictureType,
-    Popularimeter, RemixChainEntry, SectionMarker, SectionType, SyncedLyrics,
-    SyncedLyricsContentType, SyncedLyricsLine, UserText, UserUrl, WaveformData,
+    AnimatedCover, BpmChange, Chapter, CollaborationCredit, Comment, CoverVariant,
+    CoverVariantType, CreatorNote, FloMetadata, KeyChange, LoudnessPoint, LoudnessSeries, Lyrics,
+    MetadataPlacement, Picture, PictureType, Popularimeter, RemixChainEntry, SectionMarker,
+    SectionType, SyncedLyrics, SyncedLyricsContentType, SyncedLyricsLine, UserText, UserUrl,
+    WaveformData,
 };
 
 pub use analysis::{
@@ -30,4 +44,7 @@ pub use analysis::{
     extract_waveform_rms, spectral_similarity, SpectralFingerprint,
 };
 
-pub use ebu_r128::{compute_ebu_r128_loudness, LoudnessMetrics};
+pub use ebu_r128::{
+    compute_ebu_r128_loudness, compute_loudness, compute_loudness_series, AlbumLoudness,
+    LoudnessMetrics, REPLAYGAIN_REFERENCE_LUFS,
+};
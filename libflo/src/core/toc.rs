@@ -0,0 +1,147 @@
+//! TOC entry parsing and serialization
+//!
+//! Entries are 20 bytes in every version. Since 1.4 the entries are followed
+//! by the absolute sample position of each frame, zero padded to a whole
+//! number of entries. Older readers skip it with `toc_size` and still see a
+//! valid TOC size. 1.0 - 1.3 files only have the millisecond timestamp. Those
+//! are converted when read: lossy files by their hop, anything else by
+//! rounding the timestamp back up to a sample.
+
+use super::header::{MAX_FRAMES_PER_SECOND, MAX_TOC_ENTRIES, TOC_SAMPLES_MINOR};
+use super::types::{FloResult, Header, TocEntry};
+
+/// size of a TOC entry
+pub const TOC_ENTRY_SIZE: u64 = 20;
+
+/// size of the sample position table after `entries` TOC entries
+fn sample_table_size(entries: usize) -> u64 {
+    (entries as u64 * 8).next_multiple_of(TOC_ENTRY_SIZE)
+}
+
+/// size of a TOC chunk with `entries` entries, as the current version writes it
+pub fn toc_size(entries: usize) -> u64 {
+    4 + entries as u64 * TOC_ENTRY_SIZE + sample_table_size(entries)
+}
+
+impl Header {
+    /// size of a TOC chunk with `entries` entries in this version
+    pub fn toc_size_for(&self, entries: u64) -> u64 {
+        if self.version_minor >= TOC_SAMPLES_MINOR {
+            toc_size(entries as usize)
+        } else {
+            4 + entries * TOC_ENTRY_SIZE
+        }
+    }
+
+    /// most TOC entries accepted, enough for the shortest frames all the way
+    /// through `total_samples`
+    pub fn max_toc_entries(&self) -> u64 {
+        let shortest = (self.sample_rate as u64 / MAX_FRAMES_PER_SECOND).max(1);
+        MAX_TOC_ENTRIES.max(self.total_samples.div_ceil(shortest) + 1)
+    }
+
+    /// error for writers about to produce a TOC readers would reject
+    pub(crate) fn check_toc_entries(&self, entries: usize) -> FloResult<()> {
+        let max = self.max_toc_entries();
+        if entries as u64 > max {
+            return Err(format!(
+                "Too many frames for one file: {} (at most {})",
+                entries, max
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl TocEntry {
+    /// parse a whole TOC chunk (entry count included) written for `header`
+    pub fn parse_all(toc: &[u8], header: &Header) -> FloResult<Vec<TocEntry>> {
+        if toc.len() < 4 {
+            return Ok(vec![]);
+        }
+
+        // the count decides the size, the sample positions are padded
+        let num_entries = u32::from_le_bytes(toc[..4].try_into().unwrap()) as u64;
+        if num_entries > header.max_toc_entries() {
+            return Err("Invalid TOC: too many entries".to_string());
+        }
+        // anything after the tables this version knows is for newer readers
+        if (toc.len() as u64) < header.toc_size_for(num_entries) {
+            return Err(format!(
+                "Invalid TOC: {} entries don't fit {} bytes",
+                num_entries,
+                toc.len()
+            ));
+        }
+
+        let num_entries = num_entries as usize;
+        let entries_end = 4 + num_entries * TOC_ENTRY_SIZE as usize;
+        let entries = &toc[4..entries_end];
+        let samples = (header.version_minor >= TOC_SAMPLES_MINOR)
+            .then(|| &toc[entries_end..entries_end + num_entries * 8]);
+
+        let u32_at =
+            |entry: &[u8], pos: usize| u32::from_le_bytes(entry[pos..pos + 4].try_into().unwrap());
+        let u64_at =
+            |entry: &[u8], pos: usize| u64::from_le_bytes(entry[pos..pos + 8].try_into().unwrap());
+
+        Ok(entries
+            .chunks_exact(TOC_ENTRY_SIZE as usize)
+            .enumerate()
+            .map(|(i, entry)| {
+                let mut toc_entry = TocEntry {
+                    frame_index: u32_at(entry, 0),
+                    byte_offset: u64_at(entry, 4),
+                    frame_size: u32_at(entry, 12),
+                    timestamp_ms: u32_at(entry, 16),
+                    first_sample: 0,
+                };
+                toc_entry.first_sample = match samples {
+                    Some(table) => u64_at(table, i * 8),
+                    None => legacy_first_sample(&toc_entry, header),
+                };
+                toc_entry
+            })
+            .collect())
+    }
+
+    /// a whole TOC chunk as the current version writes it
+    pub fn write_all(entries: &[TocEntry]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(toc_size(entries.len()) as usize);
+        out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for entry in entries {
+            out.extend_from_slice(&entry.frame_index.to_le_bytes());
+            out.extend_from_slice(&entry.byte_offset.to_le_bytes());
+            out.extend_from_slice(&entry.frame_size.to_le_bytes());
+            out.extend_from_slice(&entry.timestamp_ms.to_le_bytes());
+        }
+        for entry in entries {
+            out.extend_from_slice(&entry.first_sample.to_le_bytes());
+        }
+        out.resize(toc_size(entries.len()) as usize, 0);
+        out
+    }
+}
+
+/// hop of lossy files without gapless fields, the long block
+const LEGACY_LOSSY_HOP: u64 = 1024;
+
+/// best guess at the sample position of an entry from before 1.4
+///
+/// Lossy frames are all one hop long, so those are exact. The hop is the
+/// encoder delay where there is one, older files only had long blocks.
+/// Timestamps were rounded down, the first sample with that timestamp is
+/// exact whenever the frame starts on a whole millisecond (the default
+/// lossless frame does).
+fn legacy_first_sample(entry: &TocEntry, header: &Header) -> u64 {
+    if header.flags & 0x01 != 0 {
+        let hop = if header.has_gapless() {
+            header.encoder_delay as u64
+        } else {
+            LEGACY_LOSSY_HOP
+        };
+        entry.frame_index as u64 * hop
+    } else {
+        (entry.timestamp_ms as u64 * header.sample_rate as u64).div_ceil(1000)
+    }
+}
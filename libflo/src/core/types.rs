@@ -1,16 +1,29 @@
 //! common types for flo codec
 
+use super::metadata::MetadataPlacement;
+
 // constants
 
 /// Magic number "FLO!"
 pub const MAGIC: [u8; 4] = [0x46, 0x4c, 0x4f, 0x21];
 
-/// header size (excludes magic)
-pub const HEADER_SIZE: u64 = 66;
+/// header size (excludes magic), fields plus header crc
+pub const HEADER_SIZE: u64 = 70;
 
 /// format version
 pub const VERSION_MAJOR: u8 = 1;
-pub const VERSION_MINOR: u8 = 2;
+pub const VERSION_MINOR: u8 = 4;
+
+/// header flag: every frame ends with a CRC32 of its own bytes
+pub const FLAG_FRAME_CRC: u16 = 0x02;
+
+/// header flag: a metadata chunk sits at the start of DATA, before the first
+/// frame, and the header extension holds its size
+pub const FLAG_EARLY_META: u16 = 0x04;
+
+/// header flag: the header extension holds the encoder delay and padding, so
+/// decoders can trim the output to the exact original length
+pub const FLAG_GAPLESS: u16 = 0x08;
 
 // types
 
@@ -146,6 +159,17 @@ pub struct Header {
     pub data_size: u64,
     pub extra_size: u64,
     pub meta_size: u64,
+    /// crc of the header itself, 0 before 1.3
+    pub header_crc32: u32,
+    /// size of the early metadata chunk at the start of DATA, 0 if none
+    pub early_meta_size: u64,
+    /// samples per channel at the start of the frames that aren't audio,
+    /// 0 without `FLAG_GAPLESS`. For lossy files it's the preroll frame,
+    /// which decoders already play nothing for
+    pub encoder_delay: u32,
+    /// samples per channel of silence after the audio, all inside the last
+    /// frame, 0 without `FLAG_GAPLESS`
+    pub padding: u32,
 }
 
 impl Default for Header {
@@ -165,17 +189,78 @@ impl Default for Header {
             data_size: 0,
             extra_size: 0,
             meta_size: 0,
+            header_crc32: 0,
+            early_meta_size: 0,
+            encoder_delay: 0,
+            padding: 0,
+        }
+    }
+}
+
+impl Header {
+    /// do frames carry their own crc?
+    pub fn has_frame_crc(&self) -> bool {
+        (self.flags & FLAG_FRAME_CRC) != 0
+    }
+
+    /// is there a metadata chunk ahead of the frames?
+    pub fn has_early_meta(&self) -> bool {
+        (self.flags & FLAG_EARLY_META) != 0
+    }
+
+    /// are the encoder delay and padding recorded?
+    pub fn has_gapless(&self) -> bool {
+        (self.flags & FLAG_GAPLESS) != 0
+    }
+
+    /// exact length of the audio in samples per channel, if recorded
+    ///
+    /// `total_samples` less the encoder delay and padding. Lossy files have
+    /// a preroll frame and pad the last hop, so without this only a bound
+    /// on their length is known.
+    pub fn gapless_length(&self) -> Option<u64> {
+        self.has_gapless().then(|| {
+            self.total_samples
+                .saturating_sub(self.encoder_delay as u64 + self.padding as u64)
+        })
+    }
+
+    /// samples of the first lossy frame after the preroll that aren't audio
+    ///
+    /// A lossy file trimmed off the hop grid starts part way into that frame,
+    /// its encoder delay is the preroll `hop` plus these samples. Never more
+    /// than the frame, whatever a damaged header says.
+    pub fn lead_in(&self, hop: u32) -> u64 {
+        if self.has_gapless() {
+            (self.encoder_delay as u64)
+                .saturating_sub(hop as u64)
+                .min(hop as u64)
+        } else {
+            0
+        }
+    }
+
+    /// where this file keeps its metadata
+    pub fn metadata_placement(&self) -> MetadataPlacement {
+        match (self.has_early_meta(), self.meta_size) {
+            (false, _) => MetadataPlacement::End,
+            (true, 0) => MetadataPlacement::Start,
+            (true, _) => MetadataPlacement::Preview,
         }
     }
 }
 
-/// toc entry (20 bytes)
+/// toc entry (20 bytes, `first_sample` is stored after the entries)
 #[derive(Debug, Clone)]
 pub struct TocEntry {
     pub frame_index: u32,
     pub byte_offset: u64,
     pub frame_size: u32,
+    /// start of the frame in milliseconds, rounded down
     pub timestamp_ms: u32,
+    /// start of the frame in samples (per channel), converted from the
+    /// timestamp for files older than 1.4
+    pub first_sample: u64,
 }
 
 /// channel data within a frame
@@ -220,6 +305,101 @@ impl ChannelData {
     }
 }
 
+/// most predictor coefficients a channel can carry
+pub const MAX_PREDICTOR_ORDER: usize = 12;
+
+/// channel data borrowed from the file bytes, see `FrameRef::channels`
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelRef<'a> {
+    coeffs: [i32; MAX_PREDICTOR_ORDER],
+    order: u8,
+    pub shift_bits: u8,
+    pub residual_encoding: ResidualEncoding,
+    pub rice_parameter: u8,
+    pub residuals: &'a [u8],
+}
+
+impl<'a> ChannelRef<'a> {
+    pub(crate) fn new(
+        predictor_coeffs: &[i32],
+        shift_bits: u8,
+        residual_encoding: ResidualEncoding,
+        rice_parameter: u8,
+        residuals: &'a [u8],
+    ) -> Self {
+        let order = predictor_coeffs.len().min(MAX_PREDICTOR_ORDER);
+        let mut coeffs = [0; MAX_PREDICTOR_ORDER];
+        coeffs[..order].copy_from_slice(&predictor_coeffs[..order]);
+        ChannelRef {
+            coeffs,
+            order: order as u8,
+            shift_bits,
+            residual_encoding,
+            rice_parameter,
+            residuals,
+        }
+    }
+
+    pub fn predictor_coeffs(&self) -> &[i32] {
+        &self.coeffs[..self.order as usize]
+    }
+
+    /// copy into an owned `ChannelData`
+    pub fn to_channel_data(&self) -> ChannelData {
+        ChannelData {
+            predictor_coeffs: self.predictor_coeffs().to_vec(),
+            shift_bits: self.shift_bits,
+            residual_encoding: self.residual_encoding,
+            rice_parameter: self.rice_parameter,
+            residuals: self.residuals.to_vec(),
+        }
+    }
+}
+
+impl ChannelData {
+    /// borrow as a `ChannelRef`
+    pub fn as_channel_ref(&self) -> ChannelRef<'_> {
+        ChannelRef::new(
+            &self.predictor_coeffs,
+            self.shift_bits,
+            self.residual_encoding,
+            self.rice_parameter,
+            &self.residuals,
+        )
+    }
+}
+
+/// frame borrowed from the file bytes
+///
+/// Only the frame header is parsed up front, channels are parsed in place
+/// as `channels()` is iterated.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameRef<'a> {
+    pub frame_type: u8,
+    pub frame_samples: u32,
+    pub flags: u8,
+    /// channel payloads (size-prefixed), after the frame header
+    pub(crate) body: &'a [u8],
+    /// channels stored in the frame, 1 for transform frames
+    pub(crate) stored_channels: u8,
+}
+
+/// borrowed view of a flo file, see `Reader::read_ref`
+///
+/// Parses the header and TOC, everything else stays in the input slice.
+#[derive(Debug, Clone)]
+pub struct FloFileRef<'a> {
+    pub header: Header,
+    pub toc: Vec<TocEntry>,
+    /// the DATA chunk
+    pub data: &'a [u8],
+    pub extra: &'a [u8],
+    /// the META chunk, or the early metadata when everything was placed up front
+    pub metadata: &'a [u8],
+    /// metadata chunk at the start of DATA (a preview, or all of it), may be empty
+    pub early_metadata: &'a [u8],
+}
+
 /// audio frame (1 second)
 #[derive(Debug, Clone)]
 pub struct Frame {
@@ -274,7 +454,11 @@ pub struct FloFile {
     pub toc: Vec<TocEntry>,
     pub frames: Vec<Frame>,
     pub extra: Vec<u8>,
+    /// the META chunk, or the early metadata when everything was placed up front
     pub metadata: Vec<u8>,
+    /// indices of frames that failed their crc or couldn't be parsed,
+    /// only filled when reading with recovery enabled
+    pub damaged_frames: Vec<usize>,
 }
 
 /// result type for flo stuff
@@ -0,0 +1,659 @@
+//! Vorbis comment import and export
+//!
+//! FLAC, Ogg Vorbis and Opus files tag with Vorbis comments, `KEY=value`
+//! pairs where a key may repeat. Keys follow the common Xiph/MusicBrainz
+//! names, pictures travel as base64 FLAC picture blocks under
+//! METADATA_BLOCK_PICTURE. Keys without a field become user text, named the
+//! way ID3 TXXX frames name them where there's a convention, so tags move
+//! between FLAC and MP3 sources through flo™ unchanged.
+
+use super::ebu_r128;
+use super::id3::string_fields;
+use super::metadata::{Comment, FloMetadata, Lyrics, Picture, PictureType, UserText};
+use super::types::FloResult;
+
+string_fields!(text_fields, text_field_mut {
+    "TITLE" => title,
+    "SUBTITLE" => subtitle,
+    "GROUPING" => content_group,
+    "ALBUM" => album,
+    "ORIGINALALBUM" => original_album,
+    "DISCSUBTITLE" => set_subtitle,
+    "ISRC" => isrc,
+    "ARTIST" => artist,
+    "ALBUMARTIST" => album_artist,
+    "CONDUCTOR" => conductor,
+    "REMIXER" => remixer,
+    "ORIGINALARTIST" => original_artist,
+    "COMPOSER" => composer,
+    "LYRICIST" => lyricist,
+    "ORIGINALLYRICIST" => original_lyricist,
+    "ENCODEDBY" => encoded_by,
+    "GENRE" => genre,
+    "MOOD" => mood,
+    "KEY" => key,
+    "LANGUAGE" => language,
+    "RELEASEDATE" => release_time,
+    "ORIGINALDATE" => original_release_time,
+    "ENCODINGTIME" => encoding_time,
+    "TAGGINGDATE" => tagging_time,
+    "COPYRIGHT" => copyright,
+    "PRODUCEDNOTICE" => produced_notice,
+    "LABEL" => publisher,
+    "FILEOWNER" => file_owner,
+    "RADIOSTATION" => radio_station,
+    "RADIOSTATIONOWNER" => radio_station_owner,
+    "ALBUMSORT" => album_sort,
+    "ARTISTSORT" => artist_sort,
+    "TITLESORT" => title_sort,
+    "ORIGINALFILENAME" => original_filename,
+    "ENCODERSETTINGS" => encoder_settings,
+    "WEBSITE" => url_artist,
+    "URL_COMMERCIAL" => url_commercial,
+    "URL_COPYRIGHT" => url_copyright,
+    "URL_AUDIO_FILE" => url_audio_file,
+    "URL_AUDIO_SOURCE" => url_audio_source,
+    "URL_RADIO_STATION" => url_radio_station,
+    "URL_PAYMENT" => url_payment,
+    "URL_PUBLISHER" => url_publisher,
+});
+
+/// other names some taggers use, read only
+const ALIASES: [(&str, &str); 9] = [
+    ("ALBUM ARTIST", "ALBUMARTIST"),
+    ("ORGANIZATION", "LABEL"),
+    ("PUBLISHER", "LABEL"),
+    ("DESCRIPTION", "COMMENT"),
+    ("UNSYNCEDLYRICS", "LYRICS"),
+    ("TOTALTRACKS", "TRACKTOTAL"),
+    ("TOTALDISCS", "DISCTOTAL"),
+    ("YEAR", "DATE"),
+    ("ORIGINALYEAR", "ORIGINALDATE"),
+];
+
+/// keys for the involved people (ID3 TIPL) and the role each one stands for
+const INVOLVED_ROLES: [(&str, &str); 5] = [
+    ("ARRANGER", "arranger"),
+    ("ENGINEER", "engineer"),
+    ("PRODUCER", "producer"),
+    ("MIXER", "mix"),
+    ("DJMIXER", "DJ-mix"),
+];
+
+/// keys of user text and the ID3 TXXX description for the same thing
+const USER_TEXT_NAMES: [(&str, &str); 12] = [
+    ("MUSICBRAINZ_ARTISTID", "MusicBrainz Artist Id"),
+    ("MUSICBRAINZ_ALBUMID", "MusicBrainz Album Id"),
+    ("MUSICBRAINZ_ALBUMARTISTID", "MusicBrainz Album Artist Id"),
+    ("MUSICBRAINZ_RELEASEGROUPID", "MusicBrainz Release Group Id"),
+    ("MUSICBRAINZ_RELEASETRACKID", "MusicBrainz Release Track Id"),
+    ("MUSICBRAINZ_WORKID", "MusicBrainz Work Id"),
+    ("MUSICBRAINZ_DISCID", "MusicBrainz Disc Id"),
+    ("RELEASESTATUS", "MusicBrainz Album Status"),
+    ("RELEASETYPE", "MusicBrainz Album Type"),
+    ("RELEASECOUNTRY", "MusicBrainz Album Release Country"),
+    ("ACOUSTID_ID", "Acoustid Id"),
+    ("ACOUSTID_FINGERPRINT", "Acoustid Fingerprint"),
+];
+
+/// ReplayGain 2.0 reference loudness
+const REPLAYGAIN_REFERENCE_LUFS: f32 = ebu_r128::REPLAYGAIN_REFERENCE_LUFS as f32;
+
+/// Opus R128 gains are relative to EBU R128
+const R128_REFERENCE_LUFS: f32 = -23.0;
+
+impl FloMetadata {
+    /// Read Vorbis comments, as `(key, value)` pairs
+    pub fn from_vorbis_comments(comments: &[(String, String)]) -> FloMetadata {
+        let mut meta = FloMetadata::new();
+        meta.apply_vorbis_comments(comments);
+        meta
+    }
+
+    /// Set every field the comments have, leave the rest alone
+    ///
+    /// Comments, lyrics, pictures and user text replace the existing list
+    /// when there are any of that kind. Repeated keys are joined with "/".
+    pub fn apply_vorbis_comments(&mut self, comments: &[(String, String)]) {
+        // same key, one value
+        let mut entries: Vec<(String, Vec<&str>)> = Vec::new();
+        for (key, value) in comments {
+            let key = key.trim().to_ascii_uppercase();
+            let key = ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == key)
+                .map_or(key, |(_, name)| name.to_string());
+            match entries.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, values)) => values.push(value),
+                None => entries.push((key, vec![value])),
+            }
+        }
+
+        let has = |key: &str| entries.iter().any(|(k, _)| k == key);
+        if has("COMMENT") {
+            self.comments.clear();
+        }
+        if has("LYRICS") {
+            self.lyrics.clear();
+        }
+        if has("METADATA_BLOCK_PICTURE") || has("COVERART") {
+            self.pictures.clear();
+        }
+        if entries.iter().any(|(key, _)| is_user_text(key)) {
+            self.user_text.clear();
+        }
+        if INVOLVED_ROLES.iter().any(|(key, _)| has(key)) {
+            self.involved_people = None;
+        }
+
+        for (key, values) in &entries {
+            self.apply_comment(key, values, &entries);
+        }
+    }
+
+    /// Write the fields with a Vorbis comment key, pictures included
+    pub fn to_vorbis_comments(&self) -> Vec<(String, String)> {
+        let mut comments = Vec::new();
+        let mut add = |key: &str, value: String| comments.push((key.to_string(), value));
+
+        for (key, value) in text_fields(self) {
+            if let Some(value) = value {
+                add(key, value.clone());
+            }
+        }
+        let date = self
+            .recording_time
+            .clone()
+            .or_else(|| self.year.map(|year| year.to_string()));
+        if let Some(date) = date {
+            add("DATE", date);
+        }
+
+        let numbers = [
+            ("TRACKNUMBER", self.track_number),
+            ("TRACKTOTAL", self.track_total),
+            ("DISCNUMBER", self.disc_number),
+            ("DISCTOTAL", self.disc_total),
+            ("BPM", self.bpm),
+        ];
+        for (key, number) in numbers {
+            if let Some(number) = number {
+                add(key, number.to_string());
+            }
+        }
+
+        for (key, value) in self.replay_gain_tags() {
+            add(key, value);
+        }
+
+        for (role, name) in self.involved_people.iter().flatten() {
+            match INVOLVED_ROLES.iter().find(|(_, r)| r == role) {
+                Some((key, _)) => add(key, name.clone()),
+                None => add(&vorbis_key(role), name.clone()),
+            }
+        }
+        for (role, name) in self.musician_credits.iter().flatten() {
+            match role.is_empty() {
+                true => add("PERFORMER", name.clone()),
+                false => add("PERFORMER", format!("{} ({})", name, role)),
+            }
+        }
+
+        for comment in &self.comments {
+            add("COMMENT", comment.text.clone());
+        }
+        for lyrics in &self.lyrics {
+            add("LYRICS", lyrics.text.clone());
+        }
+        for text in &self.user_text {
+            let key = USER_TEXT_NAMES
+                .iter()
+                .find(|(_, name)| *name == text.description)
+                .map_or_else(|| vorbis_key(&text.description), |(key, _)| key.to_string());
+            add(&key, text.value.clone());
+        }
+        for picture in &self.pictures {
+            add(
+                "METADATA_BLOCK_PICTURE",
+                base64_encode(&picture.to_flac_picture()),
+            );
+        }
+        comments
+    }
+
+    /// Read a Vorbis comment block: a FLAC VORBIS_COMMENT block, or an Ogg
+    /// comment header after its packet type and "vorbis"/"OpusTags" magic
+    pub fn from_vorbis_comment_block(block: &[u8]) -> FloResult<FloMetadata> {
+        Ok(Self::from_vorbis_comments(&parse_comment_block(block)?))
+    }
+
+    /// Write a Vorbis comment block as FLAC stores it
+    pub fn to_vorbis_comment_block(&self, vendor: &str) -> Vec<u8> {
+        let comments = self.to_vorbis_comments();
+        let mut block = Vec::new();
+        let mut push = |text: &str| {
+            block.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            block.extend_from_slice(text.as_bytes());
+        };
+        push(vendor);
+        block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for (key, value) in &comments {
+            block.extend_from_slice(&((key.len() + 1 + value.len()) as u32).to_le_bytes());
+            block.extend_from_slice(key.as_bytes());
+            block.push(b'=');
+            block.extend_from_slice(value.as_bytes());
+        }
+        block
+    }
+
+    /// Internal: REPLAYGAIN_* tags, also written as ID3 TXXX frames
+    pub(super) fn replay_gain_tags(&self) -> Vec<(&'static str, String)> {
+        let gains = [
+            (
+                "REPLAYGAIN_TRACK_GAIN",
+                "REPLAYGAIN_TRACK_PEAK",
+                self.track_gain_db,
+                self.integrated_loudness_lufs,
+                self.true_peak_dbtp,
+            ),
+            (
+                "REPLAYGAIN_ALBUM_GAIN",
+                "REPLAYGAIN_ALBUM_PEAK",
+                self.album_gain_db,
+                self.album_loudness_lufs,
+                self.album_true_peak_dbtp,
+            ),
+        ];
+        let mut tags = Vec::new();
+        for (gain_key, peak_key, gain, lufs, dbtp) in gains {
+            let gain = gain.or_else(|| lufs.map(|lufs| REPLAYGAIN_REFERENCE_LUFS - lufs));
+            if let Some(gain) = gain {
+                tags.push((gain_key, format!("{:.2} dB", gain)));
+            }
+            if let Some(dbtp) = dbtp {
+                tags.push((peak_key, format!("{:.6}", 10f32.powf(dbtp / 20.0))));
+            }
+        }
+        tags
+    }
+
+    /// Internal: read one REPLAYGAIN_* tag (key uppercase), false for other keys
+    pub(super) fn apply_replay_gain_tag(&mut self, key: &str, value: &str) -> bool {
+        let album = key.contains("ALBUM");
+        match key {
+            "REPLAYGAIN_TRACK_GAIN" | "REPLAYGAIN_ALBUM_GAIN" => {
+                if let Some(gain) = parse_gain(value) {
+                    self.set_replay_gain(album, gain);
+                }
+            }
+            "REPLAYGAIN_TRACK_PEAK" | "REPLAYGAIN_ALBUM_PEAK" => {
+                if let Some(peak) = value.trim().parse::<f32>().ok().filter(|p| *p > 0.0) {
+                    let dbtp = Some(20.0 * peak.log10());
+                    match album {
+                        true => self.album_true_peak_dbtp = dbtp,
+                        false => self.true_peak_dbtp = dbtp,
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Internal: a ReplayGain 2.0 gain and the loudness it stands for
+    fn set_replay_gain(&mut self, album: bool, gain: f32) {
+        let lufs = Some(REPLAYGAIN_REFERENCE_LUFS - gain);
+        if album {
+            self.album_gain_db = Some(gain);
+            self.album_loudness_lufs = lufs;
+        } else {
+            self.track_gain_db = Some(gain);
+            self.integrated_loudness_lufs = lufs;
+        }
+    }
+
+    /// Internal: set the field(s) of one key
+    fn apply_comment(&mut self, key: &str, values: &[&str], entries: &[(String, Vec<&str>)]) {
+        let joined = values.join("/");
+        let first = values[0].trim();
+        match key {
+            "DATE" => {
+                if let Some(year) = first.get(..4).and_then(|y| y.parse().ok()) {
+                    self.year = Some(year);
+                }
+                // a bare year is just the year
+                if first.len() > 4 {
+                    self.recording_time = Some(first.to_string());
+                }
+            }
+            "TRACKNUMBER" | "DISCNUMBER" => {
+                let mut parts = first.splitn(2, '/');
+                let number = parts.next().and_then(|n| n.trim().parse().ok());
+                let total = parts.next().and_then(|n| n.trim().parse().ok());
+                let (field, total_field) = match key {
+                    "TRACKNUMBER" => (&mut self.track_number, &mut self.track_total),
+                    _ => (&mut self.disc_number, &mut self.disc_total),
+                };
+                *field = number.or(*field);
+                *total_field = total.or(*total_field);
+            }
+            // an explicit total wins over "3/12"
+            "TRACKTOTAL" => self.track_total = first.parse().ok().or(self.track_total),
+            "DISCTOTAL" => self.disc_total = first.parse().ok().or(self.disc_total),
+            "BPM" => {
+                if let Ok(bpm) = first.parse::<f32>() {
+                    self.bpm = Some(bpm.round() as u32);
+                }
+            }
+            "REPLAYGAIN_TRACK_GAIN"
+            | "REPLAYGAIN_ALBUM_GAIN"
+            | "REPLAYGAIN_TRACK_PEAK"
+            | "REPLAYGAIN_ALBUM_PEAK" => {
+                self.apply_replay_gain_tag(key, first);
+            }
+            // ReplayGain tags win where both are there
+            "R128_TRACK_GAIN" | "R128_ALBUM_GAIN"
+                if !entries
+                    .iter()
+                    .any(|(k, _)| *k == key.replace("R128", "REPLAYGAIN")) =>
+            {
+                // Q7.8 fixed point dB
+                if let Ok(gain) = first.parse::<i16>() {
+                    let lufs = R128_REFERENCE_LUFS - gain as f32 / 256.0;
+                    self.set_replay_gain(key.contains("ALBUM"), REPLAYGAIN_REFERENCE_LUFS - lufs);
+                }
+            }
+            "PERFORMER" => {
+                let credits = values.iter().map(|value| match value.rsplit_once(" (") {
+                    Some((name, role)) if role.ends_with(')') => {
+                        (role[..role.len() - 1].to_string(), name.to_string())
+                    }
+                    _ => (String::new(), value.to_string()),
+                });
+                self.musician_credits = Some(credits.collect());
+            }
+            "COMMENT" => {
+                self.comments.extend(values.iter().map(|text| Comment {
+                    language: None,
+                    description: None,
+                    text: text.to_string(),
+                }));
+            }
+            "LYRICS" => {
+                self.lyrics.extend(values.iter().map(|text| Lyrics {
+                    language: None,
+                    description: None,
+                    text: text.to_string(),
+                }));
+            }
+            "METADATA_BLOCK_PICTURE" => {
+                let pictures = values
+                    .iter()
+                    .filter_map(|value| base64_decode(value))
+                    .filter_map(|block| Picture::from_flac_picture(&block).ok());
+                self.pictures.extend(pictures);
+            }
+            // the old way: a bare base64 image, front cover
+            "COVERART" => {
+                let mime_type = entries
+                    .iter()
+                    .find(|(k, _)| k == "COVERARTMIME")
+                    .map_or("image/jpeg", |(_, mime)| mime[0]);
+                let pictures = values.iter().filter_map(|value| base64_decode(value));
+                self.pictures.extend(pictures.map(|data| Picture {
+                    mime_type: mime_type.to_string(),
+                    picture_type: PictureType::CoverFront,
+                    description: None,
+                    data,
+                }));
+            }
+            // always 89 dB SPL (-18 LUFS) in ReplayGain 2.0
+            "COVERARTMIME" | "REPLAYGAIN_REFERENCE_LOUDNESS" => {}
+            // ORIGINALYEAR may sit next to the full date
+            "ORIGINALDATE" => self.original_release_time = Some(first.to_string()),
+            _ => {
+                if let Some((_, role)) = INVOLVED_ROLES.iter().find(|(k, _)| *k == key) {
+                    let people = self.involved_people.get_or_insert_with(Vec::new);
+                    people.extend(
+                        values
+                            .iter()
+                            .map(|name| (role.to_string(), name.to_string())),
+                    );
+                } else if let Some(field) = text_field_mut(self, key) {
+                    if !joined.is_empty() {
+                        *field = Some(joined);
+                    }
+                } else {
+                    let description = USER_TEXT_NAMES
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .map_or(key, |(_, name)| name);
+                    self.user_text.push(UserText {
+                        description: description.to_string(),
+                        value: joined,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl Picture {
+    /// Read a FLAC PICTURE block (the body, without the block header)
+    pub fn from_flac_picture(block: &[u8]) -> FloResult<Picture> {
+        const INVALID: &str = "Invalid FLAC picture: unexpected end";
+        let mut pos = 0;
+        let read_u32 = |pos: &mut usize| -> FloResult<u32> {
+            Ok(u32::from_be_bytes(
+                take(block, pos, 4, INVALID)?.try_into().unwrap(),
+            ))
+        };
+
+        let picture_type = read_u32(&mut pos)?;
+        let mime_len = read_u32(&mut pos)? as usize;
+        let mime_type =
+            String::from_utf8_lossy(take(block, &mut pos, mime_len, INVALID)?).into_owned();
+        let description_len = read_u32(&mut pos)? as usize;
+        let description =
+            String::from_utf8_lossy(take(block, &mut pos, description_len, INVALID)?).into_owned();
+        // width, height, colour depth, palette size
+        take(block, &mut pos, 16, INVALID)?;
+        let data_len = read_u32(&mut pos)? as usize;
+        let data = take(block, &mut pos, data_len, INVALID)?.to_vec();
+
+        Ok(Picture {
+            mime_type,
+            picture_type: picture_type_from_index(picture_type),
+            description: (!description.is_empty()).then_some(description),
+            data,
+        })
+    }
+
+    /// Write a FLAC PICTURE block, image dimensions left at 0 (unknown)
+    pub fn to_flac_picture(&self) -> Vec<u8> {
+        let description = self.description.as_deref().unwrap_or("");
+        let mut block = Vec::with_capacity(32 + self.data.len());
+        block.extend_from_slice(&(self.picture_type as u32).to_be_bytes());
+        for text in [self.mime_type.as_str(), description] {
+            block.extend_from_slice(&(text.len() as u32).to_be_bytes());
+            block.extend_from_slice(text.as_bytes());
+        }
+        block.extend_from_slice(&[0; 16]);
+        block.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        block.extend_from_slice(&self.data);
+        block
+    }
+}
+
+/// Internal: APIC/FLAC picture type number, anything unknown is Other
+fn picture_type_from_index(index: u32) -> PictureType {
+    use PictureType::*;
+    const TYPES: [PictureType; 21] = [
+        Other,
+        FileIcon,
+        OtherFileIcon,
+        CoverFront,
+        CoverBack,
+        LeafletPage,
+        Media,
+        LeadArtist,
+        Artist,
+        Conductor,
+        Band,
+        Composer,
+        Lyricist,
+        RecordingLocation,
+        DuringRecording,
+        DuringPerformance,
+        VideoScreenCapture,
+        BrightColouredFish,
+        Illustration,
+        BandLogo,
+        PublisherLogo,
+    ];
+    TYPES.get(index as usize).copied().unwrap_or(Other)
+}
+
+/// Internal: the (key, value) pairs of a comment block
+fn parse_comment_block(block: &[u8]) -> FloResult<Vec<(String, String)>> {
+    // Ogg packets start with their type and codec magic
+    let block = [&b"\x03vorbis"[..], b"OpusTags"]
+        .iter()
+        .find_map(|magic| block.strip_prefix(*magic))
+        .unwrap_or(block);
+
+    const INVALID: &str = "Invalid Vorbis comment block: unexpected end";
+    let mut pos = 0;
+    let read_u32 = |pos: &mut usize| -> FloResult<u32> {
+        Ok(u32::from_le_bytes(
+            take(block, pos, 4, INVALID)?.try_into().unwrap(),
+        ))
+    };
+    let next = |pos: &mut usize| -> FloResult<&[u8]> {
+        let len = read_u32(pos)? as usize;
+        take(block, pos, len, INVALID)
+    };
+
+    // vendor string
+    next(&mut pos)?;
+    let count = read_u32(&mut pos)?;
+
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let comment = String::from_utf8_lossy(next(&mut pos)?).into_owned();
+        if let Some((key, value)) = comment.split_once('=') {
+            comments.push((key.to_string(), value.to_string()));
+        }
+    }
+    Ok(comments)
+}
+
+/// Internal: the next `len` bytes, or `error` past the end
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize, error: &str) -> FloResult<&'a [u8]> {
+    let bytes = data
+        .get(*pos..)
+        .and_then(|rest| rest.get(..len))
+        .ok_or_else(|| error.to_string())?;
+    *pos += len;
+    Ok(bytes)
+}
+
+/// Internal: "-6.50 dB"
+fn parse_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    number.trim().parse().ok()
+}
+
+fn is_user_text(key: &str) -> bool {
+    const OWN_KEYS: [&str; 18] = [
+        "DATE",
+        "TRACKNUMBER",
+        "TRACKTOTAL",
+        "DISCNUMBER",
+        "DISCTOTAL",
+        "BPM",
+        "REPLAYGAIN_TRACK_GAIN",
+        "REPLAYGAIN_TRACK_PEAK",
+        "R128_TRACK_GAIN",
+        "REPLAYGAIN_ALBUM_GAIN",
+        "REPLAYGAIN_ALBUM_PEAK",
+        "R128_ALBUM_GAIN",
+        "REPLAYGAIN_REFERENCE_LOUDNESS",
+        "PERFORMER",
+        "COMMENT",
+        "LYRICS",
+        "METADATA_BLOCK_PICTURE",
+        "COVERART",
+    ];
+    !OWN_KEYS.contains(&key)
+        && key != "COVERARTMIME"
+        && !INVOLVED_ROLES.iter().any(|(k, _)| *k == key)
+        && !text_fields(&FloMetadata::new())
+            .iter()
+            .any(|(k, _)| *k == key)
+}
+
+/// Internal: a description as a comment key, ASCII 0x20-0x7D without "="
+fn vorbis_key(description: &str) -> String {
+    description
+        .to_ascii_uppercase()
+        .chars()
+        .map(|c| match c {
+            ' '..='}' if c != '=' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Internal: None on anything that isn't base64, whitespace is skipped
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        if c == b'=' {
+            break;
+        }
+        let value = BASE64.iter().position(|&b| b == c)? as u32;
+        bits = (bits << 6) | value;
+        count += 1;
+        if count == 4 {
+            out.extend_from_slice(&bits.to_be_bytes()[1..]);
+            bits = 0;
+            count = 0;
+        }
+    }
+    match count {
+        0 => {}
+        2 => out.push((bits >> 4) as u8),
+        3 => out.extend_from_slice(&((bits >> 2) as u16).to_be_bytes()),
+        _ => return None,
+    }
+    Some(out)
+}
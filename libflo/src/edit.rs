@@ -0,0 +1,520 @@
+//! trim, split and concatenate flo files without re-encoding
+//!
+//! Lossless frames are independent, so whole frames are copied as they are and
+//! only the frames cut by an edit point are decoded and re-encoded.
+//!
+//! Lossy frames overlap their neighbours through the MDCT, so edits keep
+//! whole hops instead: the frame just before a cut is kept as the new
+//! preroll, the part of the next hop before the cut is recorded as encoder
+//! delay and the end is rounded up to the next hop with the rest recorded as
+//! padding. Nothing is re-encoded and cuts are still sample-exact. Joining
+//! separate lossy encodes is the exception: the frames whose blocks reach
+//! across the join are decoded and re-encoded on the same hop.
+//!
+//! In every case the TOC, `total_samples`, data CRC and `length_ms` in the
+//! metadata are rebuilt to match, and the chapter table is moved along with
+//! the audio.
+
+use crate::core::{Chapter, FloFile, FloMetadata, FloResult, Frame, Header};
+use crate::frame_decoder::FrameDecoder;
+use crate::lossy::{deserialize_frame, BlockSize, TransformEncoder};
+use crate::{Decoder, Encoder, Reader, Writer};
+
+/// Keep sample-frames `start..end` of a file
+///
+/// Positions are in samples per channel and `end` is clamped to the end of
+/// the file.
+pub fn trim(data: &[u8], start: u64, end: u64) -> FloResult<Vec<u8>> {
+    let file = Reader::new().read(data)?;
+    trim_file(&file, start, end)
+}
+
+/// Cut a file into pieces at the given sample positions
+///
+/// `points` must be increasing and inside the audio, so every piece has some.
+/// Returns `points.len() + 1` files, the first starting at 0 and the last
+/// running to the end.
+pub fn split(data: &[u8], points: &[u64]) -> FloResult<Vec<Vec<u8>>> {
+    if points.windows(2).any(|w| w[0] >= w[1]) {
+        return Err("Split points must be increasing".to_string());
+    }
+
+    let file = Reader::new().read(data)?;
+    let length = content_length(&file);
+    if let Some(point) = points.iter().find(|&&p| p == 0 || p >= length) {
+        return Err(format!(
+            "Split point {} is outside the audio (1..{})",
+            point, length
+        ));
+    }
+
+    let mut bounds = Vec::with_capacity(points.len() + 2);
+    bounds.push(0);
+    bounds.extend_from_slice(points);
+    bounds.push(length);
+
+    bounds
+        .windows(2)
+        .map(|w| trim_file(&file, w[0], w[1]))
+        .collect()
+}
+
+/// Cut out a single chapter as a file of its own
+///
+/// The chapter's title, artist, track number, ISRC and extra fields replace
+/// the file's, and the file's title becomes the album if it has none.
+pub fn extract_chapter(data: &[u8], index: usize) -> FloResult<Vec<u8>> {
+    let file = Reader::new().read(data)?;
+    let meta = FloMetadata::from_msgpack(&file.metadata).unwrap_or_default();
+    let (start, end) = meta
+        .chapter_range(index, content_length(&file))
+        .ok_or_else(|| {
+            format!(
+                "Chapter index {} out of bounds (total chapters: {})",
+                index,
+                meta.chapters.len()
+            )
+        })?;
+    let chapter = meta.chapters[index].clone();
+
+    let cut = cut(&file, start, end)?;
+    write_file(&file, &cut.frames, cut.lead_in, cut.length, |meta| {
+        if meta.album.is_none() {
+            meta.album = meta.title.take();
+        }
+        meta.title = chapter.title.or(meta.title.take());
+        meta.artist = chapter.artist.or(meta.artist.take());
+        meta.track_number = chapter.track_number.or(meta.track_number);
+        meta.isrc = chapter.isrc.or(meta.isrc.take());
+        meta.user_text.extend(chapter.user_text);
+        meta.chapters.clear();
+    })
+}
+
+/// Join files with the same sample rate, channels, bit depth and mode
+///
+/// The result keeps the metadata of the first file. Chapter tables are
+/// merged, and a part without one that has a title becomes a chapter. Lossy
+/// pieces that continue each other, as the ones `split` produces do, are
+/// joined as they are. For separate lossy encodes the frames around each join
+/// are re-encoded, and everything after it if the part's frames don't fall on
+/// the hop grid of the result.
+pub fn concat(parts: &[&[u8]]) -> FloResult<Vec<u8>> {
+    let files = parts
+        .iter()
+        .map(|data| Reader::new().read(data))
+        .collect::<FloResult<Vec<_>>>()?;
+    let first = files.first().ok_or("Nothing to concatenate")?;
+    let header = &first.header;
+
+    for (i, file) in files.iter().enumerate().skip(1) {
+        let h = &file.header;
+        if h.sample_rate != header.sample_rate
+            || h.channels != header.channels
+            || h.bit_depth != header.bit_depth
+            || is_lossy(h) != is_lossy(header)
+        {
+            return Err(format!(
+                "Part {} doesn't match the first part (sample rate, channels, bit depth or mode)",
+                i
+            ));
+        }
+    }
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut offsets = Vec::with_capacity(files.len());
+    let mut length = 0;
+
+    for (i, file) in files.iter().enumerate() {
+        if !is_lossy(header) {
+            offsets.push(length);
+            length += content_length(file);
+            frames.extend_from_slice(&file.frames);
+            continue;
+        }
+
+        // lossy: pieces from `split` share the frames around the cut, keep one
+        // copy. Without them the next part's preroll would overlap the previous
+        // part's padding and leave a hop of near-silence.
+        let shared = (1..=2)
+            .rev()
+            .find(|&n| {
+                n <= frames.len()
+                    && n <= file.frames.len()
+                    && frames[frames.len() - n..]
+                        .iter()
+                        .zip(&file.frames[..n])
+                        .all(|(a, b)| same_frame(a, b))
+            })
+            .unwrap_or(0);
+        if i > 0 && shared == 0 {
+            offsets.push(length);
+            join_lossy(first, &mut frames, length, file)?;
+            length += content_length(file);
+            continue;
+        }
+        let preroll = frames.len() - shared;
+        frames.extend_from_slice(&file.frames[shared..]);
+
+        // frame k (k >= 1) plays from (k-1)*hop, so a part starts after its
+        // preroll and lead-in, counted from the end of the first part's lead-in
+        let offset =
+            (preroll as u64 * lossy_hop(first) + lead_in(file)).saturating_sub(lead_in(first));
+        offsets.push(offset);
+        length = offset + content_length(file);
+    }
+
+    let chapters = merge_chapters(&files, &offsets);
+    write_file(first, &frames, lead_in(first), length, |meta| {
+        if !chapters.is_empty() {
+            meta.chapters = chapters;
+        }
+    })
+}
+
+/// append a separately encoded lossy part to `frames`
+///
+/// `frames` decode to `length` samples after the lead-in of `first`. Frame k
+/// codes the block `k*hop - zero_tail..(k+2)*hop - zero_tail` of the output,
+/// counting the preroll hop, so the frames whose block lies wholly before the
+/// join are kept, the next ones are re-encoded from the decoded audio of both
+/// sides, and the part's own frames take over once their blocks lie wholly in
+/// the part. If those don't land on the hop grid, the rest is re-encoded.
+fn join_lossy(
+    first: &FloFile,
+    frames: &mut Vec<Frame>,
+    length: u64,
+    file: &FloFile,
+) -> FloResult<()> {
+    let header = &first.header;
+    let channels = header.channels as usize;
+    let block_size = transform_block_size(frames.first().ok_or("Nothing to join to")?)?;
+    let hop = block_size.coefficients() as u64;
+    let zero_tail = block_size.zero_tail() as u64;
+
+    // output positions of the join, the part's content and the end
+    let join = hop + lead_in(first) + length;
+    let part_start = hop + lead_in(file);
+    let end = join + content_length(file);
+
+    let keep = (((join + zero_tail) / hop).saturating_sub(1) as usize).min(frames.len());
+
+    // part frame j lands on frame j + shift when the grids line up
+    let first_whole = (part_start + zero_tail).div_ceil(hop) as usize;
+    let aligned = first_whole < file.frames.len()
+        && transform_block_size(&file.frames[0])? == block_size
+        && join
+            .checked_sub(part_start)
+            .is_some_and(|gap| gap % hop == 0);
+    let (stop, part_frames) = if aligned {
+        let shift = ((join - part_start) / hop) as usize;
+        (first_whole + shift, first_whole + 1)
+    } else {
+        (end.div_ceil(hop) as usize, file.frames.len())
+    };
+
+    // decoded output from `from` on, the first frame only primes the MDCT
+    let decode = |frames: &[Frame], from: usize| -> FloResult<(u64, Vec<f32>)> {
+        let mut decoder = FrameDecoder::new(header.sample_rate, header.channels);
+        let mut samples = Vec::new();
+        for frame in &frames[from..] {
+            samples.extend(decoder.decode_frame(frame)?);
+        }
+        Ok(((from as u64 + 1) * hop, samples))
+    };
+    let (before_start, before) = decode(frames, keep.saturating_sub(2))?;
+    let (_, part) = decode(&file.frames[..part_frames], 0)?;
+
+    // the signal under the re-encoded blocks, silence where nothing decodes
+    let from = (keep as u64 * hop) as i64 - zero_tail as i64;
+    let to = (stop as u64 + 1) * hop - zero_tail;
+    let mut signal = vec![0.0f32; (to as i64 - from) as usize * channels];
+    for (n, pos) in (from..to as i64).enumerate() {
+        let source = match u64::try_from(pos) {
+            Ok(pos) if pos < join => pos.checked_sub(before_start).map(|i| (&before, i)),
+            Ok(pos) => (pos - join + part_start)
+                .checked_sub(hop)
+                .map(|i| (&part, i)),
+            Err(_) => None,
+        };
+        if let Some((samples, i)) = source {
+            let i = i as usize * channels;
+            if let Some(frame) = samples.get(i..i + channels) {
+                signal[n * channels..(n + 1) * channels].copy_from_slice(frame);
+            }
+        }
+    }
+
+    let quality = ((header.flags >> 8) & 0x0f) as f32 / 4.0;
+    let mut encoder = TransformEncoder::new(header.sample_rate, header.channels, quality)
+        .with_block_size(block_size);
+    frames.truncate(keep);
+    for k in 0..stop - keep {
+        let block =
+            k * hop as usize * channels..(k * hop as usize + block_size.samples()) * channels;
+        frames.push(encoder.encode_flo_frame(&signal[block]));
+    }
+    if aligned {
+        frames.extend_from_slice(&file.frames[first_whole..]);
+    }
+    Ok(())
+}
+
+/// block size of a transform frame
+fn transform_block_size(frame: &Frame) -> FloResult<BlockSize> {
+    frame
+        .channels
+        .first()
+        .and_then(|ch| deserialize_frame(&ch.residuals))
+        .map(|frame| frame.block_size)
+        .ok_or_else(|| "Failed to deserialize transform frame".to_string())
+}
+
+/// Number of sample-frames a file decodes to
+///
+/// Lossy files are padded to whole hops, so their real length comes from
+/// the header's gapless info, or `length_ms` in older files.
+pub fn content_length(file: &FloFile) -> u64 {
+    let total: u64 = file.frames.iter().map(|f| f.frame_samples as u64).sum();
+    if !is_lossy(&file.header) {
+        return total;
+    }
+    if let Some(length) = file.header.gapless_length() {
+        return length;
+    }
+
+    // length_ms is rounded down, so allow for the last partial millisecond
+    let hop = lossy_hop(file);
+    let decoded = total.saturating_sub(hop);
+    FloMetadata::from_msgpack(&file.metadata)
+        .ok()
+        .and_then(|m| m.length_ms)
+        .map(|ms| ((ms + 1) * file.header.sample_rate as u64).div_ceil(1000))
+        .map_or(decoded, |len| len.min(decoded))
+}
+
+/// frames covering a trimmed range, and where their content starts
+struct Cut {
+    frames: Vec<Frame>,
+    /// first sample of the result in the original file
+    offset: u64,
+    /// lossy: samples of the first hop before `offset`
+    lead_in: u64,
+    length: u64,
+}
+
+fn trim_file(file: &FloFile, start: u64, end: u64) -> FloResult<Vec<u8>> {
+    let cut = cut(file, start, end)?;
+    write_file(file, &cut.frames, cut.lead_in, cut.length, |meta| {
+        meta.chapters = rebase_chapters(&meta.chapters, cut.offset, cut.length);
+    })
+}
+
+fn cut(file: &FloFile, start: u64, end: u64) -> FloResult<Cut> {
+    let end = end.min(content_length(file));
+    if start >= end {
+        return Err(format!("Empty range: {}..{}", start, end));
+    }
+
+    if is_lossy(&file.header) {
+        Ok(trim_lossy(file, start, end))
+    } else {
+        trim_lossless(file, start, end)
+    }
+}
+
+fn trim_lossless(file: &FloFile, start: u64, end: u64) -> FloResult<Cut> {
+    let header = &file.header;
+    let channels = header.channels as usize;
+    let encoder = Encoder::new(header.sample_rate, header.channels, header.bit_depth)
+        .with_compression(header.compression_level);
+    let decoder = Decoder::new();
+
+    let mut frames = Vec::new();
+    let mut frame_start = 0u64;
+
+    for frame in &file.frames {
+        let frame_end = frame_start + frame.frame_samples as u64;
+        let from = frame_start;
+        frame_start = frame_end;
+
+        if frame_end <= start || from >= end {
+            continue;
+        }
+
+        if from >= start && frame_end <= end {
+            frames.push(frame.clone());
+            continue;
+        }
+
+        // an edit point falls inside this frame, re-encode the part we keep
+        let keep_from = (start.max(from) - from) as usize;
+        let keep_to = (end.min(frame_end) - from) as usize;
+        let kept: Vec<Vec<i32>> = decoder
+            .decode_frame_int(frame, channels)?
+            .into_iter()
+            .map(|ch| ch[keep_from..keep_to].to_vec())
+            .collect();
+        frames.push(encoder.encode_frame_int(kept, keep_to - keep_from));
+    }
+
+    Ok(Cut {
+        frames,
+        offset: start,
+        lead_in: 0,
+        length: end - start,
+    })
+}
+
+fn trim_lossy(file: &FloFile, start: u64, end: u64) -> Cut {
+    let hop = lossy_hop(file);
+
+    // frame k (k >= 1) decodes to samples (k-1)*hop..k*hop of the output
+    // before the file's own lead-in comes off, frame `first` only primes the
+    // overlap for the frame after it
+    let (start_out, end_out) = (start + lead_in(file), end + lead_in(file));
+    let first = (start_out / hop) as usize;
+    let last = (end_out.div_ceil(hop) as usize).min(file.frames.len() - 1);
+
+    Cut {
+        frames: file.frames[first..=last].to_vec(),
+        offset: start,
+        lead_in: start_out - first as u64 * hop,
+        length: end - start,
+    }
+}
+
+/// chapters overlapping `offset..offset + length`, moved to start at 0
+fn rebase_chapters(chapters: &[Chapter], offset: u64, length: u64) -> Vec<Chapter> {
+    let end = offset + length;
+    chapters
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| {
+            let next = chapters.get(i + 1).map_or(u64::MAX, |n| n.start_sample);
+            c.start_sample < end && next > offset
+        })
+        .map(|(_, c)| Chapter {
+            start_sample: c.start_sample.saturating_sub(offset),
+            ..c.clone()
+        })
+        .collect()
+}
+
+/// chapter table of joined parts starting at `offsets`
+fn merge_chapters(files: &[FloFile], offsets: &[u64]) -> Vec<Chapter> {
+    let mut merged: Vec<Chapter> = Vec::new();
+    let mut had_chapters = false;
+    for (file, &offset) in files.iter().zip(offsets) {
+        let meta = FloMetadata::from_msgpack(&file.metadata).unwrap_or_default();
+        had_chapters |= !meta.chapters.is_empty();
+        let chapters = if meta.chapters.is_empty() && meta.title.is_some() {
+            vec![Chapter {
+                start_sample: 0,
+                title: meta.title,
+                artist: meta.artist,
+                track_number: meta.track_number,
+                isrc: meta.isrc,
+                ..Chapter::default()
+            }]
+        } else {
+            meta.chapters
+        };
+
+        for chapter in chapters {
+            // a chapter split across parts continues rather than starting again
+            let continues = chapter.start_sample == 0
+                && merged.last().is_some_and(|last| {
+                    last.title == chapter.title
+                        && last.artist == chapter.artist
+                        && last.track_number == chapter.track_number
+                });
+            if !continues {
+                merged.push(Chapter {
+                    start_sample: chapter.start_sample + offset,
+                    ..chapter
+                });
+            }
+        }
+    }
+
+    // rejoining pieces of a file without chapters shouldn't add a table
+    if !had_chapters && merged.len() < 2 {
+        merged.clear();
+    }
+    merged
+}
+
+/// write `frames` with the stream parameters and metadata of `file`
+///
+/// A lossy result starts `lead_in` samples into the hop after its preroll.
+/// `length_ms` is set from `length`, `edit` makes any other metadata changes.
+fn write_file(
+    file: &FloFile,
+    frames: &[Frame],
+    lead_in: u64,
+    length: u64,
+    edit: impl FnOnce(&mut FloMetadata),
+) -> FloResult<Vec<u8>> {
+    let header = &file.header;
+    let sample_rate = header.sample_rate as u64;
+    let metadata = edit_metadata(&file.metadata, |meta| {
+        meta.length_ms = Some(length * 1000 / sample_rate);
+        edit(meta);
+    });
+
+    let mut writer = Writer::new()
+        .with_frame_crc(header.has_frame_crc())
+        .with_metadata_placement(header.metadata_placement());
+    if is_lossy(header) {
+        // the first frame is the preroll, the rest runs past `length` to the hop
+        writer = writer.with_gapless((lossy_hop(file) + lead_in) as u32, length);
+    }
+    writer.write_ex(
+        header.sample_rate,
+        header.channels,
+        header.bit_depth,
+        header.compression_level,
+        is_lossy(header),
+        ((header.flags >> 8) & 0x0f) as u8,
+        frames,
+        &metadata,
+    )
+}
+
+/// metadata with `edit` applied, left alone if it isn't flo metadata
+fn edit_metadata(metadata: &[u8], edit: impl FnOnce(&mut FloMetadata)) -> Vec<u8> {
+    if metadata.is_empty() {
+        return vec![];
+    }
+    match FloMetadata::from_msgpack(metadata) {
+        Ok(mut meta) => {
+            edit(&mut meta);
+            meta.to_msgpack().unwrap_or_else(|_| metadata.to_vec())
+        }
+        Err(_) => metadata.to_vec(),
+    }
+}
+
+fn same_frame(a: &Frame, b: &Frame) -> bool {
+    a.frame_type == b.frame_type
+        && a.frame_samples == b.frame_samples
+        && a.channels.len() == b.channels.len()
+        && a.channels
+            .iter()
+            .zip(&b.channels)
+            .all(|(x, y)| x.residuals == y.residuals)
+}
+
+fn is_lossy(header: &Header) -> bool {
+    (header.flags & 0x01) != 0
+}
+
+fn lossy_hop(file: &FloFile) -> u64 {
+    file.frames.first().map_or(1024, |f| f.frame_samples as u64)
+}
+
+/// samples of the first hop a lossy file skips, see `Header::lead_in`
+fn lead_in(file: &FloFile) -> u64 {
+    file.header.lead_in(lossy_hop(file) as u32)
+}
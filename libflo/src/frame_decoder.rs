@@ -0,0 +1,187 @@
+//! frame-at-a-time decoding shared by every decoder
+//!
+//! `Decoder`, `StreamingDecoder`, `seeking` and the wasm bindings all decode
+//! through here, so lossless reconstruction, mid-side and the lossy preroll
+//! only exist once.
+use crate::core::{ChannelRef, Concealment, FloResult, Frame, FrameRef, FrameType, Header};
+use crate::lossless::decoder::decode_interleaved;
+use crate::lossy::{deserialize_frame, TransformDecoder, TransformFrame};
+
+/// decodes one parsed frame at a time, carrying what a frame hands to the next
+///
+/// Lossless frames stand alone. Transform frames overlap the frame before
+/// them, so the first one after `new` or `restart` only primes the MDCT and
+/// decodes to nothing, like the preroll frame at the start of a lossy file.
+pub struct FrameDecoder {
+    sample_rate: u32,
+    channels: u8,
+    /// MDCT state, made on the first transform frame
+    transform: Option<TransformDecoder>,
+    /// has a transform frame gone through since the last restart?
+    primed: bool,
+    /// transform frames concealed since the last good one
+    lost_in_row: u32,
+    /// longest frame decoded so far, samples per channel
+    longest_frame: u32,
+    /// MDCT lookahead of the last transform frame, 0 for lossless
+    overlap: usize,
+}
+
+impl FrameDecoder {
+    pub fn new(sample_rate: u32, channels: u8) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            transform: None,
+            primed: false,
+            lost_in_row: 0,
+            longest_frame: 0,
+            overlap: 0,
+        }
+    }
+
+    /// decode a frame borrowed from the file bytes to interleaved samples
+    pub fn decode(&mut self, frame: &FrameRef<'_>) -> FloResult<Vec<f32>> {
+        self.decode_parts(
+            frame.frame_type,
+            frame.frame_samples,
+            frame.flags,
+            frame.channels(),
+        )
+    }
+
+    /// decode an owned frame to interleaved samples
+    pub fn decode_frame(&mut self, frame: &Frame) -> FloResult<Vec<f32>> {
+        self.decode_parts(
+            frame.frame_type,
+            frame.frame_samples,
+            frame.flags,
+            frame.channels.iter().map(|ch| Ok(ch.as_channel_ref())),
+        )
+    }
+
+    /// prime the overlap with the frame before a seek target
+    ///
+    /// Pass None if that frame is missing or damaged, the target then plays
+    /// without the overlap instead of being dropped as preroll.
+    pub fn prime(&mut self, previous: Option<&FrameRef<'_>>) {
+        if previous.is_none_or(|frame| self.decode(frame).is_err()) {
+            self.primed = true;
+        }
+    }
+
+    /// will the next transform frame only prime the overlap?
+    pub fn needs_priming(&self) -> bool {
+        !self.primed
+    }
+
+    /// longest frame decoded so far, the usual frame length of the stream
+    pub fn frame_samples(&self) -> Option<u32> {
+        (self.longest_frame > 0).then_some(self.longest_frame)
+    }
+
+    /// algorithmic delay of the stream in samples per channel
+    ///
+    /// The frame length, plus the MDCT overlap for transform frames. None
+    /// until a frame has been decoded.
+    pub fn algorithmic_delay(&self) -> Option<usize> {
+        self.frame_samples()
+            .map(|samples| samples as usize + self.overlap)
+    }
+
+    /// forget the overlap, after a seek or a gap in the stream
+    pub fn restart(&mut self) {
+        self.transform = None;
+        self.primed = false;
+        self.lost_in_row = 0;
+    }
+
+    /// stand in for a lost transform frame using the last good one
+    ///
+    /// Keeps the MDCT overlap running, see `TransformDecoder::conceal_frame`.
+    /// With nothing decoded yet the lost frame was the preroll, so there's
+    /// no output and the next frame plays. Lossless gaps are left to
+    /// `conceal_in_place`, they need the samples on both sides.
+    pub fn conceal_transform(&mut self, mode: Concealment) -> Vec<f32> {
+        self.lost_in_row += 1;
+        let lost_in_row = self.lost_in_row;
+        match self
+            .transform
+            .as_mut()
+            .and_then(|transform| transform.conceal_frame(mode, lost_in_row))
+        {
+            Some(samples) => samples,
+            None => {
+                self.primed = true;
+                Vec::new()
+            }
+        }
+    }
+
+    /// decode a frame of either kind, owned or borrowed
+    fn decode_parts<'a>(
+        &mut self,
+        frame_type: u8,
+        frame_samples: u32,
+        flags: u8,
+        mut frame_data: impl Iterator<Item = FloResult<ChannelRef<'a>>>,
+    ) -> FloResult<Vec<f32>> {
+        if !FrameType::from(frame_type).is_transform() {
+            let samples = decode_interleaved(
+                frame_data,
+                frame_samples as usize,
+                flags,
+                self.channels as usize,
+            )?;
+            self.longest_frame = self.longest_frame.max(frame_samples);
+            self.overlap = 0;
+            return Ok(samples);
+        }
+
+        // transform data is the first channel's residuals
+        let data = frame_data
+            .next()
+            .ok_or("Transform frame has no channel data")??;
+        let frame =
+            deserialize_frame(data.residuals).ok_or("Failed to deserialize transform frame")?;
+        let samples = self.synthesize(&frame);
+        self.longest_frame = self
+            .longest_frame
+            .max(frame.block_size.coefficients() as u32);
+        self.overlap = frame.block_size.overlap();
+        self.lost_in_row = 0;
+        Ok(samples)
+    }
+
+    /// run a transform frame through the MDCT, dropping the preroll output
+    fn synthesize(&mut self, frame: &TransformFrame) -> Vec<f32> {
+        let (sample_rate, channels) = (self.sample_rate, self.channels);
+        let samples = self
+            .transform
+            .get_or_insert_with(|| TransformDecoder::new(sample_rate, channels))
+            .decode_frame(frame);
+
+        if !self.primed {
+            self.primed = true;
+            return Vec::new();
+        }
+        samples
+    }
+}
+
+/// cut what comes before the audio off the output of lossy frame 1
+///
+/// `hop` is the frame length. Does nothing unless the file was trimmed off
+/// the hop grid, see `Header::lead_in`.
+pub(crate) fn trim_lead_in(samples: &mut Vec<f32>, header: &Header, hop: u32) {
+    let lead_in = header.lead_in(hop) as usize * header.channels as usize;
+    samples.drain(..lead_in.min(samples.len()));
+}
+
+/// cut the encoder padding off the output of a file's last frame
+///
+/// Does nothing for files without gapless info.
+pub(crate) fn trim_padding(samples: &mut Vec<f32>, header: &Header) {
+    let padding = header.padding as usize * header.channels as usize;
+    samples.truncate(samples.len().saturating_sub(padding));
+}
@@ -4,27 +4,40 @@ use rmp_serde::{from_slice, to_vec_named};
 use wasm_bindgen::prelude::*;
 
 pub mod core;
+pub mod edit;
 pub mod lossless;
 pub mod lossy;
 pub mod streaming;
 
+mod frame_decoder;
+mod random_access;
 mod reader;
+pub mod replay_gain;
 pub mod seeking;
 mod writer;
 
 pub use core::{
-    compute_crc32, compute_ebu_r128_loudness, extract_spectral_fingerprint, extract_waveform_peaks,
-    metadata::*, rice, ChannelData, FloFile, FloResult, FrameType, LoudnessMetrics,
-    ResidualEncoding, HEADER_SIZE, MAGIC, VERSION_MAJOR, VERSION_MINOR,
+    compute_crc32, compute_ebu_r128_loudness, compute_loudness, compute_loudness_series,
+    extract_spectral_fingerprint, extract_waveform_peaks, id3_tag_size, metadata::*, rice,
+    AlbumLoudness, ChannelData, ChannelRef, Concealment, FloFile, FloFileRef, FloResult, FrameRef,
+    FrameType, HeaderError, Id3Version, LoudnessMetrics, ResidualEncoding, FLAG_EARLY_META,
+    FLAG_FRAME_CRC, FLAG_GAPLESS, HEADER_SIZE, MAGIC, REPLAYGAIN_REFERENCE_LUFS, VERSION_MAJOR,
+    VERSION_MINOR,
 };
-pub use lossless::{lpc, Decoder, Encoder};
+pub use frame_decoder::FrameDecoder;
+pub use lossless::{lpc, ConcealingDecoder, Decoder, Encoder};
 pub use lossy::{
     deserialize_frame, serialize_frame, BlockSize, Mdct, PsychoacousticModel, QualityPreset,
     TransformDecoder as LossyDecoder, TransformEncoder as LossyEncoder, TransformFrame, WindowType,
 };
-pub use reader::Reader;
+pub use random_access::{RandomAccessDecoder, DEFAULT_CACHE_FRAMES};
+pub use reader::{ChannelRefs, Reader};
+#[cfg(feature = "async")]
+pub use streaming::{DecodeStream, EncodeSink};
 pub use streaming::{
-    DecoderState, EncodedFrame, StreamingAudioInfo, StreamingDecoder, StreamingEncoder,
+    DecoderState, Depacketizer, EncodedFrame, EncoderJournal, FeedStatus, PacketStats, Packetizer,
+    RecoveredEncode, SeekRange, StreamParams, StreamingAudioInfo, StreamingDecoder,
+    StreamingEncoder, LOW_LATENCY_MAX_MS, LOW_LATENCY_MIN_MS,
 };
 pub use writer::Writer;
 
@@ -34,7 +47,7 @@ pub use writer::Writer;
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
 pub struct AudioInfo {
-    /// version string like "1.2"
+    /// version string like "1.4"
     #[wasm_bindgen(skip)]
     pub version: String,
     /// Sample rate in Hz
@@ -47,6 +60,12 @@ pub struct AudioInfo {
     /// instants per channel (not interleaved samples). Use metadata.length_ms
     /// for a quick duration lookup.
     pub total_samples: u64,
+    /// Samples per channel at the start that aren't audio (the lossy
+    /// preroll), 0 if the file doesn't record it
+    pub encoder_delay: u32,
+    /// Samples per channel of padding after the audio, 0 if the file
+    /// doesn't record it
+    pub padding: u32,
     /// Duration in seconds
     pub duration_secs: f64,
     /// File size in bytes
@@ -108,6 +127,7 @@ pub fn encode(
         sample_rate,
         channels,
         50, // 50 peaks per second default
+        10, // 10 loudness points per second default
     );
 
     let encoder = Encoder::new(sample_rate, channels, bit_depth);
@@ -157,6 +177,7 @@ pub fn encode_lossy(
         sample_rate,
         channels,
         50, // 50 peaks per second default
+        10, // 10 loudness points per second default
     );
 
     let mut encoder = lossy::TransformEncoder::new(sample_rate, channels, quality_f32);
@@ -197,6 +218,7 @@ pub fn encode_with_bitrate(
         sample_rate,
         channels,
         50, // 50 peaks per second default
+        10, // 10 loudness points per second default
     );
 
     let mut encoder = lossy::TransformEncoder::new(sample_rate, channels, quality);
@@ -213,15 +235,17 @@ pub fn encode_with_bitrate(
 /// * `sample_rate` - Sample rate in Hz
 /// * `channels` - Number of audio channels
 /// * `peaks_per_second` - Number of peaks per second (default: 50)
+/// * `loudness_points_per_second` - Loudness series resolution (default: 10)
 ///
 /// # Returns
-/// Updated metadata with analysis data (waveform, spectrum, loudness)
+/// Updated metadata with analysis data (waveform, spectrum, loudness, track gain)
 fn add_analysis_data_if_missing(
     metadata: &[u8],
     samples: &[f32],
     sample_rate: u32,
     channels: u8,
     peaks_per_second: u32,
+    loudness_points_per_second: u32,
 ) -> Vec<u8> {
     // Try to parse existing metadata
     let mut flo_metadata: FloMetadata = if !metadata.is_empty() {
@@ -257,18 +281,36 @@ fn add_analysis_data_if_missing(
         }
     }
 
-    // Add loudness metrics if not present
-    if flo_metadata.loudness_profile.is_empty() {
-        let loudness_metrics =
-            core::ebu_r128::compute_ebu_r128_loudness(samples, channels, sample_rate);
-
-        // Convert loudness metrics to LoudnessPoint format
-        let loudness_point = core::metadata::LoudnessPoint {
-            timestamp_ms: 0,
-            lufs: loudness_metrics.integrated_lufs as f32,
-        };
-
-        flo_metadata.loudness_profile = vec![loudness_point];
+    // Loudness series, and track gain and peak for players that normalise,
+    // both from one pass when both are missing
+    match (
+        flo_metadata.loudness_series.is_none(),
+        flo_metadata.track_gain_db.is_none(),
+    ) {
+        (true, true) => {
+            let (loudness_metrics, series) = core::ebu_r128::compute_loudness(
+                samples,
+                channels,
+                sample_rate,
+                loudness_points_per_second,
+            );
+            flo_metadata.loudness_series = Some(series);
+            flo_metadata.set_track_loudness(&loudness_metrics);
+        }
+        (true, false) => {
+            flo_metadata.loudness_series = Some(core::ebu_r128::compute_loudness_series(
+                samples,
+                channels,
+                sample_rate,
+                loudness_points_per_second,
+            ));
+        }
+        (false, true) => {
+            let loudness_metrics =
+                core::ebu_r128::compute_ebu_r128_loudness(samples, channels, sample_rate);
+            flo_metadata.set_track_loudness(&loudness_metrics);
+        }
+        (false, false) => {}
     }
 
     // Always set length_ms (duration in milliseconds).
@@ -294,86 +336,71 @@ fn add_analysis_data_if_missing(
 /// Interleaved audio samples (f32, -1.0 to 1.0)
 #[wasm_bindgen]
 pub fn decode(data: &[u8]) -> Result<Vec<f32>, JsValue> {
-    // figure out if its transform/lossy
-    let reader = Reader::new();
-    let file = reader.read(data).map_err(to_js_err)?;
-
-    // any transform frames means lossy
-    let is_transform = file
-        .frames
-        .iter()
-        .any(|f| f.frame_type == (FrameType::Transform as u8));
-
-    if is_transform {
-        // lossy
-        decode_transform_file(&file).map_err(to_js_err)
-    } else {
-        // lossless
-        let decoder = Decoder::new();
-        decoder.decode_file(&file).map_err(to_js_err)
-    }
+    decode_with_concealment(data, None).map_err(to_js_err)
 }
 
-/// Decode a lossy flo file that uses transform-based compression
+/// decode a possibly damaged flo file
+///
+/// Frames that fail their CRC or can't be parsed are filled in instead of
+/// failing the whole decode. Use `find_damaged_frames` to see which ones.
 ///
 /// # Arguments
-/// * `file` - Parsed flo file
+/// * `data` - flo file bytes
+/// * `mode` - How to fill damaged frames (silence or interpolation)
 ///
 /// # Returns
 /// Interleaved audio samples (f32, -1.0 to 1.0)
-/// Decode a transform-based lossy file
-fn decode_transform_file(file: &FloFile) -> FloResult<Vec<f32>> {
-    let mut decoder = lossy::TransformDecoder::new(file.header.sample_rate, file.header.channels);
-    let mut all_samples = Vec::new();
-    let mut frame_count = 0;
-
-    for frame in &file.frames {
-        if frame.channels.is_empty() {
-            continue;
-        }
-
-        // transform data is in first channels residuals
-        let frame_data = &frame.channels[0].residuals;
+#[wasm_bindgen]
+pub fn decode_tolerant(data: &[u8], mode: Concealment) -> Result<Vec<f32>, JsValue> {
+    decode_with_concealment(data, Some(mode)).map_err(to_js_err)
+}
 
-        if let Some(transform_frame) = lossy::deserialize_frame(frame_data) {
-            let samples = decoder.decode_frame(&transform_frame);
+/// find frames that fail their integrity check
+///
+/// # Arguments
+/// * `data` - flo file bytes
+///
+/// # Returns
+/// Indices of damaged frames (empty if the file is intact)
+#[wasm_bindgen]
+pub fn find_damaged_frames(data: &[u8]) -> Result<Vec<u32>, JsValue> {
+    let reader = Reader::new().with_recovery(true);
+    let file = reader.read(data).map_err(to_js_err)?;
+    Ok(file.damaged_frames.iter().map(|&i| i as u32).collect())
+}
 
-            // skip first frame (pre-roll for overlap-add)
-            if frame_count > 0 {
-                all_samples.extend(samples);
-            }
-            frame_count += 1;
-        } else {
-            return Err("Failed to deserialize transform frame".to_string());
-        }
+/// Decode either kind of file, concealing damaged frames if a mode is given
+fn decode_with_concealment(data: &[u8], concealment: Option<Concealment>) -> FloResult<Vec<f32>> {
+    match concealment {
+        Some(mode) => Decoder::new().with_concealment(mode).decode(data),
+        None => Decoder::new().decode(data),
     }
-
-    Ok(all_samples)
 }
 
 /// Validate flo file integrity
 ///
+/// Files with frame CRCs have every frame checked on its own,
+/// `find_damaged_frames` then says which ones failed.
+///
 /// # Arguments
 /// * `data` - flo file bytes
 ///
 /// # Returns
-/// true if file is valid and CRC matches
+/// true if file is valid and every CRC matches
 #[wasm_bindgen]
 pub fn validate(data: &[u8]) -> Result<bool, JsValue> {
-    let reader = Reader::new();
-    match reader.read(data) {
-        Ok(file) => {
-            let start = (4 + file.header.header_size + file.header.toc_size) as usize;
-            let end = start + (file.header.data_size as usize);
-            if end <= data.len() {
-                let computed = core::crc32::compute(&data[start..end]);
-                Ok(computed == file.header.data_crc32)
-            } else {
-                Ok(false)
-            }
-        }
-        Err(_) => Ok(false),
+    let reader = Reader::new().with_recovery(true);
+    let Ok(file) = reader.read(data) else {
+        return Ok(false);
+    };
+    if file.header.check_file_size(data.len() as u64).is_err() || !file.damaged_frames.is_empty() {
+        return Ok(false);
     }
+
+    // the chunk crc also covers early metadata, which frame crcs don't
+    let start = (4 + file.header.header_size + file.header.toc_size) as usize;
+    let end = start + (file.header.data_size as usize);
+    Ok(core::crc32::compute(&data[start..end]) == file.header.data_crc32)
 }
 
 /// Get information about a flo file
@@ -388,11 +415,13 @@ pub fn info(data: &[u8]) -> Result<AudioInfo, JsValue> {
     let reader = Reader::new();
     let file = reader.read(data).map_err(to_js_err)?;
 
-    // Use length_ms from metadata for duration
+    // exact length if the file records it, else length_ms from metadata
     let metadata = FloMetadata::from_msgpack(&file.metadata).unwrap_or_default();
-    let duration_secs = metadata
-        .length_ms
-        .map(|ms| ms as f64 / 1000.0)
+    let duration_secs = file
+        .header
+        .gapless_length()
+        .map(|length| length as f64 / file.header.sample_rate as f64)
+        .or_else(|| metadata.length_ms.map(|ms| ms as f64 / 1000.0))
         .unwrap_or_else(|| {
             // Fallback: calculate from total_samples / sample_rate
             file.header.total_samples as f64 / file.header.sample_rate as f64
@@ -430,6 +459,8 @@ pub fn info(data: &[u8]) -> Result<AudioInfo, JsValue> {
         channels: file.header.channels,
         bit_depth: file.header.bit_depth,
         total_samples: file.header.total_samples,
+        encoder_delay: file.header.encoder_delay,
+        padding: file.header.padding,
         duration_secs,
         file_size: data.len(),
         compression_ratio,
@@ -472,8 +503,11 @@ pub fn format_time_ms(milliseconds: f64) -> String {
 
 /// Extract TOC (Table of Contents) entries from a flo file
 ///
+/// Only the first `index_size` bytes of the file are needed.
+///
 /// # Returns
-/// Array of TOC entries with frame indices, byte offsets, and timestamps
+/// Array of TOC entries with frame indices, byte offsets, timestamps and
+/// sample positions
 #[wasm_bindgen]
 pub fn get_toc(flo_data: &[u8]) -> Result<Vec<JsValue>, JsValue> {
     let toc = seeking::get_toc(flo_data).map_err(to_js_err)?;
@@ -490,6 +524,12 @@ pub fn get_toc(flo_data: &[u8]) -> Result<Vec<JsValue>, JsValue> {
             .ok();
             js_sys::Reflect::set(&obj, &"frame_size".into(), &entry.frame_size.into()).ok();
             js_sys::Reflect::set(&obj, &"timestamp_ms".into(), &entry.timestamp_ms.into()).ok();
+            js_sys::Reflect::set(
+                &obj,
+                &"first_sample".into(),
+                &(entry.first_sample as f64).into(),
+            )
+            .ok();
             obj.into()
         })
         .collect();
@@ -509,10 +549,51 @@ pub fn decode_frame_at(flo_data: &[u8], frame_index: u32) -> Result<Vec<f32>, Js
     seeking::decode_frame_at(flo_data, frame_index).map_err(to_js_err)
 }
 
-/// Seek to a specific time in milliseconds
+/// Decode sample-frames `start..end` without decoding the entire file
+///
+/// # Arguments
+/// * `flo_data` - Complete flo file bytes
+/// * `start` - First sample-frame (samples per channel)
+/// * `end` - Sample-frame to stop before, clamped to the end of the audio
+///
+/// # Returns
+/// Exactly the requested interleaved samples, as a full decode gives them
+#[wasm_bindgen]
+pub fn decode_range(flo_data: &[u8], start: f64, end: f64) -> Result<Vec<f32>, JsValue> {
+    seeking::decode_range(flo_data, start as u64, end as u64).map_err(to_js_err)
+}
+
+/// Decode the audio between two times in milliseconds
 ///
 /// # Arguments
 /// * `flo_data` - Complete flo file bytes
+/// * `start_ms` - Start time in milliseconds
+/// * `end_ms` - End time in milliseconds, clamped to the end of the audio
+///
+/// # Returns
+/// Interleaved samples from `start_ms` up to `end_ms`, rounded down to the sample
+#[wasm_bindgen]
+pub fn decode_range_ms(flo_data: &[u8], start_ms: u32, end_ms: u32) -> Result<Vec<f32>, JsValue> {
+    seeking::decode_range_ms(flo_data, start_ms, end_ms).map_err(to_js_err)
+}
+
+/// Number of bytes at the start of a file holding its header and TOC
+///
+/// # Arguments
+/// * `header_bytes` - The start of a flo file, at least the whole header
+///
+/// # Returns
+/// How many bytes `get_toc`, `seek_to_time` and `seek_to_sample` need
+#[wasm_bindgen]
+pub fn index_size(header_bytes: &[u8]) -> Result<f64, JsValue> {
+    let size = seeking::index_size(header_bytes).map_err(to_js_err)?;
+    Ok(size as f64)
+}
+
+/// Seek to a specific time in milliseconds
+///
+/// # Arguments
+/// * `flo_data` - flo file bytes, the header and TOC are enough
 /// * `time_ms` - Target time in milliseconds
 ///
 /// # Returns
@@ -520,7 +601,50 @@ pub fn decode_frame_at(flo_data: &[u8], frame_index: u32) -> Result<Vec<f32>, Js
 #[wasm_bindgen]
 pub fn seek_to_time(flo_data: &[u8], time_ms: u32) -> Result<JsValue, JsValue> {
     let result = seeking::seek_to_time(flo_data, time_ms).map_err(to_js_err)?;
+    seek_result_to_js(&result)
+}
+
+/// Seek to a sample of the decoded audio (per channel)
+///
+/// # Arguments
+/// * `flo_data` - flo file bytes, the header and TOC are enough
+/// * `sample` - Target sample position
+///
+/// # Returns
+/// Seek result object, same shape as `seek_to_time`
+#[wasm_bindgen]
+pub fn seek_to_sample(flo_data: &[u8], sample: f64) -> Result<JsValue, JsValue> {
+    let table = seeking::SeekTable::parse(flo_data).map_err(to_js_err)?;
+    let result = table.seek_to_sample(sample as u64).map_err(to_js_err)?;
+    seek_result_to_js(&result)
+}
 
+/// Seek to the start of a chapter
+///
+/// # Arguments
+/// * `flo_data` - Complete flo file bytes
+/// * `chapter_index` - Zero-based chapter index
+///
+/// # Returns
+/// Seek result object, same shape as `seek_to_time`
+#[wasm_bindgen]
+pub fn seek_to_chapter(flo_data: &[u8], chapter_index: usize) -> Result<JsValue, JsValue> {
+    let result = seeking::seek_to_chapter(flo_data, chapter_index).map_err(to_js_err)?;
+    seek_result_to_js(&result)
+}
+
+/// Chapter table of a flo file
+///
+/// # Returns
+/// Array of chapter objects (start_sample, title, artist, track_number, isrc, user_text)
+#[wasm_bindgen]
+pub fn get_chapters(flo_data: &[u8]) -> Result<JsValue, JsValue> {
+    let chapters = seeking::get_chapters(flo_data).map_err(to_js_err)?;
+    serde_wasm_bindgen::to_value(&chapters)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+fn seek_result_to_js(result: &seeking::SeekResult) -> Result<JsValue, JsValue> {
     let obj = js_sys::Object::new();
     js_sys::Reflect::set(&obj, &"frame_index".into(), &result.frame_index.into())?;
     js_sys::Reflect::set(
@@ -539,6 +663,77 @@ pub fn seek_to_time(flo_data: &[u8], time_ms: u32) -> Result<JsValue, JsValue> {
     Ok(obj.into())
 }
 
+// random access decoder wasm api
+
+/// Decoder for reading a flo™ file out of order, e.g. for waveforms and scrubbing
+///
+/// Parses the file once and keeps recently decoded frames.
+#[wasm_bindgen]
+pub struct WasmRandomAccessDecoder {
+    inner: RandomAccessDecoder,
+}
+
+#[wasm_bindgen]
+impl WasmRandomAccessDecoder {
+    /// Parse a flo™ file, the bytes are copied
+    #[wasm_bindgen(constructor)]
+    pub fn new(flo_data: &[u8]) -> Result<WasmRandomAccessDecoder, JsValue> {
+        let inner = RandomAccessDecoder::new(flo_data.to_vec()).map_err(to_js_err)?;
+        Ok(Self { inner })
+    }
+
+    /// Keep up to this many decoded frames (16 by default)
+    #[wasm_bindgen]
+    pub fn with_cache_frames(mut self, frames: usize) -> Self {
+        self.inner = self.inner.with_cache_frames(frames);
+        self
+    }
+
+    /// Read `len` sample-frames starting at `sample`, interleaved
+    ///
+    /// Stops early at the end of the audio.
+    #[wasm_bindgen]
+    pub fn read_at(&mut self, sample: f64, len: usize) -> Result<Vec<f32>, JsValue> {
+        self.inner.read_at(sample as u64, len).map_err(to_js_err)
+    }
+
+    /// Decode one frame by index
+    #[wasm_bindgen]
+    pub fn decode_frame(&mut self, index: usize) -> Result<Vec<f32>, JsValue> {
+        self.inner.decode_frame(index).map_err(to_js_err)
+    }
+
+    /// Frame holding a sample, undefined past the end
+    #[wasm_bindgen]
+    pub fn frame_at(&self, sample: f64) -> Option<usize> {
+        self.inner.frame_at(sample as u64)
+    }
+
+    /// Sample rate in Hz
+    #[wasm_bindgen]
+    pub fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    /// Number of channels
+    #[wasm_bindgen]
+    pub fn channels(&self) -> u8 {
+        self.inner.channels()
+    }
+
+    /// Length of the audio in sample-frames
+    #[wasm_bindgen]
+    pub fn total_samples(&self) -> f64 {
+        self.inner.total_samples() as f64
+    }
+
+    /// Number of frames
+    #[wasm_bindgen]
+    pub fn num_frames(&self) -> usize {
+        self.inner.num_frames()
+    }
+}
+
 // streaming decoder wasm api
 
 #[wasm_bindgen]
@@ -556,10 +751,81 @@ impl WasmStreamingDecoder {
         }
     }
 
+    /// new decoder for a live packet stream
+    ///
+    /// Can join the stream anywhere, it scans forward to the next sync point.
+    #[wasm_bindgen]
+    pub fn new_live() -> Self {
+        Self {
+            inner: StreamingDecoder::new_live(),
+        }
+    }
+
+    /// is this a live packet stream?
+    #[wasm_bindgen]
+    pub fn is_live(&self) -> bool {
+        self.inner.is_live()
+    }
+
+    /// conceal damaged or lost frames instead of throwing
+    #[wasm_bindgen]
+    pub fn with_concealment(mut self, mode: Concealment) -> Self {
+        self.inner = self.inner.with_concealment(mode);
+        self
+    }
+
+    /// indices of frames found damaged so far
+    #[wasm_bindgen]
+    pub fn damaged_frames(&self) -> Vec<u32> {
+        self.inner
+            .damaged_frames()
+            .into_iter()
+            .map(|i| i as u32)
+            .collect()
+    }
+
+    /// algorithmic delay of the stream in ms, undefined until a frame is decoded
+    #[wasm_bindgen]
+    pub fn algorithmic_delay_ms(&self) -> Option<f64> {
+        self.inner.algorithmic_delay_ms()
+    }
+
+    /// keep at most `bytes` buffered, feed_partial() then takes only what fits
+    #[wasm_bindgen]
+    pub fn with_max_buffer(mut self, bytes: usize) -> Self {
+        self.inner = self.inner.with_max_buffer(bytes);
+        self
+    }
+
     /// feed data to the decoder, call as bytes come in from network
+    ///
+    /// Returns true if frames are ready to decode. With a buffer limit use
+    /// `feed_partial`, this fails if `data` doesn't fit.
     #[wasm_bindgen]
     pub fn feed(&mut self, data: &[u8]) -> Result<bool, JsValue> {
-        self.inner.feed(data).map_err(to_js_err)
+        let status = self.inner.feed(data).map_err(to_js_err)?;
+        if status.accepted < data.len() {
+            return Err(JsValue::from_str("Buffer full, use feed_partial()"));
+        }
+        Ok(status.frames_ready > 0)
+    }
+
+    /// feed as much of `data` as fits the buffer limit
+    ///
+    /// Returns how many bytes were taken. Less than `data.length` means the
+    /// buffer is full: decode some frames, then feed the rest again.
+    #[wasm_bindgen]
+    pub fn feed_partial(&mut self, data: &[u8]) -> Result<usize, JsValue> {
+        self.inner
+            .feed(data)
+            .map(|status| status.accepted)
+            .map_err(to_js_err)
+    }
+
+    /// is the buffer at its limit? decode frames before feeding more
+    #[wasm_bindgen]
+    pub fn is_full(&self) -> bool {
+        self.inner.is_full()
     }
 
     /// Check if the decoder is ready to produce audio
@@ -586,6 +852,7 @@ impl WasmStreamingDecoder {
         match self.inner.state() {
             DecoderState::WaitingForHeader => "waiting_for_header".into(),
             DecoderState::WaitingForToc => "waiting_for_toc".into(),
+            DecoderState::Syncing => "syncing".into(),
             DecoderState::Ready => "ready".into(),
             DecoderState::Finished => "finished".into(),
             DecoderState::Error => "error".into(),
@@ -608,13 +875,33 @@ impl WasmStreamingDecoder {
                     &"total_samples".into(),
                     &(info.total_samples as f64).into(),
                 )?;
+                js_sys::Reflect::set(&obj, &"encoder_delay".into(), &info.encoder_delay.into())?;
+                js_sys::Reflect::set(&obj, &"padding".into(), &info.padding.into())?;
                 js_sys::Reflect::set(&obj, &"is_lossy".into(), &info.is_lossy.into())?;
+                js_sys::Reflect::set(&obj, &"is_live".into(), &info.is_live.into())?;
                 Ok(obj.into())
             }
             None => Ok(JsValue::NULL),
         }
     }
 
+    /// Get the metadata as soon as it has arrived
+    ///
+    /// Files with early metadata (see `set_metadata_placement`) have it right
+    /// after the TOC, before any audio. Returns null until metadata is available.
+    #[wasm_bindgen]
+    pub fn get_metadata(&self) -> Result<JsValue, JsValue> {
+        match self.inner.metadata() {
+            Some(bytes) => {
+                let meta = FloMetadata::from_msgpack(bytes)
+                    .map_err(|e| JsValue::from_str(&format!("Invalid metadata: {}", e)))?;
+                serde_wasm_bindgen::to_value(&meta)
+                    .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+            }
+            None => Ok(JsValue::NULL),
+        }
+    }
+
     /// decode all currently available samples
     #[wasm_bindgen]
     pub fn decode_available(&mut self) -> Result<Vec<f32>, JsValue> {
@@ -647,6 +934,14 @@ impl WasmStreamingDecoder {
         }
     }
 
+    /// give up on a frame that hasn't arrived in time, returns its stand-in
+    ///
+    /// Uses the `with_concealment` mode, a late packet for it is dropped.
+    #[wasm_bindgen]
+    pub fn conceal_frame(&mut self) -> Result<Vec<f32>, JsValue> {
+        self.inner.conceal_frame().map_err(to_js_err)
+    }
+
     /// how many frames ready to decode
     #[wasm_bindgen]
     pub fn available_frames(&self) -> usize {
@@ -659,6 +954,38 @@ impl WasmStreamingDecoder {
         self.inner.current_frame_index()
     }
 
+    /// Seek to a frame (needs the TOC)
+    ///
+    /// Returns `{ frame_index, start, end }`: the file bytes the decoder still
+    /// needs. Fetch them (e.g. with a Range request) and feed() them in order
+    /// starting at `start`. Nothing is needed when `start === end`.
+    #[wasm_bindgen]
+    pub fn seek_to_frame(&mut self, frame_index: usize) -> Result<JsValue, JsValue> {
+        let range = self.inner.seek_to_frame(frame_index).map_err(to_js_err)?;
+        seek_range_to_js(&range)
+    }
+
+    /// Seek to a time in milliseconds, see seek_to_frame()
+    ///
+    /// The next frame is trimmed so playback starts right at `ms`.
+    #[wasm_bindgen]
+    pub fn seek_to_ms(&mut self, ms: u32) -> Result<JsValue, JsValue> {
+        let range = self.inner.seek_to_ms(ms).map_err(to_js_err)?;
+        seek_range_to_js(&range)
+    }
+
+    /// Seek to a sample position (per channel), see seek_to_frame()
+    ///
+    /// The next frame is trimmed so playback starts right at `sample`.
+    #[wasm_bindgen]
+    pub fn seek_to_sample(&mut self, sample: f64) -> Result<JsValue, JsValue> {
+        let range = self
+            .inner
+            .seek_to_sample(sample as u64)
+            .map_err(to_js_err)?;
+        seek_range_to_js(&range)
+    }
+
     /// Reset the decoder to initial state
     ///
     /// Use this to start decoding a new stream.
@@ -674,6 +1001,14 @@ impl WasmStreamingDecoder {
     }
 }
 
+fn seek_range_to_js(range: &SeekRange) -> Result<JsValue, JsValue> {
+    let obj = js_sys::Object::new();
+    js_sys::Reflect::set(&obj, &"frame_index".into(), &range.frame_index.into())?;
+    js_sys::Reflect::set(&obj, &"start".into(), &(range.start as f64).into())?;
+    js_sys::Reflect::set(&obj, &"end".into(), &(range.end as f64).into())?;
+    Ok(obj.into())
+}
+
 impl Default for WasmStreamingDecoder {
     fn default() -> Self {
         Self::new()
@@ -705,6 +1040,25 @@ impl WasmStreamingEncoder {
         }
     }
 
+    /// Create a new lossy (transform) streaming encoder
+    ///
+    /// Frames keep the MDCT overlap across push_samples() calls, so finalize()
+    /// gives the same file as encoding all the samples at once.
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Sample rate in Hz (e.g., 44100)
+    /// * `channels` - Number of channels (1 or 2)
+    /// * `quality` - Quality 0.0-1.0
+    ///
+    /// # Returns
+    /// New encoder instance
+    #[wasm_bindgen]
+    pub fn new_lossy(sample_rate: u32, channels: u8, quality: f32) -> Self {
+        Self {
+            inner: StreamingEncoder::new_lossy(sample_rate, channels, quality),
+        }
+    }
+
     /// Set compression level (0-9)
     ///
     /// # Arguments
@@ -718,6 +1072,61 @@ impl WasmStreamingEncoder {
         self
     }
 
+    /// Append a CRC32 to every frame
+    ///
+    /// Lets decoders tell exactly which frames are damaged instead of
+    /// rejecting the whole file.
+    ///
+    /// # Returns
+    /// Self for method chaining
+    #[wasm_bindgen]
+    pub fn with_frame_crc(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.with_frame_crc(enabled);
+        self
+    }
+
+    /// Put the metadata (or a preview of it) ahead of the audio in finalize()
+    ///
+    /// Lets a streaming decoder show title, cover and lyrics before the file
+    /// has downloaded.
+    ///
+    /// # Returns
+    /// Self for method chaining
+    #[wasm_bindgen]
+    pub fn with_metadata_placement(mut self, placement: MetadataPlacement) -> Self {
+        self.inner = self.inner.with_metadata_placement(placement);
+        self
+    }
+
+    /// Emit short frames for live monitoring
+    ///
+    /// Lossless frames just get shorter, lossy ones switch to a low delay
+    /// window that only overlaps the next frame by 2.5 ms.
+    /// Call before pushing any samples.
+    ///
+    /// # Arguments
+    /// * `frame_ms` - Frame duration, clamped to 2.5-20 ms
+    ///
+    /// # Returns
+    /// Self for method chaining
+    #[wasm_bindgen]
+    pub fn with_low_latency(mut self, frame_ms: f32) -> Self {
+        self.inner = self.inner.with_low_latency(frame_ms);
+        self
+    }
+
+    /// Algorithmic delay in milliseconds
+    ///
+    /// How long a sample waits before the frame that plays it is ready:
+    /// the frame length, plus the MDCT overlap for lossy streams.
+    ///
+    /// # Returns
+    /// Delay in milliseconds
+    #[wasm_bindgen]
+    pub fn algorithmic_delay_ms(&self) -> f64 {
+        self.inner.algorithmic_delay_ms()
+    }
+
     /// Push audio samples to the encoder
     ///
     /// Samples should be interleaved if multi-channel (e.g., [L0, R0, L1, R1, ...] for stereo).
@@ -756,6 +1165,31 @@ impl WasmStreamingEncoder {
         })
     }
 
+    /// Get the next encoded frame as a live packet
+    ///
+    /// Live packets are self-describing and can be sent straight over a socket
+    /// or other unbounded stream, no finalize() needed.
+    ///
+    /// # Returns
+    /// Packet bytes or null if no frames are ready yet
+    #[wasm_bindgen]
+    pub fn next_live_packet(&mut self) -> Option<Vec<u8>> {
+        self.inner.next_live_packet()
+    }
+
+    /// Flush remaining samples as a live packet
+    ///
+    /// Lossy streams can end with more than one packet, drain the rest with
+    /// next_live_packet().
+    ///
+    /// # Returns
+    /// Packet bytes or null if nothing was buffered
+    #[wasm_bindgen]
+    pub fn flush_live_packet(&mut self) -> Result<Option<Vec<u8>>, JsValue> {
+        let frame = self.inner.flush().map_err(to_js_err)?;
+        Ok(frame.map(|f| self.inner.live_packet(&f)))
+    }
+
     /// Get number of samples currently buffered
     ///
     /// # Returns
@@ -776,14 +1210,15 @@ impl WasmStreamingEncoder {
 
     /// Flush remaining samples and finalize encoding
     ///
-    /// Call this when done pushing samples. Encodes any remaining partial frame.
+    /// Call this when done pushing samples. Encodes any remaining partial frame
+    /// (or the closing frames of a lossy stream) and queues it for next_frame().
     /// After this, call finalize() to get the complete flo file.
     ///
     /// # Returns
     /// Error if encoding fails
     #[wasm_bindgen]
     pub fn flush(&mut self) -> Result<(), JsValue> {
-        self.inner.flush().map_err(to_js_err).map(|_| ())
+        self.inner.finish().map_err(to_js_err)
     }
 
     /// Build a complete flo™ file from all accumulated frames
@@ -906,6 +1341,64 @@ pub fn get_cover_art(data: &[u8]) -> Result<JsValue, JsValue> {
     }
 }
 
+/// Read an ID3v2.3/v2.4 tag into metadata
+///
+/// # Arguments
+/// * `tag` - ID3 tag bytes, or an MP3 file starting with one
+///
+/// # Returns
+/// MessagePack bytes containing metadata
+#[wasm_bindgen]
+pub fn metadata_from_id3(tag: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let meta = FloMetadata::from_id3(tag).map_err(to_js_err)?;
+    meta.to_msgpack()
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Write metadata as an ID3v2 tag, e.g. for a sidecar or an MP3 export
+///
+/// # Arguments
+/// * `metadata` - MessagePack metadata bytes
+/// * `version` - ID3v2.3 or ID3v2.4
+///
+/// # Returns
+/// ID3 tag bytes
+#[wasm_bindgen]
+pub fn metadata_to_id3(metadata: &[u8], version: Id3Version) -> Result<Vec<u8>, JsValue> {
+    let meta = FloMetadata::from_msgpack(metadata)
+        .map_err(|e| JsValue::from_str(&format!("Invalid metadata: {}", e)))?;
+    meta.to_id3(version).map_err(to_js_err)
+}
+
+/// Read a Vorbis comment block (FLAC VORBIS_COMMENT, Ogg comment header) into metadata
+///
+/// # Arguments
+/// * `block` - Vorbis comment block bytes
+///
+/// # Returns
+/// MessagePack bytes containing metadata
+#[wasm_bindgen]
+pub fn metadata_from_vorbis_comments(block: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let meta = FloMetadata::from_vorbis_comment_block(block).map_err(to_js_err)?;
+    meta.to_msgpack()
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Write metadata as a Vorbis comment block, laid out as FLAC stores it
+///
+/// # Arguments
+/// * `metadata` - MessagePack metadata bytes
+/// * `vendor` - Vendor string of the block
+///
+/// # Returns
+/// Vorbis comment block bytes
+#[wasm_bindgen]
+pub fn metadata_to_vorbis_comments(metadata: &[u8], vendor: &str) -> Result<Vec<u8>, JsValue> {
+    let meta = FloMetadata::from_msgpack(metadata)
+        .map_err(|e| JsValue::from_str(&format!("Invalid metadata: {}", e)))?;
+    Ok(meta.to_vorbis_comment_block(vendor))
+}
+
 /// Set a single field in existing metadata bytes
 ///
 /// Uses serde to dynamically set fields - field names match FloMetadata struct.
@@ -1054,6 +1547,27 @@ pub fn update_metadata(flo_data: &[u8], new_metadata: &[u8]) -> Result<Vec<u8>,
     update_metadata_bytes(flo_data, new_metadata).map_err(to_js_err)
 }
 
+/// Measure files as one album and write track and album gain into each, without re-encoding
+///
+/// # Arguments
+/// * `files` - Array of flo file bytes (Uint8Array), in album order
+///
+/// # Returns
+/// Array of the updated files, in the same order
+#[wasm_bindgen]
+pub fn apply_album_gain(files: js_sys::Array) -> Result<js_sys::Array, JsValue> {
+    let files: Vec<Vec<u8>> = files
+        .iter()
+        .map(|file| js_sys::Uint8Array::new(&file).to_vec())
+        .collect();
+    let parts: Vec<&[u8]> = files.iter().map(Vec::as_slice).collect();
+    let updated = replay_gain::apply_album_gain(&parts).map_err(to_js_err)?;
+    Ok(updated
+        .iter()
+        .map(|file| JsValue::from(js_sys::Uint8Array::from(file.as_slice())))
+        .collect())
+}
+
 /// update metadata without re-encoding (native)
 pub fn update_metadata_bytes(flo_data: &[u8], new_metadata: &[u8]) -> FloResult<Vec<u8>> {
     // basic checks
@@ -1070,6 +1584,11 @@ pub fn update_metadata_bytes(flo_data: &[u8], new_metadata: &[u8]) -> FloResult<
     let reader = Reader::new();
     let file = reader.read(flo_data)?;
 
+    // an early chunk sits in DATA, so the frames have to move
+    if file.header.has_early_meta() {
+        return rewrite_with_metadata(&file, new_metadata, file.header.metadata_placement());
+    }
+
     // find where metadata starts
     // layout: magic(4) + header + toc + data + extra + metadata
     let meta_offset = 4
@@ -1091,10 +1610,62 @@ pub fn update_metadata_bytes(flo_data: &[u8], new_metadata: &[u8]) -> FloResult<
     let meta_size_offset = 4 + 2 + 2 + 4 + 1 + 1 + 8 + 1 + 3 + 4 + 8 + 8 + 8 + 8;
     let new_meta_size = new_metadata.len() as u64;
     result[meta_size_offset..meta_size_offset + 8].copy_from_slice(&new_meta_size.to_le_bytes());
+    core::header::reseal_header(&mut result);
 
     Ok(result)
 }
 
+/// Move the metadata of a flo file, e.g. ahead of the audio for progressive playback
+///
+/// # Arguments
+/// * `flo_data` - Original flo file bytes
+/// * `placement` - Where the metadata should go
+///
+/// # Returns
+/// New flo file with the same audio and metadata
+#[wasm_bindgen]
+pub fn set_metadata_placement(
+    flo_data: &[u8],
+    placement: MetadataPlacement,
+) -> Result<Vec<u8>, JsValue> {
+    set_metadata_placement_bytes(flo_data, placement).map_err(to_js_err)
+}
+
+/// move the metadata of a flo file without re-encoding (native)
+pub fn set_metadata_placement_bytes(
+    flo_data: &[u8],
+    placement: MetadataPlacement,
+) -> FloResult<Vec<u8>> {
+    let file = Reader::new().read(flo_data)?;
+    let metadata = file.metadata.clone();
+    rewrite_with_metadata(&file, &metadata, placement)
+}
+
+/// write the frames of `file` back out with new metadata
+fn rewrite_with_metadata(
+    file: &FloFile,
+    metadata: &[u8],
+    placement: MetadataPlacement,
+) -> FloResult<Vec<u8>> {
+    let header = &file.header;
+    let mut writer = Writer::new()
+        .with_frame_crc(header.has_frame_crc())
+        .with_metadata_placement(placement);
+    if let Some(length) = header.gapless_length() {
+        writer = writer.with_gapless(header.encoder_delay, length);
+    }
+    writer.write_ex(
+        header.sample_rate,
+        header.channels,
+        header.bit_depth,
+        header.compression_level,
+        (header.flags & 0x01) != 0,
+        ((header.flags >> 8) & 0x0f) as u8,
+        &file.frames,
+        metadata,
+    )
+}
+
 /// Replace just the metadata in a flo file (convenience function)
 ///
 /// Takes a metadata object directly instead of MessagePack bytes.
@@ -1184,7 +1755,7 @@ pub fn has_metadata(flo_data: &[u8]) -> bool {
             .unwrap_or([0; 8]),
     );
 
-    meta_size > 0
+    meta_size > 0 || core::Header::parse(flo_data).is_ok_and(|h| h.early_meta_size > 0)
 }
 
 /// Extract waveform peaks from audio samples (native version)
@@ -1414,6 +1985,35 @@ pub fn compute_loudness_metrics(
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
+/// Compute momentary and short-term loudness over time
+///
+/// # Arguments
+/// * `samples` - Audio samples (interleaved if multi-channel)
+/// * `channels` - Number of audio channels
+/// * `sample_rate` - Sample rate in Hz
+/// * `points_per_second` - Number of points per second (default: 10)
+///
+/// # Returns
+/// LoudnessSeries object with momentary and short-term loudness in 0.01 LU
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn compute_loudness_series_wasm(
+    samples: &[f32],
+    channels: u32,
+    sample_rate: u32,
+    points_per_second: Option<u32>,
+) -> Result<JsValue, JsValue> {
+    use crate::core::compute_loudness_series;
+    let series = compute_loudness_series(
+        samples,
+        channels as u8,
+        sample_rate,
+        points_per_second.unwrap_or(10),
+    );
+    serde_wasm_bindgen::to_value(&series)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
 /// Extract waveform peaks from audio samples
 ///
 /// # Arguments
@@ -1460,7 +2060,7 @@ mod tests {
 
     #[test]
     fn test_version() {
-        assert_eq!(version(), "1.2");
+        assert_eq!(version(), "1.4");
     }
 
     #[test]
@@ -1,276 +1,416 @@
 use crate::core::audio_constants::i32_to_f32;
-use crate::core::types::{ChannelData, FloFile};
-use crate::{core::rice, FloResult, Reader};
+use crate::core::integrity::{conceal_in_place, Concealment};
+use crate::core::types::{ChannelRef, FloFile, FloFileRef, Frame, FrameRef, FrameType, Header};
+use crate::frame_decoder::trim_lead_in;
+use crate::{core::rice, FloResult, FrameDecoder, Reader};
 
 /// audio decoder for flo format
 pub struct Decoder;
 
+/// decoder that conceals damaged frames, from `Decoder::with_concealment`
+pub struct ConcealingDecoder {
+    mode: Concealment,
+}
+
 impl Decoder {
     pub fn new() -> Self {
         Decoder
     }
 
+    /// conceal damaged frames instead of failing
+    ///
+    /// Reads with recovery enabled, so frames that fail their crc or can't
+    /// be parsed are filled in using `mode`.
+    pub fn with_concealment(self, mode: Concealment) -> ConcealingDecoder {
+        ConcealingDecoder { mode }
+    }
+
     /// decode flo file to samples
     pub fn decode(&self, data: &[u8]) -> FloResult<Vec<f32>> {
-        let reader = Reader::new();
-        let file = reader.read(data)?;
-        self.decode_file(&file)
+        self.decode_ref(&Reader::new().read_ref(data)?)
+    }
+
+    /// decode from a borrowed file, straight from the input bytes
+    ///
+    /// Damaged frames are an error, concealment needs `with_concealment`.
+    pub fn decode_ref(&self, file: &FloFileRef<'_>) -> FloResult<Vec<f32>> {
+        let mut frames = FrameDecoder::new(file.header.sample_rate, file.header.channels);
+        let mut samples = Vec::new();
+        for frame in file.frames() {
+            samples.extend(frames.decode(&frame?)?);
+        }
+        let preroll = file
+            .frame_header(0)
+            .filter(|(frame_type, _)| frame_type.is_transform())
+            .map_or(0, |(_, frame_samples)| frame_samples);
+        trim_to_length(&mut samples, &file.header, preroll);
+        Ok(samples)
+    }
+
+    /// decode a single borrowed lossless frame to interleaved samples
+    pub fn decode_frame_ref(&self, frame: &FrameRef<'_>, channels: usize) -> FloResult<Vec<f32>> {
+        decode_interleaved(
+            frame.channels(),
+            frame.frame_samples as usize,
+            frame.flags,
+            channels,
+        )
     }
 
     /// decode from parsed file
     pub fn decode_file(&self, file: &FloFile) -> FloResult<Vec<f32>> {
-        let channels = file.header.channels as usize;
-        let mut all_samples: Vec<Vec<i32>> = vec![vec![]; channels];
+        if let Some(&index) = file.damaged_frames.first() {
+            return Err(format!("Frame {} is damaged", index));
+        }
+        decode_file_with(file, None)
+    }
 
-        for frame in &file.frames {
-            let use_mid_side = channels == 2 && (frame.flags & 0x01) != 0;
+    /// decode one frame to per-channel integer samples
+    pub(crate) fn decode_frame_int(
+        &self,
+        frame: &Frame,
+        channels: usize,
+    ) -> FloResult<Vec<Vec<i32>>> {
+        decode_channels(
+            frame.channels.iter().map(|ch| Ok(ch.as_channel_ref())),
+            frame.frame_samples as usize,
+            frame.flags,
+            channels,
+        )
+    }
+}
 
-            let mut frame_channels: Vec<Vec<i32>> = Vec::with_capacity(channels);
+impl ConcealingDecoder {
+    /// decode flo file to samples, concealing damaged frames
+    pub fn decode(&self, data: &[u8]) -> FloResult<Vec<f32>> {
+        let reader = Reader::new().with_recovery(true);
+        let file = reader.read(data)?;
+        self.decode_file(&file)
+    }
 
-            for ch_data in &frame.channels {
-                let samples = self.decode_channel_int(ch_data, frame.frame_samples as usize)?;
-                frame_channels.push(samples);
-            }
+    /// decode from parsed file, concealing its `damaged_frames`
+    pub fn decode_file(&self, file: &FloFile) -> FloResult<Vec<f32>> {
+        decode_file_with(file, Some(self.mode))
+    }
+}
 
-            // mid-side to left-right
-            if use_mid_side && frame_channels.len() == 2 {
-                let (left, right) = self.decode_mid_side(&frame_channels[0], &frame_channels[1]);
-                all_samples[0].extend(left);
-                all_samples[1].extend(right);
-            } else {
-                for (ch_idx, samples) in frame_channels.into_iter().enumerate() {
-                    if ch_idx < channels {
-                        all_samples[ch_idx].extend(samples);
-                    }
-                }
+/// decode a parsed file, concealing damaged frames if a mode is given
+fn decode_file_with(file: &FloFile, concealment: Option<Concealment>) -> FloResult<Vec<f32>> {
+    // damaged frames are silent placeholders, any transform frame means lossy
+    let is_transform = file
+        .frames
+        .iter()
+        .any(|f| FrameType::from(f.frame_type).is_transform());
+
+    let channels = file.header.channels as usize;
+    let mut frames = FrameDecoder::new(file.header.sample_rate, file.header.channels);
+    let mut interleaved = Vec::new();
+    // (start, len) in sample-frames of every damaged lossless frame
+    let mut gaps = Vec::new();
+    // sorted, so walk it alongside the frames
+    let mut damaged = file.damaged_frames.iter().peekable();
+
+    for (index, frame) in file.frames.iter().enumerate() {
+        let is_damaged = damaged.next_if_eq(&&index).is_some();
+        match concealment.filter(|_| is_damaged) {
+            Some(mode) if is_transform => interleaved.extend(frames.conceal_transform(mode)),
+            Some(_) => {
+                gaps.push((
+                    interleaved.len() / channels.max(1),
+                    frame.frame_samples as usize,
+                ));
+                interleaved.extend(frames.decode_frame(frame)?);
             }
+            None => interleaved.extend(frames.decode_frame(frame)?),
         }
+    }
 
-        // interleave and convert to f32
-        let max_len = all_samples.iter().map(|v| v.len()).max().unwrap_or(0);
-        let mut interleaved = Vec::with_capacity(max_len * channels);
-
-        // Fast path for stereo (most common case)
-        if channels == 2 && all_samples[0].len() == all_samples[1].len() {
-            let left = &all_samples[0];
-            let right = &all_samples[1];
-            for i in 0..left.len() {
-                interleaved.push(i32_to_f32(left[i]));
-                interleaved.push(i32_to_f32(right[i]));
-            }
-        } else {
-            // General case for mono or mismatched lengths
-            for i in 0..max_len {
-                for ch in 0..channels {
-                    let sample = all_samples[ch].get(i).copied().unwrap_or(0);
-                    interleaved.push(i32_to_f32(sample));
-                }
-            }
+    if let Some(mode) = concealment {
+        for (start, len) in gaps {
+            conceal_in_place(&mut interleaved, channels, start, len, mode);
         }
+    }
 
-        Ok(interleaved)
+    let preroll = file
+        .frames
+        .first()
+        .filter(|_| is_transform)
+        .map_or(0, |f| f.frame_samples);
+    trim_to_length(&mut interleaved, &file.header, preroll);
+    Ok(interleaved)
+}
+
+/// drop the encoder delay and padding around the audio, if the file records them
+///
+/// `preroll` is the length of lossy frame 0, which has no output, 0 for
+/// lossless files.
+fn trim_to_length(samples: &mut Vec<f32>, header: &Header, preroll: u32) {
+    if preroll > 0 {
+        trim_lead_in(samples, header, preroll);
+    }
+    if let Some(length) = header.gapless_length() {
+        samples.truncate(length as usize * header.channels as usize);
     }
+}
+
+/// decode the channels of one lossless frame to interleaved samples
+pub(crate) fn decode_interleaved<'a>(
+    frame_data: impl Iterator<Item = FloResult<ChannelRef<'a>>>,
+    frame_samples: usize,
+    flags: u8,
+    channels: usize,
+) -> FloResult<Vec<f32>> {
+    let frame_channels = decode_channels(frame_data, frame_samples, flags, channels)?;
+    Ok(interleave(
+        &frame_channels[..channels.min(frame_channels.len())],
+        channels,
+    ))
+}
+
+/// decode the channels of one frame, owned or borrowed
+fn decode_channels<'a>(
+    frame_data: impl Iterator<Item = FloResult<ChannelRef<'a>>>,
+    frame_samples: usize,
+    flags: u8,
+    channels: usize,
+) -> FloResult<Vec<Vec<i32>>> {
+    let use_mid_side = channels == 2 && (flags & 0x01) != 0;
+
+    let mut frame_channels: Vec<Vec<i32>> = Vec::with_capacity(channels);
 
-    /// Convert mid-side back to left-right
-    fn decode_mid_side(&self, mid: &[i32], side: &[i32]) -> (Vec<i32>, Vec<i32>) {
-        // FLAC-style: mid = L + R, side = L - R
-        // So: L = (mid + side) / 2, R = (mid - side) / 2
-        let left: Vec<i32> = mid
-            .iter()
-            .zip(side.iter())
-            .map(|(&m, &s)| (m + s) / 2)
-            .collect();
-        let right: Vec<i32> = mid
-            .iter()
-            .zip(side.iter())
-            .map(|(&m, &s)| (m - s) / 2)
-            .collect();
-        (left, right)
+    for ch_data in frame_data {
+        frame_channels.push(decode_channel_int(&ch_data?, frame_samples));
     }
 
-    /// Decode a single channel to integers
-    fn decode_channel_int(
-        &self,
-        ch_data: &ChannelData,
-        frame_samples: usize,
-    ) -> FloResult<Vec<i32>> {
-        let has_coeffs = !ch_data.predictor_coeffs.is_empty();
-        let has_residuals = !ch_data.residuals.is_empty();
-        let shift_bits = ch_data.shift_bits;
+    // mid-side to left-right
+    if use_mid_side && frame_channels.len() == 2 {
+        let (left, right) = decode_mid_side(&frame_channels[0], &frame_channels[1]);
+        frame_channels = vec![left, right];
+    }
 
-        // Check for fixed predictor marker: shift_bits >= 128 means fixed order (128 + order)
-        let is_fixed_predictor = !has_coeffs && has_residuals && shift_bits >= 128;
+    Ok(frame_channels)
+}
 
-        if is_fixed_predictor {
-            // Fixed predictor: order stored as (128 + order)
-            let fixed_order = (shift_bits - 128) as usize;
+/// Convert mid-side back to left-right
+fn decode_mid_side(mid: &[i32], side: &[i32]) -> (Vec<i32>, Vec<i32>) {
+    // FLAC-style: mid = L + R, side = L - R
+    // So: L = (mid + side) / 2, R = (mid - side) / 2
+    let left: Vec<i32> = mid
+        .iter()
+        .zip(side.iter())
+        .map(|(&m, &s)| (m + s) / 2)
+        .collect();
+    let right: Vec<i32> = mid
+        .iter()
+        .zip(side.iter())
+        .map(|(&m, &s)| (m - s) / 2)
+        .collect();
+    (left, right)
+}
 
-            let residuals =
-                rice::decode_i32(&ch_data.residuals, ch_data.rice_parameter, frame_samples);
+/// Decode a single channel to integers
+fn decode_channel_int(ch_data: &ChannelRef<'_>, frame_samples: usize) -> Vec<i32> {
+    let has_coeffs = !ch_data.predictor_coeffs().is_empty();
+    let has_residuals = !ch_data.residuals.is_empty();
+    let shift_bits = ch_data.shift_bits;
 
-            return Ok(self.reconstruct_fixed(fixed_order, &residuals, frame_samples));
-        }
+    // Check for fixed predictor marker: shift_bits >= 128 means fixed order (128 + order)
+    let is_fixed_predictor = !has_coeffs && has_residuals && shift_bits >= 128;
 
-        if has_coeffs {
-            // LPC decoding with stored coefficients
-            let residuals =
-                rice::decode_i32(&ch_data.residuals, ch_data.rice_parameter, frame_samples);
+    if is_fixed_predictor {
+        // Fixed predictor: order stored as (128 + order)
+        let fixed_order = (shift_bits - 128) as usize;
 
-            let order = ch_data.predictor_coeffs.len();
+        let residuals = rice::decode_i32(ch_data.residuals, ch_data.rice_parameter, frame_samples);
 
-            let samples = self.reconstruct_lpc_int(
-                &ch_data.predictor_coeffs,
-                &residuals,
-                shift_bits,
-                order,
-                frame_samples,
-            );
+        return reconstruct_fixed(fixed_order, &residuals, frame_samples);
+    }
 
-            return Ok(samples);
-        }
+    if has_coeffs {
+        // LPC decoding with stored coefficients
+        let residuals = rice::decode_i32(ch_data.residuals, ch_data.rice_parameter, frame_samples);
 
-        if has_residuals {
-            // Raw PCM
-            let mut samples = Vec::with_capacity(frame_samples);
-            for chunk in ch_data.residuals.chunks(2) {
-                if chunk.len() == 2 {
-                    samples.push(i16::from_le_bytes([chunk[0], chunk[1]]) as i32);
-                }
-            }
-            while samples.len() < frame_samples {
-                samples.push(0);
-            }
-            return Ok(samples);
-        }
+        let order = ch_data.predictor_coeffs().len();
 
-        // Silence
-        Ok(vec![0; frame_samples])
+        return reconstruct_lpc_int(
+            ch_data.predictor_coeffs(),
+            &residuals,
+            shift_bits,
+            order,
+            frame_samples,
+        );
     }
 
-    /// Reconstruct from LPC prediction
-    #[inline]
-    fn reconstruct_lpc_int(
-        &self,
-        coeffs: &[i32],
-        residuals: &[i32],
-        shift: u8,
-        order: usize,
-        target_len: usize,
-    ) -> Vec<i32> {
-        let mut samples = Vec::with_capacity(target_len);
-        let actual_len = target_len.min(residuals.len());
-
-        // Warmup samples from residuals (no prediction needed)
-        let warmup_len = order.min(actual_len);
-        samples.extend_from_slice(&residuals[..warmup_len]);
-
-        // Reconstruct remaining samples using LPC prediction
-        // This is the hot loop - keep it simple and predictable
-        for i in order..actual_len {
-            let mut prediction: i64 = 0;
-
-            // Unrolled inner loop for common orders
-            // Access pattern: samples[i-1], samples[i-2], ..., samples[i-order]
-            for j in 0..order {
-                prediction += (coeffs[j] as i64) * (samples[i - j - 1] as i64);
+    if has_residuals {
+        // Raw PCM
+        let mut samples = Vec::with_capacity(frame_samples);
+        for chunk in ch_data.residuals.chunks(2) {
+            if chunk.len() == 2 {
+                samples.push(i16::from_le_bytes([chunk[0], chunk[1]]) as i32);
             }
+        }
+        while samples.len() < frame_samples {
+            samples.push(0);
+        }
+        return samples;
+    }
 
-            samples.push((prediction >> shift) as i32 + residuals[i]);
+    // Silence
+    vec![0; frame_samples]
+}
+
+/// Reconstruct from LPC prediction
+#[inline]
+fn reconstruct_lpc_int(
+    coeffs: &[i32],
+    residuals: &[i32],
+    shift: u8,
+    order: usize,
+    target_len: usize,
+) -> Vec<i32> {
+    let mut samples = Vec::with_capacity(target_len);
+    let actual_len = target_len.min(residuals.len());
+
+    // Warmup samples from residuals (no prediction needed)
+    let warmup_len = order.min(actual_len);
+    samples.extend_from_slice(&residuals[..warmup_len]);
+
+    // Reconstruct remaining samples using LPC prediction
+    // This is the hot loop - keep it simple and predictable
+    for i in order..actual_len {
+        let mut prediction: i64 = 0;
+
+        // Unrolled inner loop for common orders
+        // Access pattern: samples[i-1], samples[i-2], ..., samples[i-order]
+        for j in 0..order {
+            prediction += (coeffs[j] as i64) * (samples[i - j - 1] as i64);
         }
 
-        // Pad if needed
-        samples.resize(target_len, 0);
-        samples
+        samples.push((prediction >> shift) as i32 + residuals[i]);
     }
 
-    /// Reconstruct from fixed predictor
-    fn reconstruct_fixed(&self, order: usize, residuals: &[i32], target_len: usize) -> Vec<i32> {
-        let mut samples = Vec::with_capacity(target_len);
+    // Pad if needed
+    samples.resize(target_len, 0);
+    samples
+}
 
-        if residuals.is_empty() {
-            return vec![0; target_len];
-        }
+/// Reconstruct from fixed predictor
+fn reconstruct_fixed(order: usize, residuals: &[i32], target_len: usize) -> Vec<i32> {
+    let mut samples = Vec::with_capacity(target_len);
+
+    if residuals.is_empty() {
+        return vec![0; target_len];
+    }
 
-        match order {
-            0 => {
-                // No prediction - residuals are samples
-                samples.extend_from_slice(residuals);
+    match order {
+        0 => {
+            // No prediction - residuals are samples
+            samples.extend_from_slice(residuals);
+        }
+        1 => {
+            // s[i] = r[i] + s[i-1]
+            samples.push(residuals[0]);
+            for i in 1..residuals.len().min(target_len) {
+                samples.push(residuals[i].wrapping_add(samples[i - 1]));
+            }
+        }
+        2 => {
+            // s[i] = r[i] + 2*s[i-1] - s[i-2]
+            if !residuals.is_empty() {
+                samples.push(residuals[0]);
             }
-            1 => {
-                // s[i] = r[i] + s[i-1]
+            if residuals.len() > 1 {
+                samples.push(residuals[1].wrapping_add(samples[0]));
+            }
+            for i in 2..residuals.len().min(target_len) {
+                let pred = (2i64 * samples[i - 1] as i64 - samples[i - 2] as i64) as i32;
+                samples.push(residuals[i].wrapping_add(pred));
+            }
+        }
+        3 => {
+            // s[i] = r[i] + 3*s[i-1] - 3*s[i-2] + s[i-3]
+            if !residuals.is_empty() {
+                samples.push(residuals[0]);
+            }
+            if residuals.len() > 1 {
+                samples.push(residuals[1].wrapping_add(samples[0]));
+            }
+            if residuals.len() > 2 {
+                let pred = (2i64 * samples[1] as i64 - samples[0] as i64) as i32;
+                samples.push(residuals[2].wrapping_add(pred));
+            }
+            for i in 3..residuals.len().min(target_len) {
+                let pred = (3i64 * samples[i - 1] as i64 - 3i64 * samples[i - 2] as i64
+                    + samples[i - 3] as i64) as i32;
+                samples.push(residuals[i].wrapping_add(pred));
+            }
+        }
+        4 => {
+            // s[i] = r[i] + 4*s[i-1] - 6*s[i-2] + 4*s[i-3] - s[i-4]
+            if !residuals.is_empty() {
                 samples.push(residuals[0]);
-                for i in 1..residuals.len().min(target_len) {
-                    samples.push(residuals[i].wrapping_add(samples[i - 1]));
-                }
             }
-            2 => {
-                // s[i] = r[i] + 2*s[i-1] - s[i-2]
-                if !residuals.is_empty() {
-                    samples.push(residuals[0]);
-                }
-                if residuals.len() > 1 {
-                    samples.push(residuals[1].wrapping_add(samples[0]));
-                }
-                for i in 2..residuals.len().min(target_len) {
-                    let pred = (2i64 * samples[i - 1] as i64 - samples[i - 2] as i64) as i32;
-                    samples.push(residuals[i].wrapping_add(pred));
-                }
+            if residuals.len() > 1 {
+                samples.push(residuals[1].wrapping_add(samples[0]));
             }
-            3 => {
-                // s[i] = r[i] + 3*s[i-1] - 3*s[i-2] + s[i-3]
-                if !residuals.is_empty() {
-                    samples.push(residuals[0]);
-                }
-                if residuals.len() > 1 {
-                    samples.push(residuals[1].wrapping_add(samples[0]));
-                }
-                if residuals.len() > 2 {
-                    let pred = (2i64 * samples[1] as i64 - samples[0] as i64) as i32;
-                    samples.push(residuals[2].wrapping_add(pred));
-                }
-                for i in 3..residuals.len().min(target_len) {
-                    let pred = (3i64 * samples[i - 1] as i64 - 3i64 * samples[i - 2] as i64
-                        + samples[i - 3] as i64) as i32;
-                    samples.push(residuals[i].wrapping_add(pred));
-                }
+            if residuals.len() > 2 {
+                let pred = (2i64 * samples[1] as i64 - samples[0] as i64) as i32;
+                samples.push(residuals[2].wrapping_add(pred));
             }
-            4 => {
-                // s[i] = r[i] + 4*s[i-1] - 6*s[i-2] + 4*s[i-3] - s[i-4]
-                if !residuals.is_empty() {
-                    samples.push(residuals[0]);
-                }
-                if residuals.len() > 1 {
-                    samples.push(residuals[1].wrapping_add(samples[0]));
-                }
-                if residuals.len() > 2 {
-                    let pred = (2i64 * samples[1] as i64 - samples[0] as i64) as i32;
-                    samples.push(residuals[2].wrapping_add(pred));
-                }
-                if residuals.len() > 3 {
-                    let pred = (3i64 * samples[2] as i64 - 3i64 * samples[1] as i64
-                        + samples[0] as i64) as i32;
-                    samples.push(residuals[3].wrapping_add(pred));
-                }
-                for i in 4..residuals.len().min(target_len) {
-                    let pred = (4i64 * samples[i - 1] as i64 - 6i64 * samples[i - 2] as i64
-                        + 4i64 * samples[i - 3] as i64
-                        - samples[i - 4] as i64) as i32;
-                    samples.push(residuals[i].wrapping_add(pred));
-                }
+            if residuals.len() > 3 {
+                let pred = (3i64 * samples[2] as i64 - 3i64 * samples[1] as i64 + samples[0] as i64)
+                    as i32;
+                samples.push(residuals[3].wrapping_add(pred));
             }
-            _ => {
-                // Unknown order, just use residuals
-                samples.extend_from_slice(residuals);
+            for i in 4..residuals.len().min(target_len) {
+                let pred = (4i64 * samples[i - 1] as i64 - 6i64 * samples[i - 2] as i64
+                    + 4i64 * samples[i - 3] as i64
+                    - samples[i - 4] as i64) as i32;
+                samples.push(residuals[i].wrapping_add(pred));
             }
         }
-
-        // Pad if needed
-        while samples.len() < target_len {
-            samples.push(0);
+        _ => {
+            // Unknown order, just use residuals
+            samples.extend_from_slice(residuals);
         }
+    }
+
+    // Pad if needed
+    while samples.len() < target_len {
+        samples.push(0);
+    }
+
+    samples
+}
 
-        samples
+/// interleave per-channel samples and convert to f32
+fn interleave(all_samples: &[Vec<i32>], channels: usize) -> Vec<f32> {
+    let max_len = all_samples.iter().map(|v| v.len()).max().unwrap_or(0);
+    let mut interleaved = Vec::with_capacity(max_len * channels);
+
+    // Fast path for stereo (most common case)
+    if channels == 2 && all_samples.len() == 2 && all_samples[0].len() == all_samples[1].len() {
+        let left = &all_samples[0];
+        let right = &all_samples[1];
+        for i in 0..left.len() {
+            interleaved.push(i32_to_f32(left[i]));
+            interleaved.push(i32_to_f32(right[i]));
+        }
+    } else {
+        // General case for mono or mismatched lengths
+        for i in 0..max_len {
+            for ch in 0..channels {
+                let sample = all_samples
+                    .get(ch)
+                    .and_then(|s| s.get(i))
+                    .copied()
+                    .unwrap_or(0);
+                interleaved.push(i32_to_f32(sample));
+            }
+        }
     }
+
+    interleaved
 }
 
 impl Default for Decoder {
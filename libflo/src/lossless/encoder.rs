@@ -1,5 +1,5 @@
 use crate::core::audio_constants::f32_to_i32;
-use crate::core::{ChannelData, Frame, FrameType, ResidualEncoding};
+use crate::core::{ChannelData, Frame, FrameType, MetadataPlacement, ResidualEncoding};
 use crate::{core::rice, FloResult, Writer};
 
 use super::lpc::{
@@ -11,6 +11,8 @@ pub struct Encoder {
     channels: u8,
     bit_depth: u8,
     compression_level: u8,
+    frame_crc: bool,
+    metadata_placement: MetadataPlacement,
 }
 
 impl Encoder {
@@ -20,6 +22,8 @@ impl Encoder {
             channels,
             bit_depth,
             compression_level: 5,
+            frame_crc: false,
+            metadata_placement: MetadataPlacement::End,
         }
     }
 
@@ -28,12 +32,26 @@ impl Encoder {
         self
     }
 
+    /// append a crc32 to every frame so damage can be located per frame
+    pub fn with_frame_crc(mut self, enabled: bool) -> Self {
+        self.frame_crc = enabled;
+        self
+    }
+
+    /// put the metadata (or a preview of it) ahead of the frames
+    pub fn with_metadata_placement(mut self, placement: MetadataPlacement) -> Self {
+        self.metadata_placement = placement;
+        self
+    }
+
     /// encode samples to flo format
     pub fn encode(&self, samples: &[f32], metadata: &[u8]) -> FloResult<Vec<u8>> {
         let samples_per_frame = self.sample_rate as usize;
         let frames = self.encode_frames(samples, samples_per_frame);
 
-        let writer = Writer::new();
+        let writer = Writer::new()
+            .with_frame_crc(self.frame_crc)
+            .with_metadata_placement(self.metadata_placement);
         writer.write(
             self.sample_rate,
             self.channels,
@@ -63,7 +81,7 @@ impl Encoder {
         frames
     }
 
-    fn encode_frame(&self, samples: &[f32]) -> Frame {
+    pub(crate) fn encode_frame(&self, samples: &[f32]) -> Frame {
         let num_samples = samples.len() / self.channels as usize;
 
         // Check for silence
@@ -79,7 +97,7 @@ impl Encoder {
         let samples_i32: Vec<i32> = samples.iter().map(|&s| f32_to_i32(s)).collect();
 
         // Deinterleave channels
-        let mut channel_data: Vec<Vec<i32>> = (0..self.channels as usize)
+        let channel_data: Vec<Vec<i32>> = (0..self.channels as usize)
             .map(|ch| {
                 samples_i32
                     .iter()
@@ -90,6 +108,23 @@ impl Encoder {
             })
             .collect();
 
+        self.encode_frame_int(channel_data, num_samples)
+    }
+
+    /// encode one frame from per-channel integer samples
+    pub(crate) fn encode_frame_int(
+        &self,
+        mut channel_data: Vec<Vec<i32>>,
+        num_samples: usize,
+    ) -> Frame {
+        if channel_data.iter().all(|ch| ch.iter().all(|&s| s == 0)) {
+            let mut frame = Frame::new(FrameType::Silence as u8, num_samples as u32);
+            for _ in 0..self.channels {
+                frame.channels.push(ChannelData::new_silence());
+            }
+            return frame;
+        }
+
         // Apply mid-side coding for stereo (if it helps)
         let use_mid_side = self.channels == 2 && self.should_use_mid_side(&channel_data);
         if use_mid_side {
@@ -24,5 +24,5 @@ pub use lpc::{
     reconstruct_samples,
 };
 
-pub use decoder::Decoder;
+pub use decoder::{ConcealingDecoder, Decoder};
 pub use encoder::Encoder;
@@ -1,7 +1,15 @@
+use crate::core::integrity::Concealment;
+use crate::core::{FloResult, FrameRef};
+
 use super::encoder::TransformFrame;
 use super::mdct::{BlockSize, Mdct, WindowType};
 use super::psychoacoustic::{PsychoacousticModel, NUM_BARK_BANDS};
 
+/// Time for `RepeatFade` concealment to drop by half
+const REPEAT_HALF_LIFE_MS: f32 = 20.0;
+/// Time for `Spectral` concealment to drop by half
+const SPECTRAL_HALF_LIFE_MS: f32 = 60.0;
+
 /// Transform lossy decoder
 pub struct TransformDecoder {
     /// Sample rate
@@ -10,6 +18,10 @@ pub struct TransformDecoder {
     channels: u8,
     /// MDCT processor
     mdct: Mdct,
+    /// dequantized spectrum of the last decoded frame, concealment builds on it
+    last_spectrum: Option<(BlockSize, Vec<Vec<f32>>)>,
+    /// and of the frame before, for the trend `Spectral` extrapolates
+    previous_spectrum: Option<Vec<Vec<f32>>>,
 }
 
 impl TransformDecoder {
@@ -21,6 +33,8 @@ impl TransformDecoder {
             sample_rate,
             channels,
             mdct,
+            last_spectrum: None,
+            previous_spectrum: None,
         }
     }
 
@@ -48,12 +62,117 @@ impl TransformDecoder {
         }
 
         // IMDCT + overlap-add
-        self.mdct.synthesize(&dequantized, frame.block_size)
+        let samples = self.mdct.synthesize(&dequantized, frame.block_size);
+        self.previous_spectrum = self.last_spectrum.take().map(|(_, spectrum)| spectrum);
+        self.last_spectrum = Some((frame.block_size, dequantized));
+        samples
+    }
+
+    /// Stand in for a lost frame, built from the last decoded one
+    ///
+    /// `lost_in_row` counts this frame, 1 for the first one lost. The MDCT
+    /// overlap runs on, so the next real frame joins without a click.
+    /// `Silence` lets the previous frame's overlap fade out, `Interpolate`
+    /// repeats its spectrum, `RepeatFade` repeats it halving every 20 ms and
+    /// `Spectral` carries the band energies on with random signs, halving
+    /// every 60 ms. None before any frame has been decoded.
+    pub fn conceal_frame(&mut self, mode: Concealment, lost_in_row: u32) -> Option<Vec<f32>> {
+        let (block_size, last) = self.last_spectrum.as_ref()?;
+        let block_size = *block_size;
+        let hop_ms = block_size.coefficients() as f32 * 1000.0 / self.sample_rate as f32;
+        let elapsed_ms = hop_ms * lost_in_row as f32;
+
+        let spectrum: Vec<Vec<f32>> = match mode {
+            Concealment::Silence => last.iter().map(|c| vec![0.0; c.len()]).collect(),
+            Concealment::Interpolate => last.clone(),
+            Concealment::RepeatFade => {
+                let gain = 0.5f32.powf(elapsed_ms / REPEAT_HALF_LIFE_MS);
+                last.iter()
+                    .map(|c| c.iter().map(|x| x * gain).collect())
+                    .collect()
+            }
+            Concealment::Spectral => self.extrapolate_spectrum(block_size, lost_in_row, elapsed_ms),
+        };
+
+        Some(self.mdct.synthesize(&spectrum, block_size))
+    }
+
+    /// last spectrum with each band following its trend, signs randomized
+    ///
+    /// Random signs keep a repeated tone from turning into a buzz. Bands may
+    /// fade faster than the overall decay if they were already falling, but
+    /// never grow.
+    fn extrapolate_spectrum(
+        &self,
+        block_size: BlockSize,
+        lost_in_row: u32,
+        elapsed_ms: f32,
+    ) -> Vec<Vec<f32>> {
+        let Some((_, last)) = &self.last_spectrum else {
+            return Vec::new();
+        };
+        let freq_resolution = self.sample_rate as f32 / block_size.samples() as f32;
+        let band_of =
+            |k: usize| PsychoacousticModel::freq_to_bark_band((k as f32 + 0.5) * freq_resolution);
+        let decay = 0.5f32.powf(elapsed_ms / SPECTRAL_HALF_LIFE_MS);
+        let mut seed = 0x9E37_79B9u32 ^ lost_in_row.wrapping_mul(0x85EB_CA6B);
+
+        last.iter()
+            .enumerate()
+            .map(|(ch, coeffs)| {
+                let mut energy = [[0.0f32; NUM_BARK_BANDS]; 2];
+                let previous = self
+                    .previous_spectrum
+                    .as_ref()
+                    .and_then(|p| p.get(ch))
+                    .filter(|p| p.len() == coeffs.len());
+                for (k, &c) in coeffs.iter().enumerate() {
+                    energy[0][band_of(k)] += c * c;
+                    if let Some(previous) = previous {
+                        energy[1][band_of(k)] += previous[k] * previous[k];
+                    }
+                }
+                let trend: Vec<f32> = (0..NUM_BARK_BANDS)
+                    .map(|b| match previous {
+                        Some(_) if energy[1][b] > 1e-12 => {
+                            (energy[0][b] / energy[1][b]).sqrt().clamp(0.5, 1.0)
+                        }
+                        _ => 1.0,
+                    })
+                    .collect();
+
+                coeffs
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &c)| {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 17;
+                        seed ^= seed << 5;
+                        let sign = if seed & 1 == 0 { 1.0 } else { -1.0 };
+                        c.abs() * sign * trend[band_of(k)].powi(lost_in_row as i32) * decay
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Decode a borrowed frame straight from the file bytes
+    /// Returns interleaved samples
+    pub fn decode_frame_ref(&mut self, frame: &FrameRef<'_>) -> FloResult<Vec<f32>> {
+        let data = frame
+            .channels()
+            .next()
+            .ok_or("Transform frame has no channel data")??;
+        let transform_frame =
+            deserialize_frame(data.residuals).ok_or("Failed to deserialize transform frame")?;
+        Ok(self.decode_frame(&transform_frame))
     }
 
     /// Reset decoder state
     pub fn reset(&mut self) {
         self.mdct.reset();
+        self.last_spectrum = None;
+        self.previous_spectrum = None;
     }
 }
 
@@ -71,6 +190,17 @@ pub fn deserialize_frame(data: &[u8]) -> Option<TransformFrame> {
         1 => BlockSize::Short,
         2 => BlockSize::Start,
         3 => BlockSize::Stop,
+        4 => {
+            let field =
+                |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+            let (hop, overlap) = (field(pos + 1)?, field(pos + 3)?);
+            // the MDCT needs both halves of the hop divisible by 4
+            if hop < 8 || hop % 4 != 0 || overlap % 4 != 0 || overlap == 0 || overlap > hop {
+                return None;
+            }
+            pos += 4;
+            BlockSize::LowDelay { hop, overlap }
+        }
         _ => return None,
     };
     pos += 1;
@@ -79,7 +209,7 @@ pub fn deserialize_frame(data: &[u8]) -> Option<TransformFrame> {
     let num_coeffs = block_size.coefficients();
 
     // Number of channels
-    let num_channels = data[pos] as usize;
+    let num_channels = *data.get(pos)? as usize;
     pos += 1;
 
     // Scale factors (stored as log-scale u16)
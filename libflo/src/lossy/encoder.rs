@@ -1,6 +1,9 @@
 use super::mdct::{BlockSize, Mdct, WindowType};
 use super::psychoacoustic::{PsychoacousticModel, NUM_BARK_BANDS};
-use crate::core::{ChannelData, Frame, FrameType, ResidualEncoding, I16_MAX_F32, I16_MIN_F32};
+use crate::core::{
+    ChannelData, FloResult, Frame, FrameType, MetadataPlacement, ResidualEncoding, I16_MAX_F32,
+    I16_MIN_F32,
+};
 
 /// Transform lossy encoder
 pub struct TransformEncoder {
@@ -16,6 +19,10 @@ pub struct TransformEncoder {
     quality: f32,
     /// Block size
     block_size: BlockSize,
+    /// Append a crc to every frame
+    frame_crc: bool,
+    /// Where the container puts the metadata
+    metadata_placement: MetadataPlacement,
 }
 
 /// Encoded frame data
@@ -49,9 +56,38 @@ impl TransformEncoder {
             psy_models,
             quality: quality.clamp(0.0, 1.0),
             block_size,
+            frame_crc: false,
+            metadata_placement: MetadataPlacement::End,
         }
     }
 
+    /// Append a crc32 to every frame so damage can be located per frame
+    pub fn with_frame_crc(mut self, enabled: bool) -> Self {
+        self.frame_crc = enabled;
+        self
+    }
+
+    /// Put the metadata (or a preview of it) ahead of the frames
+    pub fn with_metadata_placement(mut self, placement: MetadataPlacement) -> Self {
+        self.metadata_placement = placement;
+        self
+    }
+
+    /// Use another block size for every frame, e.g. `BlockSize::low_delay`
+    pub fn with_block_size(mut self, block_size: BlockSize) -> Self {
+        self.block_size = block_size;
+        self.mdct.prepare(block_size);
+        self.psy_models = (0..self.channels)
+            .map(|_| PsychoacousticModel::new(self.sample_rate, block_size.samples()))
+            .collect();
+        self
+    }
+
+    /// Block size every frame is coded with
+    pub fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
     /// Set quality (0.0-1.0)
     pub fn set_quality(&mut self, quality: f32) {
         self.quality = quality.clamp(0.0, 1.0);
@@ -173,11 +209,12 @@ impl TransformEncoder {
         // - Proper number of frames to cover all samples
         let num_samples_per_channel = samples.len() / self.channels as usize;
 
-        // Add hop_size samples of pre-roll (zeros) at start for proper reconstruction
-        let pre_roll = hop_size;
-        let total_samples = num_samples_per_channel + pre_roll;
-        let num_hops = total_samples.div_ceil(hop_size);
-        let total_samples_needed = (num_hops + 1) * hop_size;
+        // Add hop_size samples of pre-roll (zeros) at start for proper reconstruction,
+        // low delay windows start late by their zero tail so that goes on top
+        let zero_tail = self.block_size.zero_tail();
+        let pre_roll = hop_size + zero_tail;
+        let num_hops = (num_samples_per_channel + hop_size).div_ceil(hop_size);
+        let total_samples_needed = (num_hops + 1) * hop_size + zero_tail;
 
         // Create padded buffer with pre-roll zeros at start
         let mut padded = vec![0.0f32; total_samples_needed * self.channels as usize];
@@ -205,38 +242,81 @@ impl TransformEncoder {
                 break;
             }
 
-            let frame_samples = &padded[start..end];
-            let transform_frame = self.encode_frame(frame_samples);
-
-            // Serialize the transform frame
-            let frame_data = serialize_frame(&transform_frame);
-
-            // Create a flo Frame with transform type
-            let mut flo_frame = Frame::new(FrameType::Transform as u8, hop_size as u32);
-            flo_frame.channels.push(ChannelData {
-                predictor_coeffs: vec![],
-                shift_bits: 0,
-                residual_encoding: ResidualEncoding::Raw,
-                rice_parameter: 0,
-                residuals: frame_data,
-            });
-
-            encoded_frames.push(flo_frame);
+            encoded_frames.push(self.encode_flo_frame(&padded[start..end]));
         }
 
-        // Write using the standard Writer
-        let writer = crate::Writer::new();
+        // Write using the standard Writer, frame 0 is the delay and the
+        // last hop is padded out
+        let writer = crate::Writer::new()
+            .with_frame_crc(self.frame_crc)
+            .with_metadata_placement(self.metadata_placement)
+            .with_gapless(hop_size as u32, num_samples_per_channel as u64);
         writer.write_ex(
             self.sample_rate,
             self.channels,
-            16,                                          // bit_depth for lossy
+            16,   // bit_depth for lossy
             5,    // compression level (not used for transform)
             true, // is_lossy
-            ((self.quality * 4.0).round() as u8).min(4), // quality as 0-4
+            self.quality_level(),
             &encoded_frames,
             metadata,
         )
     }
+
+    /// Encode one block (block_size * channels, interleaved) as a flo frame
+    ///
+    /// Blocks overlap by half, each frame advances the stream by one hop.
+    pub(crate) fn encode_flo_frame(&mut self, samples: &[f32]) -> Frame {
+        let transform_frame = self.encode_frame(samples);
+
+        // Serialize the transform frame
+        let frame_data = serialize_frame(&transform_frame);
+
+        // Create a flo Frame with transform type
+        let mut flo_frame = Frame::new(
+            FrameType::Transform as u8,
+            transform_frame.num_samples as u32,
+        );
+        flo_frame.channels.push(ChannelData {
+            predictor_coeffs: vec![],
+            shift_bits: 0,
+            residual_encoding: ResidualEncoding::Raw,
+            rice_parameter: 0,
+            residuals: frame_data,
+        });
+        flo_frame
+    }
+
+    /// Quality as the 0-4 level stored in the header flags
+    pub(crate) fn quality_level(&self) -> u8 {
+        ((self.quality * 4.0).round() as u8).min(4)
+    }
+
+    /// Quality as set, 0.0-1.0
+    pub(crate) fn quality(&self) -> f32 {
+        self.quality
+    }
+
+    /// Band energies the masking model carries from frame to frame, per channel
+    pub(crate) fn masking_state(&self) -> Vec<Vec<f32>> {
+        self.psy_models
+            .iter()
+            .map(|model| model.prev_energy.clone())
+            .collect()
+    }
+
+    /// Pick up the masking state of an earlier encoder, see `masking_state`
+    pub(crate) fn restore_masking_state(&mut self, state: &[Vec<f32>]) -> FloResult<()> {
+        if state.len() != self.psy_models.len()
+            || state.iter().any(|bands| bands.len() != NUM_BARK_BANDS)
+        {
+            return Err("Masking state doesn't match the encoder".to_string());
+        }
+        for (model, bands) in self.psy_models.iter_mut().zip(state) {
+            model.prev_energy.copy_from_slice(bands);
+        }
+        Ok(())
+    }
 }
 
 /// Serialize a transform frame to bytes (optimized)
@@ -249,8 +329,15 @@ pub fn serialize_frame(frame: &TransformFrame) -> Vec<u8> {
         BlockSize::Short => 1,
         BlockSize::Start => 2,
         BlockSize::Stop => 3,
+        BlockSize::LowDelay { .. } => 4,
     });
 
+    // Low delay blocks carry their hop and overlap (2 bytes each)
+    if let BlockSize::LowDelay { hop, overlap } = frame.block_size {
+        data.extend_from_slice(&hop.to_le_bytes());
+        data.extend_from_slice(&overlap.to_le_bytes());
+    }
+
     // Number of channels (1 byte)
     data.push(frame.coefficients.len() as u8);
 
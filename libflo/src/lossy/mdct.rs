@@ -27,14 +27,33 @@ pub enum BlockSize {
     Start,
     /// Stop block - transition from short to long
     Stop,
+    /// Low delay block for live streams - `hop` coefficients, and a window
+    /// that only overlaps the next block by `overlap` samples
+    LowDelay { hop: u16, overlap: u16 },
 }
 
 impl BlockSize {
+    /// Low delay block for frames of about `frame_ms`, overlapping by 2.5 ms
+    ///
+    /// Hop and overlap are rounded to multiples of 4 so the FFT halves evenly.
+    pub fn low_delay(sample_rate: u32, frame_ms: f32) -> Self {
+        let samples = |ms: f32| {
+            let quarter = (sample_rate as f32 * ms / 4000.0).round() as usize;
+            (quarter * 4).clamp(8, 4096) as u16
+        };
+        let hop = samples(frame_ms);
+        BlockSize::LowDelay {
+            hop,
+            overlap: samples(2.5).min(hop),
+        }
+    }
+
     /// Get the number of samples for this block size
     pub fn samples(self) -> usize {
         match self {
             BlockSize::Long | BlockSize::Start | BlockSize::Stop => 2048,
             BlockSize::Short => 256,
+            BlockSize::LowDelay { hop, .. } => 2 * hop as usize,
         }
     }
 
@@ -42,6 +61,22 @@ impl BlockSize {
     pub fn coefficients(self) -> usize {
         self.samples() / 2
     }
+
+    /// Samples shared with the next block, a whole hop except for low delay blocks
+    pub fn overlap(self) -> usize {
+        match self {
+            BlockSize::LowDelay { overlap, .. } => overlap as usize,
+            _ => self.coefficients(),
+        }
+    }
+
+    /// Samples at each end of the window that are zero
+    ///
+    /// The encoder doesn't need the last ones yet, and the decoder can play
+    /// the first ones of the next hop early, which is where the delay goes.
+    pub fn zero_tail(self) -> usize {
+        (self.coefficients() - self.overlap()) / 2
+    }
 }
 
 /// FFT-based MDCT transform for a specific window size
@@ -63,8 +98,6 @@ struct MdctTransform {
 impl MdctTransform {
     fn new(window_size: usize, window_type: WindowType) -> Self {
         let n = window_size;
-        let n2 = n / 2;
-        let n4 = n / 4;
 
         // Create window
         let window = match window_type {
@@ -73,6 +106,22 @@ impl MdctTransform {
             WindowType::Vorbis => Self::vorbis_window(n),
         };
 
+        Self::with_window(window)
+    }
+
+    /// Transform for a low delay block, see `low_overlap_window`
+    fn low_delay(block_size: BlockSize) -> Self {
+        Self::with_window(Self::low_overlap_window(
+            block_size.samples(),
+            block_size.overlap(),
+        ))
+    }
+
+    fn with_window(window: Vec<f32>) -> Self {
+        let n = window.len();
+        let n2 = n / 2;
+        let n4 = n / 4;
+
         // Create FFT planner
         let mut planner = FftPlanner::new();
         let fft = planner.plan_fft_forward(n4);
@@ -112,6 +161,26 @@ impl MdctTransform {
             .collect()
     }
 
+    /// Low overlap window: zeros, a Vorbis-shaped ramp of `overlap` samples,
+    /// then flat, mirrored for the second half
+    ///
+    /// Still power complementary, so overlap-add reconstructs perfectly, but
+    /// neighbouring blocks only overlap by `overlap` samples.
+    fn low_overlap_window(n: usize, overlap: usize) -> Vec<f32> {
+        let zeros = (n / 2 - overlap) / 2;
+        let half: Vec<f32> = (0..n / 2)
+            .map(|i| match i.checked_sub(zeros) {
+                None => 0.0,
+                Some(j) if j < overlap => {
+                    let x = (PI * (j as f32 + 0.5) / (2 * overlap) as f32).sin();
+                    (PI / 2.0 * x * x).sin()
+                }
+                Some(_) => 1.0,
+            })
+            .collect();
+        half.iter().chain(half.iter().rev()).copied().collect()
+    }
+
     /// Kaiser-Bessel Derived window
     fn kbd_window(n: usize, alpha: f32) -> Vec<f32> {
         let half = n / 2;
@@ -298,6 +367,8 @@ pub struct Mdct {
     long_transform: MdctTransform,
     /// Short block transform (256 samples)
     short_transform: MdctTransform,
+    /// Low delay transform, built by `prepare` for the block size in use
+    low_delay_transform: Option<(BlockSize, MdctTransform)>,
     /// Previous frame's windowed samples for overlap-add (per channel)
     overlap_buffer: Vec<Vec<f32>>,
     /// Number of channels
@@ -316,6 +387,7 @@ impl Mdct {
         Self {
             long_transform,
             short_transform,
+            low_delay_transform: None,
             overlap_buffer,
             channels,
         }
@@ -331,6 +403,36 @@ impl Mdct {
         MdctTransform::vorbis_window(n)
     }
 
+    /// Build the transform for a low delay block size
+    ///
+    /// Long and short transforms always exist. `synthesize` and `analyze`
+    /// call this themselves. `forward` and `inverse` build an unprepared
+    /// low delay transform for the one call, so prepare it when reusing it.
+    pub fn prepare(&mut self, block_size: BlockSize) {
+        if !matches!(block_size, BlockSize::LowDelay { .. }) {
+            return;
+        }
+        if self
+            .low_delay_transform
+            .as_ref()
+            .is_none_or(|(prepared, _)| *prepared != block_size)
+        {
+            self.low_delay_transform = Some((block_size, MdctTransform::low_delay(block_size)));
+        }
+    }
+
+    /// The transform for `block_size`, if one is built
+    fn transform(&self, block_size: BlockSize) -> Option<&MdctTransform> {
+        match block_size {
+            BlockSize::Long | BlockSize::Start | BlockSize::Stop => Some(&self.long_transform),
+            BlockSize::Short => Some(&self.short_transform),
+            BlockSize::LowDelay { .. } => match &self.low_delay_transform {
+                Some((prepared, transform)) if *prepared == block_size => Some(transform),
+                _ => None,
+            },
+        }
+    }
+
     /// Forward MDCT: N time samples → N/2 frequency coefficients
     ///
     /// X[k] = Σ x[n] * w[n] * cos(π/N * (n + 0.5 + N/2) * (k + 0.5))
@@ -338,12 +440,10 @@ impl Mdct {
         let n = block_size.samples();
         assert!(samples.len() >= n, "Not enough samples for MDCT");
 
-        let transform = match block_size {
-            BlockSize::Long | BlockSize::Start | BlockSize::Stop => &self.long_transform,
-            BlockSize::Short => &self.short_transform,
-        };
-
-        transform.forward(&samples[..n])
+        match self.transform(block_size) {
+            Some(transform) => transform.forward(&samples[..n]),
+            None => MdctTransform::low_delay(block_size).forward(&samples[..n]),
+        }
     }
 
     /// Inverse MDCT: N/2 frequency coefficients → N time samples
@@ -353,12 +453,10 @@ impl Mdct {
         let n2 = block_size.coefficients();
         assert!(coeffs.len() >= n2, "Not enough coefficients for IMDCT");
 
-        let transform = match block_size {
-            BlockSize::Long | BlockSize::Start | BlockSize::Stop => &self.long_transform,
-            BlockSize::Short => &self.short_transform,
-        };
-
-        transform.inverse(&coeffs[..n2])
+        match self.transform(block_size) {
+            Some(transform) => transform.inverse(&coeffs[..n2]),
+            None => MdctTransform::low_delay(block_size).inverse(&coeffs[..n2]),
+        }
     }
 
     /// Process a frame with overlap-add for perfect reconstruction
@@ -369,8 +467,7 @@ impl Mdct {
         channel: usize,
         block_size: BlockSize,
     ) -> (Vec<f32>, Vec<f32>) {
-        let n = block_size.samples();
-        let n2 = n / 2;
+        self.prepare(block_size);
 
         // Forward MDCT
         let coeffs = self.forward(samples, block_size);
@@ -378,16 +475,36 @@ impl Mdct {
         // Inverse MDCT (for testing/verification)
         let reconstructed = self.inverse(&coeffs, block_size);
 
-        // Overlap-add with previous frame
-        let mut output = vec![0.0f32; n2];
-        for i in 0..n2 {
-            output[i] = reconstructed[i] + self.overlap_buffer[channel][i];
+        let output = self.overlap_add(channel, &reconstructed, block_size);
+        (coeffs, output)
+    }
+
+    /// Overlap-add one channel's IMDCT output with the previous block
+    ///
+    /// Returns the hop that is now final. Low delay blocks skip the zero
+    /// tail, so the hop ends where the next block's window starts rising.
+    fn overlap_add(
+        &mut self,
+        channel: usize,
+        reconstructed: &[f32],
+        block_size: BlockSize,
+    ) -> Vec<f32> {
+        let n2 = block_size.coefficients();
+        let overlap = block_size.overlap();
+        let start = block_size.zero_tail();
+
+        let previous = &mut self.overlap_buffer[channel];
+        previous.resize(overlap, 0.0);
+
+        let mut output = reconstructed[start..start + n2].to_vec();
+        for (out, prev) in output.iter_mut().zip(previous.iter()) {
+            *out += prev;
         }
 
-        // Store second half for next frame's overlap
-        self.overlap_buffer[channel].copy_from_slice(&reconstructed[n2..n2 + n2]);
+        // Store the overlapping part for the next block
+        previous.copy_from_slice(&reconstructed[n2 + start..n2 + start + overlap]);
 
-        (coeffs, output)
+        output
     }
 
     /// Reset overlap buffers (e.g., for seeking)
@@ -401,6 +518,7 @@ impl Mdct {
     /// Input: interleaved samples [L, R, L, R, ...]
     /// Output: MDCT coefficients per channel
     pub fn analyze(&mut self, samples: &[f32], block_size: BlockSize) -> Vec<Vec<f32>> {
+        self.prepare(block_size);
         let n = block_size.samples();
         let samples_per_channel = samples.len() / self.channels;
 
@@ -435,25 +553,15 @@ impl Mdct {
     /// Input: MDCT coefficients per channel
     /// Output: interleaved samples
     pub fn synthesize(&mut self, coeffs: &[Vec<f32>], block_size: BlockSize) -> Vec<f32> {
-        let n = block_size.samples();
-        let n2 = n / 2;
+        self.prepare(block_size);
+        let n2 = block_size.coefficients();
 
         // IMDCT + overlap-add for each channel
         let mut channel_outputs: Vec<Vec<f32>> = Vec::with_capacity(self.channels);
 
         for (ch, ch_coeffs) in coeffs.iter().enumerate() {
             let reconstructed = self.inverse(ch_coeffs, block_size);
-
-            // Overlap-add
-            let mut output = vec![0.0f32; n2];
-            for i in 0..n2 {
-                output[i] = reconstructed[i] + self.overlap_buffer[ch][i];
-            }
-
-            // Store for next frame
-            self.overlap_buffer[ch].copy_from_slice(&reconstructed[n2..n2 + n2]);
-
-            channel_outputs.push(output);
+            channel_outputs.push(self.overlap_add(ch, &reconstructed, block_size));
         }
 
         // Interleave
@@ -0,0 +1,223 @@
+//! long-lived decoder for reading a file out of order
+//!
+//! `seeking::decode_frame_at` and `decode_range` parse the file on every
+//! call. `RandomAccessDecoder` parses it once, keeps the most recently used
+//! frames decoded and carries the MDCT state along when frames are read in
+//! order, so a lossy frame only needs its neighbour decoded after a jump.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::core::{FloFileRef, FloResult, FrameType, Header, TocEntry};
+use crate::frame_decoder::{trim_lead_in, trim_padding, FrameDecoder};
+use crate::reader::{toc_frame, Reader};
+
+/// decoded frames kept by default
+pub const DEFAULT_CACHE_FRAMES: usize = 16;
+
+/// Decodes any part of a flo™ file, as often and in whatever order needed
+///
+/// ```no_run
+/// # use libflo_audio::RandomAccessDecoder;
+/// # fn run(flo_data: Vec<u8>) -> Result<(), String> {
+/// let mut decoder = RandomAccessDecoder::new(flo_data)?;
+/// let rate = decoder.sample_rate() as u64;
+/// // a second of audio from 1:30, interleaved
+/// let samples = decoder.read_at(90 * rate, rate as usize)?;
+/// # Ok(()) }
+/// ```
+pub struct RandomAccessDecoder {
+    data: Vec<u8>,
+    header: Header,
+    toc: Vec<TocEntry>,
+    /// where the DATA chunk sits in `data`
+    data_chunk: Range<usize>,
+    /// audio sample each frame's output starts at, and where the last one ends
+    frame_starts: Vec<u64>,
+    /// length of the audio in samples per channel
+    length: u64,
+    decoder: FrameDecoder,
+    /// frame the decoder state follows on from
+    last_decoded: Option<usize>,
+    /// decoded frames, most recently used first
+    cache: VecDeque<(usize, Vec<f32>)>,
+    cache_frames: usize,
+}
+
+impl RandomAccessDecoder {
+    /// parse a flo™ file, frames are decoded as they're asked for
+    pub fn new(flo_data: Vec<u8>) -> FloResult<Self> {
+        let file = Reader::new().read_ref(&flo_data)?;
+        let frame_starts = frame_starts(&file)?;
+        let end = frame_starts[frame_starts.len() - 1];
+        let length = file.header.gapless_length().map_or(end, |len| len.min(end));
+
+        let data_start = 4 + (file.header.header_size + file.header.toc_size) as usize;
+        let data_chunk = data_start..data_start + file.data.len();
+        let num_frames = frame_starts.len() - 1;
+        let FloFileRef {
+            header, mut toc, ..
+        } = file;
+        toc.truncate(num_frames);
+
+        Ok(Self {
+            decoder: FrameDecoder::new(header.sample_rate, header.channels),
+            data: flo_data,
+            header,
+            toc,
+            data_chunk,
+            frame_starts,
+            length,
+            last_decoded: None,
+            cache: VecDeque::new(),
+            cache_frames: DEFAULT_CACHE_FRAMES,
+        })
+    }
+
+    /// how many decoded frames to keep, at least one
+    pub fn with_cache_frames(mut self, frames: usize) -> Self {
+        self.cache_frames = frames.max(1);
+        self.cache.truncate(self.cache_frames);
+        self
+    }
+
+    /// file header
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// sample rate in Hz
+    pub fn sample_rate(&self) -> u32 {
+        self.header.sample_rate
+    }
+
+    /// number of channels
+    pub fn channels(&self) -> u8 {
+        self.header.channels
+    }
+
+    /// length of the audio in samples per channel
+    pub fn total_samples(&self) -> u64 {
+        self.length
+    }
+
+    /// number of frames
+    pub fn num_frames(&self) -> usize {
+        self.toc.len()
+    }
+
+    /// samples of the audio frame `index` decodes to, per channel
+    ///
+    /// Empty for the preroll frame 0 of a lossy file.
+    pub fn frame_range(&self, index: usize) -> Option<Range<u64>> {
+        let start = *self.frame_starts.get(index)?;
+        let end = *self.frame_starts.get(index + 1)?;
+        Some(start.min(self.length)..end.min(self.length))
+    }
+
+    /// frame whose output holds `sample`, None past the end of the audio
+    pub fn frame_at(&self, sample: u64) -> Option<usize> {
+        if sample >= self.length {
+            return None;
+        }
+        Some(self.frame_starts.partition_point(|&start| start <= sample) - 1)
+    }
+
+    /// frames decoded and kept right now
+    pub fn cached_frames(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// decode one frame, the same samples `seeking::decode_frame_at` gives
+    pub fn decode_frame(&mut self, index: usize) -> FloResult<Vec<f32>> {
+        Ok(self.decoded_frame(index)?.to_vec())
+    }
+
+    /// read `len` sample-frames starting at `sample`, interleaved
+    ///
+    /// The same samples a full decode gives for them. Stops early at the end
+    /// of the audio.
+    pub fn read_at(&mut self, sample: u64, len: usize) -> FloResult<Vec<f32>> {
+        let end = sample.saturating_add(len as u64).min(self.length);
+        let Some(first) = self.frame_at(sample) else {
+            return Ok(vec![]);
+        };
+
+        let channels = self.header.channels as usize;
+        let mut samples = Vec::with_capacity((end - sample) as usize * channels);
+        for index in first..self.num_frames() {
+            let from = self.frame_starts[index];
+            if from >= end {
+                break;
+            }
+            let to = self.frame_starts[index + 1].min(end);
+
+            let frame = self.decoded_frame(index)?;
+            let keep_from = (sample.saturating_sub(from) as usize * channels).min(frame.len());
+            let keep_to = ((to - from) as usize * channels).min(frame.len());
+            samples.extend_from_slice(&frame[keep_from.min(keep_to)..keep_to]);
+        }
+        Ok(samples)
+    }
+
+    /// frame `index` decoded, from the cache if it's there
+    fn decoded_frame(&mut self, index: usize) -> FloResult<&[f32]> {
+        if let Some(pos) = self.cache.iter().position(|(cached, _)| *cached == index) {
+            let entry = self.cache.remove(pos).unwrap();
+            self.cache.push_front(entry);
+            return Ok(&self.cache[0].1);
+        }
+
+        let data = &self.data[self.data_chunk.clone()];
+        let frame = toc_frame(data, &self.header, &self.toc, index)?;
+
+        // a transform frame overlaps the one before it, after a jump
+        // that one primes the MDCT again
+        if FrameType::from(frame.frame_type).is_transform()
+            && (index == 0 || self.last_decoded != Some(index - 1))
+        {
+            self.decoder.restart();
+            if index > 0 {
+                let previous = toc_frame(data, &self.header, &self.toc, index - 1).ok();
+                self.decoder.prime(previous.as_ref());
+            }
+        }
+
+        let mut samples = self.decoder.decode(&frame)?;
+        self.last_decoded = Some(index);
+        if index == 1 && FrameType::from(frame.frame_type).is_transform() {
+            trim_lead_in(&mut samples, &self.header, frame.frame_samples);
+        }
+        if index + 1 == self.toc.len() {
+            trim_padding(&mut samples, &self.header);
+        }
+
+        self.cache.truncate(self.cache_frames - 1);
+        self.cache.push_front((index, samples));
+        Ok(&self.cache[0].1)
+    }
+}
+
+/// Internal: where each frame's output starts, then where the last one ends
+///
+/// Read from the frame headers so older files with rounded TOCs are exact
+/// too. Lossy frame 0 is the preroll and has no output, frame 1 loses the
+/// lead-in of a file trimmed off the hop grid.
+fn frame_starts(file: &FloFileRef<'_>) -> FloResult<Vec<u64>> {
+    let mut starts = vec![0u64];
+    for index in 0..file.num_frames() {
+        let (frame_type, frame_samples) = file
+            .frame_header(index)
+            .ok_or_else(|| format!("Frame {}: Unexpected end of file", index))?;
+        let output = match index {
+            0 if frame_type.is_transform() => 0,
+            1 if frame_type.is_transform() => {
+                (frame_samples as u64).saturating_sub(file.header.lead_in(frame_samples))
+            }
+            _ => frame_samples as u64,
+        };
+        let end = starts[index] + output;
+        starts.push(end);
+    }
+    Ok(starts)
+}
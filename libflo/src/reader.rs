@@ -1,46 +1,60 @@
 use crate::core::{
-    ChannelData, FloFile, FloResult, Frame, FrameType, Header, ResidualEncoding, TocEntry,
+    verify_frame_crc, ChannelData, ChannelRef, FloFile, FloFileRef, FloResult, Frame, FrameRef,
+    FrameType, Header, ResidualEncoding, TocEntry, MAX_PREDICTOR_ORDER,
 };
-use crate::MAGIC;
 
 /// binary reader for flo format
-pub struct Reader;
+pub struct Reader {
+    recover: bool,
+}
 
 impl Reader {
     /// new reader
     pub fn new() -> Self {
-        Reader
+        Reader { recover: false }
+    }
+
+    /// keep going past damaged frames instead of failing
+    ///
+    /// Frames that fail their crc (or can't be parsed) are replaced with
+    /// silent placeholders of the expected length and listed in
+    /// `FloFile::damaged_frames`. A truncated META chunk reads as empty.
+    pub fn with_recovery(mut self, enabled: bool) -> Self {
+        self.recover = enabled;
+        self
     }
 
     /// read and parse a flo file
     pub fn read(&self, data: &[u8]) -> FloResult<FloFile> {
         let mut cursor = Cursor::new(data);
 
-        // magic
-        let magic = cursor.read_bytes(4)?;
-        if magic != MAGIC {
-            return Err("Invalid flo file: bad magic".to_string());
-        }
-
-        // header
+        // header (magic included)
         let header = self.read_header(&mut cursor)?;
 
         // toc
-        let toc = self.read_toc(&mut cursor, header.toc_size as usize)?;
+        let toc = self.read_toc(&mut cursor, &header)?;
 
         // Read DATA chunk
-        let frames = self.read_data_chunk(
-            &mut cursor,
-            header.data_size as usize,
-            header.channels,
-            &toc,
-        )?;
+        let (frames, damaged_frames) = self.read_data_chunk(&mut cursor, &header, &toc)?;
 
         // Skip EXTRA chunk
         cursor.skip(header.extra_size as usize)?;
 
         // Read META chunk
-        let metadata = cursor.read_bytes(header.meta_size as usize)?;
+        let mut metadata = match cursor.read_bytes(header.meta_size as usize) {
+            Ok(metadata) => metadata,
+            Err(_) if self.recover => vec![],
+            Err(e) => return Err(e),
+        };
+
+        // everything may have been placed ahead of the frames instead
+        if metadata.is_empty() {
+            let data_start = 4 + (header.header_size + header.toc_size) as usize;
+            let end = data_start
+                .saturating_add(header.early_meta_size as usize)
+                .min(data.len());
+            metadata = data[data_start.min(end)..end].to_vec();
+        }
 
         Ok(FloFile {
             header,
@@ -48,210 +62,150 @@ impl Reader {
             frames,
             extra: vec![],
             metadata,
+            damaged_frames,
         })
     }
 
-    fn read_header(&self, cursor: &mut Cursor) -> FloResult<Header> {
-        Ok(Header {
-            version_major: cursor.read_u8()?,
-            version_minor: cursor.read_u8()?,
-            flags: cursor.read_u16_le()?,
-            sample_rate: cursor.read_u32_le()?,
-            channels: cursor.read_u8()?,
-            bit_depth: cursor.read_u8()?,
-            total_samples: cursor.read_u64_le()?,
-            compression_level: cursor.read_u8()?,
-            data_crc32: {
-                cursor.skip(3)?; // reserved
-                cursor.read_u32_le()?
-            },
-            header_size: cursor.read_u64_le()?,
-            toc_size: cursor.read_u64_le()?,
-            data_size: cursor.read_u64_le()?,
-            extra_size: cursor.read_u64_le()?,
-            meta_size: cursor.read_u64_le()?,
-        })
-    }
+    /// parse a flo file in place, without copying frame payloads
+    ///
+    /// Only the header and TOC are parsed here. Frames are parsed (and their
+    /// crc checked) when accessed through `FloFileRef::frame`. With recovery
+    /// enabled, chunks running past the end of the data are cut short.
+    pub fn read_ref<'a>(&self, data: &'a [u8]) -> FloResult<FloFileRef<'a>> {
+        let mut cursor = Cursor::new(data);
 
-    fn read_toc(&self, cursor: &mut Cursor, toc_size: usize) -> FloResult<Vec<TocEntry>> {
-        if toc_size < 4 {
-            return Ok(vec![]);
-        }
+        let header = self.read_header(&mut cursor)?;
+        let toc = self.read_toc(&mut cursor, &header)?;
+
+        let data_chunk = cursor.take(header.data_size as usize, self.recover)?;
+        let extra = cursor.take(header.extra_size as usize, self.recover)?;
+        let early_metadata = &data_chunk[..(header.early_meta_size as usize).min(data_chunk.len())];
+        let metadata = match cursor.take(header.meta_size as usize, self.recover)? {
+            [] => early_metadata,
+            metadata => metadata,
+        };
 
-        let num_entries = cursor.read_u32_le()? as usize;
+        Ok(FloFileRef {
+            header,
+            toc,
+            data: data_chunk,
+            extra,
+            metadata,
+            early_metadata,
+        })
+    }
 
-        if num_entries > 100_000 {
-            return Err("Invalid TOC: too many entries".to_string());
+    fn read_header(&self, cursor: &mut Cursor) -> FloResult<Header> {
+        let header = Header::parse(cursor.data)?;
+        if !self.recover {
+            header.check_file_size(cursor.data.len() as u64)?;
         }
 
-        let mut entries = Vec::with_capacity(num_entries);
-
-        for _ in 0..num_entries {
-            entries.push(TocEntry {
-                frame_index: cursor.read_u32_le()?,
-                byte_offset: cursor.read_u64_le()?,
-                frame_size: cursor.read_u32_le()?,
-                timestamp_ms: cursor.read_u32_le()?,
-            });
-        }
+        // a newer minor version may append fields we don't know about
+        cursor.pos = 4 + header.header_size as usize;
+        Ok(header)
+    }
 
-        Ok(entries)
+    fn read_toc(&self, cursor: &mut Cursor, header: &Header) -> FloResult<Vec<TocEntry>> {
+        let toc = cursor.take(header.toc_size as usize, false)?;
+        TocEntry::parse_all(toc, header)
     }
 
     fn read_data_chunk(
         &self,
         cursor: &mut Cursor,
-        data_size: usize,
-        channels: u8,
+        header: &Header,
         toc: &[TocEntry],
-    ) -> FloResult<Vec<Frame>> {
+    ) -> FloResult<(Vec<Frame>, Vec<usize>)> {
         let data_start = cursor.pos;
-        let data_end = cursor.pos + data_size;
+        let data_end = data_start
+            .checked_add(header.data_size as usize)
+            .ok_or("DATA chunk size overflows")?;
         let mut frames = Vec::with_capacity(toc.len());
-
-        for toc_entry in toc.iter() {
-            let frame_start = data_start + toc_entry.byte_offset as usize;
-
-            if frame_start >= data_end {
-                break;
+        let mut damaged = Vec::new();
+
+        for (index, toc_entry) in toc.iter().enumerate() {
+            // a frame starting past the chunk is as lost as one failing its crc
+            let frame = match data_start.checked_add(toc_entry.byte_offset as usize) {
+                Some(frame_start) if frame_start < data_end => self.read_checked_frame(
+                    cursor,
+                    header,
+                    frame_start,
+                    toc_entry.frame_size as usize,
+                ),
+                _ => Err("Starts past the end of the DATA chunk".to_string()),
+            };
+
+            match frame {
+                Ok(frame) => frames.push(frame),
+                Err(_) if self.recover => {
+                    damaged.push(index);
+                    frames.push(Self::placeholder_frame(header.channels));
+                }
+                Err(e) => return Err(format!("Frame {}: {}", index, e)),
             }
-
-            cursor.pos = frame_start;
-            let frame_size = toc_entry.frame_size as usize;
-
-            let frame = self.read_frame(cursor, channels, frame_size)?;
-            frames.push(frame);
         }
 
-        cursor.pos = data_end;
-        Ok(frames)
-    }
-
-    fn read_frame(&self, cursor: &mut Cursor, channels: u8, frame_size: usize) -> FloResult<Frame> {
-        let frame_start = cursor.pos;
-        let frame_end = frame_start + frame_size;
-
-        // frame header: type(1) + samples(4) + flags(1)
-        let frame_type_byte = cursor.read_u8()?;
-        let frame_samples = cursor.read_u32_le()?;
-        let flags = cursor.read_u8()?;
-
-        let frame_type = FrameType::from(frame_type_byte);
-        let mut frame = Frame::new(frame_type_byte, frame_samples);
-        frame.flags = flags;
-
-        // transform frames are one blob, others are per-channel
-        let num_channels_to_read = if frame_type == FrameType::Transform {
-            1
-        } else {
-            channels as usize
-        };
-
-        // read each channels data
-        for _ch_idx in 0..num_channels_to_read {
-            // channel size
-            let ch_size = cursor.read_u32_le()? as usize;
-            let ch_end = cursor.pos + ch_size;
-
-            let ch_data =
-                self.read_channel_data(cursor, frame_type, frame_samples as usize, ch_end)?;
-            frame.channels.push(ch_data);
-
-            // move to end of channel
-            cursor.pos = ch_end;
+        if !damaged.is_empty() {
+            Self::size_placeholders(&mut frames, &damaged, header);
         }
 
-        cursor.pos = frame_end;
-        Ok(frame)
+        cursor.pos = data_end;
+        Ok((frames, damaged))
     }
 
-    fn read_channel_data(
+    fn read_checked_frame(
         &self,
         cursor: &mut Cursor,
-        frame_type: FrameType,
-        frame_samples: usize,
-        channel_end: usize,
-    ) -> FloResult<ChannelData> {
-        if frame_samples > 2_000_000 {
-            return Err("Invalid frame: too many samples".to_string());
+        header: &Header,
+        frame_start: usize,
+        frame_size: usize,
+    ) -> FloResult<Frame> {
+        let bytes = frame_start
+            .checked_add(frame_size)
+            .and_then(|frame_end| cursor.data.get(frame_start..frame_end))
+            .ok_or("Unexpected end of file")?;
+        if header.has_frame_crc() && !verify_frame_crc(bytes) {
+            return Err("CRC mismatch".to_string());
         }
+        parse_frame(bytes, header.channels)?.to_frame()
+    }
 
-        match frame_type {
-            FrameType::Silence => Ok(ChannelData::new_silence()),
-
-            FrameType::Raw => {
-                let bytes_needed = frame_samples.saturating_mul(2);
-                let available = channel_end.saturating_sub(cursor.pos);
-                let bytes_to_read = bytes_needed.min(available);
-                let residuals = cursor.read_bytes(bytes_to_read)?;
-                Ok(ChannelData::new_raw(residuals))
-            }
-
-            FrameType::Transform => {
-                // serialized mdct data
-                let remaining = channel_end.saturating_sub(cursor.pos);
-                let residuals = if remaining > 0 {
-                    cursor.read_bytes(remaining)?
-                } else {
-                    vec![]
-                };
-
-                Ok(ChannelData {
-                    predictor_coeffs: vec![],
-                    shift_bits: 0,
-                    residual_encoding: ResidualEncoding::Raw,
-                    rice_parameter: 0,
-                    residuals,
-                })
-            }
-
-            _ if frame_type.is_alpc() => {
-                // predictor order
-                let order = cursor.read_u8()? as usize;
-
-                if order > 12 {
-                    return Err("Invalid LPC order".to_string());
-                }
-
-                // predictor coeffs
-                let mut predictor_coeffs = Vec::with_capacity(order);
-                for _ in 0..order {
-                    if cursor.pos + 4 > channel_end {
-                        break;
-                    }
-                    predictor_coeffs.push(cursor.read_i32_le()?);
-                }
+    /// silent stand-in for a frame we couldn't read
+    fn placeholder_frame(channels: u8) -> Frame {
+        let mut frame = Frame::new(FrameType::Silence as u8, 0);
+        for _ in 0..channels {
+            frame.channels.push(ChannelData::new_silence());
+        }
+        frame
+    }
 
-                let shift_bits = cursor.read_u8()?;
-
-                let residual_encoding_byte = cursor.read_u8()?;
-                let residual_encoding = ResidualEncoding::from(residual_encoding_byte);
-
-                // rice param only for rice encoding
-                let rice_parameter = if residual_encoding == ResidualEncoding::Rice {
-                    cursor.read_u8()?
-                } else {
-                    0
-                };
-
-                // rest is residuals
-                let remaining = channel_end.saturating_sub(cursor.pos);
-                let residuals = if remaining > 0 {
-                    cursor.read_bytes(remaining)?
-                } else {
-                    vec![]
-                };
-
-                Ok(ChannelData {
-                    predictor_coeffs,
-                    shift_bits,
-                    residual_encoding,
-                    rice_parameter,
-                    residuals,
-                })
+    /// give placeholders the length the damaged frames most likely had
+    ///
+    /// All frames but the last share a size, the last one takes whatever is
+    /// left of `total_samples`.
+    fn size_placeholders(frames: &mut [Frame], damaged: &[usize], header: &Header) {
+        let typical = frames
+            .iter()
+            .enumerate()
+            .find(|(i, _)| !damaged.contains(i))
+            .map(|(_, f)| f.frame_samples)
+            .unwrap_or(header.sample_rate);
+
+        let last = frames.len() - 1;
+        for &i in damaged {
+            if i != last {
+                frames[i].frame_samples = typical;
             }
+        }
 
-            _ => Ok(ChannelData::new_silence()),
+        if damaged.contains(&last) {
+            let others: u64 = frames[..last].iter().map(|f| f.frame_samples as u64).sum();
+            frames[last].frame_samples = if header.total_samples > others {
+                (header.total_samples - others) as u32
+            } else {
+                typical
+            };
         }
     }
 }
@@ -275,47 +229,269 @@ impl<'a> Cursor<'a> {
     }
 
     fn read_bytes(&mut self, count: usize) -> FloResult<Vec<u8>> {
-        if self.pos + count > self.data.len() {
+        let bytes = self
+            .pos
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or("Unexpected end of file")?
+            .to_vec();
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    /// borrow the next `count` bytes, or whatever is left if `clamp`
+    fn take(&mut self, count: usize, clamp: bool) -> FloResult<&'a [u8]> {
+        let end = self.pos.saturating_add(count);
+        if end > self.data.len() && !clamp {
             return Err("Unexpected end of file".to_string());
         }
-        let bytes = self.data[self.pos..self.pos + count].to_vec();
-        self.pos += count;
+        let end = end.min(self.data.len());
+        let bytes = &self.data[self.pos.min(end)..end];
+        self.pos = end;
         Ok(bytes)
     }
 
     fn skip(&mut self, count: usize) -> FloResult<()> {
-        self.pos = (self.pos + count).min(self.data.len());
+        self.pos = self.pos.saturating_add(count).min(self.data.len());
         Ok(())
     }
+}
 
-    fn read_u8(&mut self) -> FloResult<u8> {
-        if self.pos >= self.data.len() {
-            return Err("Unexpected end of file".to_string());
+// borrowed frame parsing
+
+impl<'a> FloFileRef<'a> {
+    /// number of frames that start inside the DATA chunk
+    pub fn num_frames(&self) -> usize {
+        self.toc
+            .iter()
+            .take_while(|entry| (entry.byte_offset as usize) < self.data.len())
+            .count()
+    }
+
+    /// parse frame `index` in place, checking its crc if the file has them
+    pub fn frame(&self, index: usize) -> FloResult<FrameRef<'a>> {
+        toc_frame(self.data, &self.header, &self.toc, index)
+    }
+
+    /// type and sample count of frame `index`, read from its first bytes
+    ///
+    /// Skips the crc check and the channel data, for walking the frames
+    /// without parsing them.
+    pub(crate) fn frame_header(&self, index: usize) -> Option<(FrameType, u32)> {
+        let start = self.toc.get(index)?.byte_offset as usize;
+        let bytes = self.data.get(start..start.checked_add(5)?)?;
+        let samples = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        Some((FrameType::from(bytes[0]), samples))
+    }
+
+    /// every frame in order
+    pub fn frames(&self) -> impl Iterator<Item = FloResult<FrameRef<'a>>> + '_ {
+        (0..self.num_frames()).map(move |index| self.frame(index))
+    }
+
+    /// does any frame hold transform (lossy) data?
+    pub fn is_transform(&self) -> bool {
+        self.frames()
+            .any(|f| f.is_ok_and(|f| f.frame_type == FrameType::Transform as u8))
+    }
+}
+
+impl<'a> FrameRef<'a> {
+    /// channels of this frame, parsed in place
+    pub fn channels(&self) -> ChannelRefs<'a> {
+        ChannelRefs {
+            frame_type: FrameType::from(self.frame_type),
+            frame_samples: self.frame_samples as usize,
+            body: self.body,
+            pos: 0,
+            remaining: self.stored_channels,
+        }
+    }
+
+    /// copy into an owned `Frame`
+    pub fn to_frame(&self) -> FloResult<Frame> {
+        let mut frame = Frame::new(self.frame_type, self.frame_samples);
+        frame.flags = self.flags;
+        for channel in self.channels() {
+            frame.channels.push(channel?.to_channel_data());
+        }
+        Ok(frame)
+    }
+}
+
+/// iterator over the channels of a `FrameRef`
+pub struct ChannelRefs<'a> {
+    frame_type: FrameType,
+    frame_samples: usize,
+    body: &'a [u8],
+    pos: usize,
+    remaining: u8,
+}
+
+impl<'a> Iterator for ChannelRefs<'a> {
+    type Item = FloResult<ChannelRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
         }
-        let val = self.data[self.pos];
-        self.pos += 1;
-        Ok(val)
+        self.remaining -= 1;
+
+        let result = self.next_channel();
+        if result.is_err() {
+            self.remaining = 0;
+        }
+        Some(result)
+    }
+}
+
+impl<'a> ChannelRefs<'a> {
+    fn next_channel(&mut self) -> FloResult<ChannelRef<'a>> {
+        let size_bytes = self
+            .body
+            .get(self.pos..self.pos + 4)
+            .ok_or("Unexpected end of file")?;
+        let ch_size =
+            u32::from_le_bytes([size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]]);
+
+        let ch_start = self.pos + 4;
+        let ch_end = ch_start
+            .checked_add(ch_size as usize)
+            .filter(|&end| end <= self.body.len())
+            .ok_or("Unexpected end of file")?;
+        self.pos = ch_end;
+
+        parse_channel(
+            self.frame_type,
+            self.frame_samples,
+            &self.body[ch_start..ch_end],
+        )
     }
+}
 
-    fn read_u16_le(&mut self) -> FloResult<u16> {
-        let bytes = self.read_bytes(2)?;
-        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+/// parse frame `index` of a DATA chunk in place, checking its crc if the file has them
+pub(crate) fn toc_frame<'a>(
+    data: &'a [u8],
+    header: &Header,
+    toc: &[TocEntry],
+    index: usize,
+) -> FloResult<FrameRef<'a>> {
+    let entry = toc.get(index).ok_or_else(|| {
+        format!(
+            "Frame index {} out of bounds (total frames: {})",
+            index,
+            toc.len()
+        )
+    })?;
+
+    let start = entry.byte_offset as usize;
+    let bytes = start
+        .checked_add(entry.frame_size as usize)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| format!("Frame {}: Unexpected end of file", index))?;
+
+    if header.has_frame_crc() && !verify_frame_crc(bytes) {
+        return Err(format!("Frame {}: CRC mismatch", index));
     }
+    parse_frame(bytes, header.channels).map_err(|e| format!("Frame {}: {}", index, e))
+}
 
-    fn read_u32_le(&mut self) -> FloResult<u32> {
-        let bytes = self.read_bytes(4)?;
-        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+/// parse the frame header, channels are left for `FrameRef::channels`
+pub(crate) fn parse_frame(bytes: &[u8], channels: u8) -> FloResult<FrameRef<'_>> {
+    if bytes.len() < 6 {
+        return Err("Unexpected end of file".to_string());
     }
 
-    fn read_i32_le(&mut self) -> FloResult<i32> {
-        let bytes = self.read_bytes(4)?;
-        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    // frame header: type(1) + samples(4) + flags(1)
+    let frame_type = bytes[0];
+    let frame_samples = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+    let flags = bytes[5];
+
+    // transform frames are one blob, others are per-channel
+    let stored_channels = if FrameType::from(frame_type) == FrameType::Transform {
+        1
+    } else {
+        channels
+    };
+
+    Ok(FrameRef {
+        frame_type,
+        frame_samples,
+        flags,
+        body: &bytes[6..],
+        stored_channels,
+    })
+}
+
+fn parse_channel(
+    frame_type: FrameType,
+    frame_samples: usize,
+    ch: &[u8],
+) -> FloResult<ChannelRef<'_>> {
+    if frame_samples > 2_000_000 {
+        return Err("Invalid frame: too many samples".to_string());
     }
 
-    fn read_u64_le(&mut self) -> FloResult<u64> {
-        let bytes = self.read_bytes(8)?;
-        Ok(u64::from_le_bytes([
-            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
-        ]))
+    match frame_type {
+        FrameType::Silence => Ok(ChannelRef::new(&[], 0, ResidualEncoding::Rice, 0, &[])),
+
+        FrameType::Raw => {
+            let bytes_to_read = frame_samples.saturating_mul(2).min(ch.len());
+            Ok(ChannelRef::new(
+                &[],
+                0,
+                ResidualEncoding::Raw,
+                0,
+                &ch[..bytes_to_read],
+            ))
+        }
+
+        // serialized mdct data
+        FrameType::Transform => Ok(ChannelRef::new(&[], 0, ResidualEncoding::Raw, 0, ch)),
+
+        _ if frame_type.is_alpc() => {
+            let byte = |pos: usize| ch.get(pos).copied().ok_or("Unexpected end of file");
+
+            // predictor order
+            let order = byte(0)? as usize;
+            if order > MAX_PREDICTOR_ORDER {
+                return Err("Invalid LPC order".to_string());
+            }
+
+            // predictor coeffs
+            let mut coeffs = [0i32; MAX_PREDICTOR_ORDER];
+            let mut pos = 1;
+            let mut stored = 0;
+            while stored < order && pos + 4 <= ch.len() {
+                coeffs[stored] =
+                    i32::from_le_bytes([ch[pos], ch[pos + 1], ch[pos + 2], ch[pos + 3]]);
+                stored += 1;
+                pos += 4;
+            }
+
+            let shift_bits = byte(pos)?;
+            let residual_encoding = ResidualEncoding::from(byte(pos + 1)?);
+            pos += 2;
+
+            // rice param only for rice encoding
+            let rice_parameter = if residual_encoding == ResidualEncoding::Rice {
+                let rice_parameter = byte(pos)?;
+                pos += 1;
+                rice_parameter
+            } else {
+                0
+            };
+
+            // rest is residuals
+            Ok(ChannelRef::new(
+                &coeffs[..stored],
+                shift_bits,
+                residual_encoding,
+                rice_parameter,
+                &ch[pos..],
+            ))
+        }
+
+        _ => Ok(ChannelRef::new(&[], 0, ResidualEncoding::Rice, 0, &[])),
     }
 }
@@ -0,0 +1,65 @@
+//! ReplayGain 2.0 track and album gain for existing flo files
+//!
+//! Each file is decoded once to measure it, then only its metadata is
+//! rewritten. Album gain comes from the gated 400 ms blocks of every track
+//! together (see `AlbumLoudness`), as ReplayGain 2.0 and EBU R128 scanners do.
+
+use crate::core::{AlbumLoudness, FloMetadata, FloResult, LoudnessMetrics};
+use crate::{update_metadata_bytes, Decoder, Reader};
+
+/// Measure files as one album and write track and album gain into each
+///
+/// Returns the files in the same order with the loudness fields set, the
+/// audio is copied as it is. Files without metadata get some.
+pub fn apply_album_gain(files: &[&[u8]]) -> FloResult<Vec<Vec<u8>>> {
+    if files.is_empty() {
+        return Err("No files to measure".to_string());
+    }
+
+    let mut album = AlbumLoudness::new();
+    let tracks = files
+        .iter()
+        .enumerate()
+        .map(|(i, data)| measure_track(&mut album, data).map_err(|e| format!("File {}: {}", i, e)))
+        .collect::<FloResult<Vec<_>>>()?;
+    let album = album.metrics();
+
+    files
+        .iter()
+        .zip(&tracks)
+        .map(|(data, track)| {
+            write_gain(data, |meta| {
+                meta.set_track_loudness(track);
+                meta.set_album_loudness(&album);
+            })
+        })
+        .collect()
+}
+
+/// Measure one file and write its track gain, album fields are left alone
+pub fn apply_track_gain(data: &[u8]) -> FloResult<Vec<u8>> {
+    let track = measure_track(&mut AlbumLoudness::new(), data)?;
+    write_gain(data, |meta| meta.set_track_loudness(&track))
+}
+
+/// Internal: decode a file and add it to the album
+fn measure_track(album: &mut AlbumLoudness, data: &[u8]) -> FloResult<LoudnessMetrics> {
+    let file = Reader::new().read_ref(data)?;
+    let samples = Decoder::new().decode_ref(&file)?;
+    Ok(album.add_track(&samples, file.header.channels, file.header.sample_rate))
+}
+
+/// Internal: the file with `edit` applied to its metadata
+fn write_gain(data: &[u8], edit: impl FnOnce(&mut FloMetadata)) -> FloResult<Vec<u8>> {
+    let file = Reader::new().read_ref(data)?;
+    let mut meta = if file.metadata.is_empty() {
+        FloMetadata::new()
+    } else {
+        FloMetadata::from_msgpack(file.metadata).map_err(|e| format!("Invalid metadata: {}", e))?
+    };
+    edit(&mut meta);
+    let bytes = meta
+        .to_msgpack()
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+    update_metadata_bytes(data, &bytes)
+}
@@ -1,6 +1,7 @@
 /// TOC-based seeking and frame access functionality
 /// gives access to frames without fully decoding the entire file.
-use crate::core::{FloFile, FloResult, TocEntry};
+use crate::core::{Chapter, FloFileRef, FloMetadata, FloResult, FrameType, Header, TocEntry};
+use crate::frame_decoder::{trim_lead_in, trim_padding, FrameDecoder};
 use crate::reader::Reader;
 
 /// Represents the result of a seek operation
@@ -18,17 +19,152 @@ pub struct SeekResult {
     pub next_timestamp_ms: u32,
 }
 
+/// Frame positions of a flo file, read from its header and TOC alone
+///
+/// Building one doesn't touch the frames, so a seek bar over a long file
+/// costs no more than its TOC. Only the first `index_size` bytes of the
+/// file are needed.
+#[derive(Debug, Clone)]
+pub struct SeekTable {
+    /// File header
+    pub header: Header,
+    /// TOC entries in frame order
+    pub entries: Vec<TocEntry>,
+}
+
+impl SeekTable {
+    /// Parse the header and TOC at the start of `flo_data`
+    ///
+    /// Anything after the TOC may be missing.
+    pub fn parse(flo_data: &[u8]) -> FloResult<Self> {
+        let header = Header::parse(flo_data)?;
+        let toc_start = 4 + header.header_size as usize;
+        let toc_end = toc_start.saturating_add(header.toc_size as usize);
+        let toc = flo_data.get(toc_start..toc_end).ok_or_else(|| {
+            format!(
+                "Need {} bytes for the header and TOC, got {}",
+                toc_end,
+                flo_data.len()
+            )
+        })?;
+
+        let entries = TocEntry::parse_all(toc, &header)?;
+        Ok(SeekTable { header, entries })
+    }
+
+    /// Number of frames
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Does the file have no frames?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Length of a frame in samples (per channel), None past the last frame
+    pub fn frame_samples(&self, index: usize) -> Option<u64> {
+        let start = self.entries.get(index)?.first_sample;
+        Some(match self.entries.get(index + 1) {
+            Some(next) => next.first_sample.saturating_sub(start),
+            // the last frame runs to the end of the samples the TOC covers
+            None => {
+                (self.entries[0].first_sample + self.header.total_samples).saturating_sub(start)
+            }
+        })
+    }
+
+    /// Find the frame holding a sample of the decoded audio
+    ///
+    /// `sample` counts samples per channel from the start of the audio, as
+    /// `decode` returns it. `sample_offset` is exact, it's where the sample
+    /// sits in what `decode_frame_at` gives for the frame. Positions past the
+    /// end land at the end of the last frame.
+    pub fn seek_to_sample(&self, sample: u64) -> FloResult<SeekResult> {
+        let sample = self
+            .header
+            .gapless_length()
+            .map_or(sample, |length| sample.min(length));
+        let (index, frame_start) = locate_sample(&self.header, &self.entries, sample)
+            .ok_or("No TOC available for seeking")?;
+
+        let entry = &self.entries[index];
+        let frame_samples = self.frame_samples(index).unwrap_or(0);
+        let sample_offset = (sample - frame_start).min(frame_samples) as u32;
+        let next_timestamp_ms = match self.entries.get(index + 1) {
+            Some(next) => next.timestamp_ms,
+            None => {
+                let sample_rate = self.header.sample_rate as u64;
+                entry.timestamp_ms + (frame_samples * 1000 / sample_rate) as u32
+            }
+        };
+
+        Ok(SeekResult {
+            frame_index: index as u32,
+            byte_offset: entry.byte_offset,
+            timestamp_ms: entry.timestamp_ms,
+            sample_offset,
+            next_timestamp_ms,
+        })
+    }
+
+    /// Find the frame playing at a time in milliseconds, see `seek_to_sample`
+    pub fn seek_to_ms(&self, time_ms: u32) -> FloResult<SeekResult> {
+        let sample = time_ms as u64 * self.header.sample_rate as u64 / 1000;
+        self.seek_to_sample(sample)
+    }
+}
+
+/// Internal: the frame whose decoded output holds audio sample `sample`, and
+/// the audio sample that output starts at
+///
+/// Goes by the TOC sample positions. Lossy frame 0 is the preroll and has
+/// no output, every later frame plays from its position less the preroll
+/// and the lead-in of a file trimmed off the hop grid.
+pub(crate) fn locate_sample(
+    header: &Header,
+    toc: &[TocEntry],
+    sample: u64,
+) -> Option<(usize, u64)> {
+    let first = toc.first()?.first_sample;
+    let skip = match toc.get(1) {
+        Some(next) if header.flags & 0x01 != 0 => {
+            let preroll = next.first_sample.saturating_sub(first);
+            preroll + header.lead_in(preroll as u32)
+        }
+        _ => 0,
+    };
+
+    let position = (first + skip).saturating_add(sample);
+    let index = toc
+        .partition_point(|entry| entry.first_sample <= position)
+        .saturating_sub(1);
+    let frame_start = (toc[index].first_sample - first).saturating_sub(skip);
+    Some((index, frame_start.min(sample)))
+}
+
+/// Number of bytes at the start of a file that hold its header and TOC
+///
+/// # Arguments
+/// * `header_bytes` - The start of a flo file, at least the whole header
+///
+/// # Returns
+/// How much of the file `SeekTable::parse` needs
+pub fn index_size(header_bytes: &[u8]) -> FloResult<u64> {
+    let header = Header::parse(header_bytes)?;
+    Ok(4 + header.header_size + header.toc_size)
+}
+
 /// Extract TOC entries from a flo file
 ///
 /// # Arguments
-/// * `flo_data` - Complete flo file bytes
+/// * `flo_data` - flo file bytes, the header and TOC are enough
 ///
 /// # Returns
-/// Vector of TOC entries with frame indices, byte offsets, and timestamps
+/// Vector of TOC entries with frame indices, byte offsets, timestamps and
+/// sample positions
 pub fn get_toc(flo_data: &[u8]) -> FloResult<Vec<TocEntry>> {
-    let reader = Reader::new();
-    let file = reader.read(flo_data)?;
-    Ok(file.toc)
+    Ok(SeekTable::parse(flo_data)?.entries)
 }
 
 /// Decode a single frame at a specific index without decoding the entire file
@@ -38,170 +174,194 @@ pub fn get_toc(flo_data: &[u8]) -> FloResult<Vec<TocEntry>> {
 /// * `frame_index` - Zero-based frame index
 ///
 /// # Returns
-/// Raw interleaved audio samples for that frame (f32, -1.0 to 1.0)
+/// Raw interleaved audio samples for that frame (f32, -1.0 to 1.0), the
+/// same samples a full decode gives for it. Empty for the preroll frame 0
+/// of a lossy file, and the last frame stops where the audio does.
 ///
 /// # Note
-/// This function decodes only the requested frame
+/// This function decodes only the requested frame, reading it in place
+/// from `flo_data` without copying the rest of the file
 pub fn decode_frame_at(flo_data: &[u8], frame_index: u32) -> FloResult<Vec<f32>> {
-    let reader = Reader::new();
-    let file = reader.read(flo_data)?;
+    let file = Reader::new().read_ref(flo_data)?;
 
-    if frame_index as usize >= file.frames.len() {
+    if frame_index as usize >= file.num_frames() {
         return Err(format!(
             "Frame index {} out of bounds (total frames: {})",
             frame_index,
-            file.frames.len()
+            file.num_frames()
         ));
     }
 
-    let frame = &file.frames[frame_index as usize];
+    let index = frame_index as usize;
+    let frame = file.frame(index)?;
+    let mut decoder = FrameDecoder::new(file.header.sample_rate, file.header.channels);
 
-    // Detect if this is a lossy (transform) frame
-    let is_transform = frame.frame_type == (crate::FrameType::Transform as u8);
+    // a transform frame overlaps the one before it, decoding that one is
+    // enough to prime the MDCT. Frame 0 is the preroll and has no output
+    if index > 0 && FrameType::from(frame.frame_type).is_transform() {
+        decoder.prime(file.frame(index - 1).ok().as_ref());
+    }
 
-    if is_transform {
-        // Decode lossy frame
-        decode_frame_lossy(&file, frame_index as usize)
-    } else {
-        // Decode lossless frame
-        decode_frame_lossless(&file, frame_index as usize)
+    let mut samples = decoder.decode(&frame)?;
+    if index == 1 && FrameType::from(frame.frame_type).is_transform() {
+        trim_lead_in(&mut samples, &file.header, frame.frame_samples);
     }
+    if index + 1 == file.num_frames() {
+        trim_padding(&mut samples, &file.header);
+    }
+    Ok(samples)
 }
 
-/// Seek to a specific time in milliseconds and get frame information
+/// Decode sample-frames `start..end` without decoding the entire file
 ///
 /// # Arguments
 /// * `flo_data` - Complete flo file bytes
-/// * `time_ms` - Target time in milliseconds
+/// * `start` - First sample-frame (samples per channel) to return
+/// * `end` - Sample-frame to stop before, clamped to the end of the audio
 ///
 /// # Returns
-/// SeekResult with frame information and sample offset for sub-frame seeking
-pub fn seek_to_time(flo_data: &[u8], target_ms: u32) -> FloResult<SeekResult> {
-    let reader = Reader::new();
-    let file = reader.read(flo_data)?;
-
-    if file.toc.is_empty() {
-        return Err("No TOC available for seeking".to_string());
-    }
-
-    // Binary search for the frame containing target_ms
-    let mut frame_index = binary_search_frame(&file.toc, target_ms);
-
-    // Clamp to valid range
-    if frame_index as usize >= file.frames.len() {
-        frame_index = (file.frames.len() - 1) as u32;
-    }
-
-    let toc_entry = &file.toc[frame_index as usize];
-
-    // Calculate sample offset within this frame for sub-frame accuracy
-    let frame_duration_ms = if frame_index + 1 < file.toc.len() as u32 {
-        file.toc[(frame_index + 1) as usize].timestamp_ms - toc_entry.timestamp_ms
-    } else {
-        // Last frame: estimate from duration
-        let last_frame_samples = file.frames[frame_index as usize].frame_samples;
-        ((last_frame_samples as u64 * 1000) / file.header.sample_rate as u64) as u32
-    };
-
-    // How far into this frame should we start?
-    let ms_into_frame = target_ms.saturating_sub(toc_entry.timestamp_ms);
-
-    // Convert to sample offset
-    let sample_offset = ((ms_into_frame as u64 * file.header.sample_rate as u64) / 1000) as u32;
-
-    // Clamp to actual frame size
-    let frame = &file.frames[frame_index as usize];
-    let sample_offset = sample_offset.min(frame.frame_samples);
-
-    let next_timestamp_ms = if frame_index + 1 < file.toc.len() as u32 {
-        file.toc[(frame_index + 1) as usize].timestamp_ms
-    } else {
-        // Estimate next frame time based on last frame samples
-        toc_entry.timestamp_ms + frame_duration_ms
-    };
+/// Exactly `end - start` interleaved sample-frames, the same samples a full
+/// decode gives for them
+///
+/// # Note
+/// Only the frames overlapping the range are decoded, plus the one before
+/// them on lossy files to prime the MDCT
+pub fn decode_range(flo_data: &[u8], start: u64, end: u64) -> FloResult<Vec<f32>> {
+    let file = Reader::new().read_ref(flo_data)?;
+    decode_file_range(&file, start, end)
+}
 
-    Ok(SeekResult {
-        frame_index,
-        byte_offset: toc_entry.byte_offset,
-        timestamp_ms: toc_entry.timestamp_ms,
-        sample_offset,
-        next_timestamp_ms,
-    })
+/// Decode the audio from `start_ms` to `end_ms`, see `decode_range`
+///
+/// Times are rounded down to the sample.
+pub fn decode_range_ms(flo_data: &[u8], start_ms: u32, end_ms: u32) -> FloResult<Vec<f32>> {
+    let file = Reader::new().read_ref(flo_data)?;
+    let sample_rate = file.header.sample_rate as u64;
+    decode_file_range(
+        &file,
+        start_ms as u64 * sample_rate / 1000,
+        end_ms as u64 * sample_rate / 1000,
+    )
 }
 
-/// Find the best frame for a given timestamp using binary search
-/// Returns the frame index that should be played for the given time
-fn binary_search_frame(toc: &[TocEntry], target_ms: u32) -> u32 {
-    if toc.is_empty() {
-        return 0;
+/// Internal: decode a range of a borrowed file, see `decode_range`
+fn decode_file_range(file: &FloFileRef<'_>, start: u64, end: u64) -> FloResult<Vec<f32>> {
+    if start > end {
+        return Err(format!("Invalid range: {}..{}", start, end));
     }
 
-    let mut left = 0;
-    let mut right = toc.len() - 1;
-
-    // Binary search for the rightmost frame where timestamp_ms <= target_ms
-    while left < right {
-        let mid = left + (right - left + 1) / 2;
+    let channels = file.header.channels as usize;
+    let end = file.header.gapless_length().map_or(end, |len| end.min(len));
+    let start = start.min(end);
+    let mut decoder = FrameDecoder::new(file.header.sample_rate, file.header.channels);
+    let mut samples = Vec::new();
+
+    // where the current frame's output starts, lossy frame 0 is the preroll
+    let mut frame_start = 0u64;
+    for index in 0..file.num_frames() {
+        let (frame_type, frame_samples) = file
+            .frame_header(index)
+            .ok_or_else(|| format!("Frame {}: Unexpected end of file", index))?;
+        let preroll = index == 0 && frame_type.is_transform();
+        // frame 1 starts with the lead-in of a file trimmed off the hop grid
+        let lead_in = match index {
+            1 if frame_type.is_transform() => file.header.lead_in(frame_samples),
+            _ => 0,
+        };
+        let from = frame_start;
+        let to = from
+            + if preroll {
+                0
+            } else {
+                (frame_samples as u64).saturating_sub(lead_in)
+            };
+        frame_start = to;
+
+        if from >= end {
+            break;
+        }
+        if to <= start {
+            continue;
+        }
 
-        if toc[mid].timestamp_ms <= target_ms {
-            left = mid;
-        } else {
-            right = mid - 1;
+        // the frame before the range primes the overlap
+        if index > 0 && frame_type.is_transform() && decoder.needs_priming() {
+            decoder.prime(file.frame(index - 1).ok().as_ref());
         }
+        let mut decoded = decoder.decode(&file.frame(index)?)?;
+        if lead_in > 0 {
+            trim_lead_in(&mut decoded, &file.header, frame_samples);
+        }
+        let keep_from = (start.saturating_sub(from) as usize * channels).min(decoded.len());
+        let keep_to = ((end.min(to) - from) as usize * channels).min(decoded.len());
+        samples.extend_from_slice(&decoded[keep_from..keep_to]);
     }
 
-    left as u32
+    Ok(samples)
 }
 
-/// Internal: Decode a lossless frame
-fn decode_frame_lossless(file: &FloFile, frame_index: usize) -> FloResult<Vec<f32>> {
-    let frame = &file.frames[frame_index];
-    let decoder = crate::Decoder::new();
-
-    // Create a temporary FloFile with just this frame
-    let frames = vec![frame.clone()];
-    let temp_file = FloFile {
-        header: file.header.clone(),
-        toc: file.toc.clone(),
-        frames: frames,
-        extra: vec![],
-        metadata: file.metadata.clone(),
-    };
-
-    decoder.decode_file(&temp_file)
+/// Seek to a specific time in milliseconds and get frame information
+///
+/// # Arguments
+/// * `flo_data` - flo file bytes, the header and TOC are enough
+/// * `time_ms` - Target time in milliseconds
+///
+/// # Returns
+/// SeekResult with frame information and sample offset for sub-frame seeking
+pub fn seek_to_time(flo_data: &[u8], target_ms: u32) -> FloResult<SeekResult> {
+    SeekTable::parse(flo_data)?.seek_to_ms(target_ms)
 }
 
-/// Internal: Decode a lossy frame
-fn decode_frame_lossy(file: &FloFile, frame_index: usize) -> FloResult<Vec<f32>> {
-    let frame = &file.frames[frame_index];
-
-    if frame.channels.is_empty() {
-        return Err("Transform frame has no channel data".to_string());
-    }
-
-    // Transform data is in first channel's residuals
-    let frame_data = &frame.channels[0].residuals;
+/// Read the chapter table of a flo file
+///
+/// # Returns
+/// Chapters sorted by start sample, empty if the file has none
+pub fn get_chapters(flo_data: &[u8]) -> FloResult<Vec<Chapter>> {
+    let file = Reader::new().read_ref(flo_data)?;
+    Ok(file_metadata(file.metadata)?.chapters)
+}
 
-    if let Some(transform_frame) = crate::lossy::deserialize_frame(frame_data) {
-        let mut decoder =
-            crate::lossy::TransformDecoder::new(file.header.sample_rate, file.header.channels);
+/// Seek to the first sample of a chapter
+///
+/// # Arguments
+/// * `flo_data` - Complete flo file bytes
+/// * `chapter_index` - Zero-based chapter index
+///
+/// # Returns
+/// SeekResult for the frame holding the chapter start, `sample_offset` is
+/// exact rather than rounded to milliseconds
+pub fn seek_to_chapter(flo_data: &[u8], chapter_index: usize) -> FloResult<SeekResult> {
+    let file = Reader::new().read_ref(flo_data)?;
+    let chapters = file_metadata(file.metadata)?.chapters;
+    let chapter = chapters.get(chapter_index).ok_or_else(|| {
+        format!(
+            "Chapter index {} out of bounds (total chapters: {})",
+            chapter_index,
+            chapters.len()
+        )
+    })?;
+
+    let table = SeekTable {
+        header: file.header,
+        entries: file.toc,
+    };
+    table.seek_to_sample(chapter.start_sample)
+}
 
-        // For lossy frames, we need to maintain decoder state across frames
-        // Skip frames before the target to maintain state
-        for i in 0..frame_index {
-            let f = &file.frames[i];
-            if f.channels.is_empty() {
-                continue;
-            }
-            let ch_data = &f.channels[0].residuals;
-            if let Some(tf) = crate::lossy::deserialize_frame(ch_data) {
-                let _ = decoder.decode_frame(&tf);
-            }
-        }
+/// Find the chapter playing at a time in milliseconds
+///
+/// # Returns
+/// Chapter index, None if the file has no chapters or `time_ms` is before the first
+pub fn chapter_at_time(flo_data: &[u8], time_ms: u32) -> FloResult<Option<usize>> {
+    let file = Reader::new().read_ref(flo_data)?;
+    let sample = time_ms as u64 * file.header.sample_rate as u64 / 1000;
+    Ok(file_metadata(file.metadata)?.chapter_at(sample))
+}
 
-        // Now decode the target frame
-        Ok(decoder.decode_frame(&transform_frame))
-    } else {
-        Err("Failed to deserialize transform frame".to_string())
+/// Internal: the flo metadata of a file, empty if it has none
+fn file_metadata(metadata: &[u8]) -> FloResult<FloMetadata> {
+    if metadata.is_empty() {
+        return Ok(FloMetadata::new());
     }
+    FloMetadata::from_msgpack(metadata).map_err(|e| format!("Invalid metadata: {}", e))
 }
@@ -0,0 +1,269 @@
+//! futures adapters over async byte streams (`async` feature)
+//!
+//! `DecodeStream` reads flo™ bytes from an `AsyncRead` and yields decoded
+//! sample blocks, `EncodeSink` takes sample blocks and writes flo™ bytes to
+//! an `AsyncWrite`. Both only touch the byte stream when they're polled, so a
+//! slow consumer holds the reader back and a slow writer holds the sender
+//! back. Dropping either one cancels it, nothing runs in the background.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::{Sink, Stream};
+
+use super::{DecoderState, StreamingDecoder, StreamingEncoder};
+use crate::core::FloResult;
+
+/// bytes read from the source at a time
+const READ_CHUNK: usize = 16 * 1024;
+
+/// Decoded sample blocks from a flo™ file or live stream arriving as bytes
+///
+/// Yields one block of interleaved samples per frame. Bytes are read only
+/// when the next block is asked for and the buffered ones don't make a frame
+/// yet, so `with_max_buffer` on the decoder bounds memory too.
+pub struct DecodeStream<R> {
+    reader: R,
+    decoder: StreamingDecoder,
+    chunk: Vec<u8>,
+    /// read but not taken by the decoder yet
+    pending: Vec<u8>,
+    /// the reader has no more bytes
+    eof: bool,
+    /// ended with an error, nothing more comes
+    failed: bool,
+}
+
+impl<R: AsyncRead + Unpin> DecodeStream<R> {
+    /// decode everything `reader` produces
+    pub fn new(reader: R) -> Self {
+        Self::with_decoder(reader, StreamingDecoder::new())
+    }
+
+    /// decode with a configured decoder, e.g. concealment or a buffer limit
+    pub fn with_decoder(reader: R, decoder: StreamingDecoder) -> Self {
+        Self {
+            reader,
+            decoder,
+            chunk: vec![0; READ_CHUNK],
+            pending: Vec::new(),
+            eof: false,
+            failed: false,
+        }
+    }
+
+    /// the decoder, for `info()`, `metadata()` or `damaged_frames()`
+    pub fn decoder(&self) -> &StreamingDecoder {
+        &self.decoder
+    }
+
+    /// give back the reader and decoder
+    pub fn into_inner(self) -> (R, StreamingDecoder) {
+        (self.reader, self.decoder)
+    }
+
+    /// hand pending bytes to the decoder, false if it took none
+    fn feed_pending(&mut self) -> FloResult<bool> {
+        let status = self.decoder.feed(&self.pending)?;
+        self.pending.drain(..status.accepted);
+        Ok(status.accepted > 0)
+    }
+
+    /// next block, reading only when the buffered bytes don't make one
+    fn poll_block(&mut self, cx: &mut Context<'_>) -> Poll<Option<FloResult<Vec<f32>>>> {
+        if self.failed {
+            return Poll::Ready(None);
+        }
+        loop {
+            match self.decoder.next_frame() {
+                // the lossy preroll plays nothing
+                Ok(Some(samples)) if samples.is_empty() => continue,
+                Ok(Some(samples)) => return Poll::Ready(Some(Ok(samples))),
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+            if matches!(
+                self.decoder.state(),
+                DecoderState::Finished | DecoderState::Error
+            ) {
+                return Poll::Ready(None);
+            }
+
+            if !self.pending.is_empty() {
+                match self.feed_pending() {
+                    Ok(true) => continue,
+                    // the decoder is full and has nothing to decode, it never lets that happen
+                    Ok(false) => {
+                        return Poll::Ready(Some(Err("Decoder buffer stalled".to_string())))
+                    }
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                }
+            }
+
+            if self.eof {
+                if self.decoder.is_live() {
+                    return Poll::Ready(None);
+                }
+                return Poll::Ready(Some(Err("Stream ended before the last frame".to_string())));
+            }
+
+            match Pin::new(&mut self.reader).poll_read(cx, &mut self.chunk) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.to_string()))),
+                Poll::Ready(Ok(0)) => self.eof = true,
+                Poll::Ready(Ok(n)) => self.pending.extend_from_slice(&self.chunk[..n]),
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for DecodeStream<R> {
+    type Item = FloResult<Vec<f32>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = this.poll_block(cx);
+        if let Poll::Ready(Some(Err(_))) = &item {
+            this.failed = true;
+        }
+        item
+    }
+}
+
+/// What an `EncodeSink` writes
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SinkOutput {
+    /// a live packet per frame, as soon as it's encoded
+    Live,
+    /// a complete file with this metadata when the sink is closed
+    File(Vec<u8>),
+}
+
+/// Encodes sample blocks sent into it and writes flo™ bytes
+///
+/// Blocks are interleaved samples of any length. `live` writes each frame
+/// as a live packet as soon as it's encoded, `file` writes a whole file when
+/// the sink is closed (the TOC needs every frame first). Encoded bytes are
+/// written before the next block is taken, so a slow writer holds the sender
+/// back. Close the sink to encode the last partial frame.
+pub struct EncodeSink<W> {
+    writer: W,
+    encoder: StreamingEncoder,
+    output: SinkOutput,
+    /// encoded bytes not written yet
+    buffer: Vec<u8>,
+    /// how much of `buffer` is written
+    written: usize,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> EncodeSink<W> {
+    /// write live packets as frames are encoded
+    pub fn live(encoder: StreamingEncoder, writer: W) -> Self {
+        Self::with_output(encoder, writer, SinkOutput::Live)
+    }
+
+    /// write a flo™ file with `metadata` (MessagePack) on close
+    pub fn file(encoder: StreamingEncoder, writer: W, metadata: &[u8]) -> Self {
+        Self::with_output(encoder, writer, SinkOutput::File(metadata.to_vec()))
+    }
+
+    fn with_output(encoder: StreamingEncoder, writer: W, output: SinkOutput) -> Self {
+        Self {
+            writer,
+            encoder,
+            output,
+            buffer: Vec::new(),
+            written: 0,
+            finished: false,
+        }
+    }
+
+    /// the encoder, e.g. for `algorithmic_delay()`
+    pub fn encoder(&self) -> &StreamingEncoder {
+        &self.encoder
+    }
+
+    /// give back the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// queue the live packets of every finished frame
+    fn queue_packets(&mut self) {
+        if self.output == SinkOutput::Live {
+            while let Some(packet) = self.encoder.next_live_packet() {
+                self.buffer.extend_from_slice(&packet);
+            }
+        }
+    }
+
+    /// write out the buffered bytes
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<FloResult<()>> {
+        while self.written < self.buffer.len() {
+            match Pin::new(&mut self.writer).poll_write(cx, &self.buffer[self.written..]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.to_string())),
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err("Writer closed before all bytes were written".into()))
+                }
+                Poll::Ready(Ok(n)) => self.written += n,
+            }
+        }
+        self.buffer.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<Vec<f32>> for EncodeSink<W> {
+    type Error = String;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<FloResult<()>> {
+        self.get_mut().poll_write_buffer(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, samples: Vec<f32>) -> FloResult<()> {
+        let this = self.get_mut();
+        if this.finished {
+            return Err("Sink is closed".to_string());
+        }
+        this.encoder.push_samples(&samples)?;
+        this.queue_packets();
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<FloResult<()>> {
+        let this = self.get_mut();
+        if let Err(e) = futures::ready!(this.poll_write_buffer(cx)) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut this.writer)
+            .poll_flush(cx)
+            .map_err(|e| e.to_string())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<FloResult<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            this.finished = true;
+            match &this.output {
+                SinkOutput::Live => {
+                    this.encoder.finish()?;
+                    this.queue_packets();
+                }
+                SinkOutput::File(metadata) => {
+                    let file = this.encoder.finalize(metadata)?;
+                    this.buffer.extend_from_slice(&file);
+                }
+            }
+        }
+        if let Err(e) = futures::ready!(this.poll_write_buffer(cx)) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut this.writer)
+            .poll_close(cx)
+            .map_err(|e| e.to_string())
+    }
+}
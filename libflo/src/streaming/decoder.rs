@@ -1,14 +1,41 @@
-use crate::core::audio_constants::i32_to_f32;
-use crate::core::{rice, ChannelData, FloResult, Frame, FrameType, Header, TocEntry};
-use crate::lossless::Decoder as LosslessDecoder;
-use crate::lossy::{deserialize_frame, TransformDecoder};
-use crate::{Reader, ResidualEncoding, MAGIC};
+use crate::core::header::{BASE_HEADER_SIZE, MAX_HEADER_SIZE};
+use crate::core::{
+    conceal_in_place, verify_frame_crc, Concealment, FloResult, FrameRef, Header, TocEntry,
+    CONCEAL_HISTORY,
+};
+use crate::frame_decoder::{trim_lead_in, trim_padding, FrameDecoder};
+use crate::reader::parse_frame;
+use crate::seeking::locate_sample;
+use crate::MAGIC;
+use std::ops::Range;
+
+use super::live::{find_sync, LivePacketHeader, LIVE_HEADER_SIZE, LIVE_SYNC};
+use super::types::{DecoderState, FeedStatus, SeekRange, StreamingAudioInfo};
+
+/// longest run of lost live audio we fill in, anything bigger is treated as a restart
+const MAX_CONCEALED_GAP_SECS: u64 = 10;
+
+/// most live frames of `frame_samples` that fit in the longest gap we fill in
+fn max_gap_frames(header: &Header, frame_samples: Option<u32>) -> u64 {
+    let frame_samples = frame_samples.unwrap_or(header.sample_rate).max(1) as u64;
+    MAX_CONCEALED_GAP_SECS * header.sample_rate as u64 / frame_samples
+}
 
-use super::types::{DecoderState, StreamingAudioInfo};
+/// live frames given up on with `conceal_frame` that are remembered, so
+/// they're dropped if they turn up late
+const MAX_LATE_FRAMES: usize = 64;
 
 pub struct StreamingDecoder {
     /// incoming data buffer
     buffer: Vec<u8>,
+    /// file offset of the first buffered byte, moves when a seek drops data
+    buffer_offset: usize,
+    /// early metadata kept aside once its bytes are dropped
+    early_metadata: Option<Vec<u8>>,
+    /// sample-frames to drop from the output after seek_to_ms
+    skip_samples: usize,
+    /// most bytes to buffer, None for no limit
+    max_buffer: Option<usize>,
     /// current state
     state: DecoderState,
     /// parsed header
@@ -19,12 +46,26 @@ pub struct StreamingDecoder {
     current_frame: usize,
     /// where data chunk starts
     data_offset: usize,
-    /// lossy decoder when needed
-    lossy_decoder: Option<TransformDecoder>,
+    /// decodes the frames, made once the stream parameters are known
+    frame_decoder: Option<FrameDecoder>,
     /// is lossy?
     is_lossy: bool,
-    /// skipped preroll frame?
-    skipped_preroll: bool,
+    /// decoding a live packet stream instead of a file
+    live: bool,
+    /// created with new_live, stays live across resets
+    live_only: bool,
+    /// frame index we expect next on a live stream
+    next_live_index: Option<u32>,
+    /// sample position we expect next on a live stream
+    next_live_sample: Option<u64>,
+    /// how to fill in damaged frames, None reports them as errors
+    concealment: Option<Concealment>,
+    /// runs of frames that failed their crc, couldn't be parsed or never arrived
+    damaged_frames: Vec<Range<usize>>,
+    /// last sample-frames handed out (interleaved), concealment carries on from here
+    history: Vec<f32>,
+    /// live frames concealed with `conceal_frame`, dropped if they arrive after all
+    given_up: Vec<u32>,
 }
 
 impl StreamingDecoder {
@@ -32,17 +73,76 @@ impl StreamingDecoder {
     pub fn new() -> Self {
         Self {
             buffer: Vec::with_capacity(64 * 1024),
+            buffer_offset: 0,
+            early_metadata: None,
+            skip_samples: 0,
+            max_buffer: None,
             state: DecoderState::WaitingForHeader,
             header: None,
             toc: Vec::new(),
             current_frame: 0,
             data_offset: 0,
-            lossy_decoder: None,
+            frame_decoder: None,
             is_lossy: false,
-            skipped_preroll: false,
+            live: false,
+            live_only: false,
+            next_live_index: None,
+            next_live_sample: None,
+            concealment: None,
+            damaged_frames: Vec::new(),
+            history: Vec::new(),
+            given_up: Vec::new(),
         }
     }
 
+    /// conceal damaged or missing frames instead of returning an error
+    ///
+    /// Damaged frames are still listed in `damaged_frames()`. On a live stream
+    /// lost packets are never an error, without a mode they are just skipped.
+    pub fn with_concealment(mut self, mode: Concealment) -> Self {
+        self.concealment = Some(mode);
+        self
+    }
+
+    /// keep at most `bytes` buffered
+    ///
+    /// `feed` takes only what fits and reports the rest, see `FeedStatus`.
+    /// Whatever the header, TOC or the next frame need is always taken, so a
+    /// small limit can't stall decoding.
+    pub fn with_max_buffer(mut self, bytes: usize) -> Self {
+        self.max_buffer = Some(bytes);
+        self
+    }
+
+    /// frames found damaged so far (bad crc, unparseable, or lost on a live stream)
+    pub fn damaged_frames(&self) -> Vec<usize> {
+        self.damaged_frames.iter().cloned().flatten().collect()
+    }
+
+    /// same as `damaged_frames`, as runs of frame indices
+    pub fn damaged_ranges(&self) -> &[Range<usize>] {
+        &self.damaged_frames
+    }
+
+    /// new decoder for a live packet stream
+    ///
+    /// The decoder scans for the next sync word, so it can be fed a stream
+    /// that was joined at any point, including the middle of a packet.
+    /// `new()` also switches to live mode when the data starts with a sync word.
+    pub fn new_live() -> Self {
+        Self {
+            state: DecoderState::Syncing,
+            live: true,
+            live_only: true,
+            ..Self::new()
+        }
+    }
+
+    /// is this a live packet stream?
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
     /// current state
     pub fn state(&self) -> DecoderState {
         self.state
@@ -55,30 +155,180 @@ impl StreamingDecoder {
             channels: h.channels,
             bit_depth: h.bit_depth,
             total_samples: h.total_samples,
+            encoder_delay: h.encoder_delay,
+            padding: h.padding,
             is_lossy: self.is_lossy,
+            is_live: self.live,
         })
     }
 
+    /// algorithmic delay of the stream in samples per channel
+    ///
+    /// The frame length, plus the MDCT overlap for lossy streams, the same as
+    /// `StreamingEncoder::algorithmic_delay` on the sending side. None until
+    /// a frame has been decoded.
+    pub fn algorithmic_delay(&self) -> Option<usize> {
+        self.frame_decoder.as_ref()?.algorithmic_delay()
+    }
+
+    /// algorithmic delay in milliseconds, see `algorithmic_delay`
+    pub fn algorithmic_delay_ms(&self) -> Option<f64> {
+        let sample_rate = self.header.as_ref()?.sample_rate;
+        Some(self.algorithmic_delay()? as f64 * 1000.0 / sample_rate as f64)
+    }
+
+    /// metadata bytes (MessagePack) as soon as they have arrived
+    ///
+    /// Files written with a `MetadataPlacement` other than `End` carry an early
+    /// chunk (a preview, or everything) right after the TOC, it is returned
+    /// before any audio is buffered. Once the whole file is in, the META
+    /// chunk takes over. None until either is available.
+    pub fn metadata(&self) -> Option<&[u8]> {
+        let header = self.header.as_ref()?;
+        if self.live {
+            return None;
+        }
+
+        let file_end = header.file_size()? as usize;
+        let meta_start = file_end - header.meta_size as usize;
+        if header.meta_size > 0 && meta_start >= self.buffer_offset {
+            let meta = (meta_start - self.buffer_offset)..(file_end - self.buffer_offset);
+            if let Some(meta) = self.buffer.get(meta) {
+                return Some(meta);
+            }
+        }
+
+        self.early_metadata
+            .as_deref()
+            .or_else(|| self.buffered_early_metadata())
+    }
+
+    /// jump to a frame of the file
+    ///
+    /// Needs the TOC. Buffered bytes before the frame (before the frame that
+    /// primes it on lossy streams) are dropped. The returned range is what the
+    /// decoder is still missing from there on, fetch it (e.g. with an HTTP
+    /// range request) and `feed` it in order starting at `start`.
+    pub fn seek_to_frame(&mut self, frame_index: usize) -> FloResult<SeekRange> {
+        if self.live {
+            return Err("Can't seek in a live stream".to_string());
+        }
+        if !matches!(self.state, DecoderState::Ready | DecoderState::Finished) {
+            return Err("Can't seek before the TOC has arrived".to_string());
+        }
+        if frame_index >= self.toc.len() {
+            return Err(format!(
+                "Frame index {} out of bounds (total frames: {})",
+                frame_index,
+                self.toc.len()
+            ));
+        }
+
+        let header = self.header.clone().ok_or("No header")?;
+        let file_end = header.file_size().ok_or("Invalid chunk sizes")?;
+
+        self.state = DecoderState::Ready;
+        self.current_frame = frame_index;
+        self.skip_samples = 0;
+        self.history.clear();
+        // the overlap starts over, on lossy streams the previous frame primes it
+        if let Some(frames) = self.frame_decoder.as_mut() {
+            frames.restart();
+        }
+
+        let first = if self.needs_priming() {
+            frame_index - 1
+        } else {
+            frame_index
+        };
+        self.discard_before(self.data_offset + self.toc[first].byte_offset as usize);
+
+        let buffered_end = (self.buffer_offset + self.buffer.len()) as u64;
+        Ok(SeekRange {
+            frame_index,
+            start: buffered_end.min(file_end),
+            end: file_end,
+        })
+    }
+
+    /// jump to a time in milliseconds, see `seek_to_frame`
+    ///
+    /// The frame holding `ms` is trimmed so the output starts right at it.
+    pub fn seek_to_ms(&mut self, ms: u32) -> FloResult<SeekRange> {
+        let header = self.header.as_ref().ok_or("No header")?;
+        self.seek_to_sample(ms as u64 * header.sample_rate as u64 / 1000)
+    }
+
+    /// jump to a sample of the audio (per channel), see `seek_to_frame`
+    ///
+    /// Frames are found by the sample positions in the TOC and the one
+    /// holding `sample` is trimmed so the output starts right at it. A
+    /// sample past the end of the audio is an error.
+    pub fn seek_to_sample(&mut self, sample: u64) -> FloResult<SeekRange> {
+        let header = self.header.as_ref().ok_or("No header")?;
+        let length = header.gapless_length().unwrap_or(header.total_samples);
+        if sample > length {
+            return Err(format!(
+                "Sample {} is past the end of the audio ({} samples)",
+                sample, length
+            ));
+        }
+        let (frame_index, frame_start) =
+            locate_sample(header, &self.toc, sample).ok_or("No TOC available for seeking")?;
+
+        let range = self.seek_to_frame(frame_index)?;
+        self.skip_samples = (sample - frame_start) as usize;
+        Ok(range)
+    }
+
     /// how many frames ready to decode
     pub fn frames_available(&self) -> usize {
+        if self.live {
+            return self.count_live_packets();
+        }
         if self.state != DecoderState::Ready {
             return 0;
         }
-        self.count_complete_frames()
+        self.buffered_frames_end()
     }
 
-    /// feed more data, returns true if new frames available
-    pub fn feed(&mut self, data: &[u8]) -> FloResult<bool> {
-        if self.state == DecoderState::Error || self.state == DecoderState::Finished {
-            return Ok(false);
+    /// feed more data
+    ///
+    /// Bytes of decoded frames are dropped as decoding goes, the header, TOC
+    /// and metadata are kept aside. With `with_max_buffer` only part of `data`
+    /// may be taken, feed the rest again after decoding some frames.
+    pub fn feed(&mut self, data: &[u8]) -> FloResult<FeedStatus> {
+        let mut accepted = 0;
+        while self.state != DecoderState::Error && accepted < data.len() {
+            let take = (data.len() - accepted).min(self.room());
+            if take == 0 {
+                break;
+            }
+
+            self.buffer
+                .extend_from_slice(&data[accepted..accepted + take]);
+            accepted += take;
+            self.try_advance_state()?;
         }
 
-        self.buffer.extend_from_slice(data);
-        self.try_advance_state()
+        Ok(FeedStatus {
+            accepted,
+            frames_ready: self.available_frames(),
+            full: self.room() == 0,
+        })
+    }
+
+    /// is the buffer at its limit?
+    pub fn is_full(&self) -> bool {
+        self.room() == 0
     }
 
     /// decode next frame, or None if nothing ready
     pub fn next_frame(&mut self) -> FloResult<Option<Vec<f32>>> {
+        if self.live {
+            return self.next_live_frame();
+        }
+
         if self.state != DecoderState::Ready {
             return Ok(None);
         }
@@ -93,45 +343,127 @@ impl StreamingDecoder {
             return Ok(None);
         }
 
-        let toc_entry = &self.toc[self.current_frame];
-        let frame_start = self.data_offset + toc_entry.byte_offset as usize;
-        let frame_end = frame_start + toc_entry.frame_size as usize;
-
-        if frame_end > self.buffer.len() {
+        let index = self.current_frame;
+        if self.buffered_frames_end() <= index {
             return Ok(None);
         }
 
-        let frame_data = &self.buffer[frame_start..frame_end];
-        let frame = self.parse_frame(frame_data, header.channels)?;
+        if self.needs_priming() {
+            // after a seek, run the previous frame through the MDCT first
+            let primer = self
+                .buffered_frame_range(index - 1)
+                .and_then(|range| parse_checked_frame(&self.buffer[range], &header));
+            if let Some(frames) = self.frame_decoder.as_mut() {
+                frames.prime(primer.as_ref());
+            }
+        }
 
+        let frame = self
+            .buffered_frame_range(index)
+            .and_then(|range| parse_checked_frame(&self.buffer[range], &header));
+        let frames = self.frame_decoder.as_mut().ok_or("No header")?;
+        let decoded = frame.and_then(|frame| frames.decode(&frame).ok());
         self.current_frame += 1;
-        let samples = self.decode_frame(&frame, &header)?;
+        self.discard_consumed();
+
+        let channels = header.channels as usize;
+        let samples = match decoded {
+            Some(samples) => {
+                self.remember_tail(&samples, channels);
+                samples
+            }
+            None => {
+                self.mark_damaged(index..index + 1);
+                let mode = self
+                    .concealment
+                    .ok_or_else(|| format!("Frame {} is damaged", index))?;
+                self.conceal_file_frame(index, &header, mode)?
+            }
+        };
+        Ok(Some(self.finish_file_frame(samples, index, &header)))
+    }
+
+    /// give up on the next frame and play a stand-in for it
+    ///
+    /// For frames that are late rather than damaged: a live packet that
+    /// hasn't arrived in time, or a file frame whose bytes are still on the
+    /// way. Uses the `with_concealment` mode, silence without one. The frame
+    /// is listed in `damaged_frames()` and decoding carries on after it, a
+    /// live packet for it that turns up later is dropped. Lossy streams keep
+    /// the MDCT overlap running, so the next frame joins without a click.
+    pub fn conceal_frame(&mut self) -> FloResult<Vec<f32>> {
+        let header = self.header.clone().ok_or("No header")?;
+        let mode = self.concealment.unwrap_or_default();
+        let channels = header.channels as usize;
+
+        if !self.live {
+            if self.state != DecoderState::Ready || self.current_frame >= self.toc.len() {
+                return Err("No frame to conceal".to_string());
+            }
+            let index = self.current_frame;
+            self.current_frame += 1;
+            self.discard_consumed();
+            self.mark_damaged(index..index + 1);
+            let samples = self.conceal_file_frame(index, &header, mode)?;
+            return Ok(self.finish_file_frame(samples, index, &header));
+        }
 
-        Ok(Some(samples))
+        let index = self.next_live_index.ok_or("No live frame decoded yet")?;
+        let frames = self.frame_decoder.as_mut().ok_or("No header")?;
+        let len = frames.frame_samples().unwrap_or(header.sample_rate) as usize;
+        let samples = if self.is_lossy {
+            let samples = frames.conceal_transform(mode);
+            self.remember_tail(&samples, channels);
+            samples
+        } else {
+            self.conceal_gap(len, None, mode, channels)
+        };
+
+        self.mark_damaged(index as usize..index as usize + 1);
+        if self.given_up.len() == MAX_LATE_FRAMES {
+            self.given_up.remove(0);
+        }
+        self.given_up.push(index);
+        self.current_frame = index as usize + 1;
+        self.next_live_index = Some(index.wrapping_add(1));
+        self.next_live_sample = self.next_live_sample.map(|s| s + len as u64);
+        Ok(samples)
     }
 
     /// decode everything we have
+    ///
+    /// Live streams never finish, files finish once the last frame is out.
     pub fn decode_available(&mut self) -> FloResult<Vec<f32>> {
-        if self.state != DecoderState::Ready {
-            return Ok(Vec::new());
+        let mut samples = Vec::new();
+        while let Some(frame) = self.next_frame()? {
+            samples.extend(frame);
         }
-
-        let samples = self.decode_with_standard_decoder()?;
-        self.state = DecoderState::Finished;
         Ok(samples)
     }
 
     /// reset for reuse
     pub fn reset(&mut self) {
         self.buffer.clear();
-        self.state = DecoderState::WaitingForHeader;
+        self.buffer_offset = 0;
+        self.early_metadata = None;
+        self.skip_samples = 0;
+        self.state = if self.live_only {
+            DecoderState::Syncing
+        } else {
+            DecoderState::WaitingForHeader
+        };
+        self.live = self.live_only;
+        self.next_live_index = None;
+        self.next_live_sample = None;
+        self.damaged_frames.clear();
+        self.history.clear();
+        self.given_up.clear();
         self.header = None;
         self.toc.clear();
         self.current_frame = 0;
         self.data_offset = 0;
-        self.lossy_decoder = None;
+        self.frame_decoder = None;
         self.is_lossy = false;
-        self.skipped_preroll = false;
     }
 
     /// bytes buffered
@@ -141,10 +473,13 @@ impl StreamingDecoder {
 
     /// frames ready to decode
     pub fn available_frames(&self) -> usize {
+        if self.live {
+            return self.count_live_packets();
+        }
         if self.state != DecoderState::Ready {
             return 0;
         }
-        self.count_complete_frames()
+        self.buffered_frames_end()
             .saturating_sub(self.current_frame)
     }
 
@@ -156,21 +491,26 @@ impl StreamingDecoder {
     // internal stuff
 
     fn try_advance_state(&mut self) -> FloResult<bool> {
-        match self.state {
-            DecoderState::WaitingForHeader => {
-                if self.try_parse_header()? {
+        if self.live {
+            return Ok(self.sync_live()?.is_some());
+        }
+
+        let state = self.state;
+        match state {
+            DecoderState::WaitingForHeader if self.try_parse_header()? => {
+                if !self.live {
                     self.state = DecoderState::WaitingForToc;
-                    return self.try_advance_state();
                 }
+                return self.try_advance_state();
             }
-            DecoderState::WaitingForToc => {
-                if self.try_parse_toc()? {
-                    self.state = DecoderState::Ready;
-                    return Ok(true);
-                }
+            DecoderState::WaitingForToc if self.try_parse_toc()? => {
+                self.state = DecoderState::Ready;
+                // the header and TOC are parsed, their bytes can go
+                self.discard_consumed();
+                return Ok(true);
             }
             DecoderState::Ready => {
-                return Ok(self.count_complete_frames() > self.current_frame);
+                return Ok(self.buffered_frames_end() > self.current_frame);
             }
             _ => {}
         }
@@ -178,8 +518,16 @@ impl StreamingDecoder {
     }
 
     fn try_parse_header(&mut self) -> FloResult<bool> {
-        // need at least 70 bytes
-        if self.buffer.len() < 70 {
+        // a live stream has no file header, just packets
+        if self.buffer.len() >= LIVE_SYNC.len() && self.buffer[0..4] == LIVE_SYNC {
+            self.live = true;
+            self.state = DecoderState::Syncing;
+            return Ok(true);
+        }
+
+        // fixed fields first, they tell us how big the whole header is
+        let fixed_end = (4 + BASE_HEADER_SIZE) as usize;
+        if self.buffer.len() < fixed_end {
             return Ok(false);
         }
 
@@ -188,91 +536,23 @@ impl StreamingDecoder {
             return Err("Invalid flo file: bad magic".to_string());
         }
 
-        let header = Header {
-            version_major: self.buffer[4],
-            version_minor: self.buffer[5],
-            flags: u16::from_le_bytes([self.buffer[6], self.buffer[7]]),
-            sample_rate: u32::from_le_bytes([
-                self.buffer[8],
-                self.buffer[9],
-                self.buffer[10],
-                self.buffer[11],
-            ]),
-            channels: self.buffer[12],
-            bit_depth: self.buffer[13],
-            total_samples: u64::from_le_bytes([
-                self.buffer[14],
-                self.buffer[15],
-                self.buffer[16],
-                self.buffer[17],
-                self.buffer[18],
-                self.buffer[19],
-                self.buffer[20],
-                self.buffer[21],
-            ]),
-            compression_level: self.buffer[22],
-            data_crc32: u32::from_le_bytes([
-                self.buffer[26],
-                self.buffer[27],
-                self.buffer[28],
-                self.buffer[29],
-            ]),
-            header_size: u64::from_le_bytes([
-                self.buffer[30],
-                self.buffer[31],
-                self.buffer[32],
-                self.buffer[33],
-                self.buffer[34],
-                self.buffer[35],
-                self.buffer[36],
-                self.buffer[37],
-            ]),
-            toc_size: u64::from_le_bytes([
-                self.buffer[38],
-                self.buffer[39],
-                self.buffer[40],
-                self.buffer[41],
-                self.buffer[42],
-                self.buffer[43],
-                self.buffer[44],
-                self.buffer[45],
-            ]),
-            data_size: u64::from_le_bytes([
-                self.buffer[46],
-                self.buffer[47],
-                self.buffer[48],
-                self.buffer[49],
-                self.buffer[50],
-                self.buffer[51],
-                self.buffer[52],
-                self.buffer[53],
-            ]),
-            extra_size: u64::from_le_bytes([
-                self.buffer[54],
-                self.buffer[55],
-                self.buffer[56],
-                self.buffer[57],
-                self.buffer[58],
-                self.buffer[59],
-                self.buffer[60],
-                self.buffer[61],
-            ]),
-            meta_size: u64::from_le_bytes([
-                self.buffer[62],
-                self.buffer[63],
-                self.buffer[64],
-                self.buffer[65],
-                self.buffer[66],
-                self.buffer[67],
-                self.buffer[68],
-                self.buffer[69],
-            ]),
+        let mut size_bytes = [0u8; 8];
+        size_bytes.copy_from_slice(&self.buffer[30..38]);
+        let header_end = 4 + u64::from_le_bytes(size_bytes).min(MAX_HEADER_SIZE) as usize;
+        if self.buffer.len() < header_end {
+            return Ok(false);
+        }
+
+        let header = match Header::parse(&self.buffer) {
+            Ok(header) => header,
+            Err(e) => {
+                self.state = DecoderState::Error;
+                return Err(e.to_string());
+            }
         };
 
         self.is_lossy = (header.flags & 0x01) != 0;
-        if self.is_lossy {
-            self.lossy_decoder = Some(TransformDecoder::new(header.sample_rate, header.channels));
-        }
+        self.frame_decoder = Some(FrameDecoder::new(header.sample_rate, header.channels));
 
         self.header = Some(header);
         Ok(true)
@@ -280,499 +560,487 @@ impl StreamingDecoder {
 
     fn try_parse_toc(&mut self) -> FloResult<bool> {
         let header = self.header.as_ref().ok_or("No header")?;
-        let toc_start = 70;
+        let toc_start = 4 + header.header_size as usize;
         let toc_end = toc_start + header.toc_size as usize;
 
         if self.buffer.len() < toc_end {
             return Ok(false);
         }
 
-        if header.toc_size >= 4 {
-            let num_entries = u32::from_le_bytes([
-                self.buffer[toc_start],
-                self.buffer[toc_start + 1],
-                self.buffer[toc_start + 2],
-                self.buffer[toc_start + 3],
-            ]) as usize;
-
-            let entries_start = toc_start + 4;
-            for i in 0..num_entries {
-                let offset = entries_start + i * 20;
-                if offset + 20 > self.buffer.len() {
-                    return Ok(false);
-                }
-
-                self.toc.push(TocEntry {
-                    frame_index: u32::from_le_bytes([
-                        self.buffer[offset],
-                        self.buffer[offset + 1],
-                        self.buffer[offset + 2],
-                        self.buffer[offset + 3],
-                    ]),
-                    byte_offset: u64::from_le_bytes([
-                        self.buffer[offset + 4],
-                        self.buffer[offset + 5],
-                        self.buffer[offset + 6],
-                        self.buffer[offset + 7],
-                        self.buffer[offset + 8],
-                        self.buffer[offset + 9],
-                        self.buffer[offset + 10],
-                        self.buffer[offset + 11],
-                    ]),
-                    frame_size: u32::from_le_bytes([
-                        self.buffer[offset + 12],
-                        self.buffer[offset + 13],
-                        self.buffer[offset + 14],
-                        self.buffer[offset + 15],
-                    ]),
-                    timestamp_ms: u32::from_le_bytes([
-                        self.buffer[offset + 16],
-                        self.buffer[offset + 17],
-                        self.buffer[offset + 18],
-                        self.buffer[offset + 19],
-                    ]),
-                });
+        match TocEntry::parse_all(&self.buffer[toc_start..toc_end], header) {
+            Ok(toc) => self.toc = toc,
+            Err(e) => {
+                self.state = DecoderState::Error;
+                return Err(e);
             }
         }
-
         self.data_offset = toc_end;
         Ok(true)
     }
 
-    fn count_complete_frames(&self) -> usize {
-        let mut count = 0;
-        for entry in &self.toc {
-            let frame_end =
-                self.data_offset + entry.byte_offset as usize + entry.frame_size as usize;
-            if frame_end <= self.buffer.len() {
-                count += 1;
-            } else {
-                break;
+    /// Drop bytes until a complete, valid live packet sits at the start of the buffer
+    ///
+    /// Returns its header, or None when more data is needed. Garbage, false
+    /// sync words and packets with a bad payload crc are skipped.
+    fn sync_live(&mut self) -> FloResult<Option<LivePacketHeader>> {
+        loop {
+            let Some(pos) = find_sync(&self.buffer) else {
+                // keep a possible partial sync word at the end
+                let keep = (LIVE_SYNC.len() - 1).min(self.buffer.len());
+                self.buffer.drain(..self.buffer.len() - keep);
+                return Ok(None);
+            };
+            self.buffer.drain(..pos);
+
+            if self.buffer.len() < LIVE_HEADER_SIZE {
+                return Ok(None);
+            }
+
+            let Some(packet) = LivePacketHeader::parse(&self.buffer) else {
+                // not a real packet start, look for the next one
+                self.buffer.drain(..1);
+                continue;
+            };
+
+            if self.buffer.len() < packet.packet_size() {
+                return Ok(None);
+            }
+
+            if !packet.verify_payload(&self.buffer[LIVE_HEADER_SIZE..packet.packet_size()]) {
+                // damaged packet, skip it and resync
+                self.buffer.drain(..1);
+                continue;
             }
+
+            return Ok(Some(packet));
         }
-        count
     }
 
-    fn parse_frame(&self, data: &[u8], channels: u8) -> FloResult<Frame> {
-        if data.len() < 6 {
-            return Err("Frame too small".to_string());
+    fn next_live_frame(&mut self) -> FloResult<Option<Vec<f32>>> {
+        if self.state == DecoderState::Error {
+            return Ok(None);
         }
 
-        let frame_type_byte = data[0];
-        let frame_samples = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
-        let flags = data[5];
-
-        let frame_type = FrameType::from(frame_type_byte);
-        let mut frame = Frame::new(frame_type_byte, frame_samples);
-        frame.flags = flags;
-
-        let num_channels = if frame_type == FrameType::Transform {
-            1
-        } else {
-            channels as usize
+        // drop packets we already played a stand-in for, the next one may be due
+        let (packet, header) = loop {
+            let Some(packet) = self.sync_live()? else {
+                return Ok(None);
+            };
+            let header = self.apply_live_params(&packet);
+            match self.given_up.iter().position(|&i| i == packet.frame_index) {
+                Some(pos) => {
+                    self.given_up.remove(pos);
+                    self.buffer.drain(..packet.packet_size());
+                }
+                None => break (packet, header),
+            }
         };
+        let channels = header.channels as usize;
 
-        let mut pos = 6;
-        for _ in 0..num_channels {
-            if pos + 4 > data.len() {
-                return Err("Frame truncated".to_string());
+        // frames between the last one we saw and this one never arrived, a
+        // jump past the longest gap we fill in (or back) is a restart of the
+        // stream. Indices wrap around on long streams.
+        let frame_samples = self.frame_decoder.as_ref().and_then(|f| f.frame_samples());
+        let max_missing = max_gap_frames(&header, frame_samples);
+        let missing = match self.next_live_index {
+            Some(expected) => {
+                let missing = packet.frame_index.wrapping_sub(expected);
+                if missing as u64 <= max_missing {
+                    if packet.frame_index >= expected {
+                        self.mark_damaged(expected as usize..packet.frame_index as usize);
+                    } else {
+                        self.mark_damaged(expected as usize..u32::MAX as usize + 1);
+                        self.mark_damaged(0..packet.frame_index as usize);
+                    }
+                    missing
+                } else {
+                    0
+                }
             }
+            None => 0,
+        };
+        let gap_samples = self
+            .next_live_sample
+            .map_or(0, |s| packet.first_sample.saturating_sub(s));
+        // both the frames and the samples missing have to fit the longest gap,
+        // lossy concealment runs once per frame
+        let conceal = self.concealment.filter(|_| {
+            missing > 0
+                && missing as u64 <= max_missing
+                && gap_samples <= MAX_CONCEALED_GAP_SECS * header.sample_rate as u64
+        });
+
+        let mut output = Vec::new();
+        let frames = self.frame_decoder.as_mut().ok_or("No header")?;
 
-            let ch_size =
-                u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
-                    as usize;
-            pos += 4;
-
-            if pos + ch_size > data.len() {
-                return Err("Channel data truncated".to_string());
+        if self.is_lossy {
+            match conceal {
+                Some(mode) if !frames.needs_priming() => {
+                    // keep the MDCT running through the gap
+                    for _ in 0..missing {
+                        output.extend(frames.conceal_transform(mode));
+                    }
+                }
+                // a gap breaks the MDCT overlap, prime again
+                _ if self.next_live_index != Some(packet.frame_index) => frames.restart(),
+                _ => {}
             }
-
-            let ch_data = &data[pos..pos + ch_size];
-            pos += ch_size;
-
-            let channel = match frame_type {
-                FrameType::Silence => ChannelData::new_silence(),
-                FrameType::Raw | FrameType::Transform => ChannelData {
-                    predictor_coeffs: vec![],
-                    shift_bits: 0,
-                    residual_encoding: ResidualEncoding::Raw,
-                    rice_parameter: 0,
-                    residuals: ch_data.to_vec(),
-                },
-                _ => self.parse_alpc_channel(ch_data, frame_type)?,
-            };
-
-            frame.channels.push(channel);
         }
+        self.remember_tail(&output, channels);
+
+        let frames = self.frame_decoder.as_mut().ok_or("No header")?;
+        let decoded = parse_frame(
+            &self.buffer[LIVE_HEADER_SIZE..packet.packet_size()],
+            header.channels,
+        )
+        .and_then(|frame| Ok((frame.frame_samples, frames.decode(&frame)?)));
+        self.buffer.drain(..packet.packet_size());
+        let (frame_samples, samples) = decoded?;
+
+        self.state = DecoderState::Ready;
+        self.current_frame = packet.frame_index as usize + 1;
+        self.next_live_index = Some(packet.frame_index.wrapping_add(1));
+        self.next_live_sample = Some(packet.first_sample + frame_samples as u64);
+
+        if let Some(mode) = conceal.filter(|_| !self.is_lossy) {
+            let next = samples.get(..channels);
+            output.extend(self.conceal_gap(gap_samples as usize, next, mode, channels));
+        }
+        self.remember_tail(&samples, channels);
+        output.extend(samples);
 
-        Ok(frame)
+        Ok(Some(output))
     }
 
-    fn parse_alpc_channel(&self, data: &[u8], _frame_type: FrameType) -> FloResult<ChannelData> {
-        if data.is_empty() {
-            return Ok(ChannelData::new_silence());
+    /// add frames to the damaged runs, joining the last run where they touch
+    fn mark_damaged(&mut self, frames: Range<usize>) {
+        if frames.is_empty() {
+            return;
         }
-
-        let order = data[0] as usize;
-        if order > 12 {
-            return Err("Invalid LPC order".to_string());
-        }
-
-        let coeff_bytes = order * 4;
-        let min_size = 1 + coeff_bytes + 2; // order + coeffs + shift + encoding
-        if data.len() < min_size {
-            return Err("ALPC channel too small".to_string());
+        match self.damaged_frames.last_mut() {
+            Some(last) if last.end == frames.start => last.end = frames.end,
+            _ => self.damaged_frames.push(frames),
         }
+    }
 
-        // Read coefficients
-        let mut coefficients = Vec::with_capacity(order);
-        for i in 0..order {
-            let offset = 1 + i * 4;
-            let coeff = i32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-            coefficients.push(coeff);
+    /// Take stream parameters from a live packet, restarting if they changed
+    fn apply_live_params(&mut self, packet: &LivePacketHeader) -> Header {
+        let changed = self.header.as_ref().is_none_or(|h| {
+            h.sample_rate != packet.sample_rate
+                || h.channels != packet.channels
+                || h.bit_depth != packet.bit_depth
+                || h.flags != packet.flags
+        });
+
+        if changed {
+            self.header = Some(Header {
+                flags: packet.flags,
+                sample_rate: packet.sample_rate,
+                channels: packet.channels,
+                bit_depth: packet.bit_depth,
+                ..Header::default()
+            });
+            self.is_lossy = packet.is_lossy();
+            self.frame_decoder = Some(FrameDecoder::new(packet.sample_rate, packet.channels));
+            self.next_live_index = None;
+            self.history.clear();
+            self.given_up.clear();
         }
 
-        let mut pos = 1 + coeff_bytes;
-
-        // Read shift_bits
-        let shift_bits = data[pos];
-        pos += 1;
-
-        // Read residual encoding
-        let residual_encoding_byte = data[pos];
-        let residual_encoding = ResidualEncoding::from(residual_encoding_byte);
-        pos += 1;
+        self.header.clone().unwrap_or_default()
+    }
 
-        // Read rice parameter (only for Rice encoding)
-        let rice_parameter = if residual_encoding == ResidualEncoding::Rice {
-            if pos >= data.len() {
-                return Err("Missing rice parameter".to_string());
+    /// complete packets sitting back to back at the front of the buffer
+    fn count_live_packets(&self) -> usize {
+        let mut count = 0;
+        let mut pos = 0;
+
+        // the same packets sync_live would accept
+        while let Some(offset) = find_sync(&self.buffer[pos..]) {
+            pos += offset;
+            let Some(packet) = LivePacketHeader::parse(&self.buffer[pos..]) else {
+                pos += 1;
+                continue;
+            };
+            let end = pos + packet.packet_size();
+            if end > self.buffer.len() {
+                break;
             }
-            let rp = data[pos];
-            pos += 1;
-            rp
-        } else {
-            0
-        };
-
-        // Rest is residuals
-        let residuals = data[pos..].to_vec();
-
-        Ok(ChannelData {
-            predictor_coeffs: coefficients,
-            shift_bits,
-            residual_encoding,
-            rice_parameter,
-            residuals,
-        })
+            if !packet.verify_payload(&self.buffer[pos + LIVE_HEADER_SIZE..end]) {
+                pos += 1;
+                continue;
+            }
+            count += 1;
+            pos = end;
+        }
+        count
     }
 
-    fn decode_frame(&mut self, frame: &Frame, header: &Header) -> FloResult<Vec<f32>> {
-        let frame_type = FrameType::from(frame.frame_type);
+    /// one past the last frame buffered without a gap from the current one
+    fn buffered_frames_end(&self) -> usize {
+        (self.first_needed_frame()..self.toc.len())
+            .find(|&i| self.buffered_frame(i).is_none())
+            .unwrap_or(self.toc.len())
+    }
 
-        // Handle Transform (lossy) frames
-        if frame_type == FrameType::Transform {
-            if frame.channels.is_empty() {
-                return Ok(Vec::new());
-            }
+    /// bytes of a file frame, if all of them are buffered
+    fn buffered_frame(&self, index: usize) -> Option<&[u8]> {
+        self.buffer.get(self.buffered_frame_range(index)?)
+    }
 
-            let frame_data = &frame.channels[0].residuals;
-            if let Some(transform_frame) = deserialize_frame(frame_data) {
-                let decoder = self.lossy_decoder.get_or_insert_with(|| {
-                    TransformDecoder::new(header.sample_rate, header.channels)
-                });
-                let samples = decoder.decode_frame(&transform_frame);
-
-                // Skip first frame (preroll) for lossy
-                if !self.skipped_preroll {
-                    self.skipped_preroll = true;
-                    return Ok(Vec::new());
-                }
+    /// where a file frame sits in the buffer, if all of it is there
+    fn buffered_frame_range(&self, index: usize) -> Option<Range<usize>> {
+        let entry = self.toc.get(index)?;
+        let start =
+            (self.data_offset + entry.byte_offset as usize).checked_sub(self.buffer_offset)?;
+        let end = start + entry.frame_size as usize;
+        (end <= self.buffer.len()).then_some(start..end)
+    }
 
-                return Ok(samples);
-            }
-            return Ok(Vec::new());
+    /// the early metadata chunk, if it's buffered
+    fn buffered_early_metadata(&self) -> Option<&[u8]> {
+        let header = self.header.as_ref()?;
+        let toc_parsed = matches!(self.state, DecoderState::Ready | DecoderState::Finished);
+        if !toc_parsed || header.early_meta_size == 0 {
+            return None;
         }
+        let start = self.data_offset.checked_sub(self.buffer_offset)?;
+        self.buffer
+            .get(start..start + header.early_meta_size as usize)
+    }
 
-        // Handle lossless frames (Silence, Raw, ALPC variants)
-        let channels = header.channels as usize;
-        let frame_samples = frame.frame_samples as usize;
-        let use_mid_side = channels == 2 && (frame.flags & 0x01) != 0;
-
-        let mut frame_channels: Vec<Vec<i32>> = Vec::with_capacity(channels);
+    /// a lossy seek landed mid-file, the frame before has to prime the overlap
+    fn needs_priming(&self) -> bool {
+        self.is_lossy
+            && self.current_frame > 0
+            && self
+                .frame_decoder
+                .as_ref()
+                .is_some_and(FrameDecoder::needs_priming)
+    }
 
-        for ch_data in &frame.channels {
-            let samples = self.decode_channel_int(ch_data, frame_samples)?;
-            frame_channels.push(samples);
+    /// first frame whose bytes are still needed
+    fn first_needed_frame(&self) -> usize {
+        if self.needs_priming() {
+            self.current_frame - 1
+        } else {
+            self.current_frame
         }
+    }
 
-        // Convert mid-side back to left-right if needed
-        let mut all_samples: Vec<Vec<i32>> = vec![vec![]; channels];
-        if use_mid_side && frame_channels.len() == 2 {
-            let (left, right) = self.decode_mid_side(&frame_channels[0], &frame_channels[1]);
-            all_samples[0] = left;
-            all_samples[1] = right;
-        } else {
-            for (ch_idx, samples) in frame_channels.into_iter().enumerate() {
-                if ch_idx < channels {
-                    all_samples[ch_idx] = samples;
-                }
-            }
+    /// buffered bytes needed before decoding can move on
+    fn needed_bytes(&self) -> usize {
+        if self.live {
+            let packet_end = find_sync(&self.buffer).and_then(|pos| {
+                LivePacketHeader::parse(&self.buffer[pos..]).map(|p| pos + p.packet_size())
+            });
+            return packet_end.unwrap_or(LIVE_HEADER_SIZE);
         }
 
-        // Interleave and convert to f32
-        let max_len = all_samples.iter().map(|v| v.len()).max().unwrap_or(0);
-        let mut interleaved = Vec::with_capacity(max_len * channels);
+        let Some(header) = self.header.as_ref() else {
+            return 4 + MAX_HEADER_SIZE as usize;
+        };
+        let end = match self.state {
+            DecoderState::WaitingForToc => 4 + (header.header_size + header.toc_size) as usize,
+            _ => match self.toc.get(self.current_frame) {
+                Some(entry) => {
+                    self.data_offset + (entry.byte_offset + entry.frame_size as u64) as usize
+                }
+                // past the frames, take the rest of the file for its metadata
+                None => header.file_size().unwrap_or(0) as usize,
+            },
+        };
+        end.saturating_sub(self.buffer_offset)
+    }
 
-        for i in 0..max_len {
-            for ch in 0..channels {
-                let sample = all_samples[ch].get(i).copied().unwrap_or(0);
-                interleaved.push(i32_to_f32(sample));
-            }
+    /// how many more bytes fit under the buffer limit
+    fn room(&self) -> usize {
+        match self.max_buffer {
+            Some(max) => max
+                .max(self.needed_bytes())
+                .saturating_sub(self.buffer.len()),
+            None => usize::MAX,
         }
-
-        Ok(interleaved)
     }
 
-    /// Decode a single channel to integers
-    fn decode_channel_int(
-        &self,
-        ch_data: &ChannelData,
-        frame_samples: usize,
-    ) -> FloResult<Vec<i32>> {
-        let has_coeffs = !ch_data.predictor_coeffs.is_empty();
-        let has_residuals = !ch_data.residuals.is_empty();
-        let shift_bits = ch_data.shift_bits;
+    /// drop the bytes of decoded frames, the header and the TOC
+    ///
+    /// The early metadata is kept aside, EXTRA and META after the frames stay.
+    fn discard_consumed(&mut self) {
+        let Some(header) = self.header.as_ref() else {
+            return;
+        };
 
-        // Check for fixed predictor marker: shift_bits >= 128 means fixed order (128 + order)
-        let is_fixed_predictor = !has_coeffs && has_residuals && shift_bits >= 128;
+        let mut keep_from = match self.toc.get(self.first_needed_frame()) {
+            Some(entry) => self.data_offset + entry.byte_offset as usize,
+            None => self.data_offset + header.data_size as usize,
+        };
 
-        if is_fixed_predictor {
-            let fixed_order = (shift_bits - 128) as usize;
-            let residuals =
-                rice::decode_i32(&ch_data.residuals, ch_data.rice_parameter, frame_samples);
-            return Ok(self.reconstruct_fixed(fixed_order, &residuals, frame_samples));
+        // early metadata that's still arriving stays in the buffer
+        let early_pending = header.early_meta_size > 0
+            && self.early_metadata.is_none()
+            && self.buffered_early_metadata().is_none()
+            && self.buffer_offset <= self.data_offset;
+        if early_pending {
+            keep_from = keep_from.min(self.data_offset);
         }
 
-        if has_coeffs {
-            // LPC decoding with stored coefficients
-            // Decode residuals based on encoding type
-            let residuals = match ch_data.residual_encoding {
-                ResidualEncoding::Rice => {
-                    rice::decode_i32(&ch_data.residuals, ch_data.rice_parameter, frame_samples)
-                }
-                ResidualEncoding::Raw | ResidualEncoding::Golomb => {
-                    // Raw residuals as i16 (Golomb not implemented, fallback to raw)
-                    let mut res = Vec::with_capacity(frame_samples);
-                    for chunk in ch_data.residuals.chunks(2) {
-                        if chunk.len() == 2 {
-                            res.push(i16::from_le_bytes([chunk[0], chunk[1]]) as i32);
-                        }
-                    }
-                    while res.len() < frame_samples {
-                        res.push(0);
-                    }
-                    res
-                }
-            };
+        let buffered_end = self.buffer_offset + self.buffer.len();
+        self.discard_before(keep_from.clamp(self.buffer_offset, buffered_end));
+    }
 
-            let order = ch_data.predictor_coeffs.len();
-            let samples = self.reconstruct_lpc_int(
-                &ch_data.predictor_coeffs,
-                &residuals,
-                shift_bits,
-                order,
-                frame_samples,
-            );
-            return Ok(samples);
+    /// drop buffered bytes before file offset `offset`, the early metadata is kept
+    fn discard_before(&mut self, offset: usize) {
+        if self.early_metadata.is_none() {
+            self.early_metadata = self.buffered_early_metadata().map(|meta| meta.to_vec());
         }
 
-        if has_residuals {
-            // Raw PCM (no prediction)
-            let mut samples = Vec::with_capacity(frame_samples);
-            for chunk in ch_data.residuals.chunks(2) {
-                if chunk.len() == 2 {
-                    samples.push(i16::from_le_bytes([chunk[0], chunk[1]]) as i32);
-                }
-            }
-            while samples.len() < frame_samples {
-                samples.push(0);
-            }
-            return Ok(samples);
+        let buffered_end = self.buffer_offset + self.buffer.len();
+        if (self.buffer_offset..=buffered_end).contains(&offset) {
+            self.buffer.drain(..offset - self.buffer_offset);
+        } else {
+            // nothing buffered is any use, data starts over at `offset`
+            self.buffer.clear();
         }
+        self.buffer_offset = offset;
+    }
 
-        // Silence
-        Ok(vec![0; frame_samples])
-    }
-
-    /// Convert mid-side back to left-right
-    fn decode_mid_side(&self, mid: &[i32], side: &[i32]) -> (Vec<i32>, Vec<i32>) {
-        let left: Vec<i32> = mid
-            .iter()
-            .zip(side.iter())
-            .map(|(&m, &s)| (m + s) / 2)
-            .collect();
-        let right: Vec<i32> = mid
-            .iter()
-            .zip(side.iter())
-            .map(|(&m, &s)| (m - s) / 2)
-            .collect();
-        (left, right)
-    }
-
-    /// Reconstruct from LPC prediction
-    fn reconstruct_lpc_int(
-        &self,
-        coeffs: &[i32],
-        residuals: &[i32],
-        shift: u8,
-        order: usize,
-        target_len: usize,
-    ) -> Vec<i32> {
-        let mut samples = Vec::with_capacity(target_len);
-
-        // Warmup samples from residuals
-        for i in 0..order.min(residuals.len()) {
-            samples.push(residuals[i]);
-        }
-
-        // Reconstruct remaining
-        for i in order..target_len.min(residuals.len()) {
-            let mut prediction: i64 = 0;
-            for (j, &coeff) in coeffs.iter().enumerate() {
-                if i > j {
-                    prediction += (coeff as i64) * (samples[i - j - 1] as i64);
-                }
-            }
-            prediction >>= shift;
-            samples.push(prediction as i32 + residuals[i]);
+    /// trim a file frame's output: the lead-in and padding around the audio,
+    /// then what a seek skips
+    fn finish_file_frame(
+        &mut self,
+        mut samples: Vec<f32>,
+        index: usize,
+        header: &Header,
+    ) -> Vec<f32> {
+        if index == 1 && self.is_lossy {
+            // frame 1 is a whole hop before the lead-in comes off
+            let hop = samples.len() / (header.channels as usize).max(1);
+            trim_lead_in(&mut samples, header, hop as u32);
         }
-
-        while samples.len() < target_len {
-            samples.push(0);
+        if index + 1 == self.toc.len() {
+            trim_padding(&mut samples, header);
         }
+        self.skip_seeked(samples, header.channels as usize)
+    }
 
+    /// drop what's left of the samples before a seek_to_ms target
+    fn skip_seeked(&mut self, mut samples: Vec<f32>, channels: usize) -> Vec<f32> {
+        let skip = (self.skip_samples * channels).min(samples.len());
+        self.skip_samples -= skip / channels.max(1);
+        samples.drain(..skip);
         samples
     }
 
-    /// Reconstruct from fixed predictor
-    fn reconstruct_fixed(&self, order: usize, residuals: &[i32], target_len: usize) -> Vec<i32> {
-        let mut samples = Vec::with_capacity(target_len);
-
-        if residuals.is_empty() {
-            return vec![0; target_len];
+    /// fill in a damaged or missing frame of a file
+    fn conceal_file_frame(
+        &mut self,
+        index: usize,
+        header: &Header,
+        mode: Concealment,
+    ) -> FloResult<Vec<f32>> {
+        if self.is_lossy {
+            let frames = self.frame_decoder.as_mut().ok_or("No header")?;
+            let samples = frames.conceal_transform(mode);
+            self.remember_tail(&samples, header.channels as usize);
+            return Ok(samples);
         }
 
-        match order {
-            0 => samples.extend_from_slice(residuals),
-            1 => {
-                samples.push(residuals[0]);
-                for i in 1..residuals.len().min(target_len) {
-                    samples.push(residuals[i].wrapping_add(samples[i - 1]));
-                }
-            }
-            2 => {
-                if !residuals.is_empty() {
-                    samples.push(residuals[0]);
-                }
-                if residuals.len() > 1 {
-                    samples.push(residuals[1].wrapping_add(samples[0]));
-                }
-                for i in 2..residuals.len().min(target_len) {
-                    let pred = (2i64 * samples[i - 1] as i64 - samples[i - 2] as i64) as i32;
-                    samples.push(residuals[i].wrapping_add(pred));
-                }
-            }
-            3 => {
-                if !residuals.is_empty() {
-                    samples.push(residuals[0]);
-                }
-                if residuals.len() > 1 {
-                    samples.push(residuals[1].wrapping_add(samples[0]));
-                }
-                if residuals.len() > 2 {
-                    let pred = (2i64 * samples[1] as i64 - samples[0] as i64) as i32;
-                    samples.push(residuals[2].wrapping_add(pred));
-                }
-                for i in 3..residuals.len().min(target_len) {
-                    let pred = (3i64 * samples[i - 1] as i64 - 3i64 * samples[i - 2] as i64
-                        + samples[i - 3] as i64) as i32;
-                    samples.push(residuals[i].wrapping_add(pred));
-                }
-            }
-            4 => {
-                if !residuals.is_empty() {
-                    samples.push(residuals[0]);
-                }
-                if residuals.len() > 1 {
-                    samples.push(residuals[1].wrapping_add(samples[0]));
-                }
-                if residuals.len() > 2 {
-                    let pred = (2i64 * samples[1] as i64 - samples[0] as i64) as i32;
-                    samples.push(residuals[2].wrapping_add(pred));
-                }
-                if residuals.len() > 3 {
-                    let pred = (3i64 * samples[2] as i64 - 3i64 * samples[1] as i64
-                        + samples[0] as i64) as i32;
-                    samples.push(residuals[3].wrapping_add(pred));
-                }
-                for i in 4..residuals.len().min(target_len) {
-                    let pred = (4i64 * samples[i - 1] as i64 - 6i64 * samples[i - 2] as i64
-                        + 4i64 * samples[i - 3] as i64
-                        - samples[i - 4] as i64) as i32;
-                    samples.push(residuals[i].wrapping_add(pred));
-                }
-            }
-            _ => samples.extend_from_slice(residuals),
-        }
+        // every frame but the last has the usual length
+        let typical = self
+            .frame_decoder
+            .as_ref()
+            .and_then(FrameDecoder::frame_samples)
+            .or_else(|| {
+                // the last frame is usually short, don't go by that one
+                let next = self
+                    .buffered_frame(index + 1)
+                    .filter(|_| index + 2 < self.toc.len())?;
+                parse_frame(next, header.channels)
+                    .ok()
+                    .map(|f| f.frame_samples)
+            })
+            .unwrap_or(header.sample_rate) as u64;
+        let len = if index + 1 < self.toc.len() {
+            typical
+        } else {
+            header
+                .total_samples
+                .saturating_sub(index as u64 * typical)
+                .min(typical)
+        };
 
-        while samples.len() < target_len {
-            samples.push(0);
-        }
+        let channels = header.channels as usize;
+        let next = match mode {
+            Concealment::Silence => None,
+            _ => self.peek_first_sample(index + 1, header),
+        };
 
-        samples
+        Ok(self.conceal_gap(len as usize, next.as_deref(), mode, channels))
     }
 
-    fn decode_with_standard_decoder(&self) -> FloResult<Vec<f32>> {
-        let reader = Reader::new();
-        let file = reader.read(&self.buffer)?;
+    /// first sample-frame of a lossless frame that's already buffered
+    fn peek_first_sample(&self, index: usize, header: &Header) -> Option<Vec<f32>> {
+        let frame = parse_checked_frame(self.buffered_frame(index)?, header)?;
+        let samples = FrameDecoder::new(header.sample_rate, header.channels)
+            .decode(&frame)
+            .ok()?;
+        samples.get(..header.channels as usize).map(|s| s.to_vec())
+    }
 
-        let is_transform = file
-            .frames
-            .iter()
-            .any(|f| f.frame_type == (FrameType::Transform as u8));
+    /// `len` concealed sample-frames between the last output and `next`
+    fn conceal_gap(
+        &mut self,
+        len: usize,
+        next: Option<&[f32]>,
+        mode: Concealment,
+        channels: usize,
+    ) -> Vec<f32> {
+        if channels == 0 {
+            return Vec::new();
+        }
+        if !self.history.len().is_multiple_of(channels) {
+            self.history.clear();
+        }
+        let start = self.history.len() / channels;
 
-        if is_transform {
-            let mut decoder = TransformDecoder::new(file.header.sample_rate, file.header.channels);
-            let mut all_samples = Vec::new();
-            let mut frame_count = 0;
+        let mut samples = Vec::with_capacity((start + len + 1) * channels);
+        samples.extend_from_slice(&self.history);
+        samples.resize((start + len) * channels, 0.0);
+        if let Some(next) = next.filter(|next| next.len() == channels) {
+            samples.extend_from_slice(next);
+        }
 
-            for frame in &file.frames {
-                if frame.channels.is_empty() {
-                    continue;
-                }
-                let frame_data = &frame.channels[0].residuals;
-                if let Some(transform_frame) = deserialize_frame(frame_data) {
-                    let samples = decoder.decode_frame(&transform_frame);
-                    if frame_count > 0 {
-                        all_samples.extend(samples);
-                    }
-                    frame_count += 1;
-                }
-            }
-            Ok(all_samples)
+        conceal_in_place(&mut samples, channels, start, len, mode);
+
+        let gap = samples[start * channels..(start + len) * channels].to_vec();
+        self.remember_tail(&gap, channels);
+        gap
+    }
+
+    /// keep the last `CONCEAL_HISTORY` sample-frames handed out
+    fn remember_tail(&mut self, samples: &[f32], channels: usize) {
+        let keep = CONCEAL_HISTORY * channels;
+        if samples.len() >= keep {
+            self.history.clear();
+            self.history
+                .extend_from_slice(&samples[samples.len() - keep..]);
         } else {
-            let decoder = LosslessDecoder::new();
-            decoder.decode_file(&file)
+            self.history.extend_from_slice(samples);
+            let excess = self.history.len().saturating_sub(keep);
+            self.history.drain(..excess);
         }
     }
 }
 
+/// parse a frame from the data chunk, None if it's damaged
+fn parse_checked_frame<'a>(data: &'a [u8], header: &Header) -> Option<FrameRef<'a>> {
+    if header.has_frame_crc() && !verify_frame_crc(data) {
+        return None;
+    }
+    parse_frame(data, header.channels).ok()
+}
+
 impl Default for StreamingDecoder {
     fn default() -> Self {
         Self::new()
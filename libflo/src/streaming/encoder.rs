@@ -1,6 +1,23 @@
-use crate::core::{ChannelData, FloResult, FrameType};
+use crate::compute_crc32;
+use crate::core::integrity::append_frame_crc;
+use crate::core::{
+    FloResult, Header, MetadataPlacement, TocEntry, FLAG_EARLY_META, FLAG_FRAME_CRC, FLAG_GAPLESS,
+};
 use crate::lossless::Encoder;
-use crate::{compute_crc32, Reader, MAGIC};
+use crate::lossy::{BlockSize, TransformEncoder};
+use crate::writer::{frame_to_bytes, place_metadata};
+use serde::{Deserialize, Serialize};
+
+use super::live::write_live_packet;
+use super::rtp::StreamParams;
+
+/// Shortest frame `with_low_latency` accepts, in milliseconds
+pub const LOW_LATENCY_MIN_MS: f32 = 2.5;
+/// Longest frame `with_low_latency` accepts, in milliseconds
+pub const LOW_LATENCY_MAX_MS: f32 = 20.0;
+
+/// Version of the `checkpoint` format
+const CHECKPOINT_VERSION: u8 = 1;
 
 pub struct StreamingEncoder {
     sample_rate: u32,
@@ -13,21 +30,61 @@ pub struct StreamingEncoder {
     encoder: Encoder,
     total_samples: u64,
     frame_index: u32,
+    frame_crc: bool,
+    metadata_placement: MetadataPlacement,
+    /// transform encoder for lossy streams, keeps MDCT and masking state
+    transform: Option<TransformEncoder>,
+    /// samples pushed so far (per channel)
+    pushed_samples: u64,
 }
 
 /// An encoded frame ready for transmission
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncodedFrame {
     /// Frame index
     pub index: u32,
     /// Timestamp in milliseconds
     pub timestamp_ms: u32,
+    /// Position of the first sample of this frame in the stream
+    ///
+    /// Lossy frame 0 is the MDCT preroll and decodes to nothing, so lossy
+    /// frame k plays from one hop before this.
+    pub first_sample: u64,
     /// Encoded frame data
+    #[serde(with = "serde_bytes")]
     pub data: Vec<u8>,
     /// Number of samples in this frame
     pub samples: u32,
 }
 
+/// Everything a `StreamingEncoder` needs to carry on, see `checkpoint`
+#[derive(Serialize, Deserialize)]
+struct EncoderState {
+    version: u8,
+    sample_rate: u32,
+    channels: u8,
+    bit_depth: u8,
+    compression_level: u8,
+    samples_per_frame: u64,
+    frame_crc: bool,
+    metadata_placement: u8,
+    sample_buffer: Vec<f32>,
+    pending_frames: Vec<EncodedFrame>,
+    total_samples: u64,
+    frame_index: u32,
+    pushed_samples: u64,
+    lossy: Option<LossyState>,
+}
+
+/// transform encoder settings and the masking state between frames
+#[derive(Serialize, Deserialize)]
+struct LossyState {
+    quality: f32,
+    /// hop and overlap of a low delay block, None for long blocks
+    low_delay: Option<(u16, u16)>,
+    masking: Vec<Vec<f32>>,
+}
+
 impl StreamingEncoder {
     /// Create a new streaming encoder
     pub fn new(sample_rate: u32, channels: u8, bit_depth: u8) -> Self {
@@ -44,9 +101,80 @@ impl StreamingEncoder {
             encoder: Encoder::new(sample_rate, channels, bit_depth),
             total_samples: 0,
             frame_index: 0,
+            frame_crc: false,
+            metadata_placement: MetadataPlacement::End,
+            transform: None,
+            pushed_samples: 0,
+        }
+    }
+
+    /// Create a new lossy (transform) streaming encoder
+    ///
+    /// `quality` is 0.0-1.0 like `TransformEncoder`. Frames advance one MDCT hop
+    /// and the file from `finalize` is identical to `TransformEncoder::encode_to_flo`.
+    pub fn new_lossy(sample_rate: u32, channels: u8, quality: f32) -> Self {
+        let hop = BlockSize::Long.coefficients();
+
+        Self {
+            samples_per_frame: hop,
+            // a hop of silence primes the overlap, frame 0 decodes to nothing
+            sample_buffer: vec![0.0; hop * channels as usize],
+            transform: Some(TransformEncoder::new(sample_rate, channels, quality)),
+            ..Self::new(sample_rate, channels, 16)
+        }
+    }
+
+    /// Emit frames of `frame_ms` for live monitoring, clamped to 2.5-20 ms
+    ///
+    /// Lossless frames just get shorter. Lossy streams switch to a low delay
+    /// MDCT block whose window overlaps the next one by 2.5 ms, so a frame
+    /// only waits that long for lookahead instead of a whole hop.
+    /// Call it before pushing any samples.
+    pub fn with_low_latency(mut self, frame_ms: f32) -> Self {
+        let frame_ms = frame_ms.clamp(LOW_LATENCY_MIN_MS, LOW_LATENCY_MAX_MS);
+        match self.transform.take() {
+            Some(transform) => {
+                let block = BlockSize::low_delay(self.sample_rate, frame_ms);
+                self.samples_per_frame = block.coefficients();
+                // the window starts late by its zero tail, so the preroll grows
+                self.sample_buffer =
+                    vec![0.0; (block.coefficients() + block.zero_tail()) * self.channels as usize];
+                self.transform = Some(transform.with_block_size(block));
+            }
+            None => {
+                let samples = (self.sample_rate as f32 * frame_ms / 1000.0).round() as usize;
+                self.samples_per_frame = samples.max(1);
+            }
+        }
+        self
+    }
+
+    /// Samples per channel in each frame (one hop for lossy streams)
+    pub fn frame_samples(&self) -> usize {
+        self.samples_per_frame
+    }
+
+    /// Algorithmic delay in samples per channel
+    ///
+    /// How long a sample waits before the frame that plays it can be
+    /// emitted: the frame length, plus the MDCT overlap for lossy streams.
+    pub fn algorithmic_delay(&self) -> usize {
+        match &self.transform {
+            Some(transform) => self.samples_per_frame + transform.block_size().overlap(),
+            None => self.samples_per_frame,
         }
     }
 
+    /// Algorithmic delay in milliseconds, see `algorithmic_delay`
+    pub fn algorithmic_delay_ms(&self) -> f64 {
+        self.algorithmic_delay() as f64 * 1000.0 / self.sample_rate as f64
+    }
+
+    /// Is this a lossy stream?
+    pub fn is_lossy(&self) -> bool {
+        self.transform.is_some()
+    }
+
     /// Set compression level (0-9)
     pub fn with_compression(mut self, level: u8) -> Self {
         self.compression_level = level.min(9);
@@ -55,7 +183,23 @@ impl StreamingEncoder {
         self
     }
 
+    /// Append a CRC32 to every frame so damage can be located per frame
+    pub fn with_frame_crc(mut self, enabled: bool) -> Self {
+        self.frame_crc = enabled;
+        self
+    }
+
+    /// Put the metadata (or a preview of it) ahead of the frames in `finalize`
+    ///
+    /// A `StreamingDecoder` can then show it before the rest of the file arrives.
+    pub fn with_metadata_placement(mut self, placement: MetadataPlacement) -> Self {
+        self.metadata_placement = placement;
+        self
+    }
+
     /// Get number of pending samples in buffer
+    ///
+    /// Lossy streams keep one hop buffered for the MDCT overlap.
     pub fn pending_samples(&self) -> usize {
         self.sample_buffer.len() / self.channels as usize
     }
@@ -70,6 +214,7 @@ impl StreamingEncoder {
     /// Samples should be interleaved if multi-channel
     pub fn push_samples(&mut self, samples: &[f32]) -> FloResult<()> {
         self.sample_buffer.extend_from_slice(samples);
+        self.pushed_samples += (samples.len() / self.channels as usize) as u64;
         self.try_encode_frames()?;
         Ok(())
     }
@@ -83,92 +228,137 @@ impl StreamingEncoder {
         }
     }
 
-    /// Flush remaining samples (may produce a partial frame)
-    pub fn flush(&mut self) -> FloResult<Option<EncodedFrame>> {
-        if self.sample_buffer.is_empty() {
-            return Ok(None);
+    /// What a receiver needs to decode the frames, for `Packetizer::new`
+    pub fn stream_params(&self) -> StreamParams {
+        StreamParams {
+            flags: self.flags(),
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            bit_depth: self.bit_depth,
         }
+    }
 
-        let samples_per_channel = self.sample_buffer.len() / self.channels as usize;
-        let timestamp_ms = (self.total_samples as f64 / self.sample_rate as f64 * 1000.0) as u32;
+    /// Get the next encoded frame wrapped as a live packet
+    ///
+    /// Live packets can be sent as-is over an unbounded stream, see `live_packet`.
+    pub fn next_live_packet(&mut self) -> Option<Vec<u8>> {
+        self.next_frame().map(|frame| self.live_packet(&frame))
+    }
 
-        let frame_data = self.encode_frame_data(&self.sample_buffer)?;
+    /// Wrap an encoded frame as a self-contained live packet
+    ///
+    /// The packet carries a sync word, the stream parameters and its own length,
+    /// so a `StreamingDecoder` can pick the stream up at any packet boundary
+    /// without ever seeing a file header or TOC.
+    pub fn live_packet(&self, frame: &EncodedFrame) -> Vec<u8> {
+        write_live_packet(
+            self.flags(),
+            self.sample_rate,
+            self.channels,
+            self.bit_depth,
+            frame.index,
+            frame.first_sample,
+            &frame.data,
+        )
+    }
 
-        let encoded = EncodedFrame {
-            index: self.frame_index,
-            timestamp_ms,
-            data: frame_data,
-            samples: samples_per_channel as u32,
-        };
+    /// Flush remaining samples and return the frame they make
+    ///
+    /// Lossless streams end with at most one partial frame, it's returned
+    /// rather than queued. Lossy streams end with the frames that finish the
+    /// MDCT overlap, the first is returned and the rest are queued for
+    /// `next_frame`. Frames queued before the flush stay where they are, use
+    /// `finish` to queue everything in order.
+    pub fn flush(&mut self) -> FloResult<Option<EncodedFrame>> {
+        let queued = self.pending_frames.len();
+        self.finish()?;
+        if self.pending_frames.len() > queued {
+            Ok(Some(self.pending_frames.remove(queued)))
+        } else {
+            Ok(None)
+        }
+    }
 
-        self.total_samples += samples_per_channel as u64;
-        self.frame_index += 1;
-        self.sample_buffer.clear();
+    /// Encode whatever is still buffered and queue it for `next_frame`/`finalize`
+    pub fn finish(&mut self) -> FloResult<()> {
+        if self.transform.is_some() {
+            return self.finish_lossy();
+        }
+        if self.sample_buffer.is_empty() {
+            return Ok(());
+        }
 
-        Ok(Some(encoded))
+        let samples = std::mem::take(&mut self.sample_buffer);
+        let frame_data = self.encode_frame_data(&samples);
+
+        self.queue_frame(frame_data, (samples.len() / self.channels as usize) as u32);
+        Ok(())
     }
 
     /// Build a complete flo™ file from accumulated frames
+    ///
+    /// Frames already taken with `next_frame` are not part of the file. More
+    /// frames than `Header::max_toc_entries` allows is an error.
     pub fn finalize(&mut self, metadata: &[u8]) -> FloResult<Vec<u8>> {
-        if let Some(frame) = self.flush()? {
-            self.pending_frames.push(frame);
-        }
+        self.finish()?;
 
-        // Build TOC
-        let mut toc_data = Vec::new();
-        let num_frames = self.pending_frames.len() as u32;
-        toc_data.extend_from_slice(&num_frames.to_le_bytes());
+        let (early_metadata, metadata) = place_metadata(metadata, self.metadata_placement);
 
-        let mut byte_offset: u64 = 0;
+        // Build TOC
+        let mut toc = Vec::with_capacity(self.pending_frames.len());
+        let mut byte_offset = early_metadata.len() as u64;
         for frame in &self.pending_frames {
-            toc_data.extend_from_slice(&frame.index.to_le_bytes());
-            toc_data.extend_from_slice(&byte_offset.to_le_bytes());
-            toc_data.extend_from_slice(&(frame.data.len() as u32).to_le_bytes());
-            toc_data.extend_from_slice(&frame.timestamp_ms.to_le_bytes());
+            toc.push(TocEntry {
+                frame_index: frame.index,
+                byte_offset,
+                frame_size: frame.data.len() as u32,
+                timestamp_ms: frame.timestamp_ms,
+                first_sample: frame.first_sample,
+            });
+
             byte_offset += frame.data.len() as u64;
         }
+        let toc_data = TocEntry::write_all(&toc);
 
-        // Build DATA
-        let mut data_chunk = Vec::new();
+        // Build DATA, early metadata first
+        let mut data_chunk = early_metadata.clone();
         for frame in &self.pending_frames {
             data_chunk.extend_from_slice(&frame.data);
         }
 
         let data_crc32 = compute_crc32(&data_chunk);
 
-        let header_size: u64 = 66;
-        let toc_size = toc_data.len() as u64;
-        let data_size = data_chunk.len() as u64;
-        let extra_size: u64 = 0;
-        let meta_size = metadata.len() as u64;
-
-        let mut output = Vec::new();
-
-        // Magic
-        output.extend_from_slice(&MAGIC);
-
-        // Header
-        output.push(1); // version_major
-        output.push(2); // version_minor
-        output.extend_from_slice(&0u16.to_le_bytes()); // flags
-        output.extend_from_slice(&self.sample_rate.to_le_bytes());
-        output.push(self.channels);
-        output.push(self.bit_depth);
-        // Calculate total samples across all frames
-        let total_samples: u64 = self
-            .pending_frames
-            .iter()
-            .map(|frame| frame.samples as u64)
-            .sum();
-        output.extend_from_slice(&total_samples.to_le_bytes());
-        output.push(self.compression_level);
-        output.extend_from_slice(&[0u8; 3]); // reserved
-        output.extend_from_slice(&data_crc32.to_le_bytes());
-        output.extend_from_slice(&header_size.to_le_bytes());
-        output.extend_from_slice(&toc_size.to_le_bytes());
-        output.extend_from_slice(&data_size.to_le_bytes());
-        output.extend_from_slice(&extra_size.to_le_bytes());
-        output.extend_from_slice(&meta_size.to_le_bytes());
+        let mut flags = self.flags();
+        if !early_metadata.is_empty() {
+            flags |= FLAG_EARLY_META;
+        }
+
+        let total_samples = self.pending_frames.iter().map(|f| f.samples as u64).sum();
+        let (encoder_delay, padding) = self.gapless_info(total_samples);
+        if self.transform.is_some() {
+            flags |= FLAG_GAPLESS;
+        }
+
+        let header = Header {
+            flags,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            bit_depth: self.bit_depth,
+            total_samples,
+            compression_level: self.compression_level,
+            data_crc32,
+            toc_size: toc_data.len() as u64,
+            data_size: data_chunk.len() as u64,
+            meta_size: metadata.len() as u64,
+            early_meta_size: early_metadata.len() as u64,
+            encoder_delay,
+            padding,
+            ..Header::default()
+        };
+        header.check_toc_entries(toc.len())?;
+
+        // Magic + header
+        let mut output = header.to_bytes();
 
         // TOC
         output.extend_from_slice(&toc_data);
@@ -177,82 +367,199 @@ impl StreamingEncoder {
         output.extend_from_slice(&data_chunk);
 
         // META
-        output.extend_from_slice(metadata);
+        output.extend_from_slice(&metadata);
 
         self.pending_frames.clear();
 
         Ok(output)
     }
 
+    /// Serialize the encoder so a crashed capture can carry on with `resume`
+    ///
+    /// Holds the settings, frame position, buffered samples, frames not
+    /// taken yet and, for lossy streams, the masking model state, so the
+    /// resumed encoder produces exactly the frames this one would have.
+    /// `EncoderJournal` writes these to disk along with the frames.
+    pub fn checkpoint(&self) -> FloResult<Vec<u8>> {
+        let state = EncoderState {
+            version: CHECKPOINT_VERSION,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            bit_depth: self.bit_depth,
+            compression_level: self.compression_level,
+            samples_per_frame: self.samples_per_frame as u64,
+            frame_crc: self.frame_crc,
+            metadata_placement: self.metadata_placement as u8,
+            sample_buffer: self.sample_buffer.clone(),
+            pending_frames: self.pending_frames.clone(),
+            total_samples: self.total_samples,
+            frame_index: self.frame_index,
+            pushed_samples: self.pushed_samples,
+            lossy: self.transform.as_ref().map(|transform| LossyState {
+                quality: transform.quality(),
+                low_delay: match transform.block_size() {
+                    BlockSize::LowDelay { hop, overlap } => Some((hop, overlap)),
+                    _ => None,
+                },
+                masking: transform.masking_state(),
+            }),
+        };
+        rmp_serde::to_vec_named(&state).map_err(|e| format!("Encoder checkpoint failed: {}", e))
+    }
+
+    /// Carry on from a `checkpoint`
+    pub fn resume(checkpoint: &[u8]) -> FloResult<Self> {
+        let state: EncoderState = rmp_serde::from_slice(checkpoint)
+            .map_err(|e| format!("Invalid encoder checkpoint: {}", e))?;
+        if state.version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "Unsupported encoder checkpoint version {}",
+                state.version
+            ));
+        }
+        let channels = state.channels as usize;
+        if channels == 0
+            || state.sample_rate == 0
+            || state.samples_per_frame == 0
+            || !state.sample_buffer.len().is_multiple_of(channels)
+        {
+            return Err("Invalid encoder checkpoint".to_string());
+        }
+
+        let mut encoder = Self::new(state.sample_rate, state.channels, state.bit_depth)
+            .with_compression(state.compression_level)
+            .with_frame_crc(state.frame_crc)
+            .with_metadata_placement(match state.metadata_placement {
+                1 => MetadataPlacement::Preview,
+                2 => MetadataPlacement::Start,
+                _ => MetadataPlacement::End,
+            });
+
+        if let Some(lossy) = state.lossy {
+            let mut transform =
+                TransformEncoder::new(state.sample_rate, state.channels, lossy.quality);
+            if let Some((hop, overlap)) = lossy.low_delay {
+                if hop < 8 || hop % 4 != 0 || overlap % 4 != 0 || overlap == 0 || overlap > hop {
+                    return Err("Invalid encoder checkpoint".to_string());
+                }
+                transform = transform.with_block_size(BlockSize::LowDelay { hop, overlap });
+            }
+            transform.restore_masking_state(&lossy.masking)?;
+            if transform.block_size().coefficients() as u64 != state.samples_per_frame {
+                return Err("Invalid encoder checkpoint".to_string());
+            }
+            encoder.transform = Some(transform);
+        }
+
+        encoder.samples_per_frame = state.samples_per_frame as usize;
+        encoder.sample_buffer = state.sample_buffer;
+        encoder.pending_frames = state.pending_frames;
+        encoder.total_samples = state.total_samples;
+        encoder.frame_index = state.frame_index;
+        encoder.pushed_samples = state.pushed_samples;
+        Ok(encoder)
+    }
+
+    /// Put frames taken earlier back in front of the queue, for `finalize`
+    pub(crate) fn requeue_frames(&mut self, mut frames: Vec<EncodedFrame>) {
+        frames.append(&mut self.pending_frames);
+        self.pending_frames = frames;
+    }
+
     // ========================================================================
     // Internal methods
     // ========================================================================
 
     fn try_encode_frames(&mut self) -> FloResult<()> {
-        let frame_samples = self.samples_per_frame * self.channels as usize;
+        let hop = self.samples_per_frame * self.channels as usize;
 
-        while self.sample_buffer.len() >= frame_samples {
-            let frame_data: Vec<f32> = self.sample_buffer.drain(..frame_samples).collect();
-            let timestamp_ms =
-                (self.total_samples as f64 / self.sample_rate as f64 * 1000.0) as u32;
-
-            let encoded_data = self.encode_frame_data(&frame_data)?;
-
-            self.pending_frames.push(EncodedFrame {
-                index: self.frame_index,
-                timestamp_ms,
-                data: encoded_data,
-                samples: self.samples_per_frame as u32,
-            });
+        // lossy blocks are two hops long and overlap by one, the zero tail
+        // of a low delay window isn't needed yet and gets padded
+        let block = match &self.transform {
+            Some(transform) => {
+                2 * hop - transform.block_size().zero_tail() * self.channels as usize
+            }
+            None => hop,
+        };
 
-            self.total_samples += self.samples_per_frame as u64;
-            self.frame_index += 1;
+        let mut buffer = std::mem::take(&mut self.sample_buffer);
+        let mut consumed = 0;
+        while buffer.len() - consumed >= block {
+            let encoded_data = self.encode_frame_data(&buffer[consumed..consumed + block]);
+            consumed += hop;
+            self.queue_frame(encoded_data, self.samples_per_frame as u32);
         }
 
+        buffer.drain(..consumed);
+        self.sample_buffer = buffer;
         Ok(())
     }
 
-    fn encode_frame_data(&self, samples: &[f32]) -> FloResult<Vec<u8>> {
-        let temp_flo = self.encoder.encode(samples, &[])?;
+    /// Pad with silence and encode the frames `encode_to_flo` would end with
+    fn finish_lossy(&mut self) -> FloResult<()> {
+        let hop = self.samples_per_frame as u64;
+        let total_frames = (self.pushed_samples + hop).div_ceil(hop);
+        let remaining = total_frames.saturating_sub(self.frame_index as u64) as usize;
+        if remaining == 0 {
+            return Ok(());
+        }
 
-        let reader = Reader::new();
-        let file = reader.read(&temp_flo)?;
+        let padded = (remaining + 1) * self.samples_per_frame * self.channels as usize;
+        self.sample_buffer
+            .resize(padded.max(self.sample_buffer.len()), 0.0);
+        self.try_encode_frames()?;
+        self.sample_buffer.clear();
+        Ok(())
+    }
 
-        if file.frames.is_empty() {
-            return Err("No frames encoded".to_string());
-        }
+    /// encoder delay and padding of a lossy file holding the pending frames
+    ///
+    /// The first pending frame primes the overlap and the audio runs to the
+    /// last pushed sample. Frames taken with `next_frame` are gone, so the
+    /// file starts that much later.
+    fn gapless_info(&self, total_samples: u64) -> (u32, u32) {
+        let (Some(_), Some(first)) = (&self.transform, self.pending_frames.first()) else {
+            return (0, 0);
+        };
+        let hop = self.samples_per_frame as u64;
+        let length = self.pushed_samples.saturating_sub(first.index as u64 * hop);
+        let padding = total_samples.saturating_sub(hop + length);
+        (hop as u32, padding as u32)
+    }
 
-        let frame = &file.frames[0];
-        let mut data = Vec::new();
+    fn queue_frame(&mut self, data: Vec<u8>, samples: u32) {
+        self.pending_frames.push(EncodedFrame {
+            index: self.frame_index,
+            timestamp_ms: (self.total_samples * 1000 / self.sample_rate as u64) as u32,
+            first_sample: self.total_samples,
+            data,
+            samples,
+        });
 
-        // Frame header
-        data.push(frame.frame_type);
-        data.extend_from_slice(&frame.frame_samples.to_le_bytes());
-        data.push(frame.flags);
+        self.total_samples += samples as u64;
+        self.frame_index += 1;
+    }
 
-        // Channel data
-        for ch in &frame.channels {
-            let ch_data = self.serialize_channel(ch, FrameType::from(frame.frame_type));
-            data.extend_from_slice(&(ch_data.len() as u32).to_le_bytes());
-            data.extend_from_slice(&ch_data);
+    fn flags(&self) -> u16 {
+        let mut flags = 0;
+        if let Some(ref transform) = self.transform {
+            flags |= 0x01 | (transform.quality_level() as u16) << 8;
         }
-
-        Ok(data)
+        if self.frame_crc {
+            flags |= FLAG_FRAME_CRC;
+        }
+        flags
     }
 
-    fn serialize_channel(&self, ch: &ChannelData, frame_type: FrameType) -> Vec<u8> {
-        match frame_type {
-            FrameType::Silence => vec![],
-            FrameType::Raw | FrameType::Transform => ch.residuals.clone(),
-            _ => {
-                let mut data = Vec::new();
-                data.push(ch.rice_parameter);
-                for &coeff in &ch.predictor_coeffs {
-                    data.extend_from_slice(&coeff.to_le_bytes());
-                }
-                data.extend_from_slice(&ch.residuals);
-                data
-            }
+    fn encode_frame_data(&mut self, samples: &[f32]) -> Vec<u8> {
+        let frame = match self.transform.as_mut() {
+            Some(transform) => transform.encode_flo_frame(samples),
+            None => self.encoder.encode_frame(samples),
+        };
+        let mut data = frame_to_bytes(&frame);
+        if self.frame_crc {
+            append_frame_crc(&mut data, 0);
         }
+        data
     }
 }
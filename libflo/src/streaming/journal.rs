@@ -0,0 +1,223 @@
+//! on-disk journal for long streaming encodes
+//!
+//! An append-only file: `FLOJ` and a version byte, then records. Each
+//! record is a kind byte, a u32 payload length, the payload and a CRC32 of
+//! all three (little-endian). Frame records hold one encoded frame, checkpoint
+//! records a `StreamingEncoder::checkpoint`. A crash can at worst tear the
+//! last record, recovery stops at the last checkpoint that made it to disk
+//! and keeps the frames written before it.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::{EncodedFrame, StreamingEncoder};
+use crate::core::{compute_crc32, FloResult};
+
+/// Magic at the start of a journal
+pub const JOURNAL_MAGIC: [u8; 4] = *b"FLOJ";
+/// Journal format version
+pub const JOURNAL_VERSION: u8 = 1;
+
+const JOURNAL_HEADER_SIZE: usize = 5;
+const RECORD_FRAME: u8 = 1;
+const RECORD_CHECKPOINT: u8 = 2;
+/// kind and payload length ahead of the payload
+const RECORD_HEADER_SIZE: usize = 5;
+/// index, first sample, samples and timestamp ahead of a frame's bytes
+const FRAME_HEADER_SIZE: usize = 20;
+
+/// Writes a `StreamingEncoder`'s frames and checkpoints to disk as it goes
+///
+/// ```no_run
+/// # use libflo_audio::{EncoderJournal, StreamingEncoder};
+/// # fn run(chunks: Vec<Vec<f32>>) -> Result<(), String> {
+/// let mut encoder = StreamingEncoder::new(48000, 2, 16);
+/// let mut journal = EncoderJournal::create("capture.floj", &encoder)?;
+/// for chunk in chunks {
+///     encoder.push_samples(&chunk)?;
+///     journal.record(&mut encoder)?;
+/// }
+/// let flo_file = journal.finalize(&mut encoder, &[])?;
+/// # Ok(()) }
+/// ```
+///
+/// After a crash, `EncoderJournal::resume` carries on recording and
+/// `EncoderJournal::recover` turns what was written into a file.
+pub struct EncoderJournal {
+    file: File,
+    path: PathBuf,
+}
+
+/// What a journal held when it was read back
+pub struct RecoveredEncode {
+    /// encoder as of the last checkpoint
+    pub encoder: StreamingEncoder,
+    /// frames written up to that checkpoint
+    pub frames: Vec<EncodedFrame>,
+}
+
+impl RecoveredEncode {
+    /// build a flo™ file from the recorded frames and the encoder's buffered samples
+    pub fn finalize(mut self, metadata: &[u8]) -> FloResult<Vec<u8>> {
+        self.encoder.requeue_frames(self.frames);
+        self.encoder.finalize(metadata)
+    }
+}
+
+impl EncoderJournal {
+    /// start a journal at `path` for `encoder`, replacing any file there
+    pub fn create(path: impl AsRef<Path>, encoder: &StreamingEncoder) -> FloResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err(|e| e.to_string())?;
+        let mut journal = Self { file, path };
+
+        let mut bytes = JOURNAL_MAGIC.to_vec();
+        bytes.push(JOURNAL_VERSION);
+        push_record(&mut bytes, RECORD_CHECKPOINT, &encoder.checkpoint()?);
+        journal.write(&bytes)?;
+        Ok(journal)
+    }
+
+    /// write out the frames the encoder has ready, then a checkpoint
+    ///
+    /// The frames are taken from the encoder and returned, so they can still
+    /// be sent on. Everything is synced to disk before this returns. The
+    /// checkpoint includes the samples waiting for the next frame, so call it
+    /// about once a frame rather than after every small push.
+    pub fn record(&mut self, encoder: &mut StreamingEncoder) -> FloResult<Vec<EncodedFrame>> {
+        let frames: Vec<EncodedFrame> = std::iter::from_fn(|| encoder.next_frame()).collect();
+
+        let mut bytes = Vec::new();
+        for frame in &frames {
+            push_record(&mut bytes, RECORD_FRAME, &frame_to_record(frame));
+        }
+        push_record(&mut bytes, RECORD_CHECKPOINT, &encoder.checkpoint()?);
+        self.write(&bytes)?;
+        Ok(frames)
+    }
+
+    /// encode what's left and build the flo™ file from every recorded frame
+    ///
+    /// The journal file stays, delete it once the result is safely stored.
+    pub fn finalize(
+        mut self,
+        encoder: &mut StreamingEncoder,
+        metadata: &[u8],
+    ) -> FloResult<Vec<u8>> {
+        encoder.finish()?;
+        self.record(encoder)?;
+        Self::recover(&self.path)?.finalize(metadata)
+    }
+
+    /// read a journal back, e.g. after a crash
+    pub fn recover(path: impl AsRef<Path>) -> FloResult<RecoveredEncode> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        read_journal(&bytes).map(|(recovered, _)| recovered)
+    }
+
+    /// carry on recording into a journal after a crash
+    ///
+    /// Anything after the last complete checkpoint is cut off, the returned
+    /// encoder picks up from there. Push the samples captured since.
+    pub fn resume(path: impl AsRef<Path>) -> FloResult<(Self, StreamingEncoder)> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let (recovered, end) = read_journal(&bytes)?;
+
+        // appends go after the cut
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        file.set_len(end as u64).map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+        let journal = Self {
+            file,
+            path: path.to_path_buf(),
+        };
+        Ok((journal, recovered.encoder))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> FloResult<()> {
+        self.file.write_all(bytes).map_err(|e| e.to_string())?;
+        self.file.sync_data().map_err(|e| e.to_string())
+    }
+}
+
+/// append a record with its length and crc
+fn push_record(out: &mut Vec<u8>, kind: u8, payload: &[u8]) {
+    let start = out.len();
+    out.push(kind);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    let crc = compute_crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_le_bytes());
+}
+
+fn frame_to_record(frame: &EncodedFrame) -> Vec<u8> {
+    let mut out = Vec::with_capacity(FRAME_HEADER_SIZE + frame.data.len());
+    out.extend_from_slice(&frame.index.to_le_bytes());
+    out.extend_from_slice(&frame.first_sample.to_le_bytes());
+    out.extend_from_slice(&frame.samples.to_le_bytes());
+    out.extend_from_slice(&frame.timestamp_ms.to_le_bytes());
+    out.extend_from_slice(&frame.data);
+    out
+}
+
+fn frame_from_record(payload: &[u8]) -> Option<EncodedFrame> {
+    let header = payload.get(..FRAME_HEADER_SIZE)?;
+    let u32_at = |pos: usize| u32::from_le_bytes(header[pos..pos + 4].try_into().unwrap());
+    Some(EncodedFrame {
+        index: u32_at(0),
+        first_sample: u64::from_le_bytes(header[4..12].try_into().unwrap()),
+        samples: u32_at(12),
+        timestamp_ms: u32_at(16),
+        data: payload[FRAME_HEADER_SIZE..].to_vec(),
+    })
+}
+
+/// the last complete checkpoint with the frames before it, and where it ends
+fn read_journal(bytes: &[u8]) -> FloResult<(RecoveredEncode, usize)> {
+    if bytes.len() < JOURNAL_HEADER_SIZE || bytes[..4] != JOURNAL_MAGIC {
+        return Err("Not a flo encoder journal".to_string());
+    }
+    if bytes[4] != JOURNAL_VERSION {
+        return Err(format!("Unsupported journal version {}", bytes[4]));
+    }
+
+    let mut frames = Vec::new();
+    // checkpoint, frames before it and where it ends
+    let mut checkpoint = None;
+    let mut pos = JOURNAL_HEADER_SIZE;
+    while let Some((kind, payload)) = read_record(&bytes[pos..]) {
+        let next = pos + RECORD_HEADER_SIZE + payload.len() + 4;
+        match kind {
+            RECORD_FRAME => match frame_from_record(payload) {
+                Some(frame) => frames.push(frame),
+                None => break,
+            },
+            RECORD_CHECKPOINT => checkpoint = Some((payload, frames.len(), next)),
+            _ => break,
+        }
+        pos = next;
+    }
+
+    let (state, kept, end) =
+        checkpoint.ok_or_else(|| "Journal has no complete checkpoint".to_string())?;
+    let encoder = StreamingEncoder::resume(state)?;
+    frames.truncate(kept);
+    Ok((RecoveredEncode { encoder, frames }, end))
+}
+
+/// kind and payload of the record at the start of `bytes`, None if it's torn
+fn read_record(bytes: &[u8]) -> Option<(u8, &[u8])> {
+    let header = bytes.get(..RECORD_HEADER_SIZE)?;
+    let len = u32::from_le_bytes(header[1..5].try_into().unwrap()) as usize;
+    let end = RECORD_HEADER_SIZE.checked_add(len)?;
+    let stored = bytes.get(end..end + 4)?;
+    if compute_crc32(&bytes[..end]).to_le_bytes() != stored {
+        return None;
+    }
+    Some((header[0], &bytes[RECORD_HEADER_SIZE..end]))
+}
@@ -0,0 +1,162 @@
+//! live stream framing
+//!
+//! A live stream has no header, TOC or sizes up front. It is just a run of
+//! self-contained packets, each starting with a sync word and carrying the
+//! stream parameters, so a decoder can join at any packet boundary.
+//!
+//! Packet layout (little-endian):
+//!
+//! | Offset | Size | Field           |
+//! | ------ | ---- | --------------- |
+//! | 0      | 4    | sync `FLO~`     |
+//! | 4      | 1    | live version    |
+//! | 5      | 2    | flags           |
+//! | 7      | 4    | sample rate     |
+//! | 11     | 1    | channels        |
+//! | 12     | 1    | bit depth       |
+//! | 13     | 4    | frame index     |
+//! | 17     | 8    | first sample    |
+//! | 25     | 4    | payload size    |
+//! | 29     | 4    | payload crc32   |
+//! | 33     | 4    | header crc32    |
+//! | 37     | N    | frame payload   |
+//!
+//! The header crc covers bytes 4..33 and keeps a sync word that shows up
+//! inside audio data from being taken as a packet start.
+
+use crate::core::crc32;
+
+/// Sync word at the start of every live packet
+pub const LIVE_SYNC: [u8; 4] = *b"FLO~";
+
+/// Live packet format version
+pub const LIVE_VERSION: u8 = 1;
+
+/// Size of the live packet header including the sync word
+pub const LIVE_HEADER_SIZE: usize = 37;
+
+/// Largest payload a live packet may claim, anything bigger is treated as a false sync
+pub const MAX_LIVE_PAYLOAD: u32 = 16 * 1024 * 1024;
+
+/// Header of a single live packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LivePacketHeader {
+    /// Same meaning as the file header flags (bit 0 lossy, bits 8-11 quality)
+    pub flags: u16,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u8,
+    /// Bits per sample
+    pub bit_depth: u8,
+    /// Frame index since the start of the stream
+    pub frame_index: u32,
+    /// Position of the first sample of this frame since the start of the stream
+    pub first_sample: u64,
+    /// Size of the frame payload following the header
+    pub payload_size: u32,
+    /// CRC32 of the frame payload
+    pub payload_crc32: u32,
+}
+
+impl LivePacketHeader {
+    /// Is the stream lossy?
+    pub fn is_lossy(&self) -> bool {
+        (self.flags & 0x01) != 0
+    }
+
+    /// Total packet size (header + payload)
+    pub fn packet_size(&self) -> usize {
+        LIVE_HEADER_SIZE + self.payload_size as usize
+    }
+
+    /// Serialize the header, including sync word and header crc
+    pub fn to_bytes(&self) -> [u8; LIVE_HEADER_SIZE] {
+        let mut out = [0u8; LIVE_HEADER_SIZE];
+        out[0..4].copy_from_slice(&LIVE_SYNC);
+        out[4] = LIVE_VERSION;
+        out[5..7].copy_from_slice(&self.flags.to_le_bytes());
+        out[7..11].copy_from_slice(&self.sample_rate.to_le_bytes());
+        out[11] = self.channels;
+        out[12] = self.bit_depth;
+        out[13..17].copy_from_slice(&self.frame_index.to_le_bytes());
+        out[17..25].copy_from_slice(&self.first_sample.to_le_bytes());
+        out[25..29].copy_from_slice(&self.payload_size.to_le_bytes());
+        out[29..33].copy_from_slice(&self.payload_crc32.to_le_bytes());
+        let header_crc = crc32::compute(&out[4..33]);
+        out[33..37].copy_from_slice(&header_crc.to_le_bytes());
+        out
+    }
+
+    /// Parse a header from the start of `data`
+    ///
+    /// Returns None if the sync word, version or header crc don't check out,
+    /// or if there are fewer than `LIVE_HEADER_SIZE` bytes.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < LIVE_HEADER_SIZE || data[0..4] != LIVE_SYNC || data[4] != LIVE_VERSION {
+            return None;
+        }
+
+        let stored_crc = u32::from_le_bytes([data[33], data[34], data[35], data[36]]);
+        if crc32::compute(&data[4..33]) != stored_crc {
+            return None;
+        }
+
+        let header = Self {
+            flags: u16::from_le_bytes([data[5], data[6]]),
+            sample_rate: u32::from_le_bytes([data[7], data[8], data[9], data[10]]),
+            channels: data[11],
+            bit_depth: data[12],
+            frame_index: u32::from_le_bytes([data[13], data[14], data[15], data[16]]),
+            first_sample: u64::from_le_bytes([
+                data[17], data[18], data[19], data[20], data[21], data[22], data[23], data[24],
+            ]),
+            payload_size: u32::from_le_bytes([data[25], data[26], data[27], data[28]]),
+            payload_crc32: u32::from_le_bytes([data[29], data[30], data[31], data[32]]),
+        };
+
+        if header.sample_rate == 0 || header.channels == 0 || header.payload_size > MAX_LIVE_PAYLOAD
+        {
+            return None;
+        }
+
+        Some(header)
+    }
+
+    /// Does `payload` match the crc stored in this header?
+    pub fn verify_payload(&self, payload: &[u8]) -> bool {
+        payload.len() == self.payload_size as usize && crc32::compute(payload) == self.payload_crc32
+    }
+}
+
+/// Build a complete live packet around an encoded frame payload
+pub fn write_live_packet(
+    flags: u16,
+    sample_rate: u32,
+    channels: u8,
+    bit_depth: u8,
+    frame_index: u32,
+    first_sample: u64,
+    payload: &[u8],
+) -> Vec<u8> {
+    let header = LivePacketHeader {
+        flags,
+        sample_rate,
+        channels,
+        bit_depth,
+        frame_index,
+        first_sample,
+        payload_size: payload.len() as u32,
+        payload_crc32: crc32::compute(payload),
+    };
+
+    let mut packet = Vec::with_capacity(header.packet_size());
+    packet.extend_from_slice(&header.to_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Find the next possible sync word in `data`
+pub(crate) fn find_sync(data: &[u8]) -> Option<usize> {
+    data.windows(LIVE_SYNC.len()).position(|w| w == LIVE_SYNC)
+}
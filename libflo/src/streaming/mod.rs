@@ -1,13 +1,23 @@
 //! streaming encode/decode for flo audio
 //!
 //! incremental encoding and decoding for network streaming or memory constrained stuff
+#[cfg(feature = "async")]
+pub mod async_io;
 mod decoder;
 mod encoder;
+pub mod journal;
+pub mod live;
+pub mod rtp;
 mod types;
 
+#[cfg(feature = "async")]
+pub use async_io::{DecodeStream, EncodeSink};
 pub use decoder::StreamingDecoder;
-pub use encoder::{EncodedFrame, StreamingEncoder};
-pub use types::{DecoderState, StreamingAudioInfo};
+pub use encoder::{EncodedFrame, StreamingEncoder, LOW_LATENCY_MAX_MS, LOW_LATENCY_MIN_MS};
+pub use journal::{EncoderJournal, RecoveredEncode};
+pub use live::{LivePacketHeader, LIVE_SYNC};
+pub use rtp::{Depacketizer, PacketStats, Packetizer, StreamParams};
+pub use types::{DecoderState, FeedStatus, SeekRange, StreamingAudioInfo};
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,638 @@
+//! RTP-style packetization
+//!
+//! `Packetizer` turns `EncodedFrame`s into datagrams that fit an MTU, packing
+//! small frames together and splitting big ones. `Depacketizer` takes the
+//! datagrams in whatever order they arrive and hands the frames back in order
+//! behind a small jitter buffer, reporting what got lost.
+//!
+//! Every packet starts with a 12 byte RTP header (RFC 3550, big-endian):
+//! version 2, marker, payload type, sequence number, timestamp (first sample
+//! of the first frame, wrapping) and SSRC. The marker is set on packets that
+//! end a frame. Then comes a kind byte and a little-endian body:
+//!
+//! | Kind | Packet   | Body                                                |
+//! | ---- | -------- | --------------------------------------------------- |
+//! | 0    | params   | flags u16, sample rate u32, channels u8, bit depth u8 |
+//! | 1    | frames   | count u8, then per frame: entry, size u16, bytes    |
+//! | 2    | fragment | entry, frame size u32, offset u32, bytes            |
+//!
+//! An entry is frame index u32, first sample u64, samples u32 and timestamp
+//! ms u32, everything `EncodedFrame` carries. Params packets go out first and
+//! every `params_interval` packets so receivers can join mid-stream.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::core::FloResult;
+
+use super::encoder::EncodedFrame;
+use super::live::write_live_packet;
+
+/// Size of the RTP header in front of every packet
+pub const RTP_HEADER_SIZE: usize = 12;
+
+/// MTU used unless `with_mtu` says otherwise, safe for most paths
+pub const DEFAULT_MTU: usize = 1200;
+
+/// First dynamic RTP payload type
+pub const DEFAULT_PAYLOAD_TYPE: u8 = 96;
+
+const RTP_VERSION: u8 = 2;
+const KIND_PARAMS: u8 = 0;
+const KIND_FRAMES: u8 = 1;
+const KIND_FRAGMENT: u8 = 2;
+
+/// index, first sample, samples, timestamp ms
+const ENTRY_SIZE: usize = 20;
+/// smallest MTU that still fits a fragment header and some data
+const MIN_MTU: usize = RTP_HEADER_SIZE + 1 + ENTRY_SIZE + 8 + 16;
+/// sequence numbers remembered for spotting duplicates
+const SEQUENCE_HISTORY: usize = 4096;
+/// runs of lost frames remembered for `lost_frames`
+pub const LOST_HISTORY: usize = 1024;
+
+/// What a receiver needs to know to decode the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamParams {
+    /// Same meaning as the file header flags (bit 0 lossy, bits 8-11 quality)
+    pub flags: u16,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u8,
+    /// Bits per sample
+    pub bit_depth: u8,
+}
+
+impl StreamParams {
+    /// Is the stream lossy?
+    pub fn is_lossy(&self) -> bool {
+        (self.flags & 0x01) != 0
+    }
+}
+
+/// Packet counters kept by a `Depacketizer`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketStats {
+    /// Distinct packets received
+    pub received: u64,
+    /// Packets never seen, from gaps in the sequence numbers
+    pub lost: u64,
+    /// Packets that arrived after a later one
+    pub reordered: u64,
+    /// Packets received more than once
+    pub duplicates: u64,
+    /// Frames (or fragments of them) that arrived after their turn had passed
+    pub late: u64,
+}
+
+/// Splits and packs encoded frames into MTU-sized packets
+pub struct Packetizer {
+    params: StreamParams,
+    mtu: usize,
+    payload_type: u8,
+    ssrc: u32,
+    sequence: u16,
+    timestamp: u32,
+    params_interval: u32,
+    /// packets since the last params packet, None before the first
+    since_params: Option<u32>,
+}
+
+impl Packetizer {
+    /// New packetizer for a stream, see `StreamingEncoder::stream_params`
+    pub fn new(params: StreamParams) -> Self {
+        Self {
+            params,
+            mtu: DEFAULT_MTU,
+            payload_type: DEFAULT_PAYLOAD_TYPE,
+            ssrc: 0,
+            sequence: 0,
+            timestamp: 0,
+            params_interval: 100,
+            since_params: None,
+        }
+    }
+
+    /// Largest packet to produce, including the RTP header
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu.max(MIN_MTU);
+        self
+    }
+
+    /// RTP synchronization source, tells streams apart
+    pub fn with_ssrc(mut self, ssrc: u32) -> Self {
+        self.ssrc = ssrc;
+        self
+    }
+
+    /// RTP payload type (0-127), 96 by default
+    pub fn with_payload_type(mut self, payload_type: u8) -> Self {
+        self.payload_type = payload_type & 0x7F;
+        self
+    }
+
+    /// Repeat the params packet every `packets` packets, 0 for only the first
+    pub fn with_params_interval(mut self, packets: u32) -> Self {
+        self.params_interval = packets;
+        self
+    }
+
+    /// A packet describing the stream, `packetize` sends these on its own
+    pub fn params_packet(&mut self) -> Vec<u8> {
+        let mut packet = self.start_packet(KIND_PARAMS, false);
+        packet.extend_from_slice(&self.params.flags.to_le_bytes());
+        packet.extend_from_slice(&self.params.sample_rate.to_le_bytes());
+        packet.push(self.params.channels);
+        packet.push(self.params.bit_depth);
+        self.since_params = Some(0);
+        packet
+    }
+
+    /// Turn frames into packets, in the order they should be sent
+    ///
+    /// Consecutive frames share a packet while they fit, frames bigger than
+    /// the MTU are split into fragments.
+    pub fn packetize(&mut self, frames: &[EncodedFrame]) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        let mut group: Vec<&EncodedFrame> = Vec::new();
+        let mut group_size = RTP_HEADER_SIZE + 2;
+
+        for frame in frames {
+            let entry_size = ENTRY_SIZE + 2 + frame.data.len();
+            if RTP_HEADER_SIZE + 2 + entry_size > self.mtu {
+                self.aggregate(&mut group, &mut packets);
+                self.fragment(frame, &mut packets);
+                continue;
+            }
+            if group_size + entry_size > self.mtu || group.len() == u8::MAX as usize {
+                self.aggregate(&mut group, &mut packets);
+            }
+            if group.is_empty() {
+                group_size = RTP_HEADER_SIZE + 2;
+            }
+            group.push(frame);
+            group_size += entry_size;
+        }
+        self.aggregate(&mut group, &mut packets);
+        packets
+    }
+
+    /// one packet holding every frame in `group`
+    fn aggregate(&mut self, group: &mut Vec<&EncodedFrame>, packets: &mut Vec<Vec<u8>>) {
+        let Some(first) = group.first() else {
+            return;
+        };
+        self.timestamp = first.first_sample as u32;
+        self.maybe_params(packets);
+
+        let mut packet = self.start_packet(KIND_FRAMES, true);
+        packet.push(group.len() as u8);
+        for frame in group.drain(..) {
+            write_entry(&mut packet, frame);
+            packet.extend_from_slice(&(frame.data.len() as u16).to_le_bytes());
+            packet.extend_from_slice(&frame.data);
+        }
+        packets.push(packet);
+    }
+
+    /// split a frame that doesn't fit one packet
+    fn fragment(&mut self, frame: &EncodedFrame, packets: &mut Vec<Vec<u8>>) {
+        self.timestamp = frame.first_sample as u32;
+        let chunk_size = self.mtu - RTP_HEADER_SIZE - 1 - ENTRY_SIZE - 8;
+
+        let mut offset = 0;
+        for chunk in frame.data.chunks(chunk_size) {
+            self.maybe_params(packets);
+            let last = offset + chunk.len() == frame.data.len();
+            let mut packet = self.start_packet(KIND_FRAGMENT, last);
+            write_entry(&mut packet, frame);
+            packet.extend_from_slice(&(frame.data.len() as u32).to_le_bytes());
+            packet.extend_from_slice(&(offset as u32).to_le_bytes());
+            packet.extend_from_slice(chunk);
+            packets.push(packet);
+            offset += chunk.len();
+        }
+    }
+
+    /// send params first, then again every `params_interval` packets
+    fn maybe_params(&mut self, packets: &mut Vec<Vec<u8>>) {
+        let due = match self.since_params {
+            None => true,
+            Some(count) => self.params_interval > 0 && count >= self.params_interval,
+        };
+        if due {
+            packets.push(self.params_packet());
+        }
+    }
+
+    /// RTP header and kind byte, taking the next sequence number
+    fn start_packet(&mut self, kind: u8, marker: bool) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(self.mtu);
+        packet.push(RTP_VERSION << 6);
+        packet.push(((marker as u8) << 7) | self.payload_type);
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        packet.extend_from_slice(&self.timestamp.to_be_bytes());
+        packet.extend_from_slice(&self.ssrc.to_be_bytes());
+        packet.push(kind);
+
+        self.sequence = self.sequence.wrapping_add(1);
+        if let Some(count) = self.since_params.as_mut() {
+            *count += 1;
+        }
+        packet
+    }
+}
+
+fn write_entry(packet: &mut Vec<u8>, frame: &EncodedFrame) {
+    packet.extend_from_slice(&frame.index.to_le_bytes());
+    packet.extend_from_slice(&frame.first_sample.to_le_bytes());
+    packet.extend_from_slice(&frame.samples.to_le_bytes());
+    packet.extend_from_slice(&frame.timestamp_ms.to_le_bytes());
+}
+
+/// frame from an entry, with room for `size` bytes of data
+fn read_entry(body: &[u8]) -> Option<EncodedFrame> {
+    let body = body.get(..ENTRY_SIZE)?;
+    Some(EncodedFrame {
+        index: u32::from_le_bytes(body[0..4].try_into().ok()?),
+        first_sample: u64::from_le_bytes(body[4..12].try_into().ok()?),
+        samples: u32::from_le_bytes(body[12..16].try_into().ok()?),
+        timestamp_ms: u32::from_le_bytes(body[16..20].try_into().ok()?),
+        data: Vec::new(),
+    })
+}
+
+/// a frame still waiting for some of its fragments
+struct Partial {
+    /// data grows as fragments arrive, up to `size`
+    frame: EncodedFrame,
+    size: usize,
+    /// byte ranges of the fragments we have, start to end
+    ranges: BTreeMap<usize, usize>,
+    received: usize,
+}
+
+/// Puts packets from a `Packetizer` back into frames, in order
+///
+/// Frames wait in a jitter buffer until their turn. A missing frame is given
+/// up on, and reported in `lost_frames`, once a frame `jitter_depth` indices
+/// later has arrived. The first frame out also waits for that much buffer.
+/// A gap is skipped in one step however many indices it covers. At most
+/// `jitter_depth + 1` frames are put together from fragments at a time, and
+/// only those the jitter buffer would wait for.
+pub struct Depacketizer {
+    params: Option<StreamParams>,
+    jitter_depth: u32,
+    ssrc: Option<u32>,
+    /// complete frames waiting their turn
+    frames: BTreeMap<u32, EncodedFrame>,
+    /// frames missing fragments
+    partial: BTreeMap<u32, Partial>,
+    /// index of the frame `next_frame` returns next
+    next_index: Option<u32>,
+    /// highest extended sequence number and the first one
+    highest_sequence: Option<u64>,
+    first_sequence: u64,
+    /// recent sequence numbers, for spotting duplicates
+    seen: BTreeSet<u64>,
+    stats: PacketStats,
+    /// most recent runs of lost frames, oldest first
+    lost_frames: Vec<Range<u32>>,
+    lost_frame_count: u64,
+    /// the stream ended, stop waiting for missing frames
+    flushing: bool,
+}
+
+impl Depacketizer {
+    /// New depacketizer waiting up to 3 frames for a missing one
+    pub fn new() -> Self {
+        Self {
+            params: None,
+            jitter_depth: 3,
+            ssrc: None,
+            frames: BTreeMap::new(),
+            partial: BTreeMap::new(),
+            next_index: None,
+            highest_sequence: None,
+            first_sequence: 0,
+            seen: BTreeSet::new(),
+            stats: PacketStats::default(),
+            lost_frames: Vec::new(),
+            lost_frame_count: 0,
+            flushing: false,
+        }
+    }
+
+    /// How many frames past a gap to wait before calling the missing frame lost
+    ///
+    /// Deeper buffers ride out more reordering at the cost of latency.
+    pub fn with_jitter_depth(mut self, frames: u32) -> Self {
+        self.jitter_depth = frames;
+        self
+    }
+
+    /// Stream parameters, once a params packet has arrived
+    pub fn params(&self) -> Option<StreamParams> {
+        self.params
+    }
+
+    /// Packet counters so far
+    pub fn stats(&self) -> PacketStats {
+        self.stats
+    }
+
+    /// Runs of frame indices given up on, in order
+    ///
+    /// Only the last `LOST_HISTORY` runs are kept, see `lost_frame_count`.
+    pub fn lost_frames(&self) -> &[Range<u32>] {
+        &self.lost_frames
+    }
+
+    /// Number of frames given up on so far
+    pub fn lost_frame_count(&self) -> u64 {
+        self.lost_frame_count
+    }
+
+    /// Frames buffered, complete or not
+    pub fn buffered_frames(&self) -> usize {
+        self.frames.len() + self.partial.len()
+    }
+
+    /// Take one received datagram
+    ///
+    /// Errors on anything that isn't a packet from a `Packetizer`. A new SSRC
+    /// means a new stream and starts over.
+    pub fn push(&mut self, packet: &[u8]) -> FloResult<()> {
+        if packet.len() < RTP_HEADER_SIZE + 1 || packet[0] >> 6 != RTP_VERSION {
+            return Err("Not an RTP packet".to_string());
+        }
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let ssrc = u32::from_be_bytes(packet[8..12].try_into().unwrap());
+        if self.ssrc.is_some_and(|known| known != ssrc) {
+            *self = Self::new().with_jitter_depth(self.jitter_depth);
+        }
+        self.ssrc = Some(ssrc);
+
+        if !self.track_sequence(sequence) {
+            return Ok(());
+        }
+
+        let body = &packet[RTP_HEADER_SIZE + 1..];
+        match packet[RTP_HEADER_SIZE] {
+            KIND_PARAMS => self.read_params(body),
+            KIND_FRAMES => self.read_frames(body),
+            KIND_FRAGMENT => self.read_fragment(body),
+            kind => Err(format!("Unknown packet kind {}", kind)),
+        }
+    }
+
+    /// Next frame in order, None while waiting on the jitter buffer
+    pub fn next_frame(&mut self) -> Option<EncodedFrame> {
+        loop {
+            let newest = self.newest_index()?;
+            let next = match self.next_index {
+                Some(next) => next,
+                None => {
+                    let oldest = self.oldest_index()?;
+                    if !self.flushing && newest - oldest < self.jitter_depth {
+                        return None;
+                    }
+                    oldest
+                }
+            };
+
+            if let Some(frame) = self.frames.remove(&next) {
+                self.next_index = Some(next.wrapping_add(1));
+                return Some(frame);
+            }
+
+            // missing, wait unless the stream has moved on far enough
+            let waiting = newest.saturating_sub(next) < self.jitter_depth;
+            if (waiting && !self.flushing) || (self.flushing && self.frames.is_empty()) {
+                return None;
+            }
+
+            // give up on everything before the next complete frame, or before
+            // the frames still worth waiting for
+            let complete = self.frames.range(next..).next().map(|(&i, _)| i);
+            let resume = if self.flushing {
+                complete
+            } else {
+                let wait_from = (newest - self.jitter_depth).saturating_add(1);
+                Some(complete.map_or(wait_from, |i| i.min(wait_from)))
+            };
+            let resume = resume.filter(|&i| i > next)?;
+            self.partial = self.partial.split_off(&resume);
+            self.record_lost(next..resume);
+            self.next_index = Some(resume);
+        }
+    }
+
+    /// The stream has ended, stop waiting for missing frames
+    ///
+    /// `next_frame` then returns every complete frame left, skipping gaps.
+    pub fn flush(&mut self) {
+        self.flushing = true;
+    }
+
+    /// Wrap a frame as a live packet for `StreamingDecoder::new_live`
+    ///
+    /// None until the stream parameters are known.
+    pub fn live_packet(&self, frame: &EncodedFrame) -> Option<Vec<u8>> {
+        let params = self.params?;
+        Some(write_live_packet(
+            params.flags,
+            params.sample_rate,
+            params.channels,
+            params.bit_depth,
+            frame.index,
+            frame.first_sample,
+            &frame.data,
+        ))
+    }
+
+    /// Forget everything, for a new stream
+    pub fn reset(&mut self) {
+        *self = Self::new().with_jitter_depth(self.jitter_depth);
+    }
+
+    /// count the packet, false if it's a duplicate
+    fn track_sequence(&mut self, sequence: u16) -> bool {
+        let extended = match self.highest_sequence {
+            // start a cycle in, so reordering around the first packet can't underflow
+            None => sequence as u64 + (1 << 16),
+            Some(highest) => {
+                let delta = sequence.wrapping_sub(highest as u16) as i16;
+                highest.saturating_add_signed(delta as i64)
+            }
+        };
+
+        if !self.seen.insert(extended) {
+            self.stats.duplicates += 1;
+            return false;
+        }
+        if self.seen.len() > SEQUENCE_HISTORY {
+            self.seen.pop_first();
+        }
+
+        match self.highest_sequence {
+            Some(highest) if extended < highest => self.stats.reordered += 1,
+            _ => self.highest_sequence = Some(extended),
+        }
+        self.first_sequence = match self.stats.received {
+            0 => extended,
+            _ => self.first_sequence.min(extended),
+        };
+        self.stats.received += 1;
+        let expected = self.highest_sequence.unwrap_or(0) - self.first_sequence + 1;
+        self.stats.lost = expected.saturating_sub(self.stats.received);
+        true
+    }
+
+    fn read_params(&mut self, body: &[u8]) -> FloResult<()> {
+        let body = body.get(..8).ok_or("Params packet too short")?;
+        let params = StreamParams {
+            flags: u16::from_le_bytes([body[0], body[1]]),
+            sample_rate: u32::from_le_bytes(body[2..6].try_into().unwrap()),
+            channels: body[6],
+            bit_depth: body[7],
+        };
+        if params.sample_rate == 0 || params.channels == 0 {
+            return Err("Invalid stream parameters".to_string());
+        }
+        self.params = Some(params);
+        Ok(())
+    }
+
+    fn read_frames(&mut self, body: &[u8]) -> FloResult<()> {
+        let count = *body.first().ok_or("Frames packet too short")?;
+        let mut pos = 1;
+        for _ in 0..count {
+            let mut frame = read_entry(&body[pos..]).ok_or("Frames packet too short")?;
+            pos += ENTRY_SIZE;
+            let size = body
+                .get(pos..pos + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                .ok_or("Frames packet too short")?;
+            pos += 2;
+            frame.data = body
+                .get(pos..pos + size)
+                .ok_or("Frames packet too short")?
+                .to_vec();
+            pos += size;
+            self.insert_frame(frame);
+        }
+        Ok(())
+    }
+
+    fn read_fragment(&mut self, body: &[u8]) -> FloResult<()> {
+        let frame = read_entry(body).ok_or("Fragment packet too short")?;
+        let sizes = body
+            .get(ENTRY_SIZE..ENTRY_SIZE + 8)
+            .ok_or("Fragment packet too short")?;
+        let size = u32::from_le_bytes(sizes[0..4].try_into().unwrap()) as usize;
+        let offset = u32::from_le_bytes(sizes[4..8].try_into().unwrap());
+        let chunk = &body[ENTRY_SIZE + 8..];
+        let offset = offset as usize;
+        let end = offset.saturating_add(chunk.len());
+        if end > size || size > super::live::MAX_LIVE_PAYLOAD as usize {
+            return Err("Fragment outside its frame".to_string());
+        }
+        if self.is_late(frame.index) {
+            return Ok(());
+        }
+
+        let index = frame.index;
+        if !self.partial.contains_key(&index) && !self.can_start_partial(index) {
+            return Ok(());
+        }
+        let partial = self.partial.entry(index).or_insert_with(|| Partial {
+            frame: EncodedFrame {
+                data: Vec::new(),
+                ..frame
+            },
+            size,
+            ranges: BTreeMap::new(),
+            received: 0,
+        });
+        if partial.size != size || partial.ranges.get(&offset) == Some(&end) {
+            return Ok(());
+        }
+        // the packetizer cuts at fixed points, anything else would leave gaps
+        let before = partial.ranges.range(..=offset).next_back();
+        let after = partial.ranges.range(offset..).next();
+        if before.is_some_and(|(_, &e)| e > offset) || after.is_some_and(|(&s, _)| s < end) {
+            return Err("Fragment overlaps another one".to_string());
+        }
+        partial.ranges.insert(offset, end);
+        if partial.frame.data.len() < end {
+            partial.frame.data.resize(end, 0);
+        }
+        partial.frame.data[offset..end].copy_from_slice(chunk);
+        partial.received += chunk.len();
+
+        if partial.received == size {
+            let done = self.partial.remove(&index).map(|p| p.frame);
+            self.insert_frame(done.unwrap());
+        }
+        Ok(())
+    }
+
+    /// room for another frame in pieces? Only one the jitter buffer would
+    /// wait for, anything further ahead is garbage or spoofed. With nothing
+    /// buffered it may be a restarted sender, so any index goes.
+    fn can_start_partial(&self, index: u32) -> bool {
+        if self.partial.len() > self.jitter_depth as usize {
+            return false;
+        }
+        match self.newest_index() {
+            Some(newest) => index.wrapping_sub(newest) as i32 <= self.jitter_depth as i32,
+            None => true,
+        }
+    }
+
+    fn record_lost(&mut self, frames: Range<u32>) {
+        self.lost_frame_count += (frames.end - frames.start) as u64;
+        self.lost_frames.push(frames);
+        if self.lost_frames.len() > LOST_HISTORY {
+            self.lost_frames.remove(0);
+        }
+    }
+
+    fn insert_frame(&mut self, frame: EncodedFrame) {
+        if !self.is_late(frame.index) {
+            self.frames.entry(frame.index).or_insert(frame);
+        }
+    }
+
+    /// has this frame's turn passed? counts it if so
+    fn is_late(&mut self, index: u32) -> bool {
+        let late = self.next_index.is_some_and(|next| index < next);
+        if late {
+            self.stats.late += 1;
+        }
+        late
+    }
+
+    fn newest_index(&self) -> Option<u32> {
+        let complete = self.frames.last_key_value().map(|(&i, _)| i);
+        let partial = self.partial.last_key_value().map(|(&i, _)| i);
+        complete.max(partial)
+    }
+
+    fn oldest_index(&self) -> Option<u32> {
+        let complete = self.frames.first_key_value().map(|(&i, _)| i);
+        let partial = self.partial.first_key_value().map(|(&i, _)| i);
+        match (complete, partial) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl Default for Depacketizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -7,6 +7,8 @@ pub enum DecoderState {
     WaitingForHeader,
     /// Header parsed, waiting for TOC
     WaitingForToc,
+    /// Live stream, scanning for the next sync point
+    Syncing,
     /// Ready to decode frames
     Ready,
     /// End of stream reached
@@ -15,6 +17,28 @@ pub enum DecoderState {
     Error,
 }
 
+/// Where a `StreamingDecoder` continues after a seek
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekRange {
+    /// Frame the next `next_frame` call returns
+    pub frame_index: usize,
+    /// File offset of the first byte the decoder doesn't have yet
+    pub start: u64,
+    /// End of the file, `start == end` when nothing more is needed
+    pub end: u64,
+}
+
+/// What `StreamingDecoder::feed` did with a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedStatus {
+    /// Bytes taken from the chunk, feed the rest again once frames are decoded
+    pub accepted: usize,
+    /// Frames ready to decode
+    pub frames_ready: usize,
+    /// The buffer is at its limit, decode frames before feeding more
+    pub full: bool,
+}
+
 /// Audio information for streaming
 #[derive(Debug, Clone)]
 pub struct StreamingAudioInfo {
@@ -24,16 +48,23 @@ pub struct StreamingAudioInfo {
     pub channels: u8,
     /// Bits per sample
     pub bit_depth: u8,
-    /// Total samples (actual sample count)
+    /// Total samples (actual sample count), 0 for live streams
     pub total_samples: u64,
+    /// Samples at the start that aren't audio (the lossy preroll), 0 if not recorded
+    pub encoder_delay: u32,
+    /// Samples of padding after the audio, 0 if not recorded
+    pub padding: u32,
     /// Is lossy encoding
     pub is_lossy: bool,
+    /// Is this an unbounded live stream (no header or TOC)
+    pub is_live: bool,
 }
 
 impl StreamingAudioInfo {
-    /// Calculate duration in seconds
+    /// Calculate duration in seconds, without the encoder delay and padding
     pub fn duration_secs(&self) -> f64 {
-        self.total_samples as f64 / self.sample_rate as f64
+        let padding = self.encoder_delay as u64 + self.padding as u64;
+        self.total_samples.saturating_sub(padding) as f64 / self.sample_rate as f64
     }
 
     /// Get total samples per channel
@@ -1,18 +1,52 @@
-use crate::core::{crc32, FloResult, Frame, FrameType};
-use crate::{ResidualEncoding, HEADER_SIZE, MAGIC, VERSION_MAJOR, VERSION_MINOR};
+use crate::core::integrity::{append_frame_crc, FRAME_CRC_SIZE};
+use crate::core::{
+    crc32, toc_size, FloMetadata, FloResult, Frame, FrameType, Header, MetadataPlacement, TocEntry,
+    FLAG_EARLY_META, FLAG_FRAME_CRC, FLAG_GAPLESS,
+};
+use crate::ResidualEncoding;
 
 /// binary writer for flo format
 pub struct Writer {
     buffer: Vec<u8>,
+    frame_crc: bool,
+    metadata_placement: MetadataPlacement,
+    /// encoder delay and real length in samples per channel
+    gapless: Option<(u32, u64)>,
 }
 
 impl Writer {
     /// new writer
     pub fn new() -> Self {
-        Writer { buffer: Vec::new() }
+        Writer {
+            buffer: Vec::new(),
+            frame_crc: false,
+            metadata_placement: MetadataPlacement::End,
+            gapless: None,
+        }
+    }
+
+    /// append a crc32 to every frame so damage can be located per frame
+    pub fn with_frame_crc(mut self, enabled: bool) -> Self {
+        self.frame_crc = enabled;
+        self
+    }
+
+    /// put the metadata (or a preview of it) ahead of the frames
+    pub fn with_metadata_placement(mut self, placement: MetadataPlacement) -> Self {
+        self.metadata_placement = placement;
+        self
+    }
+
+    /// record that the frames hold `length` samples per channel of audio
+    /// starting `encoder_delay` samples in, the rest is padding
+    pub fn with_gapless(mut self, encoder_delay: u32, length: u64) -> Self {
+        self.gapless = Some((encoder_delay, length));
+        self
     }
 
     /// write a complete flo file
+    ///
+    /// Errors on more frames than readers accept, see `Header::max_toc_entries`.
     pub fn write(
         self,
         sample_rate: u32,
@@ -47,9 +81,22 @@ impl Writer {
         frames: &[Frame],
         metadata: &[u8],
     ) -> FloResult<Vec<u8>> {
+        let (early_metadata, metadata) = place_metadata(metadata, self.metadata_placement);
+
+        // Calculate total samples across all frames
+        let total_samples: u64 = frames.iter().map(|frame| frame.frame_samples as u64).sum();
+
+        // readers cap the TOC by the length of the stream
+        let limits = Header {
+            sample_rate,
+            total_samples,
+            ..Header::default()
+        };
+        limits.check_toc_entries(frames.len())?;
+
         // sizes
-        let toc_size = 4 + (frames.len() * 20) as u64;
-        let data_chunk = self.build_data_chunk(frames);
+        let toc_size = toc_size(frames.len());
+        let data_chunk = self.build_data_chunk(&early_metadata, frames);
         let data_size = data_chunk.len() as u64;
         let extra_size = 0u64;
         let meta_size = metadata.len() as u64;
@@ -58,7 +105,7 @@ impl Writer {
         let data_crc32 = crc32::compute(&data_chunk);
 
         // toc
-        let toc_chunk = self.build_toc_chunk(frames, sample_rate);
+        let toc_chunk = self.build_toc_chunk(early_metadata.len() as u64, frames, sample_rate);
 
         // flags
         let mut flags: u16 = 0;
@@ -66,9 +113,15 @@ impl Writer {
             flags |= 0x01; // lossy mode
             flags |= (lossy_quality as u16) << 8; // quality level
         }
-
-        // Calculate total samples across all frames
-        let total_samples: u64 = frames.iter().map(|frame| frame.frame_samples as u64).sum();
+        if self.frame_crc {
+            flags |= FLAG_FRAME_CRC;
+        }
+        if !early_metadata.is_empty() {
+            flags |= FLAG_EARLY_META;
+        }
+        if self.gapless.is_some() {
+            flags |= FLAG_GAPLESS;
+        }
 
         // header
         self.write_header_ex(
@@ -83,6 +136,7 @@ impl Writer {
             data_size,
             extra_size,
             meta_size,
+            early_metadata.len() as u64,
         );
 
         // toc
@@ -94,7 +148,7 @@ impl Writer {
         // extra (empty for now)
 
         // metadata
-        self.buffer.extend_from_slice(metadata);
+        self.buffer.extend_from_slice(&metadata);
 
         Ok(self.buffer)
     }
@@ -125,6 +179,7 @@ impl Writer {
             data_size,
             extra_size,
             meta_size,
+            0,
         );
     }
 
@@ -142,92 +197,73 @@ impl Writer {
         data_size: u64,
         extra_size: u64,
         meta_size: u64,
+        early_meta_size: u64,
     ) {
-        // Magic "FLO!"
-        self.buffer.extend_from_slice(&MAGIC);
-
-        // Version (u8, u8)
-        self.buffer.push(VERSION_MAJOR);
-        self.buffer.push(VERSION_MINOR);
-
-        // Flags (u16 LE)
-        self.buffer.extend_from_slice(&flags.to_le_bytes());
-
-        // Sample Rate (u32 LE)
-        self.buffer.extend_from_slice(&sample_rate.to_le_bytes());
-
-        // Channels (u8)
-        self.buffer.push(channels);
-
-        // Bit Depth (u8)
-        self.buffer.push(bit_depth);
-
-        // Total Samples (u64 LE)
-        self.buffer.extend_from_slice(&total_samples.to_le_bytes());
-
-        // Compression Level (u8)
-        self.buffer.push(compression_level);
-
-        // Reserved (3 bytes)
-        self.buffer.extend_from_slice(&[0, 0, 0]);
-
-        // Data CRC32 (u32 LE)
-        self.buffer.extend_from_slice(&data_crc32.to_le_bytes());
-
-        // Header Size (u64 LE) - size after magic
-        self.buffer.extend_from_slice(&HEADER_SIZE.to_le_bytes());
-
-        // TOC Size (u64 LE)
-        self.buffer.extend_from_slice(&toc_size.to_le_bytes());
-
-        // Data Size (u64 LE)
-        self.buffer.extend_from_slice(&data_size.to_le_bytes());
-
-        // Extra Size (u64 LE)
-        self.buffer.extend_from_slice(&extra_size.to_le_bytes());
-
-        // Meta Size (u64 LE)
-        self.buffer.extend_from_slice(&meta_size.to_le_bytes());
+        let (encoder_delay, padding) = match self.gapless {
+            Some((delay, length)) => (
+                delay,
+                total_samples.saturating_sub(delay as u64 + length) as u32,
+            ),
+            None => (0, 0),
+        };
+
+        let header = Header {
+            flags,
+            sample_rate,
+            channels,
+            bit_depth,
+            total_samples,
+            compression_level,
+            data_crc32,
+            toc_size,
+            data_size,
+            extra_size,
+            meta_size,
+            early_meta_size,
+            encoder_delay,
+            padding,
+            ..Header::default()
+        };
+
+        // magic, fields and header crc
+        self.buffer.extend_from_slice(&header.to_bytes());
     }
 
-    fn build_toc_chunk(&self, frames: &[Frame], sample_rate: u32) -> Vec<u8> {
-        let mut toc = Vec::new();
-
-        // Number of entries (u32 LE)
-        toc.extend_from_slice(&(frames.len() as u32).to_le_bytes());
-
-        let mut byte_offset = 0u64;
+    fn build_toc_chunk(&self, data_start: u64, frames: &[Frame], sample_rate: u32) -> Vec<u8> {
+        let mut toc = Vec::with_capacity(frames.len());
+        let mut byte_offset = data_start;
         let mut cumulative_samples = 0u64;
 
-        for (i, frame) in frames.iter().enumerate() {
-            let frame_size = frame.byte_size() as u32;
-
-            // Frame index (u32 LE)
-            toc.extend_from_slice(&(i as u32).to_le_bytes());
-
-            // Byte offset (u64 LE)
-            toc.extend_from_slice(&byte_offset.to_le_bytes());
+        let crc_size = if self.frame_crc { FRAME_CRC_SIZE } else { 0 };
 
-            // Frame size (u32 LE)
-            toc.extend_from_slice(&frame_size.to_le_bytes());
+        for (i, frame) in frames.iter().enumerate() {
+            let frame_size = (frame.byte_size() + crc_size) as u32;
 
-            // Timestamp in milliseconds (u32 LE)
-            // Calculate based on cumulative samples and sample rate
-            let timestamp_ms = (cumulative_samples * 1000 / sample_rate as u64) as u32;
-            toc.extend_from_slice(&timestamp_ms.to_le_bytes());
+            // timestamp is derived from the sample position, rounded down
+            toc.push(TocEntry {
+                frame_index: i as u32,
+                byte_offset,
+                frame_size,
+                timestamp_ms: (cumulative_samples * 1000 / sample_rate as u64) as u32,
+                first_sample: cumulative_samples,
+            });
 
             byte_offset += frame_size as u64;
             cumulative_samples += frame.frame_samples as u64;
         }
 
-        toc
+        TocEntry::write_all(&toc)
     }
 
-    fn build_data_chunk(&self, frames: &[Frame]) -> Vec<u8> {
-        let mut data = Vec::new();
+    fn build_data_chunk(&self, early_metadata: &[u8], frames: &[Frame]) -> Vec<u8> {
+        let mut data = early_metadata.to_vec();
 
         for frame in frames {
+            let frame_start = data.len();
             self.write_frame(&mut data, frame);
+            if self.frame_crc {
+                append_frame_crc(&mut data, frame_start);
+            }
         }
 
         data
@@ -306,3 +342,30 @@ impl Default for Writer {
         Self::new()
     }
 }
+
+/// serialize one frame exactly as it sits in the DATA chunk
+pub(crate) fn frame_to_bytes(frame: &Frame) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(frame.byte_size());
+    Writer::new().write_frame(&mut buffer, frame);
+    buffer
+}
+
+/// split metadata into the early chunk and the META chunk for `placement`
+///
+/// A preview needs flo metadata, anything else just stays at the end.
+pub(crate) fn place_metadata(metadata: &[u8], placement: MetadataPlacement) -> (Vec<u8>, Vec<u8>) {
+    if metadata.is_empty() {
+        return (vec![], vec![]);
+    }
+    match placement {
+        MetadataPlacement::End => (vec![], metadata.to_vec()),
+        MetadataPlacement::Start => (metadata.to_vec(), vec![]),
+        MetadataPlacement::Preview => {
+            let preview = FloMetadata::from_msgpack(metadata)
+                .ok()
+                .and_then(|meta| meta.preview().to_msgpack().ok())
+                .unwrap_or_default();
+            (preview, metadata.to_vec())
+        }
+    }
+}
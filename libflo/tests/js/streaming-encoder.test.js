@@ -343,6 +343,73 @@ describe('WasmStreamingEncoder', () => {
     });
   });
 
+  describe('lossy encoding', () => {
+    test('should encode lossy frames one hop at a time', () => {
+      const encoder = libflo.WasmStreamingEncoder.new_lossy(44100, 1, 0.5);
+      const samples = new Float32Array(5000);
+      for (let i = 0; i < samples.length; i++) {
+        samples[i] = Math.sin((i * 0.01) * 2 * Math.PI) * 0.5;
+      }
+
+      encoder.push_samples(samples);
+      // preroll plus the hops that have their overlap
+      expect(encoder.pending_frames()).toBe(4);
+
+      encoder.flush();
+      expect(encoder.pending_frames()).toBe(6);
+      expect(encoder.pending_samples()).toBe(0);
+    });
+
+    test('should produce lossy files of the pushed length', () => {
+      const encoder = libflo.WasmStreamingEncoder.new_lossy(44100, 2, 0.6);
+      const samples = new Float32Array(44100 * 2);
+      for (let i = 0; i < 44100; i++) {
+        samples[i * 2] = Math.sin((i * 0.01) * 2 * Math.PI) * 0.5;
+        samples[i * 2 + 1] = Math.cos((i * 0.015) * 2 * Math.PI) * 0.5;
+      }
+
+      for (let i = 0; i < samples.length; i += 1234 * 2) {
+        encoder.push_samples(samples.subarray(i, i + 1234 * 2));
+      }
+      encoder.flush();
+      const file = encoder.finalize(null);
+
+      const info = libflo.info(file);
+      expect(info.is_lossy).toBe(true);
+      expect(libflo.decode(file).length).toBeGreaterThanOrEqual(samples.length);
+    });
+  });
+
+  describe('low latency', () => {
+    test('should emit short lossless frames', () => {
+      const encoder = new libflo.WasmStreamingEncoder(48000, 1, 16).with_low_latency(10);
+      expect(encoder.algorithmic_delay_ms()).toBe(10);
+
+      encoder.push_samples(new Float32Array(480 * 3));
+      expect(encoder.pending_frames()).toBe(3);
+    });
+
+    test('should report lossy delay on both ends', () => {
+      const encoder = libflo.WasmStreamingEncoder.new_lossy(48000, 1, 0.5).with_low_latency(10);
+      expect(encoder.algorithmic_delay_ms()).toBe(12.5);
+
+      const samples = new Float32Array(4800);
+      for (let i = 0; i < samples.length; i++) {
+        samples[i] = Math.sin(i * 0.02) * 0.5;
+      }
+      encoder.push_samples(samples);
+
+      const decoder = libflo.WasmStreamingDecoder.new_live();
+      expect(decoder.algorithmic_delay_ms()).toBeUndefined();
+      let packet;
+      while ((packet = encoder.next_live_packet()) !== undefined) {
+        decoder.feed(packet);
+        while (decoder.next_frame() !== null) {}
+      }
+      expect(decoder.algorithmic_delay_ms()).toBe(12.5);
+    });
+  });
+
   describe('integration with WasmStreamingDecoder', () => {
     test('should produce files decodable by WasmStreamingDecoder', () => {
       const encoder = new libflo.WasmStreamingEncoder(44100, 1, 16);
@@ -362,6 +429,33 @@ describe('WasmStreamingEncoder', () => {
       expect(decoder.is_ready()).toBe(true);
     });
 
+    test('feed reports ready frames, feed_partial the bytes taken', () => {
+      const encoder = new libflo.WasmStreamingEncoder(44100, 1, 16);
+      // noise, so the frames don't fit the limit
+      const samples = new Float32Array(44100 * 3).map(() => Math.random() - 0.5);
+      encoder.push_samples(samples);
+      const file = encoder.finalize(null);
+
+      const decoder = new libflo.WasmStreamingDecoder();
+      expect(decoder.feed(new Uint8Array(0))).toBe(false);
+      expect(decoder.feed(file)).toBe(true);
+
+      const limited = new libflo.WasmStreamingDecoder().with_max_buffer(1024);
+      let pending = file;
+      let decoded = 0;
+      while (pending.length > 0) {
+        const taken = limited.feed_partial(pending);
+        pending = pending.subarray(taken);
+        let frame;
+        while ((frame = limited.next_frame()) !== null) {
+          decoded += frame.length;
+        }
+      }
+      expect(decoded).toBe(44100 * 3);
+      const full = new libflo.WasmStreamingDecoder().with_max_buffer(1024);
+      expect(() => full.feed(file)).toThrow();
+    });
+
     test('should decode to approximately original samples', () => {
       const encoder = new libflo.WasmStreamingEncoder(44100, 1, 16);
       const originalSamples = new Float32Array(44100);
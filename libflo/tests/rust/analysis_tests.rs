@@ -11,7 +11,7 @@ fn test_extract_waveform_peaks_mono() {
 
     // All peaks should be between 0.0 and 1.0
     for &peak in &waveform.peaks {
-        assert!(peak >= 0.0 && peak <= 1.0);
+        assert!((0.0..=1.0).contains(&peak));
     }
 }
 
@@ -26,7 +26,7 @@ fn test_extract_waveform_peaks_stereo() {
 
     // All peaks should be between 0.0 and 1.0
     for &peak in &waveform.peaks {
-        assert!(peak >= 0.0 && peak <= 1.0);
+        assert!((0.0..=1.0).contains(&peak));
     }
 }
 
@@ -51,7 +51,7 @@ fn test_extract_waveform_rms_mono() {
 
     // All RMS values should be between 0.0 and 1.0
     for &peak in &waveform.peaks {
-        assert!(peak >= 0.0 && peak <= 1.0);
+        assert!((0.0..=1.0).contains(&peak));
     }
 }
 
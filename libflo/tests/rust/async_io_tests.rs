@@ -0,0 +1,226 @@
+//! futures Stream/Sink adapter tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Files and live streams through an in-memory pipe
+//! - Backpressure from a slow reader reaching the sender
+//! - Cancellation and truncated input
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures::executor::{block_on, LocalPool};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::task::LocalSpawnExt;
+use futures::{SinkExt, StreamExt};
+use libflo_audio::{DecodeStream, EncodeSink, StreamingDecoder, StreamingEncoder};
+
+const SAMPLE_RATE: u32 = 48000;
+
+fn test_signal(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|i| (i as f32 * 0.021).sin() * 0.4 + (i as f32 * 0.13).sin() * 0.05)
+        .collect()
+}
+
+#[derive(Default)]
+struct PipeState {
+    bytes: VecDeque<u8>,
+    capacity: usize,
+    reader_gone: bool,
+    writer_gone: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+/// bounded in-memory byte pipe, writes wait while it's full
+fn pipe(capacity: usize) -> (PipeWriter, PipeReader) {
+    let state = Arc::new(Mutex::new(PipeState {
+        capacity,
+        ..PipeState::default()
+    }));
+    (PipeWriter(state.clone()), PipeReader(state))
+}
+
+struct PipeReader(Arc<Mutex<PipeState>>);
+struct PipeWriter(Arc<Mutex<PipeState>>);
+
+impl AsyncRead for PipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.0.lock().unwrap();
+        if state.bytes.is_empty() {
+            if state.writer_gone {
+                return Poll::Ready(Ok(0));
+            }
+            state.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(state.bytes.len());
+        for (dst, src) in buf.iter_mut().zip(state.bytes.drain(..n)) {
+            *dst = src;
+        }
+        if let Some(waker) = state.write_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        state.reader_gone = true;
+        if let Some(waker) = state.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl AsyncWrite for PipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.0.lock().unwrap();
+        if state.reader_gone {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let n = buf.len().min(state.capacity - state.bytes.len());
+        if n == 0 {
+            state.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        state.bytes.extend(&buf[..n]);
+        if let Some(waker) = state.read_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        state.writer_gone = true;
+        if let Some(waker) = state.read_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[test]
+fn test_file_through_pipe() {
+    let samples = test_signal(SAMPLE_RATE as usize * 3 + 123);
+    let (writer, reader) = pipe(4096);
+
+    let send = async move {
+        let mut sink = EncodeSink::file(StreamingEncoder::new(SAMPLE_RATE, 1, 16), writer, &[]);
+        for block in samples.chunks(1000) {
+            sink.send(block.to_vec()).await.unwrap();
+        }
+        sink.close().await.unwrap();
+        samples
+    };
+    let receive = DecodeStream::new(reader).map(Result::unwrap).concat();
+
+    let (samples, decoded) = block_on(futures::future::join(send, receive));
+    assert_eq!(decoded.len(), samples.len());
+    for (a, b) in samples.iter().zip(&decoded) {
+        assert!((a - b).abs() <= 1.0 / 32767.0);
+    }
+}
+
+#[test]
+fn test_live_backpressure() {
+    let samples = test_signal(SAMPLE_RATE as usize);
+    let blocks: Vec<Vec<f32>> = samples.chunks(480).map(<[f32]>::to_vec).collect();
+    let total = blocks.len();
+    let (writer, reader) = pipe(2048);
+
+    let mut pool = LocalPool::new();
+    let sent = Rc::new(Cell::new(0));
+    let counter = sent.clone();
+    pool.spawner()
+        .spawn_local(async move {
+            let encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.6).with_low_latency(10.0);
+            let mut sink = EncodeSink::live(encoder, writer);
+            for block in blocks {
+                sink.send(block).await.unwrap();
+                counter.set(counter.get() + 1);
+            }
+            sink.close().await.unwrap();
+        })
+        .unwrap();
+
+    // nobody reads, so the sender stops once the pipe is full
+    pool.run_until_stalled();
+    let stalled_at = sent.get();
+    assert!(stalled_at > 0 && stalled_at < total, "sent {}", stalled_at);
+
+    // reading a block lets a little more through, not everything
+    let mut stream = DecodeStream::with_decoder(reader, StreamingDecoder::new_live());
+    let first = pool.run_until(stream.next()).unwrap().unwrap();
+    assert_eq!(first.len(), 480);
+    pool.run_until_stalled();
+    assert!(sent.get() < total);
+
+    let rest = pool.run_until(stream.map(Result::unwrap).concat());
+    pool.run();
+    assert_eq!(sent.get(), total);
+    assert_eq!(first.len() + rest.len(), samples.len());
+}
+
+#[test]
+fn test_cancel_and_truncate() {
+    let samples = test_signal(SAMPLE_RATE as usize * 2);
+
+    // dropping the stream fails the sender instead of leaving it waiting
+    let (writer, reader) = pipe(1024);
+    let result = block_on(async move {
+        let send = async move {
+            let encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(10.0);
+            let mut sink = EncodeSink::live(encoder, writer);
+            for block in samples.chunks(480) {
+                sink.send(block.to_vec()).await?;
+            }
+            sink.close().await
+        };
+        let receive = async move {
+            let mut stream = DecodeStream::new(reader);
+            stream.next().await.unwrap().unwrap();
+        };
+        futures::future::join(send, receive).await.0
+    });
+    assert!(result.is_err());
+
+    // a file cut short is an error, once
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16);
+    encoder
+        .push_samples(&test_signal(SAMPLE_RATE as usize * 2))
+        .unwrap();
+    let flo_data = encoder.finalize(&[]).unwrap();
+    let truncated = &flo_data[..flo_data.len() * 3 / 4];
+    let mut stream = DecodeStream::new(futures::io::Cursor::new(truncated));
+    let items: Vec<_> = block_on(stream.by_ref().collect());
+    assert_eq!(items.len(), 2);
+    assert!(items[0].is_ok());
+    assert!(items[1].is_err());
+    assert!(block_on(stream.next()).is_none());
+}
@@ -0,0 +1,181 @@
+//! Chapter table tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Chapter table in metadata (ordering, lookup, ranges)
+//! - Seeking to a chapter
+//! - Chapters following trim / split / concat, and chapter extraction
+
+use libflo_audio::edit::{concat, extract_chapter, split, trim};
+use libflo_audio::seeking::{chapter_at_time, get_chapters, seek_to_chapter};
+use libflo_audio::{decode, Encoder, FloMetadata, Reader};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn test_signal(seconds: usize) -> Vec<f32> {
+    (0..SAMPLE_RATE as usize * seconds)
+        .map(|i| (i as f32 * 0.01).sin() * 0.5)
+        .collect()
+}
+
+/// a 3 second "album" with tracks at 0, 0.75 s and 2 s
+fn album_metadata() -> FloMetadata {
+    let mut meta = FloMetadata::new();
+    meta.title = Some("Album".to_string());
+    meta.artist = Some("Band".to_string());
+    meta.add_chapter(16000, Some("Three")).track_number = Some(3);
+    meta.add_chapter(0, Some("One")).track_number = Some(1);
+    let two = meta.add_chapter(6000, Some("Two"));
+    two.track_number = Some(2);
+    two.artist = Some("Guest".to_string());
+    meta
+}
+
+fn encode_album() -> Vec<u8> {
+    Encoder::new(SAMPLE_RATE, 1, 16)
+        .encode(&test_signal(3), &album_metadata().to_msgpack().unwrap())
+        .unwrap()
+}
+
+fn metadata(flo_data: &[u8]) -> FloMetadata {
+    let file = Reader::new().read(flo_data).unwrap();
+    FloMetadata::from_msgpack(&file.metadata).unwrap()
+}
+
+fn starts(flo_data: &[u8]) -> Vec<u64> {
+    get_chapters(flo_data)
+        .unwrap()
+        .iter()
+        .map(|c| c.start_sample)
+        .collect()
+}
+
+#[test]
+fn test_chapter_table() {
+    let meta = album_metadata();
+    let titles: Vec<_> = meta.chapters.iter().map(|c| c.title.clone()).collect();
+    assert_eq!(
+        titles,
+        vec![
+            Some("One".to_string()),
+            Some("Two".to_string()),
+            Some("Three".to_string())
+        ]
+    );
+
+    assert_eq!(meta.chapter_at(0), Some(0));
+    assert_eq!(meta.chapter_at(5999), Some(0));
+    assert_eq!(meta.chapter_at(6000), Some(1));
+    assert_eq!(meta.chapter_at(100_000), Some(2));
+    assert_eq!(meta.chapter_range(1, 24000), Some((6000, 16000)));
+    assert_eq!(meta.chapter_range(2, 24000), Some((16000, 24000)));
+    assert_eq!(meta.chapter_range(3, 24000), None);
+
+    let restored = FloMetadata::from_msgpack(&meta.to_msgpack().unwrap()).unwrap();
+    assert_eq!(restored.chapters.len(), 3);
+    assert_eq!(restored.chapters[1].artist.as_deref(), Some("Guest"));
+}
+
+#[test]
+fn test_seek_to_chapter() {
+    let flo_data = encode_album();
+    assert_eq!(starts(&flo_data), vec![0, 6000, 16000]);
+
+    let result = seek_to_chapter(&flo_data, 1).unwrap();
+    assert_eq!(result.frame_index, 0);
+    assert_eq!(result.sample_offset, 6000);
+
+    let result = seek_to_chapter(&flo_data, 2).unwrap();
+    assert_eq!(result.frame_index, 2);
+    assert_eq!(result.sample_offset, 0);
+    assert_eq!(result.timestamp_ms, 2000);
+
+    assert!(seek_to_chapter(&flo_data, 3).is_err());
+
+    assert_eq!(chapter_at_time(&flo_data, 500).unwrap(), Some(0));
+    assert_eq!(chapter_at_time(&flo_data, 1000).unwrap(), Some(1));
+    assert_eq!(chapter_at_time(&flo_data, 2500).unwrap(), Some(2));
+}
+
+#[test]
+fn test_no_chapters() {
+    let flo_data = Encoder::new(SAMPLE_RATE, 1, 16)
+        .encode(&test_signal(1), &[])
+        .unwrap();
+    assert!(get_chapters(&flo_data).unwrap().is_empty());
+    assert!(seek_to_chapter(&flo_data, 0).is_err());
+    assert_eq!(chapter_at_time(&flo_data, 0).unwrap(), None);
+}
+
+#[test]
+fn test_extract_chapter() {
+    let flo_data = encode_album();
+    let reference = decode(&flo_data).unwrap();
+
+    let track = extract_chapter(&flo_data, 1).unwrap();
+    assert_eq!(decode(&track).unwrap(), reference[6000..16000]);
+
+    let meta = metadata(&track);
+    assert_eq!(meta.title.as_deref(), Some("Two"));
+    assert_eq!(meta.artist.as_deref(), Some("Guest"));
+    assert_eq!(meta.album.as_deref(), Some("Album"));
+    assert_eq!(meta.track_number, Some(2));
+    assert_eq!(meta.length_ms, Some(1250));
+    assert!(meta.chapters.is_empty());
+
+    // falls back to the file's artist
+    let last = extract_chapter(&flo_data, 2).unwrap();
+    assert_eq!(metadata(&last).artist.as_deref(), Some("Band"));
+
+    assert!(extract_chapter(&flo_data, 3).is_err());
+}
+
+#[test]
+fn test_trim_moves_chapters() {
+    let flo_data = encode_album();
+    let trimmed = trim(&flo_data, 4000, 20000).unwrap();
+
+    let chapters = get_chapters(&trimmed).unwrap();
+    let titles: Vec<_> = chapters.iter().map(|c| c.title.as_deref()).collect();
+    assert_eq!(titles, vec![Some("One"), Some("Two"), Some("Three")]);
+    assert_eq!(starts(&trimmed), vec![0, 2000, 12000]);
+
+    // chapters entirely outside the range are dropped
+    let trimmed = trim(&flo_data, 7000, 15000).unwrap();
+    assert_eq!(starts(&trimmed), vec![0]);
+    assert_eq!(
+        get_chapters(&trimmed).unwrap()[0].title.as_deref(),
+        Some("Two")
+    );
+}
+
+#[test]
+fn test_split_and_concat_keep_chapters() {
+    let flo_data = encode_album();
+    let parts = split(&flo_data, &[10000]).unwrap();
+    assert_eq!(starts(&parts[0]), vec![0, 6000]);
+    assert_eq!(starts(&parts[1]), vec![0, 6000]);
+
+    let refs: Vec<&[u8]> = parts.iter().map(|p| p.as_slice()).collect();
+    let joined = concat(&refs).unwrap();
+    assert_eq!(starts(&joined), vec![0, 6000, 16000]);
+}
+
+#[test]
+fn test_concat_tracks_builds_chapters() {
+    let track = |title: &str, seconds: usize| {
+        let mut meta = FloMetadata::new();
+        meta.title = Some(title.to_string());
+        Encoder::new(SAMPLE_RATE, 1, 16)
+            .encode(&test_signal(seconds), &meta.to_msgpack().unwrap())
+            .unwrap()
+    };
+    let a = track("A", 1);
+    let b = track("B", 2);
+    let c = track("C", 1);
+
+    let joined = concat(&[&a, &b, &c]).unwrap();
+    let chapters = get_chapters(&joined).unwrap();
+    let titles: Vec<_> = chapters.iter().map(|c| c.title.as_deref()).collect();
+    assert_eq!(titles, vec![Some("A"), Some("B"), Some("C")]);
+    assert_eq!(starts(&joined), vec![0, 8000, 24000]);
+}
@@ -0,0 +1,239 @@
+//! Packet-loss concealment tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Lossless gaps filled by repeating or extrapolating the audio before them
+//! - Lossy stand-ins keeping the MDCT overlap and timing intact
+//! - Giving up on late frames with conceal_frame, live and from a file
+//! - Not concealing a frame index jump that only skips a few samples
+
+use libflo_audio::streaming::live::write_live_packet;
+use libflo_audio::streaming::LivePacketHeader;
+use libflo_audio::{decode_tolerant, Concealment, Reader, StreamingDecoder, StreamingEncoder};
+
+const SAMPLE_RATE: u32 = 48000;
+const FRAME: usize = 480;
+
+const MODES: [Concealment; 4] = [
+    Concealment::Silence,
+    Concealment::Interpolate,
+    Concealment::RepeatFade,
+    Concealment::Spectral,
+];
+
+/// a steady 400 Hz tone, the easiest thing to continue
+fn tone(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|i| (i as f32 * 2.0 * std::f32::consts::PI * 400.0 / SAMPLE_RATE as f32).sin() * 0.5)
+        .collect()
+}
+
+fn damage_frame(flo_data: &mut [u8], frame_index: usize) {
+    let file = Reader::new().read_ref(flo_data).unwrap();
+    let data_start = (4 + file.header.header_size + file.header.toc_size) as usize;
+    let entry = &file.toc[frame_index];
+    let pos = data_start + entry.byte_offset as usize + entry.frame_size as usize / 2;
+    flo_data[pos] ^= 0x5A;
+}
+
+fn live_packets(encoder: &mut StreamingEncoder, samples: &[f32]) -> Vec<Vec<u8>> {
+    encoder.push_samples(samples).unwrap();
+    encoder.finish().unwrap();
+    std::iter::from_fn(|| encoder.next_live_packet()).collect()
+}
+
+fn rms_error(a: &[f32], b: &[f32]) -> f32 {
+    let sum: f32 = a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum();
+    (sum / a.len() as f32).sqrt()
+}
+
+#[test]
+fn test_lossless_gap_continues_the_tone() {
+    let samples = tone(FRAME * 20);
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16)
+        .with_low_latency(10.0)
+        .with_frame_crc(true);
+    encoder.push_samples(&samples).unwrap();
+    let mut flo_data = encoder.finalize(&[]).unwrap();
+    damage_frame(&mut flo_data, 10);
+
+    let gap = FRAME * 10..FRAME * 11;
+    let error = |mode| {
+        let decoded = decode_tolerant(&flo_data, mode).unwrap();
+        assert_eq!(decoded.len(), samples.len());
+
+        // both edges join up, no step bigger than the tone's own slope
+        let step = |i: usize| (decoded[i] - decoded[i - 1]).abs();
+        assert!(step(gap.start) < 0.05, "{:?} clicks going in", mode);
+        assert!(step(gap.end) < 0.05, "{:?} clicks coming out", mode);
+
+        rms_error(&decoded[gap.clone()], &samples[gap.clone()])
+    };
+
+    let silence = error(Concealment::Silence);
+    let interpolate = error(Concealment::Interpolate);
+    let repeat = error(Concealment::RepeatFade);
+    let spectral = error(Concealment::Spectral);
+    assert!(silence > 0.3);
+    assert!(repeat < interpolate / 4.0, "{} vs {}", repeat, interpolate);
+    assert!(
+        spectral < interpolate / 2.0,
+        "{} vs {}",
+        spectral,
+        interpolate
+    );
+}
+
+#[test]
+fn test_lossy_conceal_frame_keeps_timing() {
+    let samples = tone(FRAME * 40);
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.6).with_low_latency(10.0);
+    let packets = live_packets(&mut encoder, &samples);
+
+    let mut reference = StreamingDecoder::new_live();
+    for packet in &packets {
+        reference.feed(packet).unwrap();
+    }
+    let reference = reference.decode_available().unwrap();
+    assert_eq!(reference.len(), samples.len());
+
+    for mode in MODES {
+        let mut decoder = StreamingDecoder::new_live().with_concealment(mode);
+        for packet in &packets[..20] {
+            decoder.feed(packet).unwrap();
+        }
+        let mut decoded = decoder.decode_available().unwrap();
+
+        // packet 20 misses its deadline
+        let concealed = decoder.conceal_frame().unwrap();
+        assert_eq!(concealed.len(), FRAME);
+        assert!(concealed.iter().all(|s| s.is_finite() && s.abs() < 1.0));
+        decoded.extend(&concealed);
+
+        // then turns up late, and is dropped
+        for packet in &packets[20..] {
+            decoder.feed(packet).unwrap();
+            decoded.extend(decoder.decode_available().unwrap());
+        }
+        assert_eq!(decoder.damaged_frames(), &[20]);
+        assert_eq!(decoded.len(), samples.len());
+
+        // the overlap ran on, so a frame later it's the real thing again
+        let start = FRAME * 21;
+        assert!(rms_error(&decoded[start..], &reference[start..]) < 1e-4);
+
+        // the repeating modes carry the tone through the lost hop
+        let lost = FRAME * 19..FRAME * 20;
+        let energy: f32 = decoded[lost].iter().map(|s| s * s).sum();
+        if mode != Concealment::Silence {
+            assert!(energy > 0.1 * FRAME as f32 * 0.125, "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn test_lossy_index_jump_is_not_concealed() {
+    let samples = tone(FRAME * 10);
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.6).with_low_latency(10.0);
+    let packets = live_packets(&mut encoder, &samples);
+
+    // packet 5 claims billions of frames went missing, its samples don't
+    let header = LivePacketHeader::parse(&packets[5]).unwrap();
+    let jumped = write_live_packet(
+        header.flags,
+        header.sample_rate,
+        header.channels,
+        header.bit_depth,
+        3_000_000_000,
+        header.first_sample,
+        &packets[5][header.packet_size() - header.payload_size as usize..],
+    );
+
+    let mut decoder = StreamingDecoder::new_live().with_concealment(Concealment::Spectral);
+    for packet in &packets[..5] {
+        decoder.feed(packet).unwrap();
+    }
+    decoder.decode_available().unwrap();
+    decoder.feed(&jumped).unwrap();
+
+    // a restart, the frame only primes the MDCT again
+    let decoded = decoder.decode_available().unwrap();
+    assert!(decoded.len() <= FRAME);
+    assert!(decoder.damaged_frames().is_empty());
+}
+
+#[test]
+fn test_lossless_live_conceal_frame() {
+    let samples = tone(FRAME * 12);
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(10.0);
+    let packets = live_packets(&mut encoder, &samples);
+
+    // without a mode the stand-in is silence
+    let mut decoder = StreamingDecoder::new_live();
+    assert!(decoder.conceal_frame().is_err());
+    for packet in &packets[..5] {
+        decoder.feed(packet).unwrap();
+    }
+    let mut decoded = decoder.decode_available().unwrap();
+    let concealed = decoder.conceal_frame().unwrap();
+    assert_eq!(concealed, vec![0.0; FRAME]);
+    decoded.extend(concealed);
+    for packet in &packets[5..] {
+        decoder.feed(packet).unwrap();
+    }
+    // the late packet for frame 5 is dropped, not handed out as an empty frame
+    while let Some(frame) = decoder.next_frame().unwrap() {
+        assert_eq!(frame.len(), FRAME);
+        decoded.extend(frame);
+    }
+    assert_eq!(decoded.len(), samples.len());
+    assert_eq!(decoder.damaged_frames(), &[5]);
+
+    // everything but the given up frame is exact
+    for (i, (a, b)) in samples.iter().zip(&decoded).enumerate() {
+        if !(FRAME * 5..FRAME * 6).contains(&i) {
+            assert!((a - b).abs() <= 1.0 / 32767.0);
+        }
+    }
+
+    // with one it carries on from before the gap
+    let mut decoder = StreamingDecoder::new_live().with_concealment(Concealment::RepeatFade);
+    for packet in &packets[..5] {
+        decoder.feed(packet).unwrap();
+    }
+    decoder.decode_available().unwrap();
+    let concealed = decoder.conceal_frame().unwrap();
+    assert!(rms_error(&concealed, &samples[FRAME * 5..FRAME * 6]) < 0.1);
+}
+
+#[test]
+fn test_file_conceal_frame_while_downloading() {
+    let samples = tone(FRAME * 12);
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(10.0);
+    encoder.push_samples(&samples).unwrap();
+    let flo_data = encoder.finalize(&[]).unwrap();
+
+    // the download stalls halfway through frame 5
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let data_start = (4 + file.header.header_size + file.header.toc_size) as usize;
+    let entry = &file.toc[5];
+    let stall = data_start + entry.byte_offset as usize + entry.frame_size as usize / 2;
+
+    let mut decoder = StreamingDecoder::new().with_concealment(Concealment::Spectral);
+    decoder.feed(&flo_data[..stall]).unwrap();
+    let mut decoded = decoder.decode_available().unwrap();
+    assert_eq!(decoded.len(), FRAME * 5);
+
+    decoded.extend(decoder.conceal_frame().unwrap());
+    assert_eq!(decoder.current_frame_index(), 6);
+
+    // the rest of frame 5 still comes in, it's skipped over
+    decoder.feed(&flo_data[stall..]).unwrap();
+    decoded.extend(decoder.decode_available().unwrap());
+    assert_eq!(decoded.len(), samples.len());
+    assert_eq!(decoder.damaged_frames(), &[5]);
+    for (a, b) in samples[FRAME * 6..].iter().zip(&decoded[FRAME * 6..]) {
+        assert!((a - b).abs() <= 1.0 / 32767.0);
+    }
+
+    assert!(decoder.conceal_frame().is_err());
+}
@@ -0,0 +1,333 @@
+//! Trim / split / concat tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Lossless edits at and inside frame boundaries
+//! - Lossy edits keeping the MDCT preroll, sample-exact through the encoder delay
+//! - Joining separate lossy encodes by re-encoding the frames around the join
+//! - TOC, total_samples, CRC and length_ms of the results
+
+use libflo_audio::edit::{concat, split, trim};
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::{
+    decode, seeking, validate, Decoder, Encoder, FloMetadata, RandomAccessDecoder, Reader,
+    StreamingDecoder,
+};
+
+fn test_signal(sample_rate: u32, channels: usize, seconds: usize) -> Vec<f32> {
+    (0..sample_rate as usize * channels * seconds)
+        .map(|i| ((i / channels) as f32 * 0.01 + (i % channels) as f32).sin() * 0.5)
+        .collect()
+}
+
+fn metadata() -> Vec<u8> {
+    let mut meta = FloMetadata::new();
+    meta.title = Some("Edit test".to_string());
+    meta.length_ms = Some(3000);
+    meta.to_msgpack().unwrap()
+}
+
+fn length_ms(flo_data: &[u8]) -> Option<u64> {
+    let file = Reader::new().read(flo_data).unwrap();
+    FloMetadata::from_msgpack(&file.metadata).unwrap().length_ms
+}
+
+#[test]
+fn test_trim_lossless_inside_frames() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 2, 3);
+    let flo_data = Encoder::new(sample_rate, 2, 16)
+        .encode(&samples, &metadata())
+        .unwrap();
+    let reference = Decoder::new().decode(&flo_data).unwrap();
+
+    let trimmed = trim(&flo_data, 4000, 20000).unwrap();
+    let file = Reader::new().read(&trimmed).unwrap();
+
+    assert_eq!(file.header.total_samples, 16000);
+    assert_eq!(file.toc.len(), 3);
+    assert!(validate(&trimmed).unwrap());
+    assert_eq!(length_ms(&trimmed), Some(2000));
+
+    let decoded = Decoder::new().decode(&trimmed).unwrap();
+    assert_eq!(decoded, reference[4000 * 2..20000 * 2]);
+}
+
+#[test]
+fn test_trim_lossless_whole_frames_are_copied() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 1, 3);
+    let flo_data = Encoder::new(sample_rate, 1, 16)
+        .encode(&samples, &[])
+        .unwrap();
+
+    let trimmed = trim(&flo_data, 8000, 16000).unwrap();
+
+    let original = Reader::new().read(&flo_data).unwrap();
+    let file = Reader::new().read(&trimmed).unwrap();
+    assert_eq!(file.frames.len(), 1);
+    assert_eq!(
+        file.frames[0].channels[0].residuals,
+        original.frames[1].channels[0].residuals
+    );
+}
+
+#[test]
+fn test_trim_end_is_clamped() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 1, 2);
+    let flo_data = Encoder::new(sample_rate, 1, 16)
+        .encode(&samples, &[])
+        .unwrap();
+
+    let trimmed = trim(&flo_data, 12000, u64::MAX).unwrap();
+    assert_eq!(decode(&trimmed).unwrap().len(), 4000);
+
+    assert!(trim(&flo_data, 16000, 20000).is_err());
+    assert!(trim(&flo_data, 500, 500).is_err());
+}
+
+#[test]
+fn test_split_and_concat_lossless() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 1, 3);
+    let flo_data = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &metadata())
+        .unwrap();
+    let reference = decode(&flo_data).unwrap();
+
+    let parts = split(&flo_data, &[5000, 12000]).unwrap();
+    assert_eq!(parts.len(), 3);
+
+    let lengths: Vec<usize> = parts.iter().map(|p| decode(p).unwrap().len()).collect();
+    assert_eq!(lengths, vec![5000, 7000, 12000]);
+    for part in &parts {
+        assert!(validate(part).unwrap());
+        assert!(Reader::new().read(part).unwrap().header.has_frame_crc());
+    }
+
+    let refs: Vec<&[u8]> = parts.iter().map(|p| p.as_slice()).collect();
+    let joined = concat(&refs).unwrap();
+    assert_eq!(decode(&joined).unwrap(), reference);
+    assert_eq!(length_ms(&joined), Some(3000));
+    assert_eq!(
+        Reader::new().read(&joined).unwrap().header.total_samples,
+        24000
+    );
+}
+
+#[test]
+fn test_split_rejects_unordered_points() {
+    let flo_data = Encoder::new(8000, 1, 16)
+        .encode(&test_signal(8000, 1, 1), &[])
+        .unwrap();
+    assert!(split(&flo_data, &[4000, 2000]).is_err());
+}
+
+#[test]
+fn test_split_rejects_points_at_the_ends() {
+    let flo_data = Encoder::new(8000, 1, 16)
+        .encode(&test_signal(8000, 1, 1), &[])
+        .unwrap();
+
+    // a piece would be empty, the error names the point
+    let err = split(&flo_data, &[0, 4000]).unwrap_err();
+    assert!(err.contains("Split point 0"), "{}", err);
+    let err = split(&flo_data, &[4000, 8000]).unwrap_err();
+    assert!(err.contains("Split point 8000"), "{}", err);
+    let err = split(&flo_data, &[9000]).unwrap_err();
+    assert!(err.contains("Split point 9000"), "{}", err);
+
+    // just inside both ends is fine
+    let parts = split(&flo_data, &[1, 7999]).unwrap();
+    assert_eq!(parts.len(), 3);
+    assert_eq!(decode(&parts[0]).unwrap().len(), 1);
+    assert_eq!(decode(&parts[2]).unwrap().len(), 1);
+}
+
+#[test]
+fn test_concat_rejects_mismatched_parts() {
+    let a = Encoder::new(8000, 1, 16)
+        .encode(&test_signal(8000, 1, 1), &[])
+        .unwrap();
+    let b = Encoder::new(16000, 1, 16)
+        .encode(&test_signal(16000, 1, 1), &[])
+        .unwrap();
+    assert!(concat(&[&a, &b]).is_err());
+    assert!(concat(&[]).is_err());
+}
+
+#[test]
+fn test_trim_lossy_keeps_preroll() {
+    let sample_rate = 44100u32;
+    let samples = test_signal(sample_rate, 1, 1);
+    let flo_data = TransformEncoder::new(sample_rate, 1, 0.6)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+    let reference = decode(&flo_data).unwrap();
+
+    // the frames from 6 * 1024 are kept, the 100 samples before the start
+    // are encoder delay
+    let trimmed = trim(&flo_data, 6 * 1024 + 100, 20000).unwrap();
+    let header = Reader::new().read(&trimmed).unwrap().header;
+    assert_eq!(header.encoder_delay, 1024 + 100);
+
+    let decoded = decode(&trimmed).unwrap();
+    assert_eq!(decoded.len(), 20000 - 6 * 1024 - 100);
+    assert_eq!(decoded[0], reference[6 * 1024 + 100]);
+    assert_eq!(decoded, reference[6 * 1024 + 100..20000]);
+}
+
+#[test]
+fn test_trim_lossy_is_sample_exact_everywhere() {
+    let sample_rate = 44100u32;
+    let channels = 2;
+    let samples = test_signal(sample_rate, channels, 1);
+    let flo_data = TransformEncoder::new(sample_rate, channels as u8, 0.6)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+    let reference = decode(&flo_data).unwrap();
+
+    let (start, end) = (3 * 1024 + 517, 30001usize);
+    let expected = &reference[start * channels..end * channels];
+    let trimmed = trim(&flo_data, start as u64, end as u64).unwrap();
+    assert_eq!(decode(&trimmed).unwrap(), expected);
+
+    // trimming again stacks the lead-ins
+    let again = trim(&trimmed, 900, 5000).unwrap();
+    assert_eq!(
+        decode(&again).unwrap(),
+        expected[900 * channels..5000 * channels]
+    );
+
+    // every other way of decoding agrees
+    let range = seeking::decode_range(&trimmed, 10, 2000).unwrap();
+    assert_eq!(range, expected[10 * channels..2000 * channels]);
+    let frames: Vec<f32> = (0..Reader::new().read(&trimmed).unwrap().frames.len() as u32)
+        .flat_map(|i| seeking::decode_frame_at(&trimmed, i).unwrap())
+        .collect();
+    assert_eq!(frames, expected);
+
+    let mut random = RandomAccessDecoder::new(trimmed.clone()).unwrap();
+    assert_eq!(
+        random.read_at(0, 1500).unwrap(),
+        expected[..1500 * channels]
+    );
+
+    let mut streaming = StreamingDecoder::new();
+    streaming.feed(&trimmed).unwrap();
+    assert_eq!(streaming.decode_available().unwrap(), expected);
+
+    // seeking lands on the right sample
+    let seek = seeking::seek_to_time(&trimmed, 500).unwrap();
+    let frame = seeking::decode_frame_at(&trimmed, seek.frame_index).unwrap();
+    let sample = 500 * sample_rate as usize / 1000;
+    let at = seek.sample_offset as usize * channels;
+    assert_eq!(
+        frame[at..at + channels],
+        expected[sample * channels..(sample + 1) * channels]
+    );
+
+    // pieces split between hops still join seamlessly
+    let parts = split(&flo_data, &[start as u64]).unwrap();
+    assert_eq!(decode(&parts[1]).unwrap(), reference[start * channels..]);
+    let refs: Vec<&[u8]> = parts.iter().map(|p| p.as_slice()).collect();
+    assert_eq!(decode(&concat(&refs).unwrap()).unwrap(), reference);
+}
+
+#[test]
+fn test_split_and_concat_lossy() {
+    let sample_rate = 44100u32;
+    let samples = test_signal(sample_rate, 2, 1);
+    let flo_data = TransformEncoder::new(sample_rate, 2, 0.6)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+    let reference = decode(&flo_data).unwrap();
+
+    let parts = split(&flo_data, &[10 * 1024]).unwrap();
+    assert_eq!(parts.len(), 2);
+
+    // the first part plays exactly up to the split point
+    let first = decode(&parts[0]).unwrap();
+    assert_eq!(first, reference[..10 * 1024 * 2]);
+
+    // joining the pieces again is seamless
+    let refs: Vec<&[u8]> = parts.iter().map(|p| p.as_slice()).collect();
+    let joined = concat(&refs).unwrap();
+    assert_eq!(decode(&joined).unwrap(), reference);
+}
+
+#[test]
+fn test_concat_separate_lossy_files() {
+    let sample_rate = 44100u32;
+    let channels = 2;
+    let encode = |samples: &[f32]| {
+        TransformEncoder::new(sample_rate, channels as u8, 0.6)
+            .encode_to_flo(samples, &[])
+            .unwrap()
+    };
+    let tone = |hz: f32, len: usize| -> Vec<f32> {
+        (0..len * channels)
+            .map(|i| {
+                (2.0 * std::f32::consts::PI * hz * (i / channels) as f32 / 44100.0).sin() * 0.5
+            })
+            .collect()
+    };
+
+    // the second part's frames are kept when a ends on the hop grid, and
+    // re-encoded to move onto it otherwise
+    for (len_a, len_b) in [(20000, 15000), (10 * 1024, 9 * 1024), (5000, 300)] {
+        let a = encode(&tone(440.0, len_a));
+        let b = encode(&tone(660.0, len_b));
+        let joined = concat(&[&a, &b]).unwrap();
+        assert!(validate(&joined).unwrap());
+
+        let decoded = decode(&joined).unwrap();
+        assert_eq!(decoded.len(), (len_a + len_b) * channels);
+
+        // no silent gap around the seam, every 64-sample window carries the tones
+        let seam = len_a;
+        for window in (seam.saturating_sub(2048)..(seam + 2048).min(len_a + len_b - 64)).step_by(64)
+        {
+            let rms = (decoded[window * channels..(window + 64) * channels]
+                .iter()
+                .map(|s| s * s)
+                .sum::<f32>()
+                / (64 * channels) as f32)
+                .sqrt();
+            assert!(
+                rms > 0.2,
+                "gap at {} ({} + {}): rms {}",
+                window,
+                len_a,
+                len_b,
+                rms
+            );
+        }
+
+        // away from the join both parts sound as they did
+        let (ref_a, ref_b) = (decode(&a).unwrap(), decode(&b).unwrap());
+        let close = |x: &[f32], y: &[f32]| x.iter().zip(y).all(|(x, y)| (x - y).abs() < 0.05);
+        assert!(close(&decoded[..(len_a - 2048) * channels], &ref_a));
+        let skip = 2048.min(len_b);
+        assert!(close(
+            &decoded[(len_a + skip) * channels..],
+            &ref_b[skip * channels..]
+        ));
+    }
+
+    // more parts, one trimmed so it starts inside a hop
+    let a = encode(&tone(440.0, 20000));
+    let b = trim(&encode(&tone(660.0, 15000)), 517, 15000).unwrap();
+    let joined = concat(&[&a, &b, &a]).unwrap();
+    assert_eq!(
+        decode(&joined).unwrap().len(),
+        (20000 * 2 + 14483) * channels
+    );
+
+    // a single part is left alone
+    assert_eq!(
+        decode(&concat(&[&a]).unwrap()).unwrap(),
+        decode(&a).unwrap()
+    );
+}
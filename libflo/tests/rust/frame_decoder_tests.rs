@@ -0,0 +1,199 @@
+//! Shared frame decoder tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Every decoder giving the same samples (Decoder, StreamingDecoder,
+//!   decode_frame_at and FrameDecoder over borrowed frames)
+//! - Fixed predictors, LPC, mid-side and lossy preroll handling
+//! - Concealment agreeing between the file and streaming decoders
+
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::seeking::decode_frame_at;
+use libflo_audio::{
+    decode, decode_tolerant, Concealment, Decoder, Encoder, FrameDecoder, Reader, StreamingDecoder,
+};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn test_signal(channels: usize, seconds: usize) -> Vec<f32> {
+    (0..SAMPLE_RATE as usize * seconds)
+        .flat_map(|i| {
+            let t = i as f32;
+            let left = (t * 0.031).sin() * 0.4 + (t * 0.27).sin() * 0.05;
+            let right = left * 0.8 + (t * 0.011).cos() * 0.1;
+            [left, right].into_iter().take(channels)
+        })
+        .collect()
+}
+
+/// frame-by-frame output of each decoder, checked against `decode`
+fn assert_decoders_agree(flo_data: &[u8]) {
+    let reference = decode(flo_data).unwrap();
+
+    let owned = Reader::new().read(flo_data).unwrap();
+    assert_eq!(Decoder::new().decode_file(&owned).unwrap(), reference);
+
+    // borrowed frames through one FrameDecoder
+    let file = Reader::new().read_ref(flo_data).unwrap();
+    let mut frames = FrameDecoder::new(file.header.sample_rate, file.header.channels);
+    let mut per_frame: Vec<Vec<f32>> = file
+        .frames()
+        .map(|frame| frames.decode(&frame.unwrap()).unwrap())
+        .collect();
+    // a bare FrameDecoder doesn't know about the padding after the audio
+    if let Some(last) = per_frame.last_mut() {
+        let padding = file.header.padding as usize * file.header.channels as usize;
+        last.truncate(last.len() - padding);
+    }
+    assert_eq!(per_frame.concat(), reference);
+
+    // random access gives the same frames
+    for (index, expected) in per_frame.iter().enumerate() {
+        assert_eq!(
+            &decode_frame_at(flo_data, index as u32).unwrap(),
+            expected,
+            "frame {}",
+            index
+        );
+    }
+
+    // streaming, all at once and in small pieces
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(flo_data).unwrap();
+    let mut streamed = Vec::new();
+    while let Some(frame) = decoder.next_frame().unwrap() {
+        streamed.push(frame);
+    }
+    assert_eq!(streamed, per_frame);
+
+    let mut decoder = StreamingDecoder::new();
+    let mut chunked = Vec::new();
+    for chunk in flo_data.chunks(333) {
+        decoder.feed(chunk).unwrap();
+        chunked.extend(decoder.decode_available().unwrap());
+    }
+    assert_eq!(chunked, reference);
+}
+
+/// Flip a byte in the middle of a frame's data
+fn damage_frame(flo_data: &mut [u8], frame_index: usize) {
+    let file = Reader::new().read_ref(flo_data).unwrap();
+    let data_start = (4 + file.header.header_size + file.header.toc_size) as usize;
+    let entry = &file.toc[frame_index];
+    let pos = data_start + entry.byte_offset as usize + entry.frame_size as usize / 2;
+    flo_data[pos] ^= 0x5A;
+}
+
+#[test]
+fn test_lossless_mono_decoders_agree() {
+    // level 0 only uses fixed predictors, the others LPC
+    for level in [0, 5, 9] {
+        let flo_data = Encoder::new(SAMPLE_RATE, 1, 16)
+            .with_compression(level)
+            .encode(&test_signal(1, 3), &[])
+            .unwrap();
+        assert_decoders_agree(&flo_data);
+    }
+}
+
+#[test]
+fn test_lossless_stereo_decoders_agree() {
+    // correlated channels, so frames are coded mid-side
+    let samples = test_signal(2, 3);
+    let flo_data = Encoder::new(SAMPLE_RATE, 2, 16)
+        .encode(&samples, &[])
+        .unwrap();
+
+    let file = Reader::new().read(&flo_data).unwrap();
+    assert!(file.frames.iter().any(|f| f.flags & 0x01 != 0));
+    assert_decoders_agree(&flo_data);
+
+    // a partial last frame and silence
+    let mut samples = test_signal(2, 2);
+    samples.extend(vec![0.0; 2 * 1500]);
+    let flo_data = Encoder::new(SAMPLE_RATE, 2, 24)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+    assert_decoders_agree(&flo_data);
+}
+
+#[test]
+fn test_lossy_decoders_agree() {
+    let flo_data = TransformEncoder::new(44100, 2, 0.5)
+        .encode_to_flo(&test_signal(2, 4), &[])
+        .unwrap();
+    assert_decoders_agree(&flo_data);
+
+    // frame 0 only primes the overlap, everywhere
+    assert!(decode_frame_at(&flo_data, 0).unwrap().is_empty());
+
+    let mut encoder = TransformEncoder::new(44100, 1, 0.8).with_frame_crc(true);
+    let flo_data = encoder.encode_to_flo(&test_signal(1, 2), &[]).unwrap();
+    assert_decoders_agree(&flo_data);
+}
+
+#[test]
+fn test_frame_decoder_restart() {
+    let flo_data = TransformEncoder::new(44100, 2, 0.5)
+        .encode_to_flo(&test_signal(2, 4), &[])
+        .unwrap();
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let mut frames = FrameDecoder::new(file.header.sample_rate, file.header.channels);
+    let expected: Vec<Vec<f32>> = file
+        .frames()
+        .map(|frame| frames.decode(&frame.unwrap()).unwrap())
+        .collect();
+
+    // after a restart the next frame only primes
+    frames.restart();
+    assert!(frames.needs_priming());
+    assert!(frames.decode(&file.frame(7).unwrap()).unwrap().is_empty());
+    assert_eq!(frames.decode(&file.frame(8).unwrap()).unwrap(), expected[8]);
+
+    // or prime explicitly from the previous frame
+    frames.restart();
+    frames.prime(Some(&file.frame(19).unwrap()));
+    assert!(!frames.needs_priming());
+    assert_eq!(
+        frames.decode(&file.frame(20).unwrap()).unwrap(),
+        expected[20]
+    );
+
+    // without one the target plays cold instead of being dropped
+    frames.restart();
+    frames.prime(None);
+    assert_eq!(
+        frames.decode(&file.frame(20).unwrap()).unwrap().len(),
+        expected[20].len()
+    );
+}
+
+#[test]
+fn test_concealment_decoders_agree() {
+    let lossless = Encoder::new(SAMPLE_RATE, 2, 16)
+        .with_frame_crc(true)
+        .encode(&test_signal(2, 4), &[])
+        .unwrap();
+    let mut lossy = TransformEncoder::new(44100, 2, 0.5).with_frame_crc(true);
+    let lossy = lossy.encode_to_flo(&test_signal(2, 2), &[]).unwrap();
+
+    for (mut flo_data, damaged) in [(lossless, vec![0, 2]), (lossy, vec![0, 9, 10])] {
+        for &index in &damaged {
+            damage_frame(&mut flo_data, index);
+        }
+
+        for mode in [
+            Concealment::Silence,
+            Concealment::Interpolate,
+            Concealment::RepeatFade,
+            Concealment::Spectral,
+        ] {
+            let expected = decode_tolerant(&flo_data, mode).unwrap();
+
+            let mut decoder = StreamingDecoder::new().with_concealment(mode);
+            decoder.feed(&flo_data).unwrap();
+            assert_eq!(decoder.decode_available().unwrap(), expected);
+            assert_eq!(decoder.damaged_frames(), &damaged[..]);
+        }
+    }
+}
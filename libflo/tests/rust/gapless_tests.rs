@@ -0,0 +1,150 @@
+//! Gapless playback tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Encoder delay and padding in the header of lossy files
+//! - Every decode path stopping at the original sample count
+//! - Edited and split tracks decoding back to back without a gap
+
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::seeking::decode_frame_at;
+use libflo_audio::{
+    decode, edit, info, Decoder, Encoder, MetadataPlacement, Reader, StreamingDecoder,
+    StreamingEncoder,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const HOP: usize = 1024;
+
+fn stereo_signal(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .flat_map(|i| {
+            let t = i as f32;
+            [(t * 0.021).sin() * 0.5, (t * 0.013).cos() * 0.3]
+        })
+        .collect()
+}
+
+fn streaming_decode(flo_data: &[u8]) -> Vec<f32> {
+    let mut decoder = StreamingDecoder::new();
+    let mut samples = Vec::new();
+    for chunk in flo_data.chunks(777) {
+        decoder.feed(chunk).unwrap();
+        samples.extend(decoder.decode_available().unwrap());
+    }
+    samples
+}
+
+#[test]
+fn test_delay_and_padding_recorded() {
+    for length in [1, HOP - 1, HOP, HOP + 1, 44100 + 7] {
+        let flo_data = TransformEncoder::new(SAMPLE_RATE, 2, 0.6)
+            .encode_to_flo(&stereo_signal(length), &[])
+            .unwrap();
+        let header = Reader::new().read(&flo_data).unwrap().header;
+
+        assert!(header.has_gapless());
+        assert_eq!(header.encoder_delay as usize, HOP);
+        assert!((header.padding as usize) < HOP);
+        assert_eq!(header.gapless_length(), Some(length as u64));
+
+        let audio_info = info(&flo_data).unwrap();
+        assert_eq!(audio_info.padding, header.padding);
+        assert!((audio_info.duration_secs - length as f64 / SAMPLE_RATE as f64).abs() < 1e-9);
+    }
+
+    // lossless files have neither
+    let lossless = Encoder::new(SAMPLE_RATE, 2, 16)
+        .encode(&stereo_signal(5000), &[])
+        .unwrap();
+    assert!(!Reader::new().read(&lossless).unwrap().header.has_gapless());
+}
+
+#[test]
+fn test_every_decode_path_trims_padding() {
+    for length in [HOP + 1, 3 * HOP, 20_000 + 333] {
+        let samples = stereo_signal(length);
+        let flo_data = TransformEncoder::new(SAMPLE_RATE, 2, 0.6)
+            .with_frame_crc(true)
+            .encode_to_flo(&samples, &[])
+            .unwrap();
+
+        let decoded = decode(&flo_data).unwrap();
+        assert_eq!(decoded.len(), samples.len());
+        let tolerant = Decoder::new()
+            .with_concealment(Default::default())
+            .decode(&flo_data)
+            .unwrap();
+        assert_eq!(tolerant, decoded);
+
+        let frames = Reader::new().read(&flo_data).unwrap().frames.len();
+        let by_frame: Vec<f32> = (0..frames as u32)
+            .flat_map(|i| decode_frame_at(&flo_data, i).unwrap())
+            .collect();
+        assert_eq!(by_frame, decoded);
+
+        assert_eq!(streaming_decode(&flo_data), decoded);
+
+        // a seek close to the end stops in the same place
+        let mut decoder = StreamingDecoder::new();
+        decoder.feed(&flo_data).unwrap();
+        let target_ms = (length as u64 * 1000 / SAMPLE_RATE as u64 - 5) as u32;
+        decoder.seek_to_ms(target_ms).unwrap();
+        let target = target_ms as usize * SAMPLE_RATE as usize / 1000;
+        let rest = decoder.decode_available().unwrap();
+        assert_eq!(rest.len(), (length - target) * 2);
+        assert_eq!(rest, decoded[target * 2..]);
+    }
+}
+
+#[test]
+fn test_streaming_encoder_records_gapless() {
+    let samples = stereo_signal(30_000 + 11);
+    let expected = TransformEncoder::new(SAMPLE_RATE, 2, 0.6)
+        .with_metadata_placement(MetadataPlacement::Start)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.6)
+        .with_metadata_placement(MetadataPlacement::Start);
+    for block in samples.chunks(2 * 1000) {
+        encoder.push_samples(block).unwrap();
+    }
+    let flo_data = encoder.finalize(&[]).unwrap();
+    assert_eq!(flo_data, expected);
+
+    // low delay blocks pad to their own hop
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.6).with_low_latency(10.0);
+    encoder.push_samples(&samples).unwrap();
+    let flo_data = encoder.finalize(&[]).unwrap();
+    let header = Reader::new().read(&flo_data).unwrap().header;
+    assert_eq!(header.encoder_delay as usize, encoder.frame_samples());
+    assert_eq!(decode(&flo_data).unwrap().len(), samples.len());
+    assert_eq!(streaming_decode(&flo_data).len(), samples.len());
+}
+
+#[test]
+fn test_split_tracks_play_back_to_back() {
+    let length = 3 * SAMPLE_RATE as usize + 123;
+    let samples = stereo_signal(length);
+    let album = TransformEncoder::new(SAMPLE_RATE, 2, 0.8)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+    let whole = decode(&album).unwrap();
+
+    // a cut off the hop grid ends right where it was asked to
+    let intro = edit::trim(&album, 0, 50_001).unwrap();
+    assert_eq!(decode(&intro).unwrap(), whole[..2 * 50_001]);
+
+    // lossy cuts start on a hop, tracks split there play back to back
+    let points = [49 * HOP as u64, 98 * HOP as u64];
+    let tracks = edit::split(&album, &points).unwrap();
+    let lengths: Vec<usize> = tracks
+        .iter()
+        .map(|track| decode(track).unwrap().len() / 2)
+        .collect();
+    assert_eq!(lengths, vec![49 * HOP, 49 * HOP, length - 98 * HOP]);
+
+    let parts: Vec<&[u8]> = tracks.iter().map(Vec::as_slice).collect();
+    let joined = edit::concat(&parts).unwrap();
+    assert_eq!(decode(&joined).unwrap(), whole);
+}
@@ -0,0 +1,338 @@
+//! Header validation tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Header CRC (1.3+)
+//! - Version compatibility (older/newer minor, unknown major)
+//! - Sanity limits on stream parameters and chunk sizes
+//! - TOC entry limits counting the 1.4 sample positions
+//! - Early metadata and gapless fields in the header extension
+
+use libflo_audio::core::{toc_size, Header, TocEntry};
+use libflo_audio::{
+    compute_crc32, update_metadata_bytes, Decoder, Encoder, HeaderError, Reader, StreamingDecoder,
+    FLAG_EARLY_META, FLAG_GAPLESS, HEADER_SIZE, VERSION_MINOR,
+};
+
+fn encode_test_file() -> Vec<u8> {
+    let samples: Vec<f32> = (0..16000).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+    Encoder::new(8000, 1, 16).encode(&samples, &[]).unwrap()
+}
+
+/// Rewrite a current file as 1.2: no header crc, 66 byte header, TOC
+/// without the sample positions
+fn downgrade_to_1_2(flo_data: &[u8]) -> Vec<u8> {
+    let toc_size = u64::from_le_bytes(flo_data[38..46].try_into().unwrap()) as usize;
+    let toc = &flo_data[74..74 + toc_size];
+
+    let entries = u32::from_le_bytes(toc[..4].try_into().unwrap()) as usize;
+    let old_toc_size = 4 + entries * 20;
+
+    let mut old = Vec::with_capacity(flo_data.len() - 4);
+    old.extend_from_slice(&flo_data[..70]);
+    old.extend_from_slice(&toc[..old_toc_size]);
+    old.extend_from_slice(&flo_data[74 + toc_size..]);
+    old[5] = 2;
+    old[30..38].copy_from_slice(&66u64.to_le_bytes());
+    old[38..46].copy_from_slice(&(old_toc_size as u64).to_le_bytes());
+    old
+}
+
+fn reseal(flo_data: &mut [u8]) {
+    let header_size = u64::from_le_bytes(flo_data[30..38].try_into().unwrap()) as usize;
+    let mut covered = flo_data[4..70].to_vec();
+    covered.extend_from_slice(&flo_data[74..4 + header_size]);
+    let crc = compute_crc32(&covered);
+    flo_data[70..74].copy_from_slice(&crc.to_le_bytes());
+}
+
+fn header_with(f: impl FnOnce(&mut Header)) -> Vec<u8> {
+    let mut header = Header {
+        sample_rate: 44100,
+        channels: 2,
+        bit_depth: 16,
+        ..Header::default()
+    };
+    f(&mut header);
+    header.to_bytes()
+}
+
+#[test]
+fn test_header_crc_written() {
+    let flo_data = encode_test_file();
+    let header = Header::parse(&flo_data).unwrap();
+
+    assert_eq!(header.version_minor, VERSION_MINOR);
+    assert_eq!(header.header_size, HEADER_SIZE);
+    assert!(header.has_header_crc());
+    assert_ne!(header.header_crc32, 0);
+}
+
+#[test]
+fn test_header_crc_mismatch() {
+    let mut flo_data = encode_test_file();
+    flo_data[22] ^= 0x01; // total_samples
+
+    assert!(matches!(
+        Header::parse(&flo_data),
+        Err(HeaderError::CrcMismatch { .. })
+    ));
+
+    let err = Reader::new().read(&flo_data).unwrap_err();
+    assert!(err.contains("Header CRC"), "unexpected error: {}", err);
+
+    let mut decoder = StreamingDecoder::new();
+    assert!(decoder.feed(&flo_data).is_err());
+}
+
+#[test]
+fn test_reads_1_2_file() {
+    let flo_data = encode_test_file();
+    let old = downgrade_to_1_2(&flo_data);
+
+    let header = Header::parse(&old).unwrap();
+    assert_eq!(header.version_minor, 2);
+    assert!(!header.has_header_crc());
+
+    let expected = Decoder::new().decode(&flo_data).unwrap();
+    assert_eq!(Decoder::new().decode(&old).unwrap(), expected);
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&old).unwrap();
+    assert_eq!(decoder.decode_available().unwrap(), expected);
+
+    // sample positions come from the timestamps
+    let toc = Reader::new().read(&old).unwrap().toc;
+    let positions: Vec<u64> = toc.iter().map(|entry| entry.first_sample).collect();
+    assert_eq!(positions, vec![0, 8000]);
+}
+
+#[test]
+fn test_tolerates_newer_minor() {
+    let flo_data = encode_test_file();
+
+    // a future 1.9 that appends 8 bytes of its own to the header
+    let mut newer = Vec::new();
+    newer.extend_from_slice(&flo_data[..74]);
+    newer.extend_from_slice(&[0xAB; 8]);
+    newer.extend_from_slice(&flo_data[74..]);
+    newer[5] = 9;
+    newer[30..38].copy_from_slice(&(HEADER_SIZE + 8).to_le_bytes());
+    reseal(&mut newer);
+
+    let header = Header::parse(&newer).unwrap();
+    assert_eq!(header.version_minor, 9);
+
+    let expected = Decoder::new().decode(&flo_data).unwrap();
+    assert_eq!(Decoder::new().decode(&newer).unwrap(), expected);
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&newer).unwrap();
+    assert_eq!(decoder.decode_available().unwrap(), expected);
+
+    // the appended bytes are covered by the crc
+    newer[76] ^= 0xFF;
+    assert!(matches!(
+        Header::parse(&newer),
+        Err(HeaderError::CrcMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_rejects_unknown_major() {
+    let mut flo_data = encode_test_file();
+    flo_data[4] = 2;
+
+    assert_eq!(
+        Header::parse(&flo_data).unwrap_err(),
+        HeaderError::UnsupportedVersion {
+            major: 2,
+            minor: VERSION_MINOR
+        }
+    );
+    assert!(Reader::new().read(&flo_data).is_err());
+}
+
+#[test]
+fn test_rejects_bad_magic_and_short_header() {
+    let mut flo_data = encode_test_file();
+
+    assert!(matches!(
+        Header::parse(&flo_data[..40]),
+        Err(HeaderError::TooShort { .. })
+    ));
+
+    flo_data[0] = b'X';
+    assert_eq!(Header::parse(&flo_data).unwrap_err(), HeaderError::BadMagic);
+}
+
+#[test]
+fn test_sanity_limits() {
+    let cases: Vec<(Vec<u8>, HeaderError)> = vec![
+        (
+            header_with(|h| h.sample_rate = 0),
+            HeaderError::InvalidSampleRate(0),
+        ),
+        (
+            header_with(|h| h.sample_rate = 5_000_000),
+            HeaderError::InvalidSampleRate(5_000_000),
+        ),
+        (
+            header_with(|h| h.channels = 0),
+            HeaderError::InvalidChannels(0),
+        ),
+        (
+            header_with(|h| h.channels = 200),
+            HeaderError::InvalidChannels(200),
+        ),
+        (
+            header_with(|h| h.bit_depth = 12),
+            HeaderError::InvalidBitDepth(12),
+        ),
+        (
+            header_with(|h| h.toc_size = 7),
+            HeaderError::InvalidTocSize(7),
+        ),
+        (
+            header_with(|h| h.toc_size = 4 + 20 * 1_000_000),
+            HeaderError::InvalidTocSize(4 + 20 * 1_000_000),
+        ),
+        (
+            header_with(|h| {
+                h.data_size = u64::MAX - 10;
+                h.meta_size = 100;
+            }),
+            HeaderError::ChunkSizeOverflow,
+        ),
+        (
+            header_with(|h| {
+                h.flags |= FLAG_EARLY_META;
+                h.data_size = 10;
+                h.early_meta_size = 11;
+            }),
+            HeaderError::InvalidEarlyMetaSize(11),
+        ),
+        (
+            header_with(|h| {
+                h.flags |= FLAG_GAPLESS;
+                h.total_samples = 3000;
+                h.encoder_delay = 1024;
+                h.padding = 2000;
+            }),
+            HeaderError::InvalidGapless {
+                encoder_delay: 1024,
+                padding: 2000,
+            },
+        ),
+    ];
+
+    for (bytes, expected) in cases {
+        assert_eq!(Header::parse(&bytes).unwrap_err(), expected);
+    }
+}
+
+#[test]
+fn test_toc_size_limits() {
+    let with_toc = |entries: usize, total_samples: u64| {
+        header_with(|h| {
+            h.toc_size = toc_size(entries);
+            h.total_samples = total_samples;
+        })
+    };
+
+    // the sample positions count towards the size, not the entries
+    let header = Header::parse(&with_toc(90_000, 0)).unwrap();
+    assert_eq!(header.toc_size_for(90_000), header.toc_size);
+    assert_eq!(
+        Header::parse(&with_toc(100_001, 0)).unwrap_err(),
+        HeaderError::InvalidTocSize(toc_size(100_001))
+    );
+
+    // long streams of 2.5 ms frames may have more
+    assert!(Header::parse(&with_toc(200_000, 200_000 * 110)).is_ok());
+
+    // the entries have to fit the TOC, sample positions included
+    let header = Header::parse(&with_toc(1, 0)).unwrap();
+    let mut toc = vec![0u8; toc_size(1) as usize];
+    toc[0] = 1;
+    assert_eq!(TocEntry::parse_all(&toc, &header).unwrap().len(), 1);
+    toc[0] = 2;
+    assert!(TocEntry::parse_all(&toc, &header).is_err());
+    toc[0] = 1;
+    assert!(TocEntry::parse_all(&toc[..24], &header).is_err());
+
+    // a table a newer version appends after them is skipped
+    toc.extend_from_slice(&[0xAB; 20]);
+    assert_eq!(TocEntry::parse_all(&toc, &header).unwrap().len(), 1);
+}
+
+#[test]
+fn test_chunk_past_end() {
+    let flo_data = encode_test_file();
+    let truncated = &flo_data[..flo_data.len() - 10];
+
+    let header = Header::parse(truncated).unwrap();
+    assert!(matches!(
+        header.check_file_size(truncated.len() as u64),
+        Err(HeaderError::ChunkPastEnd { chunk: "DATA", .. })
+    ));
+
+    let err = Reader::new().read(truncated).unwrap_err();
+    assert!(err.contains("DATA chunk"), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_update_metadata_keeps_header_valid() {
+    let flo_data = encode_test_file();
+    let updated = update_metadata_bytes(&flo_data, b"\x80").unwrap();
+
+    let header = Header::parse(&updated).unwrap();
+    assert_eq!(header.meta_size, 1);
+    assert!(Reader::new().read(&updated).is_ok());
+}
+
+#[test]
+fn test_early_meta_header_extension() {
+    let bytes = header_with(|h| {
+        h.flags |= FLAG_EARLY_META;
+        h.data_size = 100;
+        h.early_meta_size = 40;
+    });
+    assert_eq!(bytes.len() as u64, 4 + HEADER_SIZE + 8);
+
+    let header = Header::parse(&bytes).unwrap();
+    assert_eq!(header.header_size, HEADER_SIZE + 8);
+    assert_eq!(header.early_meta_size, 40);
+
+    // the flag needs room for the field
+    let mut short = bytes[..4 + HEADER_SIZE as usize].to_vec();
+    short[30..38].copy_from_slice(&HEADER_SIZE.to_le_bytes());
+    reseal(&mut short);
+    assert_eq!(
+        Header::parse(&short).unwrap_err(),
+        HeaderError::InvalidHeaderSize(HEADER_SIZE)
+    );
+}
+
+#[test]
+fn test_gapless_header_extension() {
+    let bytes = header_with(|h| {
+        h.flags |= FLAG_EARLY_META | FLAG_GAPLESS;
+        h.data_size = 100;
+        h.early_meta_size = 40;
+        h.total_samples = 10 * 1024;
+        h.encoder_delay = 1024;
+        h.padding = 500;
+    });
+    assert_eq!(bytes.len() as u64, 4 + HEADER_SIZE + 16);
+
+    // the gapless fields follow the early metadata size
+    let header = Header::parse(&bytes).unwrap();
+    assert_eq!(header.early_meta_size, 40);
+    assert_eq!(header.encoder_delay, 1024);
+    assert_eq!(header.padding, 500);
+    assert_eq!(header.gapless_length(), Some(9 * 1024 - 500));
+
+    // without the flag the fields aren't there and the length isn't known
+    let plain = Header::parse(&header_with(|h| h.total_samples = 10 * 1024)).unwrap();
+    assert_eq!(plain.gapless_length(), None);
+}
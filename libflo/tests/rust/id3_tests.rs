@@ -0,0 +1,386 @@
+//! ID3 tag tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Round trips through ID3v2.4 and ID3v2.3
+//! - v2.3 tags: UTF-16, split dates, genre references, whole-tag unsynchronisation
+//! - v2.4 tags: multi-value frames, per-frame unsynchronisation, plain frame sizes
+//! - Tag sizes, bad tags and merging into existing metadata
+
+use libflo_audio::{
+    id3_tag_size, Comment, FloMetadata, Id3Version, Lyrics, Picture, PictureType, Popularimeter,
+    SyncedLyrics, SyncedLyricsContentType, SyncedLyricsLine, UserText, UserUrl, WaveformData,
+};
+
+fn syncsafe(size: usize) -> [u8; 4] {
+    let size = size as u32;
+    [
+        (size >> 21) as u8 & 0x7F,
+        (size >> 14) as u8 & 0x7F,
+        (size >> 7) as u8 & 0x7F,
+        size as u8 & 0x7F,
+    ]
+}
+
+fn tag(major: u8, flags: u8, body: &[u8]) -> Vec<u8> {
+    let mut tag = vec![b'I', b'D', b'3', major, 0, flags];
+    tag.extend_from_slice(&syncsafe(body.len()));
+    tag.extend_from_slice(body);
+    tag
+}
+
+fn frame(id: &str, size: [u8; 4], flags: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = id.as_bytes().to_vec();
+    frame.extend_from_slice(&size);
+    frame.extend_from_slice(&[0, flags]);
+    frame.extend_from_slice(data);
+    frame
+}
+
+fn v23_frame(id: &str, data: &[u8]) -> Vec<u8> {
+    frame(id, (data.len() as u32).to_be_bytes(), 0, data)
+}
+
+fn v24_frame(id: &str, data: &[u8]) -> Vec<u8> {
+    frame(id, syncsafe(data.len()), 0, data)
+}
+
+fn utf16(text: &str) -> Vec<u8> {
+    let mut data = vec![0xFF, 0xFE];
+    data.extend(text.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+    data
+}
+
+fn unsync(data: &[u8]) -> Vec<u8> {
+    data.iter()
+        .flat_map(|&b| if b == 0xFF { vec![0xFF, 0x00] } else { vec![b] })
+        .collect()
+}
+
+/// a JPEG-ish picture with false sync patterns in it
+fn picture_data() -> Vec<u8> {
+    vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0xFF, 0x00, 0xFF]
+}
+
+fn full_metadata() -> FloMetadata {
+    let mut meta = FloMetadata::with_basic(
+        Some("Título ✓".to_string()),
+        Some("AC/DC".to_string()),
+        Some("Album".to_string()),
+    );
+    meta.subtitle = Some("Live".to_string());
+    meta.content_group = Some("Group".to_string());
+    meta.set_subtitle = Some("Disc One".to_string());
+    meta.isrc = Some("USXXX2600001".to_string());
+    meta.album_artist = Some("Various".to_string());
+    meta.composer = Some("Комп".to_string());
+    meta.mood = Some("Calm".to_string());
+    meta.produced_notice = Some("2026 Label".to_string());
+    meta.release_time = Some("2026-02-01".to_string());
+    meta.original_release_time = Some("1979".to_string());
+    meta.tagging_time = Some("2026-10-18T12:00:00".to_string());
+    meta.recording_time = Some("2025-11-03T09:41".to_string());
+    meta.year = Some(2025);
+    meta.track_number = Some(3);
+    meta.track_total = Some(12);
+    meta.disc_number = Some(1);
+    meta.genre = Some("(Rock)".to_string());
+    meta.bpm = Some(128);
+    meta.length_ms = Some(215_000);
+    meta.playlist_delay = Some(20);
+    meta.involved_people = Some(vec![("producer".to_string(), "Someone".to_string())]);
+    meta.musician_credits = Some(vec![("bass".to_string(), "Someone Else".to_string())]);
+    meta.url_artist = Some("https://example.com/artist".to_string());
+    meta.user_urls = vec![UserUrl {
+        description: "shop".to_string(),
+        url: "https://example.com/shop".to_string(),
+    }];
+    meta.user_text = vec![UserText {
+        description: "CATALOGNUMBER".to_string(),
+        value: "CAT-001".to_string(),
+    }];
+    meta.comments = vec![
+        Comment {
+            language: Some("eng".to_string()),
+            description: None,
+            text: "First".to_string(),
+        },
+        Comment {
+            language: None,
+            description: Some("note".to_string()),
+            text: "Zweite Ü".to_string(),
+        },
+    ];
+    meta.lyrics = vec![Lyrics {
+        language: Some("jpn".to_string()),
+        description: None,
+        text: "歌詞\nline two".to_string(),
+    }];
+    meta.synced_lyrics = vec![SyncedLyrics {
+        language: Some("eng".to_string()),
+        content_type: SyncedLyricsContentType::Chord,
+        description: Some("chords".to_string()),
+        lines: vec![
+            SyncedLyricsLine {
+                timestamp_ms: 0,
+                text: "Am".to_string(),
+            },
+            SyncedLyricsLine {
+                timestamp_ms: 1500,
+                text: "F♯m".to_string(),
+            },
+        ],
+    }];
+    meta.pictures = vec![
+        Picture {
+            mime_type: "image/jpeg".to_string(),
+            picture_type: PictureType::CoverFront,
+            description: None,
+            data: picture_data(),
+        },
+        Picture {
+            mime_type: "image/png".to_string(),
+            picture_type: PictureType::BrightColouredFish,
+            description: Some("🐟".to_string()),
+            data: vec![1, 2, 3],
+        },
+    ];
+    meta.integrated_loudness_lufs = Some(-11.5);
+    meta.track_gain_db = Some(-6.5);
+    meta.album_loudness_lufs = Some(-10.75);
+    meta.album_gain_db = Some(-7.25);
+    meta.play_count = Some(5_000_000_000);
+    meta.popularimeter = Some(Popularimeter {
+        email: Some("me@example.com".to_string()),
+        rating: 196,
+        play_count: Some(42),
+    });
+    meta
+}
+
+fn json(meta: &FloMetadata) -> serde_json::Value {
+    serde_json::to_value(meta).unwrap()
+}
+
+#[test]
+fn test_id3_round_trip() {
+    let meta = full_metadata();
+
+    let v24 = meta.to_id3(Id3Version::V24).unwrap();
+    assert_eq!(&v24[..4], b"ID3\x04");
+    assert_eq!(id3_tag_size(&v24), Some(v24.len()));
+    assert_eq!(json(&FloMetadata::from_id3(&v24).unwrap()), json(&meta));
+
+    // v2.3 has one list for all the people
+    let v23 = meta.to_id3(Id3Version::V23).unwrap();
+    assert_eq!(&v23[..4], b"ID3\x03");
+    let mut expected = meta.clone();
+    let mut people = expected.involved_people.take().unwrap();
+    people.extend(expected.musician_credits.take().unwrap());
+    expected.involved_people = Some(people);
+    assert_eq!(json(&FloMetadata::from_id3(&v23).unwrap()), json(&expected));
+
+    // v2.3 has no TDRC, TMOO and friends go to TXXX
+    let has = |tag: &[u8], id: &[u8]| tag.windows(id.len()).any(|w| w == id);
+    assert!(has(&v23, b"TYER") && has(&v23, b"TDAT") && has(&v23, b"TIME"));
+    assert!(!has(&v23, b"TDRC") && has(&v23, b"TXXX\0\0\0\x0a\0\0\0TMOO\0Calm"));
+
+    // nothing to write is an empty tag
+    let empty = FloMetadata::new().to_id3(Id3Version::V24).unwrap();
+    assert_eq!(empty.len(), 10);
+    assert!(FloMetadata::from_id3(&empty).unwrap().is_empty());
+}
+
+#[test]
+fn test_id3_v23_tag() {
+    let mut comm = vec![1];
+    comm.extend_from_slice(b"deu");
+    comm.extend(utf16("kurz"));
+    comm.extend_from_slice(&[0, 0]);
+    comm.extend(utf16("Grüße ✓"));
+
+    let mut apic = b"\0image/jpeg\0\x03\0".to_vec();
+    apic.extend(picture_data());
+
+    let frames = [
+        v23_frame("TIT2", &[&[1][..], &utf16("Ünïcødé ✓"), &[0, 0]].concat()),
+        v23_frame("TPE1", b"\0Artist One/Artist Two"),
+        v23_frame("TYER", b"\x001999"),
+        v23_frame("TDAT", b"\x000503"),
+        v23_frame("TIME", b"\x001430"),
+        v23_frame("TCON", b"\0(4)(17)Eurodisco"),
+        v23_frame("TRCK", b"\x003/12"),
+        v23_frame("TPOS", b"\x002"),
+        v23_frame("TBPM", b"\x00120.6"),
+        v23_frame("IPLS", b"\0producer\0Someone\0mix\0Other\0"),
+        v23_frame("TXXX", b"\0TMOO\0Calm"),
+        v23_frame("TXXX", b"\0replaygain_track_gain\0-6.50 dB"),
+        v23_frame("TXXX", b"\0REPLAYGAIN_ALBUM_PEAK\x000.5"),
+        v23_frame("COMM", &comm),
+        v23_frame("APIC", &apic),
+        v23_frame("PCNT", &[0, 0, 1, 0]),
+        // frames flo™ has no field for are skipped
+        v23_frame("MCDI", &[1, 2, 3]),
+    ]
+    .concat();
+
+    // padding after the frames, everything unsynchronised
+    let mut body = unsync(&frames);
+    body.extend_from_slice(&[0; 32]);
+    let meta = FloMetadata::from_id3(&tag(3, 0x80, &body)).unwrap();
+
+    assert_eq!(meta.title.as_deref(), Some("Ünïcødé ✓"));
+    assert_eq!(meta.artist.as_deref(), Some("Artist One/Artist Two"));
+    assert_eq!(meta.year, Some(1999));
+    assert_eq!(meta.recording_time.as_deref(), Some("1999-03-05T14:30"));
+    assert_eq!(meta.genre.as_deref(), Some("Disco/Rock/Eurodisco"));
+    assert_eq!((meta.track_number, meta.track_total), (Some(3), Some(12)));
+    assert_eq!((meta.disc_number, meta.disc_total), (Some(2), None));
+    assert_eq!(meta.bpm, Some(121));
+    assert_eq!(meta.mood.as_deref(), Some("Calm"));
+    // ReplayGain TXXX frames, any case
+    assert_eq!(meta.track_gain_db, Some(-6.5));
+    assert_eq!(meta.integrated_loudness_lufs, Some(-11.5));
+    assert!((meta.album_true_peak_dbtp.unwrap() + 6.02).abs() < 0.01);
+    assert!(meta.user_text.is_empty());
+    assert_eq!(
+        meta.involved_people,
+        Some(vec![
+            ("producer".to_string(), "Someone".to_string()),
+            ("mix".to_string(), "Other".to_string()),
+        ])
+    );
+    assert_eq!(meta.comments.len(), 1);
+    assert_eq!(meta.comments[0].language.as_deref(), Some("deu"));
+    assert_eq!(meta.comments[0].description.as_deref(), Some("kurz"));
+    assert_eq!(meta.comments[0].text, "Grüße ✓");
+    assert_eq!(meta.pictures.len(), 1);
+    assert_eq!(meta.pictures[0].picture_type, PictureType::CoverFront);
+    assert_eq!(meta.pictures[0].data, picture_data());
+    assert_eq!(meta.play_count, Some(256));
+
+    // four bytes that aren't four digits are left out
+    let odd_date = |tdat: &str, time: &str| {
+        let frames = [
+            v23_frame("TYER", b"\x001999"),
+            v23_frame("TDAT", format!("\x03{}", tdat).as_bytes()),
+            v23_frame("TIME", format!("\x03{}", time).as_bytes()),
+        ]
+        .concat();
+        FloMetadata::from_id3(&tag(3, 0, &frames)).unwrap()
+    };
+    let meta = odd_date("1é2", "1430");
+    assert_eq!(meta.year, Some(1999));
+    assert_eq!(meta.recording_time, None);
+    let meta = odd_date("0503", "1é2");
+    assert_eq!(meta.recording_time.as_deref(), Some("1999-03-05"));
+}
+
+#[test]
+fn test_id3_v24_tag() {
+    // unsynchronised, with a data length indicator
+    let mut apic = b"\x03image/png\0\x04back\0".to_vec();
+    apic.extend(picture_data());
+    let mut apic_frame = syncsafe(apic.len()).to_vec();
+    apic_frame.extend(unsync(&apic));
+
+    let mut comm = vec![2];
+    comm.extend_from_slice(b"eng\0\0");
+    comm.extend("big endian".encode_utf16().flat_map(|u| u.to_be_bytes()));
+
+    // some writers put plain sizes in v2.4 tags
+    let long_title = "x".repeat(256);
+    let mut tit3 = vec![0];
+    tit3.extend_from_slice(long_title.as_bytes());
+
+    let body = [
+        // extended header
+        &[0, 0, 0, 6, 1, 0][..],
+        &v24_frame("TPE1", b"\x03Artist One\0Artist Two\0"),
+        &v24_frame("TCON", b"\x0317\0Electronic\0RX"),
+        &v24_frame("TMCL", b"\x03guitar\0Someone\0drums\0Other"),
+        &v24_frame("TDRC", b"\x032024"),
+        &frame("APIC", syncsafe(apic_frame.len()), 0x03, &apic_frame),
+        &v24_frame("COMM", &comm),
+        &frame("TIT3", (tit3.len() as u32).to_be_bytes(), 0, &tit3),
+        &v24_frame("TALB", b"\x03Album"),
+        &v24_frame("POPM", b"\0\xff"),
+    ]
+    .concat();
+    let meta = FloMetadata::from_id3(&tag(4, 0x40, &body)).unwrap();
+
+    assert_eq!(meta.artist.as_deref(), Some("Artist One/Artist Two"));
+    assert_eq!(meta.genre.as_deref(), Some("Rock/Electronic/Remix"));
+    assert_eq!(
+        meta.musician_credits,
+        Some(vec![
+            ("guitar".to_string(), "Someone".to_string()),
+            ("drums".to_string(), "Other".to_string()),
+        ])
+    );
+    assert_eq!(meta.year, Some(2024));
+    assert_eq!(meta.recording_time, None);
+    assert_eq!(meta.pictures[0].picture_type, PictureType::CoverBack);
+    assert_eq!(meta.pictures[0].description.as_deref(), Some("back"));
+    assert_eq!(meta.pictures[0].data, picture_data());
+    assert_eq!(meta.comments[0].language.as_deref(), Some("eng"));
+    assert_eq!(meta.comments[0].description, None);
+    assert_eq!(meta.comments[0].text, "big endian");
+    assert_eq!(meta.subtitle.as_deref(), Some(long_title.as_str()));
+    assert_eq!(meta.album.as_deref(), Some("Album"));
+    let popm = meta.popularimeter.unwrap();
+    assert_eq!(
+        (popm.email, popm.rating, popm.play_count),
+        (None, 255, None)
+    );
+}
+
+#[test]
+fn test_id3_tag_size_and_merge() {
+    let mut meta = FloMetadata::with_basic(Some("Title".to_string()), None, None);
+    meta.add_comment("tag comment", None);
+    let id3 = meta.to_id3(Id3Version::V24).unwrap();
+
+    // an MP3 file: the tag, then the audio
+    let mut mp3 = id3.clone();
+    mp3.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+    assert_eq!(id3_tag_size(&mp3), Some(id3.len()));
+    assert_eq!(
+        FloMetadata::from_id3(&mp3).unwrap().title.as_deref(),
+        Some("Title")
+    );
+    assert_eq!(id3_tag_size(&mp3[id3.len()..]), None);
+
+    // fields the tag has replace, the rest stay
+    let mut existing =
+        FloMetadata::with_basic(Some("Old".to_string()), Some("Artist".to_string()), None);
+    existing.add_comment("old comment", None);
+    existing.waveform_data = Some(WaveformData {
+        peaks_per_second: 10,
+        peaks: vec![0.5; 10],
+        channels: 1,
+    });
+    existing.apply_id3(&id3).unwrap();
+    assert_eq!(existing.title.as_deref(), Some("Title"));
+    assert_eq!(existing.artist.as_deref(), Some("Artist"));
+    assert_eq!(existing.comments.len(), 1);
+    assert_eq!(existing.comments[0].text, "tag comment");
+    assert!(existing.waveform_data.is_some());
+
+    assert!(FloMetadata::from_id3(b"fLaC\0\0\0\0\0\0").is_err());
+    assert!(FloMetadata::from_id3(&tag(2, 0, &[])).is_err());
+    assert!(FloMetadata::from_id3(&id3[..id3.len() - 1]).is_err());
+
+    // sizes running past the tag stop parsing instead of wrapping around
+    let title = v23_frame("TIT2", b"\0Title");
+    let huge_extended = [&[0xFF; 4][..], &title].concat();
+    let meta = FloMetadata::from_id3(&tag(3, 0x40, &huge_extended)).unwrap();
+    assert_eq!(meta.title, None);
+    let huge_frame = [
+        v24_frame("TALB", b"\x03Album"),
+        frame("TIT2", [0xFF; 4], 0, b"\x03x"),
+    ]
+    .concat();
+    let meta = FloMetadata::from_id3(&tag(4, 0, &huge_frame)).unwrap();
+    assert_eq!(meta.album.as_deref(), Some("Album"));
+    assert_eq!(meta.title, None);
+}
@@ -0,0 +1,309 @@
+//! Per-frame integrity tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Frame CRCs written by the encoders
+//! - Locating damaged frames
+//! - Tolerant decoding with silence / interpolation
+//! - Recovery from TOC entries pointing past the end of the file
+//! - Damaged frames in the streaming decoder (files and live streams)
+
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::{
+    decode, decode_tolerant, find_damaged_frames, validate, Concealment, Decoder, Encoder, Reader,
+    StreamingDecoder, StreamingEncoder, FLAG_FRAME_CRC,
+};
+
+fn test_signal(sample_rate: u32, seconds: usize) -> Vec<f32> {
+    (0..sample_rate as usize * seconds)
+        .map(|i| (i as f32 * 0.01).sin() * 0.5)
+        .collect()
+}
+
+/// Flip a byte in the middle of a frame's data
+fn damage_frame(flo_data: &mut [u8], frame_index: usize) {
+    let file = Reader::new().read(flo_data).unwrap();
+    let data_start = (4 + file.header.header_size + file.header.toc_size) as usize;
+    let entry = &file.toc[frame_index];
+    let pos = data_start + entry.byte_offset as usize + entry.frame_size as usize / 2;
+    flo_data[pos] ^= 0x5A;
+}
+
+#[test]
+fn test_frame_crc_roundtrip() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 3);
+
+    let plain = Encoder::new(sample_rate, 1, 16)
+        .encode(&samples, &[])
+        .unwrap();
+    let with_crc = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+
+    let file = Reader::new().read(&with_crc).unwrap();
+    assert!(file.header.has_frame_crc());
+    assert_eq!(file.header.flags & FLAG_FRAME_CRC, FLAG_FRAME_CRC);
+    assert_eq!(with_crc.len(), plain.len() + 3 * 4);
+
+    assert!(validate(&with_crc).unwrap());
+    assert!(find_damaged_frames(&with_crc).unwrap().is_empty());
+    assert_eq!(
+        Decoder::new().decode(&with_crc).unwrap(),
+        Decoder::new().decode(&plain).unwrap()
+    );
+}
+
+#[test]
+fn test_damaged_frame_is_located() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 4);
+
+    let mut flo_data = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+    damage_frame(&mut flo_data, 2);
+
+    assert!(!validate(&flo_data).unwrap());
+    assert_eq!(find_damaged_frames(&flo_data).unwrap(), vec![2]);
+
+    let err = Decoder::new().decode(&flo_data).unwrap_err();
+    assert!(err.contains("Frame 2"), "unexpected error: {}", err);
+}
+
+#[test]
+fn test_tolerant_decode_silence() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 4);
+
+    let clean = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+    let reference = decode(&clean).unwrap();
+
+    let mut damaged = clean.clone();
+    damage_frame(&mut damaged, 1);
+
+    let decoded = decode_tolerant(&damaged, Concealment::Silence).unwrap();
+    assert_eq!(decoded.len(), reference.len());
+
+    let frame = sample_rate as usize;
+    assert!(decoded[frame..frame * 2].iter().all(|&s| s == 0.0));
+    assert_eq!(decoded[..frame], reference[..frame]);
+    assert_eq!(decoded[frame * 2..], reference[frame * 2..]);
+}
+
+#[test]
+fn test_decoder_conceals_several_frames() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 5);
+
+    let clean = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+    let reference = decode(&clean).unwrap();
+
+    // damage_frame needs a readable file, so damage copies and combine them
+    let mut damaged = clean.clone();
+    damage_frame(&mut damaged, 1);
+    let mut third = clean.clone();
+    damage_frame(&mut third, 3);
+    for (i, &byte) in third.iter().enumerate() {
+        if byte != clean[i] {
+            damaged[i] = byte;
+        }
+    }
+
+    // still a plain unit struct
+    let decoded = Decoder
+        .with_concealment(Concealment::Silence)
+        .decode(&damaged)
+        .unwrap();
+    assert_eq!(decoded.len(), reference.len());
+
+    let frame = sample_rate as usize;
+    for (index, expected) in reference.chunks(frame).enumerate() {
+        let got = &decoded[index * frame..(index + 1) * frame];
+        if index == 1 || index == 3 {
+            assert!(got.iter().all(|&s| s == 0.0));
+        } else {
+            assert_eq!(got, expected);
+        }
+    }
+}
+
+#[test]
+fn test_tolerant_decode_interpolate() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 3);
+
+    let mut flo_data = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+    damage_frame(&mut flo_data, 1);
+
+    let decoded = decode_tolerant(&flo_data, Concealment::Interpolate).unwrap();
+    let frame = sample_rate as usize;
+    assert_eq!(decoded.len(), frame * 3);
+
+    // the gap is a straight line between its neighbours
+    let before = decoded[frame - 1];
+    let after = decoded[frame * 2];
+    let step = (after - before) / (frame + 1) as f32;
+    for i in 0..frame {
+        let expected = before + step * (i + 1) as f32;
+        assert!((decoded[frame + i] - expected).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn test_tolerant_decode_lossy() {
+    let sample_rate = 44100u32;
+    let samples = test_signal(sample_rate, 1);
+
+    let mut encoder = TransformEncoder::new(sample_rate, 1, 0.6).with_frame_crc(true);
+    let clean = encoder.encode_to_flo(&samples, &[]).unwrap();
+    let reference = decode(&clean).unwrap();
+
+    let mut damaged = clean.clone();
+    damage_frame(&mut damaged, 10);
+
+    assert_eq!(find_damaged_frames(&damaged).unwrap(), vec![10]);
+    assert!(Reader::new().read(&damaged).is_err());
+
+    for mode in [Concealment::Silence, Concealment::Interpolate] {
+        let decoded = decode_tolerant(&damaged, mode).unwrap();
+        assert_eq!(decoded.len(), reference.len());
+    }
+}
+
+#[test]
+fn test_recovery_with_out_of_range_toc() {
+    let sample_rate = 8000u32;
+    let clean = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&test_signal(sample_rate, 4), &[])
+        .unwrap();
+    let header = Reader::new().read(&clean).unwrap().header;
+    let entry = |index: usize| (4 + header.header_size) as usize + 4 + index * 20;
+
+    // frame 1 claims to be 4 GB long
+    let mut too_long = clean.clone();
+    let size = entry(1) + 12;
+    too_long[size..size + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Reader::new().read(&too_long).is_err());
+    let file = Reader::new().with_recovery(true).read(&too_long).unwrap();
+    assert_eq!(file.damaged_frames, vec![1]);
+    assert_eq!(file.frames.len(), 4);
+
+    // frame 2 starts past anything addressable
+    let mut too_far = clean.clone();
+    let offset = entry(2) + 4;
+    too_far[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let err = Reader::new().read(&too_far).unwrap_err();
+    assert!(err.contains("Frame 2"), "unexpected error: {}", err);
+    let file = Reader::new().with_recovery(true).read(&too_far).unwrap();
+    assert_eq!(file.damaged_frames, vec![2]);
+    assert_eq!(file.frames.len(), 4);
+
+    // a cut off DATA chunk loses every frame from the cut on, and concealment
+    // fills them in
+    let mut cut_off = clean.clone();
+    for index in 2..4 {
+        let offset = entry(index) + 4;
+        let past_end = header.data_size + index as u64;
+        cut_off[offset..offset + 8].copy_from_slice(&past_end.to_le_bytes());
+    }
+    assert!(!validate(&cut_off).unwrap());
+    assert_eq!(find_damaged_frames(&cut_off).unwrap(), vec![2, 3]);
+    let decoded = decode_tolerant(&cut_off, Concealment::Silence).unwrap();
+    assert_eq!(decoded.len(), decode(&clean).unwrap().len());
+}
+
+#[test]
+fn test_streaming_encoder_frame_crc() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 2);
+
+    let mut encoder = StreamingEncoder::new(sample_rate, 1, 16).with_frame_crc(true);
+    encoder.push_samples(&samples).unwrap();
+    let flo_data = encoder.finalize(&[]).unwrap();
+
+    let file = Reader::new().read(&flo_data).unwrap();
+    assert!(file.header.has_frame_crc());
+    assert!(find_damaged_frames(&flo_data).unwrap().is_empty());
+    assert_eq!(decode(&flo_data).unwrap().len(), samples.len());
+}
+
+#[test]
+fn test_streaming_decoder_reports_damaged_frame() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 3);
+
+    let mut flo_data = Encoder::new(sample_rate, 1, 16)
+        .with_frame_crc(true)
+        .encode(&samples, &[])
+        .unwrap();
+    damage_frame(&mut flo_data, 1);
+
+    // strict: the damaged frame is an error, but decoding can carry on
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data).unwrap();
+    assert!(decoder.next_frame().unwrap().is_some());
+    assert!(decoder.next_frame().is_err());
+    assert!(decoder.next_frame().unwrap().is_some());
+    assert_eq!(decoder.damaged_frames(), &[1]);
+
+    // tolerant: every frame comes out with the right length
+    let mut decoder = StreamingDecoder::new().with_concealment(Concealment::Interpolate);
+    decoder.feed(&flo_data).unwrap();
+    let mut total = 0;
+    while let Some(frame) = decoder.next_frame().unwrap() {
+        assert_eq!(frame.len(), sample_rate as usize);
+        total += frame.len();
+    }
+    assert_eq!(total, samples.len());
+    assert_eq!(decoder.damaged_frames(), &[1]);
+}
+
+#[test]
+fn test_live_stream_conceals_lost_packet() {
+    let sample_rate = 8000u32;
+    let samples = test_signal(sample_rate, 4);
+
+    let mut encoder = StreamingEncoder::new(sample_rate, 1, 16);
+    encoder.push_samples(&samples).unwrap();
+    let mut packets = Vec::new();
+    while let Some(packet) = encoder.next_live_packet() {
+        packets.push(packet);
+    }
+    assert_eq!(packets.len(), 4);
+
+    // packet 2 never arrives
+    let stream: Vec<u8> = packets
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 2)
+        .flat_map(|(_, p)| p.clone())
+        .collect();
+
+    let mut decoder = StreamingDecoder::new_live();
+    decoder.feed(&stream).unwrap();
+    let skipped = decoder.decode_available().unwrap();
+    assert_eq!(skipped.len(), sample_rate as usize * 3);
+    assert_eq!(decoder.damaged_frames(), &[2]);
+
+    let mut decoder = StreamingDecoder::new_live().with_concealment(Concealment::Silence);
+    decoder.feed(&stream).unwrap();
+    let concealed = decoder.decode_available().unwrap();
+    assert_eq!(concealed.len(), samples.len());
+    assert!(
+        concealed[sample_rate as usize * 2..sample_rate as usize * 3]
+            .iter()
+            .all(|&s| s == 0.0)
+    );
+}
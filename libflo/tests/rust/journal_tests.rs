@@ -0,0 +1,126 @@
+//! Resumable streaming encoder tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Checkpoint and resume giving the same file as an uninterrupted encode
+//! - Recovering a crashed capture from its journal, torn last record and all
+//! - Resuming a journal and finishing the capture
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use libflo_audio::{decode, EncoderJournal, MetadataPlacement, StreamingEncoder};
+
+const SAMPLE_RATE: u32 = 48000;
+
+fn test_signal(channels: usize, samples: usize) -> Vec<f32> {
+    (0..samples * channels)
+        .map(|i| {
+            let t = (i / channels) as f32;
+            (t * 0.021).sin() * 0.4 + (t * 0.13).sin() * 0.05 * (i % channels) as f32
+        })
+        .collect()
+}
+
+fn journal_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("flo-{}-{}.floj", std::process::id(), name))
+}
+
+fn encoders() -> Vec<StreamingEncoder> {
+    vec![
+        StreamingEncoder::new(SAMPLE_RATE, 2, 16)
+            .with_compression(7)
+            .with_frame_crc(true)
+            .with_metadata_placement(MetadataPlacement::Start),
+        StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.7),
+        StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.4).with_low_latency(10.0),
+    ]
+}
+
+#[test]
+fn test_checkpoint_resume_matches_uninterrupted() {
+    let samples = test_signal(2, SAMPLE_RATE as usize * 2 + 333);
+    let split = 2 * 70_001;
+
+    for (mut whole, mut first) in encoders().into_iter().zip(encoders()) {
+        whole.push_samples(&samples).unwrap();
+        let expected = whole.finalize(b"meta").unwrap();
+
+        // pending frames and half a frame of samples go with the checkpoint
+        first.push_samples(&samples[..split]).unwrap();
+        assert!(first.pending_frames() > 0);
+        let checkpoint = first.checkpoint().unwrap();
+        drop(first);
+
+        let mut resumed = StreamingEncoder::resume(&checkpoint).unwrap();
+        resumed.push_samples(&samples[split..]).unwrap();
+        assert_eq!(resumed.finalize(b"meta").unwrap(), expected);
+    }
+
+    assert!(StreamingEncoder::resume(&[1, 2, 3]).is_err());
+}
+
+#[test]
+fn test_recover_crashed_capture() {
+    let path = journal_path("recover");
+    let samples = test_signal(1, SAMPLE_RATE as usize * 5);
+    let chunk = 12_000;
+
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16);
+    let mut journal = EncoderJournal::create(&path, &encoder).unwrap();
+    let mut sent = 0;
+    for block in samples[..chunk * 13].chunks(chunk) {
+        encoder.push_samples(block).unwrap();
+        sent += journal.record(&mut encoder).unwrap().len();
+    }
+    assert_eq!(sent, 3);
+
+    // the process dies halfway through writing a record
+    drop(journal);
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[1, 200, 0, 0, 0, 7, 7]).unwrap();
+    drop(file);
+
+    // the recorded frames and the samples waiting for the next one survive
+    let recovered = EncoderJournal::recover(&path).unwrap();
+    assert_eq!(recovered.frames.len(), 3);
+    assert_eq!(recovered.encoder.pending_samples(), chunk * 13 - 3 * 48000);
+    let flo_data = recovered.finalize(&[]).unwrap();
+    let decoded = decode(&flo_data).unwrap();
+    assert_eq!(decoded.len(), chunk * 13);
+    for (a, b) in samples.iter().zip(&decoded) {
+        assert!((a - b).abs() <= 1.0 / 32767.0);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(EncoderJournal::recover(&path).is_err());
+}
+
+#[test]
+fn test_resume_journal_and_finish() {
+    let samples = test_signal(2, SAMPLE_RATE as usize * 2);
+    let split = 2 * 50_000;
+
+    for (index, mut whole) in encoders().into_iter().enumerate() {
+        let path = journal_path(&format!("resume-{}", index));
+        whole.push_samples(&samples).unwrap();
+        let expected = whole.finalize(&[]).unwrap();
+
+        let mut encoder = encoders().remove(index);
+        let mut journal = EncoderJournal::create(&path, &encoder).unwrap();
+        for block in samples[..split].chunks(2 * 4096) {
+            encoder.push_samples(block).unwrap();
+            journal.record(&mut encoder).unwrap();
+        }
+        // a push that never got recorded is lost with the crash
+        encoder.push_samples(&samples[split..split + 2000]).unwrap();
+        drop((journal, encoder));
+
+        let (mut journal, mut encoder) = EncoderJournal::resume(&path).unwrap();
+        encoder.push_samples(&samples[split..]).unwrap();
+        journal.record(&mut encoder).unwrap();
+        assert_eq!(journal.finalize(&mut encoder, &[]).unwrap(), expected);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
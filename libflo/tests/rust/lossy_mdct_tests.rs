@@ -143,6 +143,63 @@ mod mdct_tests {
         assert_eq!(reconstructed.len(), 256);
     }
 
+    #[test]
+    fn test_low_delay_reconstruction() {
+        let block = BlockSize::low_delay(48000, 10.0);
+        assert_eq!(
+            block,
+            BlockSize::LowDelay {
+                hop: 480,
+                overlap: 120
+            }
+        );
+        let (hop, zero_tail) = (block.coefficients(), block.zero_tail());
+        assert_eq!(zero_tail, 180);
+
+        // windows start late by the zero tail, so the preroll grows by that
+        let signal: Vec<f32> = (0..hop * 6)
+            .map(|i| (i as f32 * 0.05).sin() * 0.5)
+            .collect();
+        let mut padded = vec![0.0f32; hop + zero_tail];
+        padded.extend(&signal);
+        padded.resize(padded.len() + 2 * hop, 0.0);
+
+        let mut mdct = Mdct::new(1, WindowType::Vorbis);
+        let mut output = Vec::new();
+        for start in (0..=padded.len() - 2 * hop).step_by(hop) {
+            let coeffs = mdct.analyze(&padded[start..start + 2 * hop], block);
+            output.extend(mdct.synthesize(&coeffs, block));
+        }
+
+        // the first hop only primes the overlap
+        let max_error = signal
+            .iter()
+            .zip(&output[hop..])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(max_error < 1e-4, "Max reconstruction error: {}", max_error);
+    }
+
+    #[test]
+    fn test_low_delay_without_prepare() {
+        let block = BlockSize::low_delay(48000, 10.0);
+        let samples: Vec<f32> = (0..block.samples())
+            .map(|i| (i as f32 * 0.05).sin())
+            .collect();
+
+        let fresh = Mdct::new(1, WindowType::Vorbis);
+        let coeffs = fresh.forward(&samples, block);
+        let reconstructed = fresh.inverse(&coeffs, block);
+        assert_eq!(coeffs.len(), block.coefficients());
+        assert_eq!(reconstructed.len(), block.samples());
+
+        // same result as a prepared transform
+        let mut prepared = Mdct::new(1, WindowType::Vorbis);
+        prepared.prepare(block);
+        assert_eq!(prepared.forward(&samples, block), coeffs);
+        assert_eq!(prepared.inverse(&coeffs, block), reconstructed);
+    }
+
     #[test]
     fn test_multichannel_analyze_synthesize() {
         let mut mdct = Mdct::new(2, WindowType::Sine);
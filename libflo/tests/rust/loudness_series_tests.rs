@@ -0,0 +1,171 @@
+//! Loudness series tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Momentary and short-term loudness of steady and changing signals
+//! - Series length and timestamps at different resolutions
+//! - Storing values in steps of 0.01 LU
+//! - Metrics and series from one pass matching the separate measurements
+//! - The series written into metadata at encode time
+
+use libflo_audio::{
+    compute_ebu_r128_loudness, compute_loudness, compute_loudness_series, encode,
+    get_metadata_bytes_native, FloMetadata, LoudnessSeries,
+};
+
+fn sine(sample_rate: u32, seconds: f32, amp: f32) -> Vec<f32> {
+    (0..(sample_rate as f32 * seconds) as usize)
+        .map(|i| amp * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin())
+        .collect()
+}
+
+#[test]
+fn test_loudness_series_steady_signal() {
+    let samples = sine(8000, 5.0, 0.5);
+    let series = compute_loudness_series(&samples, 1, 8000, 10);
+    let integrated = compute_ebu_r128_loudness(&samples, 1, 8000).integrated_lufs as f32;
+
+    assert_eq!(series.points_per_second, 10);
+    assert_eq!(series.momentary.len(), 50);
+    assert_eq!(series.short_term.len(), 50);
+
+    // once the windows are full, both match the integrated loudness
+    for &lufs in &series.momentary_lufs()[4..] {
+        assert!(
+            (lufs - integrated).abs() < 0.2,
+            "{} vs {}",
+            lufs,
+            integrated
+        );
+    }
+    for &lufs in &series.short_term_lufs()[30..] {
+        assert!(
+            (lufs - integrated).abs() < 0.2,
+            "{} vs {}",
+            lufs,
+            integrated
+        );
+    }
+}
+
+#[test]
+fn test_loudness_series_follows_changes() {
+    // 4 s quiet, 0.5 s burst 20 dB louder, 4 s quiet
+    let mut samples = sine(8000, 4.0, 0.05);
+    samples.extend(sine(8000, 0.5, 0.5));
+    samples.extend(sine(8000, 4.0, 0.05));
+    let series = compute_loudness_series(&samples, 1, 8000, 10);
+
+    let max_momentary = series.max_momentary().unwrap();
+    let max_short_term = series.max_short_term().unwrap();
+    let momentary = series.momentary_lufs();
+    let quiet = momentary[30];
+
+    // the momentary meter catches the burst, the 3 s window averages it out
+    assert!(max_momentary - quiet > 18.0);
+    assert!(max_short_term < max_momentary - 5.0);
+    assert!(max_short_term > quiet);
+
+    // and it sits where the burst is
+    let loudest = series
+        .momentary
+        .iter()
+        .enumerate()
+        .max_by_key(|&(_, &value)| value)
+        .unwrap()
+        .0;
+    let at = series.timestamp_ms(loudest);
+    assert!((4000..4500).contains(&at), "{}", at);
+
+    // back to quiet after the burst
+    assert!((momentary[80] - quiet).abs() < 0.5);
+}
+
+#[test]
+fn test_loudness_series_resolution_and_silence() {
+    // stereo, last partial point still counts
+    let samples = vec![0.0f32; 2 * 8000 * 3 + 2 * 100];
+    let series = compute_loudness_series(&samples, 2, 8000, 4);
+    assert_eq!(series.momentary.len(), 13);
+    assert_eq!(series.timestamp_ms(12), 3000);
+    assert!(series.momentary.iter().all(|&value| value == -15000));
+    assert!(series.short_term.iter().all(|&value| value == -15000));
+
+    let series = compute_loudness_series(&samples, 2, 8000, 1);
+    assert_eq!(series.short_term.len(), 4);
+    let points = series.short_term_points();
+    assert_eq!(points[1].timestamp_ms, 1000);
+    assert_eq!(points[1].lufs, -150.0);
+
+    let empty = compute_loudness_series(&[], 2, 8000, 10);
+    assert!(empty.momentary.is_empty());
+    assert_eq!(empty.max_momentary(), None);
+}
+
+#[test]
+fn test_loudness_in_one_pass() {
+    // stereo, a quiet start and an odd length
+    let mut left = sine(44100, 1.5, 0.02);
+    left.extend(sine(44100, 2.3, 0.4));
+    let samples: Vec<f32> = left.iter().flat_map(|&s| [s, s * 0.5]).collect();
+
+    let (metrics, series) = compute_loudness(&samples, 2, 44100, 25);
+    let expected = compute_ebu_r128_loudness(&samples, 2, 44100);
+    assert!((metrics.integrated_lufs - expected.integrated_lufs).abs() < 1e-6);
+    assert!((metrics.loudness_range_lu - expected.loudness_range_lu).abs() < 1e-6);
+    assert_eq!(metrics.true_peak_dbtp, expected.true_peak_dbtp);
+    assert_eq!(metrics.sample_peak_dbfs, expected.sample_peak_dbfs);
+
+    let expected = compute_loudness_series(&samples, 2, 44100, 25);
+    assert_eq!(series.points_per_second, 25);
+    assert_eq!(series.momentary.len(), expected.momentary.len());
+    // rounding to 0.01 LU can land one step apart
+    for (a, b) in series.momentary.iter().zip(&expected.momentary) {
+        assert!((a - b).abs() <= 1);
+    }
+    for (a, b) in series.short_term.iter().zip(&expected.short_term) {
+        assert!((a - b).abs() <= 1);
+    }
+}
+
+#[test]
+fn test_loudness_series_written_at_encode() {
+    let samples = sine(8000, 2.0, 0.5);
+    let data = encode(&samples, 8000, 1, 16, None).unwrap();
+    let meta = FloMetadata::from_msgpack(&get_metadata_bytes_native(&data).unwrap()).unwrap();
+
+    let series = meta.loudness_series.unwrap();
+    assert_eq!(series.points_per_second, 10);
+    assert_eq!(series.momentary.len(), 20);
+    assert!(meta.loudness_profile.is_empty());
+
+    // a series already in the metadata is kept
+    let mut meta = FloMetadata::new();
+    meta.loudness_series = Some(LoudnessSeries {
+        points_per_second: 1,
+        momentary: vec![-2000, -2100],
+        short_term: vec![-2000, -2050],
+    });
+    let data = encode(&samples, 8000, 1, 16, Some(meta.to_msgpack().unwrap())).unwrap();
+    let meta = FloMetadata::from_msgpack(&get_metadata_bytes_native(&data).unwrap()).unwrap();
+    let series = meta.loudness_series.unwrap();
+    assert_eq!(series.points_per_second, 1);
+    assert_eq!(series.momentary, [-2000, -2100]);
+    assert_eq!(series.short_term_lufs(), [-20.0, -20.5]);
+}
+
+#[test]
+fn test_loudness_series_scale() {
+    assert_eq!(LoudnessSeries::from_lufs(-23.14), -2314);
+    assert_eq!(LoudnessSeries::from_lufs(-150.0), -15000);
+    assert_eq!(LoudnessSeries::from_lufs(-1000.0), i16::MIN);
+    assert_eq!(LoudnessSeries::to_lufs(-2314), -23.14);
+
+    let series = LoudnessSeries {
+        points_per_second: 10,
+        momentary: vec![-2500, -1420, -1890],
+        short_term: vec![-2600, -2200, -2000],
+    };
+    assert_eq!(series.max_momentary(), Some(-14.2));
+    assert_eq!(series.max_short_term(), Some(-20.0));
+    assert_eq!(series.momentary_lufs(), [-25.0, -14.2, -18.9]);
+}
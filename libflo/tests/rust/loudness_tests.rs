@@ -67,7 +67,7 @@ fn test_ebu_r128_white_noise() {
         .map(|i| {
             let seed = ((i as u64).wrapping_mul(1103515245).wrapping_add(12345)) & 0x7fffffff;
             let r = (seed as f64) / (i32::MAX as f64);
-            (amp as f64 * (r - 0.5) * 2.0) as f32
+            (amp * (r - 0.5) * 2.0) as f32
         })
         .collect();
 
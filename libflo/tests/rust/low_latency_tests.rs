@@ -0,0 +1,227 @@
+//! Low-latency streaming tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Short lossless frames and when they are emitted
+//! - Low delay lossy frames matching the file encoder
+//! - Algorithmic delay reported by encoder and decoder
+//! - Concealment sizing damaged frames by the stream's frame length
+//! - Long files of 2.5 ms frames and the frame limit writers enforce
+
+use libflo_audio::core::Frame;
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::{
+    decode, BlockSize, Concealment, FrameType, Reader, StreamingDecoder, StreamingEncoder, Writer,
+};
+
+const SAMPLE_RATE: u32 = 48000;
+
+fn test_signal(channels: usize, samples: usize) -> Vec<f32> {
+    (0..samples)
+        .flat_map(|i| {
+            let t = i as f32;
+            let left = (t * 0.021).sin() * 0.4 + (t * 0.13).sin() * 0.05;
+            let right = (t * 0.017).cos() * 0.3;
+            [left, right].into_iter().take(channels)
+        })
+        .collect()
+}
+
+/// push in uneven chunks, collecting the live packets as they come out
+fn live_packets(encoder: &mut StreamingEncoder, samples: &[f32], channels: usize) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    for chunk in samples.chunks(137 * channels) {
+        encoder.push_samples(chunk).unwrap();
+        while let Some(packet) = encoder.next_live_packet() {
+            packets.push(packet);
+        }
+    }
+    encoder.finish().unwrap();
+    while let Some(packet) = encoder.next_live_packet() {
+        packets.push(packet);
+    }
+    packets
+}
+
+#[test]
+fn test_lossless_low_latency_frames() {
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 2, 16).with_low_latency(10.0);
+    assert_eq!(encoder.frame_samples(), 480);
+    assert_eq!(encoder.algorithmic_delay(), 480);
+    assert_eq!(encoder.algorithmic_delay_ms(), 10.0);
+
+    // a frame goes out as soon as it's full
+    let samples = test_signal(2, 480 * 50 + 100);
+    encoder.push_samples(&samples[..479 * 2]).unwrap();
+    assert_eq!(encoder.pending_frames(), 0);
+    encoder.push_samples(&samples[479 * 2..480 * 2]).unwrap();
+    assert_eq!(encoder.pending_frames(), 1);
+
+    // durations outside 2.5-20 ms are clamped
+    let short = StreamingEncoder::new(SAMPLE_RATE, 2, 16).with_low_latency(0.5);
+    assert_eq!(short.frame_samples(), 120);
+    let long = StreamingEncoder::new(SAMPLE_RATE, 2, 16).with_low_latency(100.0);
+    assert_eq!(long.frame_samples(), 960);
+
+    // still lossless over live packets
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 2, 16).with_low_latency(10.0);
+    let packets = live_packets(&mut encoder, &samples, 2);
+    assert_eq!(packets.len(), 51);
+
+    let mut decoder = StreamingDecoder::new_live();
+    assert_eq!(decoder.algorithmic_delay(), None);
+    let mut decoded = Vec::new();
+    for packet in &packets {
+        decoder.feed(packet).unwrap();
+        decoded.extend(decoder.decode_available().unwrap());
+    }
+    assert_eq!(decoder.algorithmic_delay(), Some(480));
+    assert_eq!(decoder.algorithmic_delay_ms(), Some(10.0));
+
+    assert_eq!(decoded.len(), samples.len());
+    for (a, b) in samples.iter().zip(&decoded) {
+        assert!((a - b).abs() <= 1.0 / 32767.0);
+    }
+}
+
+#[test]
+fn test_lossy_low_latency_matches_file_encoder() {
+    let block = BlockSize::low_delay(SAMPLE_RATE, 10.0);
+    let samples = test_signal(2, SAMPLE_RATE as usize / 2 + 77);
+
+    let mut streaming = StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.8).with_low_latency(10.0);
+    for chunk in samples.chunks(2 * 301) {
+        streaming.push_samples(chunk).unwrap();
+    }
+    let streamed = streaming.finalize(&[]).unwrap();
+
+    let expected = TransformEncoder::new(SAMPLE_RATE, 2, 0.8)
+        .with_block_size(block)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+    assert_eq!(streamed, expected);
+
+    let file = Reader::new().read(&expected).unwrap();
+    assert_eq!(file.frames[1].frame_samples, 480);
+
+    // same length as the input, and close to it
+    let decoded = decode(&expected).unwrap();
+    assert!(decoded.len() >= samples.len());
+    let (signal, noise) = samples
+        .iter()
+        .zip(&decoded)
+        .fold((0.0f64, 0.0f64), |(s, n), (&a, &b)| {
+            (s + (a * a) as f64, n + ((a - b) * (a - b)) as f64)
+        });
+    let snr = 10.0 * (signal / noise).log10();
+    assert!(snr > 15.0, "SNR too low: {:.1} dB", snr);
+}
+
+#[test]
+fn test_lossy_low_latency_delay() {
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.5).with_low_latency(10.0);
+    // a 10 ms hop plus 2.5 ms of overlap, instead of two 1024 sample hops
+    assert_eq!(encoder.algorithmic_delay(), 600);
+    assert_eq!(encoder.algorithmic_delay_ms(), 12.5);
+    assert_eq!(
+        StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.5).algorithmic_delay(),
+        2048
+    );
+
+    // frame k goes out once k hops and the overlap have been pushed
+    let samples = test_signal(1, 4800);
+    encoder.push_samples(&samples[..119]).unwrap();
+    assert_eq!(encoder.pending_frames(), 0);
+    encoder.push_samples(&samples[119..120]).unwrap();
+    assert_eq!(encoder.pending_frames(), 1);
+    encoder.push_samples(&samples[120..599]).unwrap();
+    assert_eq!(encoder.pending_frames(), 1);
+    encoder.push_samples(&samples[599..600]).unwrap();
+    assert_eq!(encoder.pending_frames(), 2);
+
+    // the live decoder reads the same delay off the frames
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.5).with_low_latency(10.0);
+    let mut decoder = StreamingDecoder::new_live();
+    let mut decoded = Vec::new();
+    for packet in live_packets(&mut encoder, &samples, 1) {
+        decoder.feed(&packet).unwrap();
+        decoded.extend(decoder.decode_available().unwrap());
+    }
+    assert_eq!(decoder.algorithmic_delay(), Some(600));
+    assert_eq!(decoder.algorithmic_delay_ms(), Some(12.5));
+
+    // preroll frame 0 plays nothing, the rest one hop each
+    assert_eq!(decoded.len(), 4800);
+}
+
+#[test]
+fn test_low_latency_concealment_frame_length() {
+    let samples = test_signal(1, 480 * 20);
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16)
+        .with_low_latency(10.0)
+        .with_frame_crc(true);
+    encoder.push_samples(&samples).unwrap();
+    let mut flo_data = encoder.finalize(&[]).unwrap();
+
+    // damage frame 5 and frame 0
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let data_start = (4 + file.header.header_size + file.header.toc_size) as usize;
+    let positions: Vec<usize> = [5, 0]
+        .iter()
+        .map(|&i| {
+            let entry = &file.toc[i];
+            data_start + entry.byte_offset as usize + entry.frame_size as usize / 2
+        })
+        .collect();
+    for pos in positions {
+        flo_data[pos] ^= 0x5A;
+    }
+
+    // the lost frames are 10 ms, not a second
+    let mut decoder = StreamingDecoder::new().with_concealment(Concealment::Interpolate);
+    decoder.feed(&flo_data).unwrap();
+    let decoded = decoder.decode_available().unwrap();
+    assert_eq!(decoder.damaged_frames(), &[0, 5]);
+    assert_eq!(decoded.len(), samples.len());
+}
+
+#[test]
+fn test_long_low_latency_file() {
+    // 200 s of 2.5 ms frames is 80000 frames, their TOC is over 100000 rows.
+    // the frame count is what matters, a low rate keeps the test quick
+    let samples = test_signal(1, 8000 * 200);
+    let mut encoder = StreamingEncoder::new(8000, 1, 16).with_low_latency(2.5);
+    for chunk in samples.chunks(800) {
+        encoder.push_samples(chunk).unwrap();
+    }
+    let flo_data = encoder.finalize(&[]).unwrap();
+
+    let file = Reader::new().read(&flo_data).unwrap();
+    assert_eq!(file.frames.len(), 80_000);
+    let decoded = decode(&flo_data).unwrap();
+    assert_eq!(decoded.len(), samples.len());
+
+    let mut decoder = StreamingDecoder::new();
+    let mut streamed = Vec::with_capacity(decoded.len());
+    for chunk in flo_data.chunks(16384) {
+        decoder.feed(chunk).unwrap();
+        streamed.extend(decoder.decode_available().unwrap());
+    }
+    assert_eq!(streamed, decoded);
+}
+
+#[test]
+fn test_writers_refuse_too_many_frames() {
+    // one sample a frame is far shorter than any encoder writes
+    let frames: Vec<Frame> = (0..100_001)
+        .map(|_| Frame::new(FrameType::Silence as u8, 1))
+        .collect();
+    let err = Writer::new()
+        .write(8000, 1, 16, 5, &frames, &[])
+        .unwrap_err();
+    assert!(err.contains("Too many frames"), "{}", err);
+
+    // up to the limit is fine
+    assert!(Writer::new()
+        .write(8000, 1, 16, 5, &frames[..100_000], &[])
+        .is_ok());
+}
@@ -0,0 +1,228 @@
+//! Metadata placement tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Early metadata (preview or everything) ahead of the frames
+//! - Readers finding frames through the TOC as before
+//! - StreamingDecoder exposing metadata before the audio arrives
+//! - Placement surviving metadata updates and edits
+
+use libflo_audio::edit::trim;
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::{
+    decode, has_metadata, set_metadata_placement_bytes, update_metadata_bytes, Encoder,
+    FloMetadata, MetadataPlacement, PictureType, Reader, StreamingDecoder, StreamingEncoder,
+    PREVIEW_MAX_PICTURE_SIZE,
+};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn test_signal(seconds: usize) -> Vec<f32> {
+    (0..SAMPLE_RATE as usize * seconds)
+        .map(|i| (i as f32 * 0.02).sin() * 0.5)
+        .collect()
+}
+
+fn full_metadata() -> FloMetadata {
+    let mut meta = FloMetadata::with_basic(
+        Some("Song".to_string()),
+        Some("Artist".to_string()),
+        Some("Album".to_string()),
+    );
+    meta.add_picture("image/png", PictureType::CoverFront, vec![7; 1000]);
+    meta.add_picture(
+        "image/png",
+        PictureType::CoverBack,
+        vec![9; PREVIEW_MAX_PICTURE_SIZE + 1],
+    );
+    meta.add_synced_lyrics_line(500, "la", None);
+    meta.add_comment("not in the preview", None);
+    meta
+}
+
+fn encode(placement: MetadataPlacement) -> Vec<u8> {
+    Encoder::new(SAMPLE_RATE, 1, 16)
+        .with_frame_crc(true)
+        .with_metadata_placement(placement)
+        .encode(&test_signal(3), &full_metadata().to_msgpack().unwrap())
+        .unwrap()
+}
+
+#[test]
+fn test_preview_subset() {
+    let preview = full_metadata().preview();
+    assert_eq!(preview.title.as_deref(), Some("Song"));
+    assert_eq!(preview.album.as_deref(), Some("Album"));
+    assert_eq!(preview.pictures.len(), 1);
+    assert_eq!(preview.pictures[0].picture_type, PictureType::CoverFront);
+    assert_eq!(preview.synced_lyrics.len(), 1);
+    assert!(preview.comments.is_empty());
+
+    // an oversized cover is left for the full metadata
+    let mut meta = FloMetadata::new();
+    meta.add_picture(
+        "image/png",
+        PictureType::CoverFront,
+        vec![0; PREVIEW_MAX_PICTURE_SIZE + 1],
+    );
+    assert!(meta.preview().pictures.is_empty());
+}
+
+#[test]
+fn test_preview_placement() {
+    let reference = encode(MetadataPlacement::End);
+    let flo_data = encode(MetadataPlacement::Preview);
+
+    let file = Reader::new().read(&flo_data).unwrap();
+    assert!(file.header.has_early_meta());
+    assert_eq!(file.header.metadata_placement(), MetadataPlacement::Preview);
+    assert_eq!(
+        file.metadata,
+        Reader::new().read(&reference).unwrap().metadata
+    );
+
+    // frames start after the preview, found through the TOC like always
+    let borrowed = Reader::new().read_ref(&flo_data).unwrap();
+    assert_eq!(borrowed.toc[0].byte_offset, file.header.early_meta_size);
+    let preview = FloMetadata::from_msgpack(borrowed.early_metadata).unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Song"));
+    assert!(preview.comments.is_empty());
+
+    assert_eq!(decode(&flo_data).unwrap(), decode(&reference).unwrap());
+    assert!(!Reader::new()
+        .read(&reference)
+        .unwrap()
+        .header
+        .has_early_meta());
+}
+
+#[test]
+fn test_start_placement() {
+    let flo_data = encode(MetadataPlacement::Start);
+    let file = Reader::new().read(&flo_data).unwrap();
+
+    assert_eq!(file.header.meta_size, 0);
+    assert_eq!(file.header.metadata_placement(), MetadataPlacement::Start);
+    assert!(has_metadata(&flo_data));
+
+    let meta = FloMetadata::from_msgpack(&file.metadata).unwrap();
+    assert_eq!(meta.comments.len(), 1);
+    assert_eq!(meta.pictures.len(), 2);
+    assert_eq!(decode(&flo_data).unwrap().len(), test_signal(3).len());
+}
+
+#[test]
+fn test_lossy_preview_placement() {
+    let samples: Vec<f32> = (0..44100).map(|i| (i as f32 * 0.03).sin() * 0.5).collect();
+    let metadata = full_metadata().to_msgpack().unwrap();
+    let encode = |placement| {
+        TransformEncoder::new(44100, 1, 0.5)
+            .with_metadata_placement(placement)
+            .encode_to_flo(&samples, &metadata)
+            .unwrap()
+    };
+
+    let flo_data = encode(MetadataPlacement::Preview);
+    assert!(Reader::new()
+        .read(&flo_data)
+        .unwrap()
+        .header
+        .has_early_meta());
+    assert_eq!(
+        decode(&flo_data).unwrap(),
+        decode(&encode(MetadataPlacement::End)).unwrap()
+    );
+}
+
+#[test]
+fn test_streaming_decoder_sees_metadata_early() {
+    let flo_data = encode(MetadataPlacement::Preview);
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let data_start = flo_data.len() - file.metadata.len() - file.extra.len() - file.data.len();
+    let preview_end = data_start + file.header.early_meta_size as usize;
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data[..preview_end - 1]).unwrap();
+    assert!(decoder.metadata().is_none());
+
+    decoder
+        .feed(&flo_data[preview_end - 1..preview_end])
+        .unwrap();
+    assert_eq!(decoder.available_frames(), 0);
+    let preview = FloMetadata::from_msgpack(decoder.metadata().unwrap()).unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Song"));
+    assert!(preview.comments.is_empty());
+
+    // the full META chunk takes over once everything is in
+    decoder.feed(&flo_data[preview_end..]).unwrap();
+    let full = FloMetadata::from_msgpack(decoder.metadata().unwrap()).unwrap();
+    assert_eq!(full.comments.len(), 1);
+    assert_eq!(
+        decoder.decode_available().unwrap(),
+        decode(&flo_data).unwrap()
+    );
+}
+
+#[test]
+fn test_streaming_decoder_metadata_at_end() {
+    let flo_data = encode(MetadataPlacement::End);
+    let mut decoder = StreamingDecoder::new();
+
+    decoder.feed(&flo_data[..flo_data.len() - 1]).unwrap();
+    assert!(decoder.metadata().is_none());
+    decoder.feed(&flo_data[flo_data.len() - 1..]).unwrap();
+    assert!(decoder.metadata().is_some());
+}
+
+#[test]
+fn test_streaming_encoder_placement() {
+    let metadata = full_metadata().to_msgpack().unwrap();
+    let mut encoder =
+        StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_metadata_placement(MetadataPlacement::Start);
+    encoder.push_samples(&test_signal(2)).unwrap();
+    let flo_data = encoder.finalize(&metadata).unwrap();
+
+    let file = Reader::new().read(&flo_data).unwrap();
+    assert_eq!(file.header.metadata_placement(), MetadataPlacement::Start);
+    assert_eq!(file.metadata, metadata);
+    assert_eq!(
+        decode(&flo_data).unwrap(),
+        decode(&encode(MetadataPlacement::End)).unwrap()[..16000]
+    );
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data[..200]).unwrap();
+    assert_eq!(decoder.metadata(), None);
+    // everything is up front, so it shows up before the last frame
+    let last_frame = file.toc.last().unwrap().frame_size as usize;
+    decoder
+        .feed(&flo_data[200..flo_data.len() - last_frame])
+        .unwrap();
+    assert_eq!(decoder.metadata(), Some(&metadata[..]));
+}
+
+#[test]
+fn test_placement_survives_updates() {
+    let flo_data = encode(MetadataPlacement::Preview);
+
+    let mut meta = full_metadata();
+    meta.title = Some("Renamed".to_string());
+    let updated = update_metadata_bytes(&flo_data, &meta.to_msgpack().unwrap()).unwrap();
+    let file = Reader::new().read_ref(&updated).unwrap();
+    assert_eq!(file.header.metadata_placement(), MetadataPlacement::Preview);
+    let preview = FloMetadata::from_msgpack(file.early_metadata).unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Renamed"));
+    assert_eq!(decode(&updated).unwrap(), decode(&flo_data).unwrap());
+
+    let trimmed = trim(&flo_data, 0, 8000).unwrap();
+    let file = Reader::new().read(&trimmed).unwrap();
+    assert_eq!(file.header.metadata_placement(), MetadataPlacement::Preview);
+
+    let moved = set_metadata_placement_bytes(&flo_data, MetadataPlacement::End).unwrap();
+    let file = Reader::new().read(&moved).unwrap();
+    assert!(!file.header.has_early_meta());
+    assert_eq!(
+        file.metadata,
+        Reader::new().read(&flo_data).unwrap().metadata
+    );
+    assert_eq!(decode(&moved).unwrap(), decode(&flo_data).unwrap());
+}
@@ -1,12 +1,23 @@
 // tests/rust/mod.rs - Include all Rust test modules
 
 mod analysis_tests;
+#[cfg(feature = "async")]
+mod async_io_tests;
+mod chapter_tests;
+mod concealment_tests;
 mod core_crc32_tests;
 mod core_metadata_tests;
 mod core_rice_tests;
 mod duration_timing_tests;
 mod edge_case_tests;
+mod edit_tests;
+mod frame_decoder_tests;
+mod gapless_tests;
+mod header_tests;
+mod id3_tests;
 mod integration_tests;
+mod integrity_tests;
+mod journal_tests;
 mod lossless_decoder_tests;
 mod lossless_encoder_tests;
 mod lossless_lpc_tests;
@@ -16,8 +27,19 @@ mod lossy_mdct_tests;
 mod lossy_psychoacoustic_tests;
 mod lossy_quality_tests;
 mod lossy_transform_tests;
+mod loudness_series_tests;
 mod loudness_tests;
+mod low_latency_tests;
+mod metadata_placement_tests;
+mod random_access_tests;
+mod range_decode_tests;
+mod reader_ref_tests;
+mod replay_gain_tests;
+mod rtp_tests;
+mod seek_table_tests;
 mod seeking_integration_tests;
 mod seeking_tests;
 mod spectral_analysis_tests;
+mod streaming_seek_tests;
 mod streaming_tests;
+mod vorbis_comment_tests;
@@ -0,0 +1,161 @@
+//! Random access decoder tests for flo™ audio codec
+//!
+//! Tests for:
+//! - read_at against slices of a full decode, in and out of order
+//! - Frames matching decode_frame_at after jumps and sequential reads
+//! - The frame cache staying within its size
+//! - Damaged gapless fields not panicking the frame layout
+
+use libflo_audio::core::Header;
+use libflo_audio::edit::trim;
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::seeking::decode_frame_at;
+use libflo_audio::{
+    compute_crc32, decode, Encoder, RandomAccessDecoder, StreamingEncoder, FLAG_GAPLESS,
+};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn stereo_signal(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .flat_map(|i| {
+            let t = i as f32;
+            [(t * 0.021).sin() * 0.5, (t * 0.013).cos() * 0.3]
+        })
+        .collect()
+}
+
+fn test_files() -> Vec<Vec<u8>> {
+    let lossless = Encoder::new(SAMPLE_RATE, 2, 16)
+        .encode(&stereo_signal(SAMPLE_RATE as usize * 5), &[])
+        .unwrap();
+
+    let samples = stereo_signal(SAMPLE_RATE as usize * 3 + 77);
+    let mut low_delay = StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.6).with_low_latency(10.0);
+    low_delay.push_samples(&samples).unwrap();
+
+    vec![
+        // frames of odd lengths
+        trim(&lossless, 3_333, 37_777).unwrap(),
+        TransformEncoder::new(SAMPLE_RATE, 2, 0.6)
+            .with_frame_crc(true)
+            .encode_to_flo(&samples, &[])
+            .unwrap(),
+        low_delay.finalize(&[]).unwrap(),
+    ]
+}
+
+#[test]
+fn test_read_at_matches_full_decode() {
+    for flo_data in test_files() {
+        let full = decode(&flo_data).unwrap();
+        let length = (full.len() / 2) as u64;
+        let mut decoder = RandomAccessDecoder::new(flo_data).unwrap();
+        assert_eq!(decoder.total_samples(), length);
+
+        // jumping back and forth, then reading on
+        let reads = [
+            (length / 2, 3000),
+            (5, 1),
+            (1000, 100),
+            (1023, 2),
+            (length - 10, 10),
+            (0, 4321),
+            (4321, 4321),
+            (8642, 10_000),
+            (length / 3, 1),
+        ];
+        for (sample, len) in reads {
+            let range = decoder.read_at(sample, len).unwrap();
+            let expected = &full[sample as usize * 2..(sample as usize + len) * 2];
+            assert_eq!(range, expected, "read {} + {}", sample, len);
+        }
+
+        // reads stop at the end of the audio
+        let tail = decoder.read_at(length - 5, 100).unwrap();
+        assert_eq!(tail, full[(length as usize - 5) * 2..]);
+        assert!(decoder.read_at(length, 10).unwrap().is_empty());
+        assert!(decoder.read_at(70, 0).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_frames_match_decode_frame_at() {
+    for flo_data in test_files() {
+        let mut decoder = RandomAccessDecoder::new(flo_data.clone()).unwrap();
+        let frames = decoder.num_frames();
+        let expected: Vec<Vec<f32>> = (0..frames as u32)
+            .map(|i| decode_frame_at(&flo_data, i).unwrap())
+            .collect();
+
+        let order: Vec<usize> = (0..frames)
+            .rev()
+            .chain((0..frames).step_by(3))
+            .chain(0..frames)
+            .collect();
+        for index in order {
+            assert_eq!(decoder.decode_frame(index).unwrap(), expected[index]);
+
+            // the frame covers the part of the audio its samples belong to
+            let range = decoder.frame_range(index).unwrap();
+            assert_eq!(
+                (range.end - range.start) as usize * 2,
+                expected[index].len()
+            );
+            if !range.is_empty() {
+                assert_eq!(decoder.frame_at(range.start), Some(index));
+            }
+        }
+        assert!(decoder.decode_frame(frames).is_err());
+        assert_eq!(decoder.frame_at(decoder.total_samples()), None);
+    }
+}
+
+#[test]
+fn test_cache_is_bounded() {
+    let flo_data = test_files().remove(1);
+    let mut decoder = RandomAccessDecoder::new(flo_data.clone())
+        .unwrap()
+        .with_cache_frames(3);
+
+    for index in 0..10 {
+        decoder.decode_frame(index).unwrap();
+    }
+    assert_eq!(decoder.cached_frames(), 3);
+
+    // a cached frame after a jump is the same as a fresh one
+    let frame_8 = decoder.decode_frame(8).unwrap();
+    decoder.decode_frame(2).unwrap();
+    assert_eq!(decoder.decode_frame(8).unwrap(), frame_8);
+    assert_eq!(frame_8, decode_frame_at(&flo_data, 8).unwrap());
+
+    assert!(RandomAccessDecoder::new(flo_data[..40].to_vec()).is_err());
+}
+
+#[test]
+fn test_large_encoder_delay_does_not_underflow() {
+    let mut flo_data = test_files().remove(1);
+    let header = Header::parse(&flo_data).unwrap();
+    assert!(header.flags & FLAG_GAPLESS != 0);
+    assert_eq!(header.early_meta_size, 0);
+
+    // a delay of several frames, as a damaged or hostile header may carry
+    let delay = 5 * 1024u32;
+    flo_data[74..78].copy_from_slice(&delay.to_le_bytes());
+    flo_data[78..82].copy_from_slice(&0u32.to_le_bytes());
+    let header_size = u64::from_le_bytes(flo_data[30..38].try_into().unwrap()) as usize;
+    let mut covered = flo_data[4..70].to_vec();
+    covered.extend_from_slice(&flo_data[74..4 + header_size]);
+    let crc = compute_crc32(&covered);
+    flo_data[70..74].copy_from_slice(&crc.to_le_bytes());
+    assert_eq!(Header::parse(&flo_data).unwrap().encoder_delay, delay);
+
+    let mut decoder = RandomAccessDecoder::new(flo_data.clone()).unwrap();
+    let total = decoder.total_samples();
+    assert_eq!(
+        decoder.read_at(0, total as usize).unwrap().len(),
+        total as usize * 2
+    );
+    assert!(decode(&flo_data).is_ok());
+    assert!(trim(&flo_data, 100, 5000).is_ok());
+}
@@ -0,0 +1,107 @@
+//! Range decoding tests for flo™ audio codec
+//!
+//! Tests for:
+//! - decode_range against slices of a full decode, lossless and lossy
+//! - Ranges inside one frame, across frames and past the end
+//! - Millisecond ranges
+
+use libflo_audio::edit::trim;
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::seeking::{decode_range, decode_range_ms};
+use libflo_audio::{decode, Encoder, StreamingEncoder};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn stereo_signal(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .flat_map(|i| {
+            let t = i as f32;
+            [(t * 0.021).sin() * 0.5, (t * 0.013).cos() * 0.3]
+        })
+        .collect()
+}
+
+fn lossless_file() -> Vec<u8> {
+    let flo_data = Encoder::new(SAMPLE_RATE, 2, 16)
+        .encode(&stereo_signal(SAMPLE_RATE as usize * 5), &[])
+        .unwrap();
+    // cuts inside frames leave frames of odd lengths
+    trim(&flo_data, 3_333, 37_777).unwrap()
+}
+
+fn lossy_files() -> Vec<Vec<u8>> {
+    let samples = stereo_signal(SAMPLE_RATE as usize * 4 + 77);
+    let mut low_delay = StreamingEncoder::new_lossy(SAMPLE_RATE, 2, 0.6).with_low_latency(10.0);
+    low_delay.push_samples(&samples).unwrap();
+    vec![
+        TransformEncoder::new(SAMPLE_RATE, 2, 0.6)
+            .with_frame_crc(true)
+            .encode_to_flo(&samples, &[])
+            .unwrap(),
+        low_delay.finalize(&[]).unwrap(),
+    ]
+}
+
+/// decode_range matches the full decode for ranges of every shape
+fn assert_ranges_match(flo_data: &[u8]) {
+    let full = decode(flo_data).unwrap();
+    let length = (full.len() / 2) as u64;
+
+    let ranges = [
+        (0, 1),
+        (0, 100),
+        (5, 6),
+        (1000, 1100),
+        (1023, 1025),
+        (2_000, 9_001),
+        (length / 2, length / 2 + 4321),
+        (length - 10, length),
+        (0, length),
+    ];
+    for (start, end) in ranges {
+        let range = decode_range(flo_data, start, end).unwrap();
+        assert_eq!(
+            range,
+            full[start as usize * 2..end as usize * 2],
+            "range {}..{}",
+            start,
+            end
+        );
+    }
+
+    // the end is clamped, an empty range is fine, a backwards one isn't
+    let tail = decode_range(flo_data, length - 5, u64::MAX).unwrap();
+    assert_eq!(tail, full[(length as usize - 5) * 2..]);
+    assert!(decode_range(flo_data, 70, 70).unwrap().is_empty());
+    assert!(decode_range(flo_data, length + 10, length + 20)
+        .unwrap()
+        .is_empty());
+    assert!(decode_range(flo_data, 71, 70).is_err());
+}
+
+#[test]
+fn test_decode_range_lossless() {
+    assert_ranges_match(&lossless_file());
+}
+
+#[test]
+fn test_decode_range_lossy() {
+    for flo_data in lossy_files() {
+        assert_ranges_match(&flo_data);
+    }
+}
+
+#[test]
+fn test_decode_range_ms() {
+    for flo_data in [lossless_file(), lossy_files().remove(0)] {
+        let full = decode(&flo_data).unwrap();
+        let (start, end) = (1234 * 8, 3000 * 8);
+
+        let range = decode_range_ms(&flo_data, 1234, 3000).unwrap();
+        assert_eq!(range, full[start * 2..end * 2]);
+
+        // past the end runs to the last sample
+        let rest = decode_range_ms(&flo_data, 2500, 60_000).unwrap();
+        assert_eq!(rest, full[2500 * 8 * 2..]);
+    }
+}
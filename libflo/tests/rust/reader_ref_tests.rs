@@ -0,0 +1,147 @@
+//! Borrowed (zero-copy) reader tests for flo™ audio codec
+//!
+//! Tests for:
+//! - FloFileRef / FrameRef parsing in place over the input
+//! - Decoding from borrowed frames matching the owned path
+//! - Frame CRCs and truncated input
+
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::seeking::decode_frame_at;
+use libflo_audio::{decode, deserialize_frame, Decoder, Encoder, LossyDecoder, Reader};
+
+fn test_signal(sample_rate: u32, channels: usize, seconds: usize) -> Vec<f32> {
+    (0..sample_rate as usize * channels * seconds)
+        .map(|i| ((i / channels) as f32 * 0.01 + (i % channels) as f32).sin() * 0.5)
+        .collect()
+}
+
+fn encode_lossless() -> Vec<u8> {
+    Encoder::new(8000, 2, 16)
+        .with_frame_crc(true)
+        .encode(&test_signal(8000, 2, 3), b"\x80")
+        .unwrap()
+}
+
+#[test]
+fn test_read_ref_matches_read() {
+    let flo_data = encode_lossless();
+    let owned = Reader::new().read(&flo_data).unwrap();
+    let borrowed = Reader::new().read_ref(&flo_data).unwrap();
+
+    assert_eq!(borrowed.num_frames(), owned.frames.len());
+    assert_eq!(borrowed.metadata, &owned.metadata[..]);
+    assert_eq!(borrowed.header.total_samples, owned.header.total_samples);
+
+    for (i, expected) in owned.frames.iter().enumerate() {
+        let frame = borrowed.frame(i).unwrap();
+        assert_eq!(frame.frame_type, expected.frame_type);
+        assert_eq!(frame.frame_samples, expected.frame_samples);
+        assert_eq!(frame.flags, expected.flags);
+
+        let channels: Vec<_> = frame.channels().map(|c| c.unwrap()).collect();
+        assert_eq!(channels.len(), expected.channels.len());
+        for (ch, exp) in channels.iter().zip(&expected.channels) {
+            assert_eq!(ch.predictor_coeffs(), &exp.predictor_coeffs[..]);
+            assert_eq!(ch.shift_bits, exp.shift_bits);
+            assert_eq!(ch.rice_parameter, exp.rice_parameter);
+            assert_eq!(ch.residuals, &exp.residuals[..]);
+        }
+    }
+}
+
+#[test]
+fn test_frame_payloads_borrow_input() {
+    let flo_data = encode_lossless();
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let input = flo_data.as_ptr_range();
+
+    for frame in file.frames() {
+        for channel in frame.unwrap().channels() {
+            let residuals = channel.unwrap().residuals.as_ptr_range();
+            assert!(input.start <= residuals.start && residuals.end <= input.end);
+        }
+    }
+}
+
+#[test]
+fn test_decode_ref_matches_decode_file() {
+    let flo_data = encode_lossless();
+    let decoder = Decoder::new();
+
+    let owned = decoder
+        .decode_file(&Reader::new().read(&flo_data).unwrap())
+        .unwrap();
+    let borrowed = decoder
+        .decode_ref(&Reader::new().read_ref(&flo_data).unwrap())
+        .unwrap();
+    assert_eq!(borrowed, owned);
+
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let frame = decoder
+        .decode_frame_ref(&file.frame(1).unwrap(), 2)
+        .unwrap();
+    assert_eq!(frame, owned[8000 * 2..16000 * 2]);
+    assert_eq!(decode_frame_at(&flo_data, 1).unwrap(), frame);
+}
+
+#[test]
+fn test_lossy_frames_from_ref() {
+    let sample_rate = 44100u32;
+    let flo_data = TransformEncoder::new(sample_rate, 2, 0.6)
+        .encode_to_flo(&test_signal(sample_rate, 2, 1), &[])
+        .unwrap();
+
+    // owned path, frame by frame
+    let owned = Reader::new().read(&flo_data).unwrap();
+    let mut decoder = LossyDecoder::new(sample_rate, 2);
+    let mut expected = Vec::new();
+    for (i, frame) in owned.frames.iter().enumerate() {
+        let transform = deserialize_frame(&frame.channels[0].residuals).unwrap();
+        let samples = decoder.decode_frame(&transform);
+        if i > 0 {
+            expected.extend(samples);
+        }
+    }
+    // the padding after the audio is cut off
+    expected.truncate(owned.header.gapless_length().unwrap() as usize * 2);
+    assert_eq!(decode(&flo_data).unwrap(), expected);
+
+    // random access only needs the previous frame for the overlap
+    let hop = 1024 * 2;
+    for k in [1usize, 5, 20] {
+        let frame = decode_frame_at(&flo_data, k as u32).unwrap();
+        assert_eq!(frame, expected[(k - 1) * hop..k * hop]);
+    }
+}
+
+#[test]
+fn test_frame_ref_crc_mismatch() {
+    let mut flo_data = encode_lossless();
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let data_start = flo_data.len() - file.metadata.len() - file.extra.len() - file.data.len();
+    let target = data_start + file.toc[1].byte_offset as usize + 20;
+    flo_data[target] ^= 0xFF;
+
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    assert!(file.frame(0).is_ok());
+    let err = file.frame(1).unwrap_err();
+    assert!(err.contains("CRC"), "unexpected error: {}", err);
+    assert!(file.frame(3).is_err());
+    assert!(Decoder::new().decode_ref(&file).is_err());
+}
+
+#[test]
+fn test_read_ref_truncated() {
+    let flo_data = encode_lossless();
+    let truncated = &flo_data[..flo_data.len() - 100];
+
+    assert!(Reader::new().read_ref(truncated).is_err());
+
+    let file = Reader::new()
+        .with_recovery(true)
+        .read_ref(truncated)
+        .unwrap();
+    assert!(file.metadata.is_empty());
+    assert!(file.frame(0).is_ok());
+    assert!(file.frame(file.num_frames() - 1).is_err());
+}
@@ -0,0 +1,132 @@
+//! ReplayGain tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Album loudness over the combined gated blocks of several tracks
+//! - Track gain filled in at encode time
+//! - Writing track and album gain into existing files without re-encoding
+
+use libflo_audio::replay_gain::{apply_album_gain, apply_track_gain};
+use libflo_audio::{
+    compute_ebu_r128_loudness, decode, encode, get_metadata_bytes_native, AlbumLoudness, Encoder,
+    FloMetadata, REPLAYGAIN_REFERENCE_LUFS,
+};
+
+fn sine(sample_rate: u32, seconds: f32, amp: f32) -> Vec<f32> {
+    (0..(sample_rate as f32 * seconds) as usize)
+        .map(|i| amp * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin())
+        .collect()
+}
+
+fn metadata(data: &[u8]) -> FloMetadata {
+    FloMetadata::from_msgpack(&get_metadata_bytes_native(data).unwrap()).unwrap()
+}
+
+#[test]
+fn test_album_loudness_gates_all_blocks() {
+    let loud = sine(8000, 4.0, 0.5);
+    let quiet = sine(8000, 1.0, 0.25);
+
+    let mut album = AlbumLoudness::new();
+    let loud_track = album.add_track(&loud, 1, 8000);
+    let quiet_track = album.add_track(&quiet, 1, 8000);
+    let metrics = album.metrics();
+
+    // 6 dB apart, the quiet track is only a fifth of the album
+    assert!((loud_track.integrated_lufs - quiet_track.integrated_lufs - 6.0).abs() < 0.5);
+    let mean = (loud_track.integrated_lufs + quiet_track.integrated_lufs) / 2.0;
+    assert!(metrics.integrated_lufs < loud_track.integrated_lufs);
+    assert!(metrics.integrated_lufs > mean + 1.5);
+    assert_eq!(metrics.true_peak_dbtp, loud_track.true_peak_dbtp);
+
+    // 20 dB down falls under the relative gate of the album
+    let mut gated = AlbumLoudness::new();
+    gated.add_track(&loud, 1, 8000);
+    gated.add_track(&sine(8000, 1.0, 0.05), 1, 8000);
+    assert!((gated.metrics().integrated_lufs - loud_track.integrated_lufs).abs() < 1e-9);
+    assert_eq!(
+        metrics.replay_gain_db(),
+        REPLAYGAIN_REFERENCE_LUFS - metrics.integrated_lufs
+    );
+
+    // one track is its own album
+    let mut single = AlbumLoudness::new();
+    single.add_track(&loud, 1, 8000);
+    let alone = compute_ebu_r128_loudness(&loud, 1, 8000);
+    assert!((single.metrics().integrated_lufs - alone.integrated_lufs).abs() < 1e-9);
+
+    // nothing measured yet
+    assert_eq!(AlbumLoudness::new().metrics().integrated_lufs, -23.0);
+}
+
+#[test]
+fn test_encode_fills_track_gain() {
+    let samples = sine(8000, 2.0, 0.25);
+    let flo_data = encode(&samples, 8000, 1, 16, None).unwrap();
+    let meta = metadata(&flo_data);
+
+    let metrics = compute_ebu_r128_loudness(&samples, 1, 8000);
+    assert_eq!(
+        meta.integrated_loudness_lufs,
+        Some(metrics.integrated_lufs as f32)
+    );
+    assert_eq!(meta.true_peak_dbtp, Some(metrics.true_peak_dbtp as f32));
+    assert_eq!(
+        meta.loudness_range_lu,
+        Some(metrics.loudness_range_lu as f32)
+    );
+    assert_eq!(meta.track_gain_db, Some(metrics.replay_gain_db() as f32));
+    assert_eq!(meta.album_gain_db, None);
+
+    // a gain that's already there is kept
+    let mut given = FloMetadata::new();
+    given.track_gain_db = Some(-3.0);
+    let flo_data = encode(&samples, 8000, 1, 16, Some(given.to_msgpack().unwrap())).unwrap();
+    assert_eq!(metadata(&flo_data).track_gain_db, Some(-3.0));
+}
+
+#[test]
+fn test_apply_album_gain_without_reencoding() {
+    let mut meta = FloMetadata::new();
+    meta.title = Some("Loud".to_string());
+    let loud = Encoder::new(8000, 1, 16)
+        .encode(&sine(8000, 3.0, 0.5), &meta.to_msgpack().unwrap())
+        .unwrap();
+    let quiet = Encoder::new(8000, 1, 16)
+        .encode(&sine(8000, 2.0, 0.25), &[])
+        .unwrap();
+
+    let updated = apply_album_gain(&[&loud, &quiet]).unwrap();
+    assert_eq!(updated.len(), 2);
+
+    let loud_meta = metadata(&updated[0]);
+    let quiet_meta = metadata(&updated[1]);
+    assert_eq!(loud_meta.title.as_deref(), Some("Loud"));
+    assert!(loud_meta.track_gain_db.unwrap() < quiet_meta.track_gain_db.unwrap());
+
+    // one album gain for both, between the two track gains
+    let album_gain = loud_meta.album_gain_db.unwrap();
+    assert_eq!(quiet_meta.album_gain_db, Some(album_gain));
+    assert_eq!(
+        quiet_meta.album_true_peak_dbtp,
+        loud_meta.album_true_peak_dbtp
+    );
+    assert!(album_gain > loud_meta.track_gain_db.unwrap());
+    assert!(album_gain < quiet_meta.track_gain_db.unwrap());
+    assert_eq!(
+        loud_meta.album_loudness_lufs,
+        Some(REPLAYGAIN_REFERENCE_LUFS as f32 - album_gain)
+    );
+
+    // the audio is untouched
+    assert_eq!(decode(&updated[0]).unwrap(), decode(&loud).unwrap());
+    assert_eq!(decode(&updated[1]).unwrap(), decode(&quiet).unwrap());
+
+    // a single track keeps whatever album gain it has
+    let track = apply_track_gain(&updated[1]).unwrap();
+    let track_meta = metadata(&track);
+    assert_eq!(track_meta.track_gain_db, quiet_meta.track_gain_db);
+    assert_eq!(track_meta.album_gain_db, Some(album_gain));
+
+    assert!(apply_album_gain(&[]).is_err());
+    assert!(apply_album_gain(&[&loud, b"not flo"]).is_err());
+}
@@ -0,0 +1,331 @@
+//! RTP packetization tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Packing small frames and splitting big ones to fit the MTU
+//! - Rejecting fragments that overlap
+//! - Bounding the frames reassembled from fragments
+//! - Reordering, duplicates and loss through the jitter buffer
+//! - Skipping a huge jump in frame index in one step
+//! - Joining mid-stream and SSRC changes
+//! - A lossy low-latency stream over UDP loopback
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use libflo_audio::streaming::rtp::RTP_HEADER_SIZE;
+use libflo_audio::{
+    Concealment, Depacketizer, EncodedFrame, Packetizer, StreamingDecoder, StreamingEncoder,
+};
+
+const SAMPLE_RATE: u32 = 48000;
+
+fn test_signal(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|i| (i as f32 * 0.021).sin() * 0.4 + (i as f32 * 0.13).sin() * 0.05)
+        .collect()
+}
+
+fn encode_frames(encoder: &mut StreamingEncoder, samples: &[f32]) -> Vec<EncodedFrame> {
+    encoder.push_samples(samples).unwrap();
+    encoder.finish().unwrap();
+    std::iter::from_fn(|| encoder.next_frame()).collect()
+}
+
+fn assert_same_frames(actual: &[EncodedFrame], expected: &[&EncodedFrame]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, b) in actual.iter().zip(expected) {
+        assert_eq!(a.index, b.index);
+        assert_eq!(a.first_sample, b.first_sample);
+        assert_eq!(a.samples, b.samples);
+        assert_eq!(a.timestamp_ms, b.timestamp_ms);
+        assert_eq!(a.data, b.data);
+    }
+}
+
+fn drain(depacketizer: &mut Depacketizer) -> Vec<EncodedFrame> {
+    std::iter::from_fn(|| depacketizer.next_frame()).collect()
+}
+
+#[test]
+fn test_aggregate_and_fragment() {
+    // 10 ms frames are small enough to share packets
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(10.0);
+    let small = encode_frames(&mut encoder, &test_signal(4800));
+    let mut packetizer = Packetizer::new(encoder.stream_params()).with_mtu(1200);
+    let packets = packetizer.packetize(&small);
+    assert!(packets.len() < small.len());
+    assert!(packets.iter().all(|p| p.len() <= 1200));
+
+    // the first packet describes the stream
+    let mut depacketizer = Depacketizer::new();
+    depacketizer.push(&packets[0]).unwrap();
+    assert_eq!(depacketizer.params(), Some(encoder.stream_params()));
+    for packet in &packets[1..] {
+        depacketizer.push(packet).unwrap();
+    }
+    depacketizer.flush();
+    assert_same_frames(&drain(&mut depacketizer), &small.iter().collect::<Vec<_>>());
+
+    // one second frames get split, the marker ends each frame
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 2, 16);
+    let big = encode_frames(&mut encoder, &test_signal(SAMPLE_RATE as usize * 3));
+    let mut packetizer = Packetizer::new(encoder.stream_params()).with_mtu(500);
+    let packets = packetizer.packetize(&big);
+    assert!(big[0].data.len() > 500);
+    assert!(packets.len() > 2 * big.len());
+    assert!(packets.iter().all(|p| p.len() <= 500));
+    let markers = packets.iter().filter(|p| p[1] & 0x80 != 0).count();
+    assert_eq!(markers, big.len());
+
+    // any order, as long as nothing is missing
+    let mut depacketizer = Depacketizer::new();
+    for packet in packets.iter().rev() {
+        depacketizer.push(packet).unwrap();
+    }
+    depacketizer.flush();
+    assert_same_frames(&drain(&mut depacketizer), &big.iter().collect::<Vec<_>>());
+
+    // a fragment moved half a chunk back overlaps its neighbour
+    let frame = std::slice::from_ref(&big[0]);
+    let packets = Packetizer::new(encoder.stream_params())
+        .with_mtu(500)
+        .packetize(frame);
+    let offset_at = RTP_HEADER_SIZE + 1 + 20 + 4;
+    let offset = |p: &[u8]| u32::from_le_bytes(p[offset_at..offset_at + 4].try_into().unwrap());
+    let mut overlapping = packets[2].clone();
+    let moved = offset(&packets[2]) / 2;
+    overlapping[offset_at..offset_at + 4].copy_from_slice(&moved.to_le_bytes());
+    overlapping[2..4].copy_from_slice(&1000u16.to_be_bytes());
+
+    let mut depacketizer = Depacketizer::new();
+    depacketizer.push(&packets[0]).unwrap();
+    depacketizer.push(&packets[1]).unwrap();
+    assert!(depacketizer.push(&overlapping).is_err());
+    for packet in &packets[2..] {
+        depacketizer.push(packet).unwrap();
+    }
+    depacketizer.flush();
+    assert_same_frames(&drain(&mut depacketizer), &[&big[0]]);
+}
+
+#[test]
+fn test_fragment_flood_is_bounded() {
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 2, 16);
+    let big = encode_frames(&mut encoder, &test_signal(SAMPLE_RATE as usize * 2));
+    let packets = Packetizer::new(encoder.stream_params())
+        .with_mtu(500)
+        .packetize(&big[..1]);
+
+    // garbage claiming frames far ahead, each as big as a payload may be
+    let index_at = RTP_HEADER_SIZE + 1;
+    let size_at = index_at + 20;
+    let flood: Vec<Vec<u8>> = (0..500u32)
+        .map(|i| {
+            let mut packet = packets[1].clone();
+            packet[2..4].copy_from_slice(&(2000 + i as u16).to_be_bytes());
+            packet[index_at..index_at + 4].copy_from_slice(&(100 + i).to_le_bytes());
+            packet[size_at..size_at + 4].copy_from_slice(&(16u32 << 20).to_le_bytes());
+            packet
+        })
+        .collect();
+
+    let mut depacketizer = Depacketizer::new();
+    depacketizer.push(&packets[0]).unwrap();
+    depacketizer.push(&packets[1]).unwrap();
+    for packet in &flood {
+        depacketizer.push(packet).unwrap();
+    }
+    assert_eq!(depacketizer.buffered_frames(), 1);
+
+    // the real frame still comes together
+    for packet in &packets[2..] {
+        depacketizer.push(packet).unwrap();
+    }
+    depacketizer.flush();
+    assert_same_frames(&drain(&mut depacketizer), &[&big[0]]);
+
+    // frames the jitter buffer would wait for are let in, up to its depth
+    let mut depacketizer = Depacketizer::new();
+    for packet in flood.iter().take(10) {
+        depacketizer.push(packet).unwrap();
+    }
+    assert_eq!(depacketizer.buffered_frames(), 4);
+}
+
+#[test]
+fn test_jitter_buffer_reorder_and_loss() {
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(20.0);
+    let frames = encode_frames(&mut encoder, &test_signal(960 * 20));
+    let mut packetizer = Packetizer::new(encoder.stream_params()).with_params_interval(0);
+
+    // one frame per packet, params first
+    let mut packets: Vec<Vec<u8>> = frames
+        .iter()
+        .flat_map(|frame| packetizer.packetize(std::slice::from_ref(frame)))
+        .collect();
+    assert_eq!(packets.len(), frames.len() + 1);
+
+    // swap neighbours, lose frame 7, send frame 12 twice
+    packets.swap(3, 4);
+    packets.swap(10, 11);
+    let duplicate = packets[13].clone();
+    packets.insert(15, duplicate);
+    packets.remove(8);
+
+    let mut depacketizer = Depacketizer::new().with_jitter_depth(2);
+    let mut out = Vec::new();
+    for packet in &packets {
+        depacketizer.push(packet).unwrap();
+        out.extend(drain(&mut depacketizer));
+    }
+    depacketizer.flush();
+    out.extend(drain(&mut depacketizer));
+
+    let expected: Vec<&EncodedFrame> = frames.iter().filter(|f| f.index != 7).collect();
+    assert_same_frames(&out, &expected);
+    assert_eq!(depacketizer.lost_frames().len(), 1);
+    assert_eq!(depacketizer.lost_frames()[0], 7..8);
+
+    let stats = depacketizer.stats();
+    assert_eq!(stats.lost, 1);
+    assert_eq!(stats.duplicates, 1);
+    assert_eq!(stats.reordered, 2);
+    assert_eq!(stats.received, frames.len() as u64);
+
+    // frame 7 turning up after it was given up on is late
+    let mut late = Packetizer::new(encoder.stream_params())
+        .packetize(std::slice::from_ref(&frames[7]))
+        .pop()
+        .unwrap();
+    late[2..4].copy_from_slice(&100u16.to_be_bytes());
+    depacketizer.push(&late).unwrap();
+    assert_eq!(depacketizer.stats().late, 1);
+    assert!(depacketizer.next_frame().is_none());
+}
+
+#[test]
+fn test_jump_far_ahead() {
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(10.0);
+    let mut frames = encode_frames(&mut encoder, &test_signal(480 * 4));
+    let mut packetizer = Packetizer::new(encoder.stream_params());
+
+    // a sender restarting its counter near the top of the index range
+    frames[2].index = 4_000_000_000;
+    frames[3].index = u32::MAX;
+    let mut depacketizer = Depacketizer::new().with_jitter_depth(0);
+    let mut out = Vec::new();
+    for frame in &frames {
+        for packet in packetizer.packetize(std::slice::from_ref(frame)) {
+            depacketizer.push(&packet).unwrap();
+        }
+        out.extend(drain(&mut depacketizer));
+    }
+
+    let indices: Vec<u32> = out.iter().map(|f| f.index).collect();
+    assert_eq!(indices, vec![0, 1, 4_000_000_000, u32::MAX]);
+    assert_eq!(
+        depacketizer.lost_frames(),
+        &[2..4_000_000_000, 4_000_000_001..u32::MAX]
+    );
+    assert_eq!(
+        depacketizer.lost_frame_count(),
+        4_000_000_000 - 2 + u32::MAX as u64 - 4_000_000_001
+    );
+}
+
+#[test]
+fn test_join_mid_stream_and_new_source() {
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16).with_low_latency(10.0);
+    let frames = encode_frames(&mut encoder, &test_signal(480 * 40));
+    let mut packetizer = Packetizer::new(encoder.stream_params())
+        .with_ssrc(7)
+        .with_params_interval(10);
+    let packets: Vec<Vec<u8>> = frames
+        .iter()
+        .flat_map(|frame| packetizer.packetize(std::slice::from_ref(frame)))
+        .collect();
+
+    // params come around again for a late joiner
+    let mut depacketizer = Depacketizer::new().with_jitter_depth(0);
+    for packet in &packets[5..] {
+        depacketizer.push(packet).unwrap();
+    }
+    assert_eq!(depacketizer.params(), Some(encoder.stream_params()));
+    let out = drain(&mut depacketizer);
+    assert_eq!(out.first().map(|f| f.index), Some(4));
+    assert!(depacketizer.lost_frames().is_empty());
+    let packet = depacketizer.live_packet(&out[0]).unwrap();
+    assert_eq!(&packet[..4], b"FLO~");
+
+    // another source is another stream
+    let mut other = Packetizer::new(encoder.stream_params()).with_ssrc(8);
+    for packet in other.packetize(&frames[..2]) {
+        depacketizer.push(&packet).unwrap();
+    }
+    depacketizer.flush();
+    assert_eq!(drain(&mut depacketizer).len(), 2);
+    assert_eq!(depacketizer.stats().received, 2);
+
+    assert!(depacketizer.push(&[1, 2, 3]).is_err());
+    assert!(depacketizer.push(&[0u8; RTP_HEADER_SIZE + 1]).is_err());
+}
+
+#[test]
+fn test_lossy_stream_over_udp_loopback() {
+    let samples = test_signal(SAMPLE_RATE as usize / 2);
+    let mut encoder = StreamingEncoder::new_lossy(SAMPLE_RATE, 1, 0.6).with_low_latency(10.0);
+    let mut packetizer = Packetizer::new(encoder.stream_params()).with_ssrc(0xF10);
+
+    let mut packets = Vec::new();
+    for chunk in samples.chunks(480) {
+        encoder.push_samples(chunk).unwrap();
+        let ready: Vec<EncodedFrame> = std::iter::from_fn(|| encoder.next_frame()).collect();
+        packets.extend(packetizer.packetize(&ready));
+    }
+    encoder.finish().unwrap();
+    let ready: Vec<EncodedFrame> = std::iter::from_fn(|| encoder.next_frame()).collect();
+    packets.extend(packetizer.packetize(&ready));
+
+    // reorder a pair and lose the packet with frame 20 on the way
+    packets.swap(5, 6);
+    let lost = packets.remove(21);
+    assert_eq!(lost[RTP_HEADER_SIZE], 1);
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.connect(receiver.local_addr().unwrap()).unwrap();
+
+    let mut depacketizer = Depacketizer::new().with_jitter_depth(3);
+    let mut decoder = StreamingDecoder::new_live().with_concealment(Concealment::Interpolate);
+    let mut decoded = Vec::new();
+    let mut buf = [0u8; 2048];
+    for packet in &packets {
+        sender.send(packet).unwrap();
+        let len = receiver.recv(&mut buf).unwrap();
+        depacketizer.push(&buf[..len]).unwrap();
+        while let Some(frame) = depacketizer.next_frame() {
+            decoder
+                .feed(&depacketizer.live_packet(&frame).unwrap())
+                .unwrap();
+            decoded.extend(decoder.decode_available().unwrap());
+        }
+    }
+    depacketizer.flush();
+    while let Some(frame) = depacketizer.next_frame() {
+        decoder
+            .feed(&depacketizer.live_packet(&frame).unwrap())
+            .unwrap();
+        decoded.extend(decoder.decode_available().unwrap());
+    }
+
+    assert_eq!(depacketizer.lost_frames().len(), 1);
+    assert_eq!(depacketizer.lost_frames()[0], 20..21);
+    assert_eq!(depacketizer.stats().lost, 1);
+    assert_eq!(decoder.damaged_frames(), &[20]);
+    // the lost hop is concealed, so the timeline stays intact
+    assert_eq!(decoded.len(), samples.len());
+    assert_eq!(decoder.algorithmic_delay_ms(), Some(12.5));
+}
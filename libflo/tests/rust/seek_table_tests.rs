@@ -0,0 +1,222 @@
+//! Seek table tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Building a seek table from the header and TOC bytes alone
+//! - Exact lookups by sample where the millisecond timestamps are rounded
+//! - Lossy lookups skipping the preroll frame
+//! - Sample positions of TOCs written before 1.4
+//! - Older readers skipping the sample positions
+
+use libflo_audio::edit::trim;
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::seeking::{decode_frame_at, get_toc, index_size, seek_to_time, SeekTable};
+use libflo_audio::{compute_crc32, decode, Encoder, Reader, FLAG_GAPLESS, VERSION_MINOR};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn mono_signal(samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|i| (i as f32 * 0.021).sin() * 0.5)
+        .collect()
+}
+
+/// lossless file with frames of odd lengths, so timestamps get rounded
+fn trimmed_file() -> Vec<u8> {
+    let flo_data = Encoder::new(SAMPLE_RATE, 1, 16)
+        .encode(&mono_signal(SAMPLE_RATE as usize * 5), &[])
+        .unwrap();
+    trim(&flo_data, 3_333, 37_777).unwrap()
+}
+
+/// Rewrite a current file as 1.3, TOC without the sample positions
+fn downgrade_toc(flo_data: &[u8]) -> Vec<u8> {
+    let header_size = u64::from_le_bytes(flo_data[30..38].try_into().unwrap()) as usize;
+    let toc_size = u64::from_le_bytes(flo_data[38..46].try_into().unwrap()) as usize;
+    let toc_start = 4 + header_size;
+    let toc = &flo_data[toc_start..toc_start + toc_size];
+
+    let entries = u32::from_le_bytes(toc[..4].try_into().unwrap()) as usize;
+    let old_toc_size = 4 + entries * 20;
+
+    let mut old = flo_data[..toc_start].to_vec();
+    old.extend_from_slice(&toc[..old_toc_size]);
+    old.extend_from_slice(&flo_data[toc_start + toc_size..]);
+    old[5] = 3;
+    old[38..46].copy_from_slice(&(old_toc_size as u64).to_le_bytes());
+
+    // reseal the header
+    let mut covered = old[4..70].to_vec();
+    covered.extend_from_slice(&old[74..toc_start]);
+    let crc = compute_crc32(&covered);
+    old[70..74].copy_from_slice(&crc.to_le_bytes());
+    old
+}
+
+/// Rewrite a current file as 1.2: no header crc, no gapless fields
+fn downgrade_to_1_2(flo_data: &[u8]) -> Vec<u8> {
+    let old = downgrade_toc(flo_data);
+    let header_size = u64::from_le_bytes(old[30..38].try_into().unwrap()) as usize;
+
+    let mut older = old[..70].to_vec();
+    older.extend_from_slice(&old[4 + header_size..]);
+    older[5] = 2;
+    older[6] &= !(FLAG_GAPLESS as u8);
+    older[30..38].copy_from_slice(&66u64.to_le_bytes());
+    older
+}
+
+#[test]
+fn test_seek_table_from_index_only() {
+    let flo_data = trimmed_file();
+    let size = index_size(&flo_data[..100]).unwrap() as usize;
+    let index = &flo_data[..size];
+
+    let table = SeekTable::parse(index).unwrap();
+    let file = Reader::new().read(&flo_data).unwrap();
+    assert_eq!(table.header.version_minor, VERSION_MINOR);
+    assert_eq!(table.len(), file.frames.len());
+
+    // sample positions add up the frame lengths
+    let mut first_sample = 0;
+    for (i, (entry, frame)) in table.entries.iter().zip(&file.frames).enumerate() {
+        assert_eq!(entry.byte_offset, file.toc[i].byte_offset);
+        assert_eq!(entry.first_sample, first_sample);
+        assert_eq!(table.frame_samples(i), Some(frame.frame_samples as u64));
+        first_sample += frame.frame_samples as u64;
+    }
+    assert_eq!(table.frame_samples(table.len()), None);
+
+    // the rest of the file isn't needed, the TOC is
+    assert_eq!(get_toc(index).unwrap().len(), table.len());
+    assert_eq!(seek_to_time(index, 2500).unwrap().frame_index, 2);
+    assert!(SeekTable::parse(&flo_data[..size - 1]).is_err());
+    assert!(index_size(&flo_data[..20]).is_err());
+}
+
+#[test]
+fn test_seek_by_sample_is_exact() {
+    let flo_data = trimmed_file();
+    let table = SeekTable::parse(&flo_data).unwrap();
+    let decoded = decode(&flo_data).unwrap();
+
+    // the timestamps alone would put these frames a few samples early
+    let rounded = table.entries.iter().any(|entry| {
+        (entry.timestamp_ms as u64 * SAMPLE_RATE as u64).div_ceil(1000) != entry.first_sample
+    });
+    assert!(rounded);
+
+    for sample in [0, 1, 4_666, 4_667, 12_000, 20_667, 34_443] {
+        let result = table.seek_to_sample(sample).unwrap();
+        let frame = decode_frame_at(&flo_data, result.frame_index).unwrap();
+        assert_eq!(
+            frame[result.sample_offset as usize], decoded[sample as usize],
+            "sample {}",
+            sample
+        );
+    }
+
+    // past the end lands at the end of the last frame
+    let last = table.seek_to_sample(u64::MAX).unwrap();
+    assert_eq!(last.frame_index as usize, table.len() - 1);
+    assert_eq!(
+        last.sample_offset as u64,
+        table.frame_samples(table.len() - 1).unwrap()
+    );
+
+    // times round down to the sample
+    let result = table.seek_to_ms(1234).unwrap();
+    let entry = &table.entries[result.frame_index as usize];
+    assert_eq!(entry.first_sample + result.sample_offset as u64, 1234 * 8);
+}
+
+#[test]
+fn test_seek_lossy_skips_preroll() {
+    let flo_data = TransformEncoder::new(SAMPLE_RATE, 1, 0.6)
+        .encode_to_flo(&mono_signal(SAMPLE_RATE as usize * 2 + 5), &[])
+        .unwrap();
+    let table = SeekTable::parse(&flo_data).unwrap();
+    let decoded = decode(&flo_data).unwrap();
+
+    // frame 0 decodes to nothing, the audio starts in frame 1
+    let start = table.seek_to_ms(0).unwrap();
+    assert_eq!((start.frame_index, start.sample_offset), (1, 0));
+
+    for sample in [0, 1023, 1024, 5000, decoded.len() as u64 - 1] {
+        let result = table.seek_to_sample(sample).unwrap();
+        let frame = decode_frame_at(&flo_data, result.frame_index).unwrap();
+        assert_eq!(
+            frame[result.sample_offset as usize],
+            decoded[sample as usize]
+        );
+    }
+
+    // the end is where the padding starts
+    let end = table.seek_to_sample(u64::MAX).unwrap();
+    let frame = decode_frame_at(&flo_data, end.frame_index).unwrap();
+    assert_eq!(end.frame_index as usize, table.len() - 1);
+    assert_eq!(end.sample_offset as usize, frame.len());
+}
+
+#[test]
+fn test_legacy_toc_positions() {
+    let lossless = Encoder::new(SAMPLE_RATE, 1, 16)
+        .encode(&mono_signal(SAMPLE_RATE as usize * 3 + 5), &[])
+        .unwrap();
+    let lossy = TransformEncoder::new(SAMPLE_RATE, 1, 0.6)
+        .encode_to_flo(&mono_signal(SAMPLE_RATE as usize * 3 + 5), &[])
+        .unwrap();
+
+    for flo_data in [lossless, lossy] {
+        let old = downgrade_toc(&flo_data);
+        let table = SeekTable::parse(&old).unwrap();
+        assert_eq!(table.header.version_minor, 3);
+
+        let expected: Vec<u64> = get_toc(&flo_data)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.first_sample)
+            .collect();
+        let converted: Vec<u64> = table.entries.iter().map(|e| e.first_sample).collect();
+        assert_eq!(converted, expected);
+        assert_eq!(decode(&old).unwrap(), decode(&flo_data).unwrap());
+    }
+
+    // 1.2 lossy files have no encoder delay to take the hop from, and at
+    // 44.1 kHz the timestamps would put frame 1 at sample 1015
+    let lossy = TransformEncoder::new(44100, 1, 0.6)
+        .encode_to_flo(&mono_signal(44100), &[])
+        .unwrap();
+    let old = downgrade_to_1_2(&lossy);
+    let table = SeekTable::parse(&old).unwrap();
+    assert_eq!(table.header.version_minor, 2);
+    assert!(!table.header.has_gapless());
+    let converted: Vec<u64> = table.entries.iter().map(|e| e.first_sample).collect();
+    let expected: Vec<u64> = (0..table.len() as u64).map(|i| i * 1024).collect();
+    assert_eq!(converted, expected);
+}
+
+#[test]
+fn test_older_reader_skips_sample_positions() {
+    let flo_data = trimmed_file();
+    let header_size = u64::from_le_bytes(flo_data[30..38].try_into().unwrap()) as usize;
+    let toc_size = u64::from_le_bytes(flo_data[38..46].try_into().unwrap());
+    let entries = u32::from_le_bytes(flo_data[4 + header_size..][..4].try_into().unwrap());
+
+    // still whole 20 byte entries as far as a 1.3 reader can tell
+    assert_eq!((toc_size - 4) % 20, 0);
+    assert!(toc_size > 4 + entries as u64 * 20);
+
+    // read as 1.3 the table is skipped and positions come from timestamps
+    let mut old = flo_data.clone();
+    old[5] = 3;
+    let mut covered = old[4..70].to_vec();
+    covered.extend_from_slice(&old[74..4 + header_size]);
+    let crc = compute_crc32(&covered);
+    old[70..74].copy_from_slice(&crc.to_le_bytes());
+
+    let file = Reader::new().read(&old).unwrap();
+    assert_eq!(file.toc.len(), entries as usize);
+    assert_eq!(decode(&old).unwrap(), decode(&flo_data).unwrap());
+    let table = SeekTable::parse(&old).unwrap();
+    assert_eq!(table.seek_to_ms(2500).unwrap().frame_index, 2);
+}
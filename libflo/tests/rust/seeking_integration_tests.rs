@@ -1,3 +1,5 @@
+#![allow(clippy::module_inception)]
+
 #[cfg(test)]
 mod seeking_integration_tests {
     use libflo_audio::seeking;
@@ -62,7 +64,10 @@ mod seeking_integration_tests {
         assert!(!frame_samples.is_empty(), "Frame should have samples");
 
         // Verify we're in the right place
-        assert!(frame_samples.len() > 0, "Should have decoded valid samples");
+        assert!(
+            !frame_samples.is_empty(),
+            "Should have decoded valid samples"
+        );
     }
 
     #[test]
@@ -73,7 +78,7 @@ mod seeking_integration_tests {
         // Encode lossy with different quality levels
         for quality in 0..=4 {
             let flo_data = encode_lossy(&original, 48000, 2, 16, quality, None)
-                .expect(&format!("Failed to encode lossy quality {}", quality));
+                .unwrap_or_else(|_| panic!("Failed to encode lossy quality {}", quality));
 
             let file_info = info(&flo_data).expect("Failed to get info");
             assert!(file_info.is_lossy, "Should be lossy");
@@ -81,11 +86,11 @@ mod seeking_integration_tests {
 
             // Seek to 2 seconds
             let seek_result = seeking::seek_to_time(&flo_data, 2000)
-                .expect(&format!("Failed to seek quality {}", quality));
+                .unwrap_or_else(|_| panic!("Failed to seek quality {}", quality));
 
             // Decode frame
             let frame_samples = seeking::decode_frame_at(&flo_data, seek_result.frame_index)
-                .expect(&format!("Failed to decode lossy frame quality {}", quality));
+                .unwrap_or_else(|_| panic!("Failed to decode lossy frame quality {}", quality));
 
             assert!(!frame_samples.is_empty(), "Lossy frame should decode");
         }
@@ -103,7 +108,7 @@ mod seeking_integration_tests {
         // Seek to each frame via time and decode
         for entry in toc.iter().take(5) {
             let seek_result = seeking::seek_to_time(&flo_data, entry.timestamp_ms)
-                .expect(&format!("Failed to seek to frame {}", entry.frame_index));
+                .unwrap_or_else(|_| panic!("Failed to seek to frame {}", entry.frame_index));
 
             assert_eq!(
                 seek_result.frame_index, entry.frame_index,
@@ -124,14 +129,14 @@ mod seeking_integration_tests {
         for sr in &sample_rates {
             let samples = create_sine_wave(*sr, 2, 3.0, 440.0);
             let flo_data = encode(&samples, *sr, 2, 16, None)
-                .expect(&format!("Failed to encode at {} Hz", sr));
+                .unwrap_or_else(|_| panic!("Failed to encode at {} Hz", sr));
 
             // Seek to 1.5 seconds
             let seek_result = seeking::seek_to_time(&flo_data, 1500)
-                .expect(&format!("Failed to seek at {} Hz", sr));
+                .unwrap_or_else(|_| panic!("Failed to seek at {} Hz", sr));
 
             let decoded = seeking::decode_frame_at(&flo_data, seek_result.frame_index)
-                .expect(&format!("Failed to decode at {} Hz", sr));
+                .unwrap_or_else(|_| panic!("Failed to decode at {} Hz", sr));
 
             // Expected samples per frame = sample_rate
             let expected_frame_samples = *sr as usize * 2; // 2 channels, 1 second
@@ -150,13 +155,13 @@ mod seeking_integration_tests {
         for ch in &channels {
             let samples = create_sine_wave(44100, *ch, 3.0, 440.0);
             let flo_data = encode(&samples, 44100, *ch, 16, None)
-                .expect(&format!("Failed to encode {} channels", ch));
+                .unwrap_or_else(|_| panic!("Failed to encode {} channels", ch));
 
             let seek_result = seeking::seek_to_time(&flo_data, 1500)
-                .expect(&format!("Failed to seek {} channels", ch));
+                .unwrap_or_else(|_| panic!("Failed to seek {} channels", ch));
 
             let decoded = seeking::decode_frame_at(&flo_data, seek_result.frame_index)
-                .expect(&format!("Failed to decode {} channels", ch));
+                .unwrap_or_else(|_| panic!("Failed to decode {} channels", ch));
 
             // Frame should have 1 second of audio, interleaved
             let expected_samples = 44100 * (*ch as usize);
@@ -229,7 +234,7 @@ mod seeking_integration_tests {
 
         for time_ms in test_times {
             let seek_result = seeking::seek_to_time(&flo_data, time_ms)
-                .expect(&format!("Failed to seek to {} ms", time_ms));
+                .unwrap_or_else(|_| panic!("Failed to seek to {} ms", time_ms));
 
             // Result should be valid
             assert!(
@@ -247,7 +252,7 @@ mod seeking_integration_tests {
 
             // Should be decodable
             let decoded = seeking::decode_frame_at(&flo_data, seek_result.frame_index)
-                .expect(&format!("Failed to decode for {} ms", time_ms));
+                .unwrap_or_else(|_| panic!("Failed to decode for {} ms", time_ms));
 
             assert!(!decoded.is_empty());
         }
@@ -269,7 +274,7 @@ mod seeking_integration_tests {
 
         for entry in &toc {
             let frame_samples = seeking::decode_frame_at(&flo_data, entry.frame_index)
-                .expect(&format!("Failed to decode frame {}", entry.frame_index));
+                .unwrap_or_else(|_| panic!("Failed to decode frame {}", entry.frame_index));
             reassembled.extend(&frame_samples);
         }
 
@@ -319,7 +324,7 @@ mod seeking_integration_tests {
         let frame_samples = 48000; // 1 second of audio
         let tolerance = frame_samples * 2; // Allow 2 frames of difference
         assert!(
-            (full_decoded.len() as i32 - reassembled.len() as i32).abs() <= tolerance as i32,
+            (full_decoded.len() as i32 - reassembled.len() as i32).abs() <= tolerance,
             "Lossy frame lengths should be similar (full: {}, reassembled: {})",
             full_decoded.len(),
             reassembled.len()
@@ -1,3 +1,5 @@
+#![allow(clippy::module_inception)]
+
 #[cfg(test)]
 mod seeking_tests {
     use libflo_audio::seeking;
@@ -85,7 +87,7 @@ mod seeking_tests {
 
         // For a 44100 Hz, 2-channel file, we expect ~88200 samples per frame (1 second * 2 channels)
         // Allow some variation for compression
-        assert!(frame0.len() > 0, "Frame should contain decoded samples");
+        assert!(!frame0.is_empty(), "Frame should contain decoded samples");
     }
 
     #[test]
@@ -99,7 +101,7 @@ mod seeking_tests {
         // Decode each frame
         for frame_idx in 0..num_frames {
             let frame_samples = seeking::decode_frame_at(&flo_data, frame_idx as u32)
-                .expect(&format!("Failed to decode frame {}", frame_idx));
+                .unwrap_or_else(|_| panic!("Failed to decode frame {}", frame_idx));
 
             assert!(
                 !frame_samples.is_empty(),
@@ -242,8 +244,8 @@ mod seeking_tests {
         let positions = vec![0, 500, 1000, 1500, 2000, 3000, 4000, 4500];
 
         for pos in positions {
-            let seek_result =
-                seeking::seek_to_time(&flo_data, pos).expect(&format!("Failed to seek to {}", pos));
+            let seek_result = seeking::seek_to_time(&flo_data, pos)
+                .unwrap_or_else(|_| panic!("Failed to seek to {}", pos));
 
             // Verify result is valid
             assert!(seek_result.frame_index < 10, "Frame index should be valid");
@@ -282,12 +284,12 @@ mod seeking_tests {
 
         for sr in sample_rates {
             let samples = create_test_audio(sr, 2, 2.0);
-            let flo_data =
-                encode(&samples, sr, 2, 16, None).expect(&format!("Failed to encode at {} Hz", sr));
+            let flo_data = encode(&samples, sr, 2, 16, None)
+                .unwrap_or_else(|_| panic!("Failed to encode at {} Hz", sr));
 
             // Seek to 1 second
             let seek_result = seeking::seek_to_time(&flo_data, 1000)
-                .expect(&format!("Failed to seek at {} Hz", sr));
+                .unwrap_or_else(|_| panic!("Failed to seek at {} Hz", sr));
 
             assert!(
                 seek_result.frame_index < 5,
@@ -296,7 +298,7 @@ mod seeking_tests {
 
             // Decode the frame
             let decoded = seeking::decode_frame_at(&flo_data, seek_result.frame_index)
-                .expect(&format!("Failed to decode at {} Hz", sr));
+                .unwrap_or_else(|_| panic!("Failed to decode at {} Hz", sr));
 
             assert!(
                 !decoded.is_empty(),
@@ -312,7 +314,7 @@ mod seeking_tests {
         for ch in channel_counts {
             let samples = create_test_audio(44100, ch, 2.0);
             let flo_data = encode(&samples, 44100, ch, 16, None)
-                .expect(&format!("Failed to encode {} channels", ch));
+                .unwrap_or_else(|_| panic!("Failed to encode {} channels", ch));
 
             // Get info to verify
             let file_info = info(&flo_data).expect("Failed to get info");
@@ -320,9 +322,9 @@ mod seeking_tests {
 
             // Seek and decode
             let seek_result = seeking::seek_to_time(&flo_data, 500)
-                .expect(&format!("Failed to seek {} channels", ch));
+                .unwrap_or_else(|_| panic!("Failed to seek {} channels", ch));
             let decoded = seeking::decode_frame_at(&flo_data, seek_result.frame_index)
-                .expect(&format!("Failed to decode {} channels", ch));
+                .unwrap_or_else(|_| panic!("Failed to decode {} channels", ch));
 
             assert!(!decoded.is_empty());
         }
@@ -355,24 +357,27 @@ mod seeking_tests {
 
     #[test]
     fn test_binary_search_exact_match() {
-        let _toc = vec![
+        let _toc = [
             libflo_audio::core::TocEntry {
                 frame_index: 0,
                 byte_offset: 0,
                 frame_size: 100,
                 timestamp_ms: 0,
+                first_sample: 0,
             },
             libflo_audio::core::TocEntry {
                 frame_index: 1,
                 byte_offset: 100,
                 frame_size: 100,
                 timestamp_ms: 1000,
+                first_sample: 44100,
             },
             libflo_audio::core::TocEntry {
                 frame_index: 2,
                 byte_offset: 200,
                 frame_size: 100,
                 timestamp_ms: 2000,
+                first_sample: 88200,
             },
         ];
 
@@ -4,7 +4,7 @@ use libflo_audio::core::analysis::{
 
 #[test]
 fn test_extract_spectral_fingerprint_mono() {
-    let samples = vec![0.5, -0.3, 0.8, -0.2, 0.1, -0.9];
+    let samples = [0.5, -0.3, 0.8, -0.2, 0.1, -0.9];
     // Repeat the pattern to get more samples for analysis
     let samples: Vec<f32> = samples.iter().cycle().take(100).cloned().collect();
     let fingerprint = extract_spectral_fingerprint(&samples, 1, 44100, Some(1024), Some(512));
@@ -28,8 +28,8 @@ fn test_extract_spectral_fingerprint_mono() {
 
 #[test]
 fn test_extract_spectral_fingerprint_stereo() {
-    let samples = vec![0.5, -0.3, 0.8, -0.2, 0.1, -0.9]; // L,R,L,R,L,R
-                                                         // Repeat the pattern to get more samples for analysis
+    let samples = [0.5, -0.3, 0.8, -0.2, 0.1, -0.9]; // L,R,L,R,L,R
+                                                     // Repeat the pattern to get more samples for analysis
     let samples: Vec<f32> = samples.iter().cycle().take(100).cloned().collect();
     let fingerprint = extract_spectral_fingerprint(&samples, 2, 44100, Some(512), Some(256));
 
@@ -0,0 +1,261 @@
+//! Streaming decoder seeking tests for flo™ audio codec
+//!
+//! Tests for:
+//! - seek_to_frame / seek_to_ms / seek_to_sample on fully and partially downloaded files
+//! - The byte range reported for the next fetch
+//! - Re-priming the MDCT overlap on lossy streams
+
+use libflo_audio::edit::trim;
+use libflo_audio::lossy::TransformEncoder;
+use libflo_audio::{
+    decode, Encoder, FloMetadata, MetadataPlacement, Reader, StreamingDecoder, StreamingEncoder,
+};
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn stereo_signal(seconds: usize) -> Vec<f32> {
+    (0..SAMPLE_RATE as usize * seconds)
+        .flat_map(|i| {
+            let t = i as f32;
+            [(t * 0.021).sin() * 0.5, (t * 0.013).cos() * 0.3]
+        })
+        .collect()
+}
+
+fn lossless_file() -> Vec<u8> {
+    let mut meta = FloMetadata::new();
+    meta.title = Some("Seekable".to_string());
+    Encoder::new(SAMPLE_RATE, 2, 16)
+        .with_metadata_placement(MetadataPlacement::Preview)
+        .encode(&stereo_signal(6), &meta.to_msgpack().unwrap())
+        .unwrap()
+}
+
+fn lossy_file() -> Vec<u8> {
+    TransformEncoder::new(44100, 2, 0.5)
+        .encode_to_flo(&stereo_signal(12), &[])
+        .unwrap()
+}
+
+/// frame-by-frame output of a decoder that never seeks
+fn decode_frames(flo_data: &[u8]) -> Vec<Vec<f32>> {
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(flo_data).unwrap();
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.next_frame().unwrap() {
+        frames.push(frame);
+    }
+    frames
+}
+
+/// bytes up to the end of the TOC
+fn header_and_toc(flo_data: &[u8]) -> usize {
+    let file = Reader::new().read_ref(flo_data).unwrap();
+    4 + file.header.header_size as usize + file.header.toc_size as usize
+}
+
+#[test]
+fn test_seek_to_frame_buffered() {
+    let flo_data = lossless_file();
+    let frames = decode_frames(&flo_data);
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data).unwrap();
+    assert_eq!(decoder.next_frame().unwrap().unwrap(), frames[0]);
+
+    let range = decoder.seek_to_frame(3).unwrap();
+    assert_eq!(range.frame_index, 3);
+    assert_eq!(range.start, range.end);
+    assert_eq!(range.end, flo_data.len() as u64);
+    assert_eq!(decoder.current_frame_index(), 3);
+    assert_eq!(decoder.available_frames(), 3);
+
+    // everything before frame 3 is gone
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let frame_3 = header_and_toc(&flo_data) + file.toc[3].byte_offset as usize;
+    assert_eq!(decoder.buffered_bytes(), flo_data.len() - frame_3);
+
+    assert_eq!(decoder.decode_available().unwrap(), frames[3..].concat());
+
+    // and back again
+    decoder.seek_to_frame(1).unwrap();
+    assert_eq!(decoder.available_frames(), 0);
+    assert!(decoder.next_frame().unwrap().is_none());
+}
+
+#[test]
+fn test_seek_to_ms_lossless() {
+    let flo_data = lossless_file();
+    let reference = decode(&flo_data).unwrap();
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data).unwrap();
+
+    // going backwards needs bytes that were dropped, feed from where it asks
+    for ms in [5999u32, 2750, 1000, 999, 1, 0] {
+        let range = decoder.seek_to_ms(ms).unwrap();
+        decoder
+            .feed(&flo_data[range.start as usize..range.end as usize])
+            .unwrap();
+
+        let skip = (ms as usize * SAMPLE_RATE as usize / 1000) * 2;
+        assert_eq!(
+            decoder.decode_available().unwrap(),
+            reference[skip..],
+            "{}ms",
+            ms
+        );
+    }
+}
+
+#[test]
+fn test_seek_ahead_of_download() {
+    let flo_data = lossless_file();
+    let frames = decode_frames(&flo_data);
+    let toc_end = header_and_toc(&flo_data);
+
+    // header, TOC, the preview and a bit of the first frame
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data[..toc_end + 500]).unwrap();
+    assert!(decoder.metadata().is_some());
+
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let range = decoder.seek_to_frame(4).unwrap();
+    assert_eq!(
+        range.start as usize,
+        toc_end + file.toc[4].byte_offset as usize
+    );
+    assert_eq!(range.end as usize, flo_data.len());
+    assert_eq!(decoder.buffered_bytes(), 0);
+    assert_eq!(decoder.available_frames(), 0);
+
+    // the preview survives the dropped bytes
+    let preview = FloMetadata::from_msgpack(decoder.metadata().unwrap()).unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Seekable"));
+
+    // a range request, arriving in pieces
+    let mut decoded = Vec::new();
+    for chunk in flo_data[range.start as usize..range.end as usize].chunks(1000) {
+        decoder.feed(chunk).unwrap();
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            decoded.push(frame);
+        }
+    }
+    assert_eq!(decoded, frames[4..]);
+    assert!(decoder.metadata().is_some());
+}
+
+#[test]
+fn test_seek_lossy_reprimes_overlap() {
+    let flo_data = lossy_file();
+    let frames = decode_frames(&flo_data);
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let toc_end = header_and_toc(&flo_data);
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data[..toc_end]).unwrap();
+
+    // frame 40 needs frame 39 for its overlap
+    let range = decoder.seek_to_frame(40).unwrap();
+    assert_eq!(
+        range.start as usize,
+        toc_end + file.toc[39].byte_offset as usize
+    );
+
+    decoder
+        .feed(&flo_data[range.start as usize..range.end as usize])
+        .unwrap();
+    assert_eq!(decoder.next_frame().unwrap().unwrap(), frames[40]);
+    assert_eq!(decoder.current_frame_index(), 41);
+    assert_eq!(decoder.decode_available().unwrap(), frames[41..].concat());
+
+    // seeking to the start plays the preroll again
+    decoder.reset();
+    decoder.feed(&flo_data).unwrap();
+    decoder.seek_to_frame(0).unwrap();
+    assert_eq!(decoder.decode_available().unwrap(), frames.concat());
+}
+
+#[test]
+fn test_seek_to_sample_odd_frames() {
+    // frames start off the millisecond grid: 4667, 12667, ...
+    let flo_data = trim(&lossless_file(), 3_333, 40_000).unwrap();
+    let reference = decode(&flo_data).unwrap();
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data).unwrap();
+
+    for sample in [20_000u64, 12_667, 12_666, 4_667, 4_664, 1, 0] {
+        let range = decoder.seek_to_sample(sample).unwrap();
+        decoder
+            .feed(&flo_data[range.start as usize..range.end as usize])
+            .unwrap();
+        assert_eq!(
+            decoder.decode_available().unwrap(),
+            reference[sample as usize * 2..],
+            "sample {}",
+            sample
+        );
+    }
+
+    // 583ms is sample 4664, three samples before frame 1
+    let range = decoder.seek_to_ms(583).unwrap();
+    assert_eq!(range.frame_index, 0);
+    decoder
+        .feed(&flo_data[range.start as usize..range.end as usize])
+        .unwrap();
+    assert_eq!(decoder.decode_available().unwrap(), reference[4664 * 2..]);
+}
+
+#[test]
+fn test_seek_to_ms_lossy() {
+    let flo_data = lossy_file();
+    let output = decode_frames(&flo_data).concat();
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data).unwrap();
+
+    for ms in [2000u32, 1234, 0] {
+        let range = decoder.seek_to_ms(ms).unwrap();
+        decoder
+            .feed(&flo_data[range.start as usize..range.end as usize])
+            .unwrap();
+
+        let skip = (ms as usize * 44100 / 1000) * 2;
+        assert_eq!(
+            decoder.decode_available().unwrap(),
+            output[skip..],
+            "{}ms",
+            ms
+        );
+    }
+}
+
+#[test]
+fn test_seek_errors() {
+    let flo_data = lossless_file();
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&flo_data[..20]).unwrap();
+    assert!(decoder.seek_to_frame(0).is_err());
+
+    decoder.feed(&flo_data[20..]).unwrap();
+    assert!(decoder.seek_to_frame(6).is_err());
+    assert!(decoder.seek_to_frame(5).is_ok());
+
+    // past the end of the audio, the end itself leaves nothing to play
+    let end = 6 * SAMPLE_RATE as u64;
+    let err = decoder.seek_to_sample(end + 1).unwrap_err();
+    assert!(err.contains("past the end"), "{}", err);
+    assert!(decoder.seek_to_ms(7000).is_err());
+    decoder.seek_to_sample(end).unwrap();
+    assert!(decoder.decode_available().unwrap().is_empty());
+
+    let mut encoder = StreamingEncoder::new(SAMPLE_RATE, 1, 16);
+    encoder.push_samples(&[0.1; 9000]).unwrap();
+    let packet = encoder.next_live_packet().unwrap();
+
+    let mut live = StreamingDecoder::new();
+    live.feed(&packet).unwrap();
+    assert!(live.seek_to_ms(0).is_err());
+}
@@ -5,9 +5,16 @@
 //! - StreamingEncoder for live encoding
 //! - Network simulation (chunked data arrival)
 //! - Quality verification (streaming vs standard decode)
+//! - Live packet streams (no header/TOC, joining mid-stream, index jumps)
+//! - Bounded memory and backpressure
 
 use libflo_audio::lossy::TransformEncoder;
-use libflo_audio::{Decoder, DecoderState, Encoder, StreamingDecoder};
+use libflo_audio::streaming::live::write_live_packet;
+use libflo_audio::streaming::LivePacketHeader;
+use libflo_audio::{
+    Concealment, Decoder, DecoderState, Encoder, FloMetadata, MetadataPlacement, Reader,
+    StreamingDecoder, StreamingEncoder,
+};
 
 #[test]
 fn test_streaming_decoder_basic() {
@@ -245,7 +252,7 @@ fn test_streaming_lossy_stereo() {
     assert_eq!(info.channels, 2);
 
     let decoded = decoder.decode_available().unwrap();
-    assert!(decoded.len() > 0, "Should decode some samples");
+    assert!(!decoded.is_empty(), "Should decode some samples");
 }
 
 #[test]
@@ -273,7 +280,7 @@ fn test_streaming_lossy_chunked() {
     assert!(info.is_lossy, "Should detect lossy");
 
     let decoded = decoder.decode_available().unwrap();
-    assert!(decoded.len() > 0);
+    assert!(!decoded.is_empty());
 }
 
 /// Test true frame-by-frame streaming decode with next_frame()
@@ -346,7 +353,7 @@ fn test_streaming_next_frame_lossy() {
         frame_count >= 2,
         "Should have at least 2 frames (after skipping preroll)"
     );
-    assert!(all_samples.len() > 0, "Should have decoded samples");
+    assert!(!all_samples.is_empty(), "Should have decoded samples");
 }
 
 /// Test incremental streaming with progressive frame decode
@@ -731,3 +738,491 @@ fn test_streaming_individual_frame_correctness() {
         "Should decode all samples"
     );
 }
+
+/// Encode samples as a live packet stream, one Vec per packet
+fn encode_live_packets(samples: &[f32], sample_rate: u32, channels: u8) -> Vec<Vec<u8>> {
+    let mut encoder = StreamingEncoder::new(sample_rate, channels, 16);
+    encoder.push_samples(samples).unwrap();
+
+    let mut packets = Vec::new();
+    while let Some(packet) = encoder.next_live_packet() {
+        packets.push(packet);
+    }
+    if let Some(frame) = encoder.flush().unwrap() {
+        packets.push(encoder.live_packet(&frame));
+    }
+    packets
+}
+
+#[test]
+fn test_streaming_encoder_matches_standard_encoder() {
+    let sample_rate = 16000u32;
+    let channels = 2u8;
+
+    let mut samples = Vec::new();
+    for i in 0..(sample_rate as usize * 5 / 2) {
+        samples.push((i as f32 * 0.013).sin() * 0.6);
+        samples.push((i as f32 * 0.007).cos() * 0.4);
+    }
+
+    let reference = Decoder::new()
+        .decode(
+            &Encoder::new(sample_rate, channels, 16)
+                .encode(&samples, &[])
+                .unwrap(),
+        )
+        .unwrap();
+
+    let mut encoder = StreamingEncoder::new(sample_rate, channels, 16);
+    encoder.push_samples(&samples).unwrap();
+    let flo_data = encoder.finalize(&[]).unwrap();
+
+    let decoded = Decoder::new().decode(&flo_data).unwrap();
+    assert_eq!(decoded, reference);
+}
+
+#[test]
+fn test_live_stream_roundtrip() {
+    let sample_rate = 16000u32;
+    let channels = 2u8;
+
+    let mut samples = Vec::new();
+    for i in 0..(sample_rate as usize * 7 / 2) {
+        samples.push((i as f32 * 0.011).sin() * 0.5);
+        samples.push((i as f32 * 0.005).sin() * 0.3);
+    }
+
+    let reference = Decoder::new()
+        .decode(
+            &Encoder::new(sample_rate, channels, 16)
+                .encode(&samples, &[])
+                .unwrap(),
+        )
+        .unwrap();
+
+    let packets = encode_live_packets(&samples, sample_rate, channels);
+    assert_eq!(packets.len(), 4);
+
+    let stream: Vec<u8> = packets.concat();
+
+    // plain new() picks up the live stream from the sync word
+    let mut decoder = StreamingDecoder::new();
+    let mut decoded = Vec::new();
+    for chunk in stream.chunks(777) {
+        decoder.feed(chunk).unwrap();
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            decoded.extend(frame);
+        }
+    }
+
+    assert!(decoder.is_live());
+    assert_eq!(decoder.state(), DecoderState::Ready);
+    let info = decoder.info().unwrap();
+    assert!(info.is_live);
+    assert_eq!(info.sample_rate, sample_rate);
+    assert_eq!(info.channels, channels);
+    assert_eq!(decoder.current_frame_index(), 4);
+    assert_eq!(decoded, reference);
+
+    // consumed packets don't pile up in the buffer
+    assert!(decoder.buffered_bytes() < 4);
+}
+
+#[test]
+fn test_live_stream_join_mid_stream() {
+    let sample_rate = 8000u32;
+    let channels = 1u8;
+
+    let samples: Vec<f32> = (0..sample_rate as usize * 4)
+        .map(|i| (i as f32 * 0.02).sin() * 0.7)
+        .collect();
+
+    let reference = Decoder::new()
+        .decode(
+            &Encoder::new(sample_rate, channels, 16)
+                .encode(&samples, &[])
+                .unwrap(),
+        )
+        .unwrap();
+
+    let packets = encode_live_packets(&samples, sample_rate, channels);
+    let stream: Vec<u8> = packets.concat();
+
+    // join halfway through the second packet
+    let join_at = packets[0].len() + packets[1].len() / 2;
+
+    let mut decoder = StreamingDecoder::new_live();
+    assert_eq!(decoder.state(), DecoderState::Syncing);
+    assert!(decoder.info().is_none());
+
+    decoder.feed(&stream[join_at..]).unwrap();
+    assert_eq!(decoder.available_frames(), 2);
+
+    let decoded = decoder.decode_available().unwrap();
+    assert_eq!(decoder.current_frame_index(), 4);
+
+    let frame_len = sample_rate as usize;
+    assert_eq!(decoded, reference[frame_len * 2..]);
+}
+
+#[test]
+fn test_live_stream_skips_damaged_packet() {
+    let sample_rate = 8000u32;
+    let channels = 1u8;
+
+    let samples: Vec<f32> = (0..sample_rate as usize * 3)
+        .map(|i| (i as f32 * 0.03).sin() * 0.5)
+        .collect();
+
+    let packets = encode_live_packets(&samples, sample_rate, channels);
+    let mut stream: Vec<u8> = packets.concat();
+
+    // flip a payload byte in the middle packet
+    let damaged = packets[0].len() + packets[1].len() - 10;
+    stream[damaged] ^= 0xFF;
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&stream).unwrap();
+    // the damaged packet isn't counted as ready either
+    assert_eq!(decoder.available_frames(), 2);
+
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.next_frame().unwrap() {
+        frames.push(frame);
+    }
+
+    assert_eq!(frames.len(), 2);
+    assert_eq!(decoder.current_frame_index(), 3);
+}
+
+#[test]
+fn test_live_stream_resyncs_on_index_jump() {
+    let sample_rate = 8000u32;
+    let samples: Vec<f32> = (0..sample_rate as usize * 5)
+        .map(|i| (i as f32 * 0.03).sin() * 0.5)
+        .collect();
+    let packets = encode_live_packets(&samples, sample_rate, 1);
+
+    // a restarted sender: frame 2 comes back near the top of the index range,
+    // then frame 3 of the new run goes missing
+    let relabel = |packet: &[u8], frame_index: u32| {
+        let header = LivePacketHeader::parse(packet).unwrap();
+        write_live_packet(
+            header.flags,
+            header.sample_rate,
+            header.channels,
+            header.bit_depth,
+            frame_index,
+            header.first_sample,
+            &packet[header.packet_size() - header.payload_size as usize..],
+        )
+    };
+    let jump = 4_000_000_000;
+    let stream = [
+        packets[0].clone(),
+        packets[1].clone(),
+        relabel(&packets[2], jump),
+        relabel(&packets[4], jump + 2),
+    ]
+    .concat();
+
+    let mut decoder = StreamingDecoder::new_live();
+    decoder.feed(&stream).unwrap();
+    let decoded = decoder.decode_available().unwrap();
+    assert_eq!(decoded.len(), sample_rate as usize * 4);
+
+    // only the frame lost after the restart is damage
+    assert_eq!(decoder.damaged_ranges().len(), 1);
+    assert_eq!(
+        decoder.damaged_ranges()[0],
+        jump as usize + 1..jump as usize + 2
+    );
+    assert_eq!(decoder.damaged_frames(), vec![jump as usize + 1]);
+}
+
+#[test]
+fn test_live_stream_gap_across_index_wrap() {
+    let sample_rate = 8000u32;
+    let samples: Vec<f32> = (0..sample_rate as usize * 4)
+        .map(|i| (i as f32 * 0.03).sin() * 0.5)
+        .collect();
+    let packets = encode_live_packets(&samples, sample_rate, 1);
+
+    // a long running stream: frames u32::MAX and 0 get lost
+    let relabel = |packet: &[u8], frame_index: u32| {
+        let header = LivePacketHeader::parse(packet).unwrap();
+        write_live_packet(
+            header.flags,
+            header.sample_rate,
+            header.channels,
+            header.bit_depth,
+            frame_index,
+            header.first_sample,
+            &packet[header.packet_size() - header.payload_size as usize..],
+        )
+    };
+    let stream = [relabel(&packets[0], u32::MAX - 1), relabel(&packets[3], 1)].concat();
+
+    let mut decoder = StreamingDecoder::new_live().with_concealment(Concealment::Silence);
+    decoder.feed(&stream).unwrap();
+    let decoded = decoder.decode_available().unwrap();
+    assert_eq!(decoded.len(), samples.len());
+    assert!(decoded[sample_rate as usize..sample_rate as usize * 3]
+        .iter()
+        .all(|&s| s == 0.0));
+
+    let last = u32::MAX as usize;
+    assert_eq!(decoder.damaged_ranges(), &[last..last + 1, 0..1]);
+}
+
+fn lossy_test_signal(sample_rate: u32, channels: u8, samples_per_channel: usize) -> Vec<f32> {
+    (0..samples_per_channel)
+        .flat_map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            (0..channels).map(move |ch| {
+                (t * (440.0 + ch as f32 * 110.0) * std::f32::consts::TAU).sin() * 0.4
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_lossy_streaming_encoder_matches_encode_to_flo() {
+    let sample_rate = 44100u32;
+    let channels = 2u8;
+    let samples = lossy_test_signal(sample_rate, channels, 57_321);
+    let metadata = b"\x80".to_vec();
+
+    for frame_crc in [false, true] {
+        let reference = TransformEncoder::new(sample_rate, channels, 0.6)
+            .with_frame_crc(frame_crc)
+            .encode_to_flo(&samples, &metadata)
+            .unwrap();
+
+        let mut encoder =
+            StreamingEncoder::new_lossy(sample_rate, channels, 0.6).with_frame_crc(frame_crc);
+        assert!(encoder.is_lossy());
+
+        // odd chunk sizes, state has to carry across calls
+        for chunk in samples.chunks(777 * channels as usize) {
+            encoder.push_samples(chunk).unwrap();
+        }
+        assert!(encoder.pending_frames() > 0);
+
+        let flo_data = encoder.finalize(&metadata).unwrap();
+        assert_eq!(flo_data, reference);
+    }
+}
+
+#[test]
+fn test_lossy_streaming_encoder_short_streams() {
+    let sample_rate = 22050u32;
+
+    for len in [0usize, 1, 1023, 1024, 1025, 3000] {
+        let samples = lossy_test_signal(sample_rate, 1, len);
+        let reference = TransformEncoder::new(sample_rate, 1, 0.4)
+            .encode_to_flo(&samples, &[])
+            .unwrap();
+
+        let mut encoder = StreamingEncoder::new_lossy(sample_rate, 1, 0.4);
+        encoder.push_samples(&samples).unwrap();
+        assert_eq!(encoder.finalize(&[]).unwrap(), reference, "length {}", len);
+    }
+}
+
+#[test]
+fn test_lossy_streaming_encoder_frames() {
+    let sample_rate = 48000u32;
+    let samples = lossy_test_signal(sample_rate, 1, 10_000);
+
+    let mut encoder = StreamingEncoder::new_lossy(sample_rate, 1, 0.5);
+    encoder.push_samples(&samples).unwrap();
+
+    // frame 0 is the preroll, each frame after it is one hop
+    let mut frames = Vec::new();
+    while let Some(frame) = encoder.next_frame() {
+        frames.push(frame);
+    }
+    assert_eq!(encoder.pending_samples(), 1024 + 10_000 % 1024);
+
+    encoder.finish().unwrap();
+    while let Some(frame) = encoder.next_frame() {
+        frames.push(frame);
+    }
+    assert_eq!(encoder.flush().unwrap().map(|f| f.index), None);
+
+    // ceil((10000 + 1024) / 1024) frames
+    assert_eq!(frames.len(), 11);
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.index, i as u32);
+        assert_eq!(frame.samples, 1024);
+        assert_eq!(frame.first_sample, i as u64 * 1024);
+        assert_eq!(frame.timestamp_ms, (i as u64 * 1024 * 1000 / 48000) as u32);
+    }
+}
+
+#[test]
+fn test_flush_returns_the_tail_frame() {
+    let sample_rate = 8000u32;
+    let samples: Vec<f32> = (0..sample_rate as usize * 3 / 2)
+        .map(|i| (i as f32 * 0.03).sin() * 0.5)
+        .collect();
+
+    // frame 0 is still queued, flush hands back the partial frame 1
+    let mut encoder = StreamingEncoder::new(sample_rate, 1, 16);
+    encoder.push_samples(&samples).unwrap();
+    let tail = encoder.flush().unwrap().unwrap();
+    assert_eq!(tail.index, 1);
+    assert_eq!(tail.samples, sample_rate / 2);
+    assert_eq!(encoder.next_frame().map(|f| f.index), Some(0));
+    assert!(encoder.next_frame().is_none());
+    assert!(encoder.flush().unwrap().is_none());
+}
+
+#[test]
+fn test_lossy_live_stream_roundtrip() {
+    let sample_rate = 32000u32;
+    let channels = 2u8;
+    let samples = lossy_test_signal(sample_rate, channels, 20_000);
+
+    let reference = TransformEncoder::new(sample_rate, channels, 0.5)
+        .encode_to_flo(&samples, &[])
+        .unwrap();
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&reference).unwrap();
+    let expected = decoder.decode_available().unwrap();
+
+    let mut encoder = StreamingEncoder::new_lossy(sample_rate, channels, 0.5);
+    let mut packets = Vec::new();
+    for chunk in samples.chunks(4096) {
+        encoder.push_samples(chunk).unwrap();
+        while let Some(packet) = encoder.next_live_packet() {
+            packets.push(packet);
+        }
+    }
+    if let Some(frame) = encoder.flush().unwrap() {
+        packets.push(encoder.live_packet(&frame));
+    }
+    while let Some(packet) = encoder.next_live_packet() {
+        packets.push(packet);
+    }
+
+    let mut decoder = StreamingDecoder::new();
+    decoder.feed(&packets.concat()).unwrap();
+    assert!(decoder.is_live());
+    // a live stream has no header to record the padding, so the last hop plays in full
+    let live = decoder.decode_available().unwrap();
+    assert_eq!(live[..expected.len()], expected[..]);
+    assert!(live.len() - expected.len() < 1024 * channels as usize);
+    assert!(decoder.info().unwrap().is_lossy);
+}
+
+/// a minute of mono audio with a preview up front and the full metadata at the end
+fn long_file_with_metadata() -> (Vec<u8>, Vec<u8>) {
+    let sample_rate = 8000u32;
+    let samples: Vec<f32> = (0..sample_rate as usize * 60)
+        .map(|i| (i as f32 * 0.013).sin() * 0.4 + (i as f32 * 0.0007).cos() * 0.2)
+        .collect();
+
+    let mut meta = FloMetadata::new();
+    meta.title = Some("Long".to_string());
+    meta.add_comment("only in the full metadata", None);
+    let metadata = meta.to_msgpack().unwrap();
+
+    let flo_data = Encoder::new(sample_rate, 1, 16)
+        .with_metadata_placement(MetadataPlacement::Preview)
+        .encode(&samples, &metadata)
+        .unwrap();
+    (flo_data, metadata)
+}
+
+#[test]
+fn test_streaming_decoder_drops_consumed_frames() {
+    let (flo_data, metadata) = long_file_with_metadata();
+    let reference = Decoder::new().decode(&flo_data).unwrap();
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let largest_frame = file.toc.iter().map(|e| e.frame_size).max().unwrap() as usize;
+    let toc_end = 4 + (file.header.header_size + file.header.toc_size) as usize;
+
+    let mut decoder = StreamingDecoder::new();
+    let mut decoded = Vec::new();
+    let mut peak = 0;
+    for chunk in flo_data.chunks(4096) {
+        let status = decoder.feed(chunk).unwrap();
+        assert_eq!(status.accepted, chunk.len());
+        assert!(!status.full);
+        peak = peak.max(decoder.buffered_bytes());
+
+        decoded.extend(decoder.decode_available().unwrap());
+        if decoder.current_frame_index() > 0 && decoder.current_frame_index() < file.toc.len() {
+            // the header, TOC and early metadata bytes are gone, the preview isn't
+            let preview = FloMetadata::from_msgpack(decoder.metadata().unwrap()).unwrap();
+            assert!(preview.comments.is_empty());
+        }
+    }
+
+    assert_eq!(decoded, reference);
+    assert_eq!(decoder.state(), DecoderState::Finished);
+    assert!(peak <= (toc_end + file.early_metadata.len()).max(largest_frame) + 4096);
+    assert!(peak < flo_data.len() / 10);
+
+    // the META chunk at the end is still there
+    assert_eq!(decoder.metadata(), Some(&metadata[..]));
+    assert_eq!(decoder.buffered_bytes(), file.extra.len() + metadata.len());
+}
+
+#[test]
+fn test_streaming_decoder_max_buffer_backpressure() {
+    let (flo_data, metadata) = long_file_with_metadata();
+    let reference = Decoder::new().decode(&flo_data).unwrap();
+    let file = Reader::new().read_ref(&flo_data).unwrap();
+    let largest_frame = file.toc.iter().map(|e| e.frame_size).max().unwrap() as usize;
+    let toc_end = 4 + (file.header.header_size + file.header.toc_size) as usize;
+
+    // smaller than a frame, it still has to make progress
+    let limit = 2048;
+    let mut decoder = StreamingDecoder::new().with_max_buffer(limit);
+
+    let status = decoder.feed(&flo_data).unwrap();
+    assert!(status.full);
+    assert!(decoder.is_full());
+    assert!(status.accepted < flo_data.len());
+    assert_eq!(status.frames_ready, 1);
+
+    let mut pos = status.accepted;
+    let mut decoded = decoder.decode_available().unwrap();
+    while pos < flo_data.len() {
+        let status = decoder.feed(&flo_data[pos..]).unwrap();
+        assert!(status.accepted > 0);
+        assert!(decoder.buffered_bytes() <= limit.max(toc_end).max(largest_frame));
+        pos += status.accepted;
+        decoded.extend(decoder.decode_available().unwrap());
+    }
+
+    assert_eq!(decoded, reference);
+    assert_eq!(decoder.metadata(), Some(&metadata[..]));
+}
+
+#[test]
+fn test_live_stream_max_buffer() {
+    let sample_rate = 8000u32;
+    let samples: Vec<f32> = (0..sample_rate as usize * 5)
+        .map(|i| (i as f32 * 0.02).sin() * 0.5)
+        .collect();
+    let packets = encode_live_packets(&samples, sample_rate, 1);
+    let stream = packets.concat();
+    let largest_packet = packets.iter().map(|p| p.len()).max().unwrap();
+
+    let mut decoder = StreamingDecoder::new_live().with_max_buffer(1024);
+    let mut pos = 0;
+    let mut decoded = Vec::new();
+    while pos < stream.len() {
+        let status = decoder.feed(&stream[pos..]).unwrap();
+        assert!(status.accepted > 0);
+        assert!(decoder.buffered_bytes() <= largest_packet.max(1024));
+        pos += status.accepted;
+        decoded.extend(decoder.decode_available().unwrap());
+    }
+
+    assert_eq!(decoded.len(), samples.len());
+}
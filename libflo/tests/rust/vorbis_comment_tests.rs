@@ -0,0 +1,271 @@
+//! Vorbis comment tests for flo™ audio codec
+//!
+//! Tests for:
+//! - Round trips through Vorbis comments and comment blocks
+//! - Reading comments as FLAC and Ogg taggers write them
+//! - FLAC picture blocks and base64 METADATA_BLOCK_PICTURE
+//! - Merging into existing metadata
+
+use libflo_audio::{Comment, FloMetadata, Lyrics, Picture, PictureType, UserText};
+
+fn comments(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn value<'a>(comments: &'a [(String, String)], key: &str) -> Vec<&'a str> {
+    comments
+        .iter()
+        .filter(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .collect()
+}
+
+fn same_picture(a: &Picture, b: &Picture) -> bool {
+    a.mime_type == b.mime_type
+        && a.picture_type == b.picture_type
+        && a.description == b.description
+        && a.data == b.data
+}
+
+fn cover() -> Picture {
+    Picture {
+        mime_type: "image/png".to_string(),
+        picture_type: PictureType::CoverFront,
+        description: Some("front".to_string()),
+        data: (0..=255u8).cycle().take(1000).collect(),
+    }
+}
+
+fn full() -> FloMetadata {
+    let mut meta = FloMetadata::with_basic(
+        Some("Title".to_string()),
+        Some("Artist".to_string()),
+        Some("Album".to_string()),
+    );
+    meta.album_artist = Some("Various".to_string());
+    meta.composer = Some("Composer".to_string());
+    meta.genre = Some("Ambient".to_string());
+    meta.mood = Some("Calm".to_string());
+    meta.publisher = Some("Label".to_string());
+    meta.isrc = Some("USABC1234567".to_string());
+    meta.url_artist = Some("https://example.com".to_string());
+    meta.year = Some(2024);
+    meta.recording_time = Some("2024-03-05".to_string());
+    meta.original_release_time = Some("1999".to_string());
+    meta.track_number = Some(3);
+    meta.track_total = Some(12);
+    meta.disc_number = Some(1);
+    meta.disc_total = Some(2);
+    meta.bpm = Some(120);
+    meta.integrated_loudness_lufs = Some(-9.5);
+    meta.true_peak_dbtp = Some(-1.0);
+    meta.involved_people = Some(vec![
+        ("producer".to_string(), "Producer".to_string()),
+        ("mix".to_string(), "Mixer".to_string()),
+    ]);
+    meta.musician_credits = Some(vec![("guitar".to_string(), "Guitarist".to_string())]);
+    meta.comments.push(Comment {
+        language: None,
+        description: None,
+        text: "A comment".to_string(),
+    });
+    meta.lyrics.push(Lyrics {
+        language: None,
+        description: None,
+        text: "la la la".to_string(),
+    });
+    meta.user_text.push(UserText {
+        description: "MusicBrainz Album Id".to_string(),
+        value: "6f1c-album".to_string(),
+    });
+    meta.user_text.push(UserText {
+        description: "CATALOGNUMBER".to_string(),
+        value: "CAT-001".to_string(),
+    });
+    meta.pictures.push(cover());
+    meta
+}
+
+#[test]
+fn test_vorbis_comment_round_trip() {
+    let meta = full();
+    let comments = meta.to_vorbis_comments();
+
+    assert_eq!(value(&comments, "DATE"), ["2024-03-05"]);
+    assert_eq!(value(&comments, "LABEL"), ["Label"]);
+    assert_eq!(value(&comments, "MUSICBRAINZ_ALBUMID"), ["6f1c-album"]);
+    assert_eq!(value(&comments, "REPLAYGAIN_TRACK_GAIN"), ["-8.50 dB"]);
+    assert_eq!(value(&comments, "PERFORMER"), ["Guitarist (guitar)"]);
+    assert_eq!(value(&comments, "METADATA_BLOCK_PICTURE").len(), 1);
+
+    let back = FloMetadata::from_vorbis_comments(&comments);
+    assert_eq!(back.title, meta.title);
+    assert_eq!(back.artist, meta.artist);
+    assert_eq!(back.album_artist, meta.album_artist);
+    assert_eq!(back.composer, meta.composer);
+    assert_eq!(back.genre, meta.genre);
+    assert_eq!(back.mood, meta.mood);
+    assert_eq!(back.publisher, meta.publisher);
+    assert_eq!(back.isrc, meta.isrc);
+    assert_eq!(back.url_artist, meta.url_artist);
+    assert_eq!(back.year, Some(2024));
+    assert_eq!(back.recording_time, meta.recording_time);
+    assert_eq!(back.original_release_time, meta.original_release_time);
+    assert_eq!(back.track_number, Some(3));
+    assert_eq!(back.track_total, Some(12));
+    assert_eq!(back.disc_number, Some(1));
+    assert_eq!(back.disc_total, Some(2));
+    assert_eq!(back.bpm, Some(120));
+    assert!((back.integrated_loudness_lufs.unwrap() + 9.5).abs() < 0.01);
+    assert!((back.true_peak_dbtp.unwrap() + 1.0).abs() < 0.01);
+    assert_eq!(back.involved_people, meta.involved_people);
+    assert_eq!(back.musician_credits, meta.musician_credits);
+    assert_eq!(back.comments.len(), 1);
+    assert_eq!(back.comments[0].text, "A comment");
+    assert_eq!(back.lyrics.len(), 1);
+    assert_eq!(back.lyrics[0].text, "la la la");
+    let user_text: Vec<_> = back
+        .user_text
+        .iter()
+        .map(|t| (t.description.as_str(), t.value.as_str()))
+        .collect();
+    assert_eq!(
+        user_text,
+        [
+            ("MusicBrainz Album Id", "6f1c-album"),
+            ("CATALOGNUMBER", "CAT-001")
+        ]
+    );
+    assert_eq!(back.pictures.len(), 1);
+    assert!(same_picture(&back.pictures[0], &meta.pictures[0]));
+
+    // the same through a comment block
+    let block = meta.to_vorbis_comment_block("reference libFLAC 1.4.3");
+    let from_block = FloMetadata::from_vorbis_comment_block(&block).unwrap();
+    assert_eq!(from_block.to_vorbis_comments(), comments);
+}
+
+#[test]
+fn test_vorbis_comments_as_taggers_write_them() {
+    let meta = FloMetadata::from_vorbis_comments(&comments(&[
+        ("title", "Song"),
+        ("Artist", "One"),
+        ("ARTIST", "Two"),
+        ("ALBUM ARTIST", "Band"),
+        ("TRACKNUMBER", "5/10"),
+        ("DISCNUMBER", "2"),
+        ("TOTALDISCS", "3"),
+        ("DATE", "2019"),
+        ("ORGANIZATION", "Records"),
+        ("DESCRIPTION", "Liner notes"),
+        ("BPM", "127.6"),
+        ("R128_TRACK_GAIN", "-1280"),
+        ("REPLAYGAIN_ALBUM_GAIN", "-7.00 dB"),
+        ("CATALOGNUMBER", "CAT-002"),
+        ("MUSICBRAINZ_ARTISTID", "artist-id"),
+        ("DJMIXER", "DJ"),
+    ]));
+
+    assert_eq!(meta.title.as_deref(), Some("Song"));
+    // repeated keys are joined
+    assert_eq!(meta.artist.as_deref(), Some("One/Two"));
+    assert_eq!(meta.album_artist.as_deref(), Some("Band"));
+    assert_eq!(meta.track_number, Some(5));
+    assert_eq!(meta.track_total, Some(10));
+    assert_eq!(meta.disc_number, Some(2));
+    assert_eq!(meta.disc_total, Some(3));
+    // a bare year is only the year
+    assert_eq!(meta.year, Some(2019));
+    assert_eq!(meta.recording_time, None);
+    assert_eq!(meta.publisher.as_deref(), Some("Records"));
+    assert_eq!(meta.comments[0].text, "Liner notes");
+    assert_eq!(meta.bpm, Some(128));
+    // -5 dB from R128, -23 LUFS reference
+    assert!((meta.integrated_loudness_lufs.unwrap() + 18.0).abs() < 0.01);
+    assert!(meta.track_gain_db.unwrap().abs() < 0.01);
+    assert_eq!(meta.album_gain_db, Some(-7.0));
+    assert!((meta.album_loudness_lufs.unwrap() + 11.0).abs() < 0.01);
+    assert_eq!(
+        meta.involved_people,
+        Some(vec![("DJ-mix".to_string(), "DJ".to_string())])
+    );
+
+    // unmapped keys become user text, MusicBrainz ones named as in ID3
+    let user_text: Vec<_> = meta
+        .user_text
+        .iter()
+        .map(|t| (t.description.as_str(), t.value.as_str()))
+        .collect();
+    assert_eq!(
+        user_text,
+        [
+            ("CATALOGNUMBER", "CAT-002"),
+            ("MusicBrainz Artist Id", "artist-id"),
+        ]
+    );
+}
+
+#[test]
+fn test_flac_picture_blocks() {
+    let picture = cover();
+    let block = picture.to_flac_picture();
+    assert_eq!(&block[..4], &[0, 0, 0, 3]);
+    assert!(same_picture(
+        &Picture::from_flac_picture(&block).unwrap(),
+        &picture
+    ));
+    assert!(Picture::from_flac_picture(&block[..block.len() - 1]).is_err());
+
+    // base64 as written by other taggers, padding and all
+    let written = full().to_vorbis_comments();
+    let encoded = value(&written, "METADATA_BLOCK_PICTURE")[0];
+    assert!(encoded.ends_with('='));
+
+    // legacy cover art without a picture block
+    let meta = FloMetadata::from_vorbis_comments(&comments(&[
+        ("COVERART", "iVBORw0KGgo="),
+        ("COVERARTMIME", "image/png"),
+    ]));
+    assert_eq!(meta.pictures.len(), 1);
+    assert_eq!(meta.pictures[0].mime_type, "image/png");
+    assert_eq!(meta.pictures[0].picture_type, PictureType::CoverFront);
+    assert_eq!(meta.pictures[0].data, b"\x89PNG\r\n\x1a\n");
+    assert!(meta.user_text.is_empty());
+
+    // not base64, ignored
+    let meta = FloMetadata::from_vorbis_comments(&comments(&[("METADATA_BLOCK_PICTURE", "@@@")]));
+    assert!(meta.pictures.is_empty());
+}
+
+#[test]
+fn test_vorbis_comment_block_and_merge() {
+    // an Ogg Vorbis comment header
+    let mut packet = b"\x03vorbis".to_vec();
+    packet.extend_from_slice(&(6u32).to_le_bytes());
+    packet.extend_from_slice(b"Xiph.o");
+    packet.extend_from_slice(&(1u32).to_le_bytes());
+    packet.extend_from_slice(&(10u32).to_le_bytes());
+    packet.extend_from_slice(b"TITLE=Ogg!");
+    let meta = FloMetadata::from_vorbis_comment_block(&packet).unwrap();
+    assert_eq!(meta.title.as_deref(), Some("Ogg!"));
+    assert!(FloMetadata::from_vorbis_comment_block(&packet[..packet.len() - 2]).is_err());
+
+    // fields present win, everything else stays
+    let mut meta = full();
+    meta.apply_vorbis_comments(&comments(&[
+        ("TITLE", "New"),
+        ("COMMENT", "New comment"),
+        ("ASIN", "B000"),
+    ]));
+    assert_eq!(meta.title.as_deref(), Some("New"));
+    assert_eq!(meta.artist.as_deref(), Some("Artist"));
+    assert_eq!(meta.comments.len(), 1);
+    assert_eq!(meta.comments[0].text, "New comment");
+    assert_eq!(meta.user_text.len(), 1);
+    assert_eq!(meta.user_text[0].description, "ASIN");
+    assert_eq!(meta.lyrics.len(), 1);
+    assert_eq!(meta.pictures.len(), 1);
+}
@@ -1,11 +1,15 @@
 use anyhow::{Context, Result};
-use std::io::{Cursor, Write};
+use std::io::{Cursor, Read, Write};
 use std::path::Path;
 use symphonia::core::audio::{AudioBufferRef, Signal};
-use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::codecs::{
+    DecoderOptions, CODEC_TYPE_FLAC, CODEC_TYPE_NULL, CODEC_TYPE_OPUS, CODEC_TYPE_VORBIS,
+};
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::{MetadataOptions, StandardTagKey, Value};
+use symphonia::core::meta::{
+    MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey, Value,
+};
 use symphonia::core::probe::Hint;
 
 /// Metadata extracted from audio file
@@ -25,10 +29,16 @@ pub struct AudioMetadata {
     pub bpm: Option<f32>,
     // Cover art stored as (mime_type, data)
     pub cover_art: Option<(String, Vec<u8>)>,
+    // Every picture in the file, cover art included
+    pub pictures: Vec<libflo_audio::Picture>,
     // Source format (e.g., "MP3", "FLAC", "WAV")
     pub source_format: Option<String>,
     // Original filename
     pub original_filename: Option<String>,
+    // ID3v2 tag at the start of the file (MP3 and some others), maps every field
+    pub id3_tag: Option<Vec<u8>>,
+    // Vorbis comments (FLAC, Ogg) as (key, value), maps every field
+    pub vorbis_comments: Option<Vec<(String, String)>>,
 }
 
 /// Read an audio file and return (samples, sample_rate, channels, metadata)
@@ -36,14 +46,32 @@ pub struct AudioMetadata {
 pub fn read_audio_file_with_metadata(path: &Path) -> Result<(Vec<f32>, u32, usize, AudioMetadata)> {
     let file = std::fs::File::open(path).context("Failed to open audio file")?;
     let mss = MediaSourceStream::new(Box::new(file), Default::default());
-    read_from_source_with_metadata(mss, path.extension().and_then(|e| e.to_str()))
+    let (samples, sample_rate, channels, mut metadata) =
+        read_from_source_with_metadata(mss, path.extension().and_then(|e| e.to_str()))?;
+    metadata.id3_tag = read_id3_tag(path);
+    Ok((samples, sample_rate, channels, metadata))
 }
 
 /// Read audio from bytes (for cross-platform/WASM support)
 pub fn read_audio_from_bytes(bytes: &[u8]) -> Result<(Vec<f32>, u32, usize, AudioMetadata)> {
     let cursor = Cursor::new(bytes.to_vec());
     let mss = MediaSourceStream::new(Box::new(cursor), Default::default());
-    read_from_source_with_metadata(mss, None)
+    let (samples, sample_rate, channels, mut metadata) = read_from_source_with_metadata(mss, None)?;
+    metadata.id3_tag = libflo_audio::id3_tag_size(bytes)
+        .and_then(|size| bytes.get(..size))
+        .map(|tag| tag.to_vec());
+    Ok((samples, sample_rate, channels, metadata))
+}
+
+/// The ID3v2 tag at the start of a file, without reading the audio
+fn read_id3_tag(path: &Path) -> Option<Vec<u8>> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut tag = vec![0u8; 10];
+    file.read_exact(&mut tag).ok()?;
+    let size = libflo_audio::id3_tag_size(&tag)?;
+    tag.resize(size, 0);
+    file.read_exact(&mut tag[10..]).ok()?;
+    Some(tag)
 }
 
 /// Read an audio file and return (samples, sample_rate, channels)
@@ -91,8 +119,10 @@ fn read_from_source_with_metadata(
     }
 
     // Also check format metadata
+    let mut format_tags = None;
     if let Some(meta_rev) = format.metadata().current() {
         extract_metadata_tags(meta_rev, &mut metadata);
+        format_tags = Some(raw_tags(meta_rev));
     }
 
     // Find the first audio track
@@ -118,6 +148,12 @@ fn read_from_source_with_metadata(
         });
     }
 
+    // FLAC and Ogg tags are Vorbis comments, keys as written
+    let codec = track.codec_params.codec;
+    if [CODEC_TYPE_FLAC, CODEC_TYPE_VORBIS, CODEC_TYPE_OPUS].contains(&codec) {
+        metadata.vorbis_comments = format_tags;
+    }
+
     let track_id = track.id;
     let sample_rate = track
         .codec_params
@@ -165,10 +201,7 @@ fn read_from_source_with_metadata(
     Ok((samples, sample_rate, channels, metadata))
 }
 
-fn extract_metadata_tags(
-    meta: &symphonia::core::meta::MetadataRevision,
-    metadata: &mut AudioMetadata,
-) {
+fn extract_metadata_tags(meta: &MetadataRevision, metadata: &mut AudioMetadata) {
     for tag in meta.tags() {
         if let Some(std_key) = tag.std_key {
             let value_str = match &tag.value {
@@ -226,13 +259,65 @@ fn extract_metadata_tags(
 
     // Extract cover art from visuals
     for visual in meta.visuals() {
-        if visual.usage == Some(symphonia::core::meta::StandardVisualKey::FrontCover)
-            || metadata.cover_art.is_none()
-        {
+        if visual.usage == Some(StandardVisualKey::FrontCover) || metadata.cover_art.is_none() {
             let mime = visual.media_type.clone();
             metadata.cover_art = Some((mime, visual.data.to_vec()));
         }
     }
+
+    if !meta.visuals().is_empty() {
+        metadata.pictures = meta
+            .visuals()
+            .iter()
+            .map(|visual| libflo_audio::Picture {
+                mime_type: visual.media_type.clone(),
+                picture_type: visual
+                    .usage
+                    .map_or(libflo_audio::PictureType::Other, picture_type),
+                description: visual
+                    .tags
+                    .iter()
+                    .find(|tag| tag.std_key == Some(StandardTagKey::Description))
+                    .map(|tag| tag.value.to_string())
+                    .filter(|description| !description.is_empty()),
+                data: visual.data.to_vec(),
+            })
+            .collect();
+    }
+}
+
+/// All tags as (key, value), keys as the file has them
+fn raw_tags(meta: &MetadataRevision) -> Vec<(String, String)> {
+    meta.tags()
+        .iter()
+        .filter(|tag| !matches!(tag.value, Value::Binary(_)))
+        .map(|tag| (tag.key.clone(), tag.value.to_string()))
+        .collect()
+}
+
+fn picture_type(usage: StandardVisualKey) -> libflo_audio::PictureType {
+    use libflo_audio::PictureType;
+    match usage {
+        StandardVisualKey::FileIcon => PictureType::FileIcon,
+        StandardVisualKey::OtherIcon => PictureType::OtherFileIcon,
+        StandardVisualKey::FrontCover => PictureType::CoverFront,
+        StandardVisualKey::BackCover => PictureType::CoverBack,
+        StandardVisualKey::Leaflet => PictureType::LeafletPage,
+        StandardVisualKey::Media => PictureType::Media,
+        StandardVisualKey::LeadArtistPerformerSoloist => PictureType::LeadArtist,
+        StandardVisualKey::ArtistPerformer => PictureType::Artist,
+        StandardVisualKey::Conductor => PictureType::Conductor,
+        StandardVisualKey::BandOrchestra => PictureType::Band,
+        StandardVisualKey::Composer => PictureType::Composer,
+        StandardVisualKey::Lyricist => PictureType::Lyricist,
+        StandardVisualKey::RecordingLocation => PictureType::RecordingLocation,
+        StandardVisualKey::RecordingSession => PictureType::DuringRecording,
+        StandardVisualKey::Performance => PictureType::DuringPerformance,
+        StandardVisualKey::ScreenCapture => PictureType::VideoScreenCapture,
+        StandardVisualKey::Illustration => PictureType::Illustration,
+        StandardVisualKey::BandArtistLogo => PictureType::BandLogo,
+        StandardVisualKey::PublisherStudioLogo => PictureType::PublisherLogo,
+    }
 }
 
 fn append_samples(buffer: &AudioBufferRef, samples: &mut Vec<f32>, channels: usize) {
@@ -0,0 +1,229 @@
+//! CUE sheet import
+//!
+//! Reads single-file CUE sheets into a flo™ chapter table. Times in a CUE
+//! sheet are mm:ss:ff with 75 frames per second.
+
+use anyhow::{bail, Context, Result};
+use libflo_audio::{Chapter, FloMetadata, UserText};
+
+/// CUE frames per second
+const CUE_FPS: u64 = 75;
+
+/// Parsed CUE sheet
+#[derive(Debug, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    /// Audio file the sheet describes
+    pub file: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A single TRACK entry
+#[derive(Debug, Default)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub isrc: Option<String>,
+    /// INDEX 01 in CUE frames (1/75 s)
+    pub start_frames: u64,
+    /// REM lines inside the track, as (key, value)
+    pub remarks: Vec<(String, String)>,
+}
+
+impl CueSheet {
+    /// Parse the text of a CUE sheet
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut sheet = CueSheet::default();
+        let mut track: Option<CueTrack> = None;
+        let mut has_start = false;
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim().trim_start_matches('\u{feff}');
+            if line.is_empty() {
+                continue;
+            }
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            let context = || format!("CUE line {}: {}", line_no + 1, line);
+
+            match keyword.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    if sheet.file.is_some() {
+                        bail!("CUE sheets with more than one FILE are not supported");
+                    }
+                    sheet.file = Some(file_name(rest));
+                }
+                "TRACK" => {
+                    if let Some(done) = track.take() {
+                        if !has_start {
+                            bail!("Track {} has no INDEX 01", done.number);
+                        }
+                        sheet.tracks.push(done);
+                    }
+                    let number = rest
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or("")
+                        .parse()
+                        .with_context(context)?;
+                    track = Some(CueTrack {
+                        number,
+                        ..CueTrack::default()
+                    });
+                    has_start = false;
+                }
+                "INDEX" => {
+                    let mut parts = rest.split_whitespace();
+                    let index: u32 = parts.next().unwrap_or("").parse().with_context(context)?;
+                    let time = parse_time(parts.next().unwrap_or("")).with_context(context)?;
+                    if index == 1 {
+                        let current = track.as_mut().with_context(context)?;
+                        current.start_frames = time;
+                        has_start = true;
+                    }
+                }
+                "TITLE" => match track.as_mut() {
+                    Some(t) => t.title = Some(unquote(rest)),
+                    None => sheet.title = Some(unquote(rest)),
+                },
+                "PERFORMER" => match track.as_mut() {
+                    Some(t) => t.performer = Some(unquote(rest)),
+                    None => sheet.performer = Some(unquote(rest)),
+                },
+                "SONGWRITER" => {
+                    if let Some(t) = track.as_mut() {
+                        t.songwriter = Some(unquote(rest));
+                    }
+                }
+                "ISRC" => {
+                    if let Some(t) = track.as_mut() {
+                        t.isrc = Some(unquote(rest));
+                    }
+                }
+                "REM" => {
+                    let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    let (key, value) = (key.to_ascii_uppercase(), unquote(value.trim()));
+                    match (track.as_mut(), key.as_str()) {
+                        (Some(t), _) => t.remarks.push((key, value)),
+                        (None, "GENRE") => sheet.genre = Some(value),
+                        (None, "DATE") => sheet.date = Some(value),
+                        _ => {}
+                    }
+                }
+                // CATALOG, FLAGS, PREGAP, POSTGAP, CDTEXTFILE
+                _ => {}
+            }
+        }
+
+        if let Some(done) = track.take() {
+            if !has_start {
+                bail!("Track {} has no INDEX 01", done.number);
+            }
+            sheet.tracks.push(done);
+        }
+        if sheet.tracks.is_empty() {
+            bail!("CUE sheet has no tracks");
+        }
+        if sheet
+            .tracks
+            .windows(2)
+            .any(|w| w[0].start_frames > w[1].start_frames)
+        {
+            bail!("CUE tracks are not in time order");
+        }
+
+        Ok(sheet)
+    }
+
+    /// Chapter table for audio at `sample_rate`
+    pub fn to_chapters(&self, sample_rate: u32) -> Vec<Chapter> {
+        self.tracks
+            .iter()
+            .map(|t| {
+                let mut user_text: Vec<UserText> = t
+                    .remarks
+                    .iter()
+                    .map(|(key, value)| UserText {
+                        description: key.clone(),
+                        value: value.clone(),
+                    })
+                    .collect();
+                if let Some(ref songwriter) = t.songwriter {
+                    user_text.push(UserText {
+                        description: "SONGWRITER".to_string(),
+                        value: songwriter.clone(),
+                    });
+                }
+
+                Chapter {
+                    start_sample: t.start_frames * sample_rate as u64 / CUE_FPS,
+                    title: t.title.clone(),
+                    artist: t.performer.clone(),
+                    track_number: Some(t.number),
+                    isrc: t.isrc.clone(),
+                    user_text,
+                }
+            })
+            .collect()
+    }
+
+    /// Set the chapter table of `meta`, and fill in album fields it lacks
+    pub fn apply(&self, meta: &mut FloMetadata, sample_rate: u32) {
+        meta.chapters = self.to_chapters(sample_rate);
+        meta.track_total = Some(self.tracks.len() as u32);
+
+        if meta.album.is_none() {
+            meta.album = self.title.clone();
+        }
+        if meta.title.is_none() {
+            meta.title = self.title.clone();
+        }
+        if meta.artist.is_none() {
+            meta.artist = self.performer.clone();
+        }
+        if meta.album_artist.is_none() {
+            meta.album_artist = self.performer.clone();
+        }
+        if meta.genre.is_none() {
+            meta.genre = self.genre.clone();
+        }
+        if meta.year.is_none() {
+            meta.year = self.date.as_deref().and_then(|d| d.get(..4)?.parse().ok());
+        }
+    }
+}
+
+/// mm:ss:ff to CUE frames
+fn parse_time(time: &str) -> Result<u64> {
+    let parts: Vec<&str> = time.split(':').collect();
+    let [mm, ss, ff] = parts.as_slice() else {
+        bail!("Invalid CUE time: {}", time);
+    };
+    let (mm, ss, ff): (u64, u64, u64) = (mm.parse()?, ss.parse()?, ff.parse()?);
+    if ss >= 60 || ff >= CUE_FPS {
+        bail!("Invalid CUE time: {}", time);
+    }
+    Ok((mm * 60 + ss) * CUE_FPS + ff)
+}
+
+/// file name of a FILE line, dropping the trailing file type
+fn file_name(rest: &str) -> String {
+    match rest.strip_prefix('"').and_then(|r| r.split_once('"')) {
+        Some((name, _)) => name.to_string(),
+        None => rest.split_whitespace().next().unwrap_or("").to_string(),
+    }
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
@@ -5,6 +5,7 @@
 //!
 
 pub mod audio;
+pub mod cue;
 
 #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
 pub mod wasm;
@@ -12,7 +13,7 @@ pub mod wasm;
 use anyhow::{Context, Result};
 
 /// Re-export libflo types
-pub use libflo_audio::FloMetadata;
+pub use libflo_audio::{Chapter, FloMetadata, Id3Version};
 
 /// Information about a flo™ file
 #[derive(Debug, Clone, serde::Serialize)]
@@ -37,11 +38,13 @@ pub fn get_flo_info(data: &[u8]) -> Result<FloInfo> {
         .read(data)
         .map_err(|e| anyhow::anyhow!("Failed to read flo file: {}", e))?;
 
-    // Use length_ms from metadata for duration
+    // exact length if the file records it, else length_ms from metadata
     let metadata = libflo_audio::FloMetadata::from_msgpack(&file.metadata).unwrap_or_default();
-    let duration_secs = metadata
-        .length_ms
-        .map(|ms| ms as f64 / 1000.0)
+    let duration_secs = file
+        .header
+        .gapless_length()
+        .map(|length| length as f64 / file.header.sample_rate as f64)
+        .or_else(|| metadata.length_ms.map(|ms| ms as f64 / 1000.0))
         .unwrap_or_else(|| {
             // Fallback: calculate from total_samples / sample_rate
             file.header.total_samples as f64 / file.header.sample_rate as f64
@@ -93,7 +96,19 @@ pub fn get_flo_info(data: &[u8]) -> Result<FloInfo> {
 /// Validate a flo™ file
 pub fn validate_flo(data: &[u8]) -> Result<bool> {
     let info = get_flo_info(data)?;
-    Ok(info.crc_valid)
+    // frame crcs are checked one by one
+    Ok(info.crc_valid && find_damaged_frames(data)?.is_empty())
+}
+
+/// Find frames that fail their per-frame CRC
+///
+/// Always empty for files written without frame CRCs.
+pub fn find_damaged_frames(data: &[u8]) -> Result<Vec<usize>> {
+    let file = libflo_audio::Reader::new()
+        .with_recovery(true)
+        .read(data)
+        .map_err(|e| anyhow::anyhow!("Failed to read flo file: {}", e))?;
+    Ok(file.damaged_frames)
 }
 
 /// Encoding options for converting audio to flo™ format
@@ -109,6 +124,10 @@ pub struct EncodeOptions {
     pub bitrate: Option<u32>,
     /// Metadata to embed
     pub metadata: Option<FloMetadata>,
+    /// Append a CRC32 to every frame
+    pub frame_crc: bool,
+    /// Points per second of the momentary and short-term loudness series
+    pub loudness_points_per_second: u32,
 }
 
 impl Default for EncodeOptions {
@@ -119,6 +138,8 @@ impl Default for EncodeOptions {
             quality: 0.6, // High quality
             bitrate: None,
             metadata: None,
+            frame_crc: false,
+            loudness_points_per_second: 10,
         }
     }
 }
@@ -162,6 +183,18 @@ impl EncodeOptions {
         self.metadata = Some(metadata);
         self
     }
+
+    /// Append a CRC32 to every frame so damage can be located per frame
+    pub fn with_frame_crc(mut self, enabled: bool) -> Self {
+        self.frame_crc = enabled;
+        self
+    }
+
+    /// Set the resolution of the loudness series in points per second
+    pub fn with_loudness_resolution(mut self, points_per_second: u32) -> Self {
+        self.loudness_points_per_second = points_per_second.max(1);
+        self
+    }
 }
 
 /// Information about a decoded audio file
@@ -232,7 +265,9 @@ pub fn encode_from_samples(
         }
 
         // Add cover art
-        if let Some((mime, data)) = source_metadata.cover_art {
+        if !source_metadata.pictures.is_empty() {
+            m.pictures = source_metadata.pictures;
+        } else if let Some((mime, data)) = source_metadata.cover_art {
             m.pictures = vec![libflo_audio::Picture {
                 picture_type: libflo_audio::PictureType::CoverFront,
                 mime_type: mime,
@@ -241,6 +276,14 @@ pub fn encode_from_samples(
             }];
         }
 
+        // symphonia only maps a few fields, the whole tag has them all
+        if let Some(ref tag) = source_metadata.id3_tag {
+            let _ = m.apply_id3(tag);
+        }
+        if let Some(ref comments) = source_metadata.vorbis_comments {
+            m.apply_vorbis_comments(comments);
+        }
+
         m
     });
 
@@ -272,6 +315,21 @@ pub fn encode_from_samples(
     };
     meta.encoder_settings = Some(settings_desc);
 
+    // Momentary and short-term loudness for loudness graphs, and track gain
+    // and peak for players that normalise, from one pass
+    if meta.loudness_series.is_none() || meta.track_gain_db.is_none() {
+        let (loudness, series) = libflo_audio::compute_loudness(
+            samples,
+            channels as u8,
+            sample_rate,
+            options.loudness_points_per_second,
+        );
+        meta.loudness_series.get_or_insert(series);
+        if meta.track_gain_db.is_none() {
+            meta.set_track_loudness(&loudness);
+        }
+    }
+
     // Calculate and set length_ms (duration in milliseconds)
     let total_samples = samples.len() / channels;
     let length_ms = (total_samples as f64 / sample_rate as f64 * 1000.0) as u64;
@@ -292,14 +350,16 @@ pub fn encode_from_samples(
         };
 
         let mut encoder =
-            libflo_audio::LossyEncoder::new(sample_rate, channels as u8, quality_value);
+            libflo_audio::LossyEncoder::new(sample_rate, channels as u8, quality_value)
+                .with_frame_crc(options.frame_crc);
         encoder
             .encode_to_flo(samples, &metadata_data)
             .map_err(|e| anyhow::anyhow!("Encoding failed: {}", e))?
     } else {
         // Lossless encoding
         let encoder = libflo_audio::Encoder::new(sample_rate, channels as u8, 16)
-            .with_compression(options.level);
+            .with_compression(options.level)
+            .with_frame_crc(options.frame_crc);
         encoder
             .encode(samples, &metadata_data)
             .map_err(|e| anyhow::anyhow!("Encoding failed: {}", e))?
@@ -325,43 +385,10 @@ pub fn decode_to_samples(flo_bytes: &[u8]) -> Result<(Vec<f32>, u32, usize)> {
     let sample_rate = file.header.sample_rate;
     let channels = file.header.channels as usize;
 
-    // Check if lossy or lossless
-    let is_lossy = (file.header.flags & 0x01) != 0;
-
-    let samples = if is_lossy {
-        // Lossy decoding using TransformDecoder
-        let mut decoder = libflo_audio::LossyDecoder::new(sample_rate, file.header.channels);
-        let mut all_samples = Vec::new();
-        let mut frame_count = 0;
-
-        for frame in &file.frames {
-            if frame.channels.is_empty() {
-                continue;
-            }
-
-            // Transform frames store serialized data in first channel's residuals
-            let frame_data = &frame.channels[0].residuals;
-
-            // Deserialize and decode
-            let transform_frame = libflo_audio::deserialize_frame(frame_data)
-                .ok_or_else(|| anyhow::anyhow!("Failed to deserialize lossy frame"))?;
-
-            let frame_samples = decoder.decode_frame(&transform_frame);
-
-            // Skip the first frame's output (pre-roll silence for proper MDCT overlap-add)
-            if frame_count > 0 {
-                all_samples.extend(frame_samples);
-            }
-            frame_count += 1;
-        }
-        all_samples
-    } else {
-        // Lossless decoding
-        let decoder = libflo_audio::Decoder::new();
-        decoder
-            .decode_file(&file)
-            .map_err(|e| anyhow::anyhow!("Lossless decoding failed: {}", e))?
-    };
+    // lossless and lossy frames both go through libflo's frame decoder
+    let samples = libflo_audio::Decoder::new()
+        .decode_file(&file)
+        .map_err(|e| anyhow::anyhow!("Decoding failed: {}", e))?;
 
     Ok((samples, sample_rate, channels))
 }
@@ -459,6 +486,113 @@ pub fn strip_metadata_no_reencode(flo_bytes: &[u8]) -> Result<Vec<u8>> {
     update_metadata_bytes(flo_bytes, &[])
 }
 
+/// Cut a flo™ file down to `start_secs..end_secs` WITHOUT re-encoding
+///
+/// `end_secs` of None keeps everything up to the end. Only the frames cut by
+/// the edit points are re-encoded (lossless), lossy frames are kept whole
+/// with the rest recorded as encoder delay and padding.
+pub fn trim_flo(flo_bytes: &[u8], start_secs: f64, end_secs: Option<f64>) -> Result<Vec<u8>> {
+    let sample_rate = flo_sample_rate(flo_bytes)?;
+    let start = secs_to_samples(start_secs, sample_rate);
+    let end = end_secs.map_or(u64::MAX, |s| secs_to_samples(s, sample_rate));
+
+    libflo_audio::edit::trim(flo_bytes, start, end)
+        .map_err(|e| anyhow::anyhow!("Failed to trim: {}", e))
+}
+
+/// Split a flo™ file at the given times WITHOUT re-encoding
+pub fn split_flo(flo_bytes: &[u8], points_secs: &[f64]) -> Result<Vec<Vec<u8>>> {
+    let sample_rate = flo_sample_rate(flo_bytes)?;
+    let points: Vec<u64> = points_secs
+        .iter()
+        .map(|&s| secs_to_samples(s, sample_rate))
+        .collect();
+
+    libflo_audio::edit::split(flo_bytes, &points)
+        .map_err(|e| anyhow::anyhow!("Failed to split: {}", e))
+}
+
+/// Join flo™ files with the same format, re-encoding only around lossy joins
+pub fn concat_flo(parts: &[&[u8]]) -> Result<Vec<u8>> {
+    libflo_audio::edit::concat(parts).map_err(|e| anyhow::anyhow!("Failed to concatenate: {}", e))
+}
+
+/// Chapter/track table of a flo™ file, empty if it has none
+pub fn get_chapters(flo_bytes: &[u8]) -> Result<Vec<Chapter>> {
+    libflo_audio::seeking::get_chapters(flo_bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to read chapters: {}", e))
+}
+
+/// Replace the chapter table with the tracks of a CUE sheet WITHOUT re-encoding
+///
+/// Album title, performer, genre and date from the sheet fill in any of
+/// those fields the file doesn't have yet.
+pub fn import_cue_no_reencode(flo_bytes: &[u8], cue_text: &str) -> Result<Vec<u8>> {
+    let sheet = cue::CueSheet::parse(cue_text)?;
+    let sample_rate = flo_sample_rate(flo_bytes)?;
+
+    let mut meta = get_metadata(flo_bytes)?.unwrap_or_default();
+    sheet.apply(&mut meta, sample_rate);
+
+    let meta_bytes = meta
+        .to_msgpack()
+        .map_err(|e| anyhow::anyhow!("Failed to serialize metadata: {}", e))?;
+    update_metadata_bytes(flo_bytes, &meta_bytes)
+}
+
+/// Set the fields of an ID3v2.3/v2.4 tag (or an MP3 file) WITHOUT re-encoding
+///
+/// Fields the tag doesn't have, and flo™-only ones like the waveform, stay.
+pub fn import_id3_no_reencode(flo_bytes: &[u8], id3_bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut meta = get_metadata(flo_bytes)?.unwrap_or_default();
+    meta.apply_id3(id3_bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to read ID3 tag: {}", e))?;
+
+    let meta_bytes = meta
+        .to_msgpack()
+        .map_err(|e| anyhow::anyhow!("Failed to serialize metadata: {}", e))?;
+    update_metadata_bytes(flo_bytes, &meta_bytes)
+}
+
+/// The metadata of a flo™ file as an ID3v2 tag, e.g. for a sidecar or an MP3
+pub fn export_id3(flo_bytes: &[u8], version: Id3Version) -> Result<Vec<u8>> {
+    let meta = get_metadata(flo_bytes)?.unwrap_or_default();
+    meta.to_id3(version)
+        .map_err(|e| anyhow::anyhow!("Failed to write ID3 tag: {}", e))
+}
+
+/// Measure files as one album and write track and album gain into each WITHOUT re-encoding
+///
+/// Album gain is ReplayGain 2.0 over the gated blocks of all tracks together.
+pub fn apply_album_gain(files: &[&[u8]]) -> Result<Vec<Vec<u8>>> {
+    libflo_audio::replay_gain::apply_album_gain(files)
+        .map_err(|e| anyhow::anyhow!("Failed to measure album: {}", e))
+}
+
+/// Measure a file and write its ReplayGain track gain WITHOUT re-encoding
+pub fn apply_track_gain(flo_bytes: &[u8]) -> Result<Vec<u8>> {
+    libflo_audio::replay_gain::apply_track_gain(flo_bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to measure track: {}", e))
+}
+
+/// Cut one chapter out as a flo™ file of its own WITHOUT re-encoding
+///
+/// The track's title, artist, number and ISRC become the file's metadata.
+pub fn extract_track(flo_bytes: &[u8], index: usize) -> Result<Vec<u8>> {
+    libflo_audio::edit::extract_chapter(flo_bytes, index)
+        .map_err(|e| anyhow::anyhow!("Failed to extract track: {}", e))
+}
+
+fn flo_sample_rate(flo_bytes: &[u8]) -> Result<u32> {
+    let header = libflo_audio::core::Header::parse(flo_bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid flo™ file: {}", e))?;
+    Ok(header.sample_rate)
+}
+
+fn secs_to_samples(secs: f64, sample_rate: u32) -> u64 {
+    (secs.max(0.0) * sample_rate as f64).round() as u64
+}
+
 /// Check if a flo™ file has metadata (fast - reads header only)
 pub fn has_metadata(flo_bytes: &[u8]) -> bool {
     libflo_audio::has_metadata(flo_bytes)
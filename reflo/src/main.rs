@@ -47,6 +47,15 @@ enum Commands {
         /// Album metadata
         #[arg(long)]
         album: Option<String>,
+        /// Append a CRC32 to every frame
+        #[arg(long)]
+        frame_crc: bool,
+        /// Loudness series points per second
+        #[arg(long, default_value = "10")]
+        loudness_resolution: u32,
+        /// CUE sheet with the track list (album/audiobook in one file)
+        #[arg(long)]
+        cue: Option<PathBuf>,
     },
     /// Decode flo™ file to WAV
     Decode {
@@ -90,6 +99,81 @@ enum Commands {
         /// Input flo™ file
         input: PathBuf,
     },
+    /// Cut a flo™ file to a time range without re-encoding
+    Trim {
+        /// Input flo™ file
+        input: PathBuf,
+        /// Output flo™ file
+        output: PathBuf,
+        /// Start time in seconds
+        #[arg(long, default_value = "0")]
+        start: f64,
+        /// End time in seconds (default: end of file)
+        #[arg(long)]
+        end: Option<f64>,
+    },
+    /// Split a flo™ file into parts without re-encoding
+    Split {
+        /// Input flo™ file
+        input: PathBuf,
+        /// Split points in seconds (e.g. --at 60,120.5)
+        #[arg(long, required = true, value_delimiter = ',')]
+        at: Vec<f64>,
+        /// Directory for the parts (default: next to the input)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Join flo™ files, re-encoding only around lossy joins
+    Concat {
+        /// Input flo™ files, in order
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+        /// Output flo™ file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// List chapters/tracks, or import them from a CUE sheet
+    Chapters {
+        /// Input flo™ file
+        input: PathBuf,
+        /// CUE sheet to import (replaces the chapter table)
+        #[arg(long)]
+        cue: Option<PathBuf>,
+        /// Output flo™ file when importing (default: update the input)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export the metadata as an ID3 tag, or import one
+    Id3 {
+        /// Input flo™ file
+        input: PathBuf,
+        /// ID3 tag or MP3 file to import
+        #[arg(long)]
+        import: Option<PathBuf>,
+        /// Export ID3v2.3 instead of ID3v2.4
+        #[arg(long)]
+        v23: bool,
+        /// Output file (default: <input>.id3 when exporting, update the input when importing)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export every chapter/track as its own flo™ file
+    Tracks {
+        /// Input flo™ file
+        input: PathBuf,
+        /// Directory for the tracks (default: next to the input)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Write ReplayGain track gain (and album gain) without re-encoding
+    Replaygain {
+        /// Input flo™ files, updated in place
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Also measure the files as one album and write album gain
+        #[arg(long)]
+        album: bool,
+    },
 }
 
 fn main() -> Result<()> {
@@ -107,6 +191,9 @@ fn main() -> Result<()> {
             title,
             artist,
             album,
+            frame_crc,
+            loudness_resolution,
+            cue,
         } => {
             // Both --lossy and --transform enable lossy mode
             let use_lossy = lossy || transform;
@@ -120,6 +207,9 @@ fn main() -> Result<()> {
                 title,
                 artist,
                 album,
+                frame_crc,
+                loudness_resolution,
+                cue,
             })?;
         }
         Commands::Decode { input, output } => {
@@ -145,6 +235,41 @@ fn main() -> Result<()> {
         Commands::Validate { input } => {
             validate(&input)?;
         }
+        Commands::Trim {
+            input,
+            output,
+            start,
+            end,
+        } => {
+            trim(&input, &output, start, end)?;
+        }
+        Commands::Split {
+            input,
+            at,
+            output_dir,
+        } => {
+            split(&input, &at, output_dir)?;
+        }
+        Commands::Concat { inputs, output } => {
+            concat(&inputs, &output)?;
+        }
+        Commands::Chapters { input, cue, output } => {
+            chapters(&input, cue, output)?;
+        }
+        Commands::Id3 {
+            input,
+            import,
+            v23,
+            output,
+        } => {
+            id3(&input, import, v23, output)?;
+        }
+        Commands::Tracks { input, output_dir } => {
+            tracks(&input, output_dir)?;
+        }
+        Commands::Replaygain { inputs, album } => {
+            replaygain(&inputs, album)?;
+        }
     }
 
     Ok(())
@@ -213,6 +338,9 @@ struct EncodeArgs {
     title: Option<String>,
     artist: Option<String>,
     album: Option<String>,
+    frame_crc: bool,
+    loudness_resolution: u32,
+    cue: Option<PathBuf>,
 }
 
 fn encode(args: EncodeArgs) -> Result<()> {
@@ -252,7 +380,10 @@ fn encode(args: EncodeArgs) -> Result<()> {
         EncodeOptions::lossless()
     };
 
-    options = options.with_level(args.level);
+    options = options
+        .with_level(args.level)
+        .with_frame_crc(args.frame_crc)
+        .with_loudness_resolution(args.loudness_resolution);
 
     // Add metadata if provided via CLI
     if args.title.is_some() || args.artist.is_some() || args.album.is_some() {
@@ -270,9 +401,15 @@ fn encode(args: EncodeArgs) -> Result<()> {
     }
 
     // Encode
-    let flo_data =
+    let mut flo_data =
         reflo::encode_from_audio(&audio_bytes, options).context("Failed to encode audio")?;
 
+    if let Some(ref cue) = args.cue {
+        let cue_text = fs::read_to_string(cue).context("Failed to read CUE sheet")?;
+        flo_data = reflo::import_cue_no_reencode(&flo_data, &cue_text)?;
+        println!("  Tracks: {}", reflo::get_chapters(&flo_data)?.len());
+    }
+
     fs::write(&args.output, &flo_data).context("Failed to write output file")?;
 
     let original_size =
@@ -386,9 +523,15 @@ fn info(input: &PathBuf, show_metadata: bool) -> Result<()> {
             if !meta.section_markers.is_empty() {
                 println!("  Sections:    {} markers", meta.section_markers.len());
             }
+            if !meta.chapters.is_empty() {
+                println!("  Chapters:    {}", meta.chapters.len());
+            }
             if meta.waveform_data.is_some() {
                 println!("  Waveform:    pre-computed");
             }
+            if let Some(series) = meta.loudness_series.as_ref() {
+                println!("  Loudness:    {} points/s", series.points_per_second);
+            }
         } else {
             println!("  (no metadata)");
         }
@@ -416,14 +559,15 @@ fn metadata(input: &PathBuf, json: bool) -> Result<()> {
                 println!("{}", json_str);
             } else {
                 // Human-readable format
-                print_metadata_readable(&meta);
+                let info = reflo::get_flo_info(&flo_data)?;
+                print_metadata_readable(&meta, info.sample_rate);
             }
             Ok(())
         }
     }
 }
 
-fn print_metadata_readable(meta: &FloMetadata) {
+fn print_metadata_readable(meta: &FloMetadata, sample_rate: u32) {
     println!("flo™ Metadata");
     println!("═══════════════════════════════════════");
 
@@ -509,6 +653,20 @@ fn print_metadata_readable(meta: &FloMetadata) {
         if let Some(v) = meta.true_peak_dbtp {
             println!("True Peak:       {:.1} dBTP", v);
         }
+        if let Some(v) = meta.track_gain_db {
+            println!("Track Gain:      {:+.2} dB", v);
+        }
+        if let Some(v) = meta.album_gain_db {
+            println!("Album Gain:      {:+.2} dB", v);
+        }
+        if let Some(series) = meta.loudness_series.as_ref() {
+            if let Some(v) = series.max_momentary() {
+                println!("Max Momentary:   {:.1} LUFS", v);
+            }
+            if let Some(v) = series.max_short_term() {
+                println!("Max Short-term:  {:.1} LUFS", v);
+            }
+        }
     }
 
     // Complex fields
@@ -550,6 +708,12 @@ fn print_metadata_readable(meta: &FloMetadata) {
         }
     }
 
+    if !meta.chapters.is_empty() {
+        println!();
+        println!("Chapters ({}):", meta.chapters.len());
+        print_chapters(&meta.chapters, sample_rate);
+    }
+
     if !meta.bpm_map.is_empty() {
         println!();
         println!("BPM Map ({} changes):", meta.bpm_map.len());
@@ -811,6 +975,204 @@ fn validate(input: &PathBuf) -> Result<()> {
         println!("✓ {} is a valid flo™ file", input.display());
         Ok(())
     } else {
+        let damaged = reflo::find_damaged_frames(&flo_data).unwrap_or_default();
+        if !damaged.is_empty() {
+            println!("  Damaged frames: {:?}", damaged);
+        }
         bail!("✗ {} is not a valid flo™ file", input.display())
     }
 }
+
+fn trim(input: &PathBuf, output: &PathBuf, start: f64, end: Option<f64>) -> Result<()> {
+    let flo_data = fs::read(input).context("Failed to read flo™ file")?;
+
+    let trimmed = reflo::trim_flo(&flo_data, start, end)?;
+    let info = reflo::get_flo_info(&trimmed)?;
+
+    fs::write(output, &trimmed).context("Failed to write output file")?;
+    println!(
+        "✓ Trimmed to {:.2}s -> {}",
+        info.duration_secs,
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn split(input: &PathBuf, at: &[f64], output_dir: Option<PathBuf>) -> Result<()> {
+    let flo_data = fs::read(input).context("Failed to read flo™ file")?;
+
+    let parts = reflo::split_flo(&flo_data, at)?;
+
+    let dir = output_dir
+        .or_else(|| input.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default();
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "part".to_string());
+
+    for (i, part) in parts.iter().enumerate() {
+        let path = dir.join(format!("{}_{:02}.flo", stem, i + 1));
+        fs::write(&path, part).context("Failed to write output file")?;
+        let info = reflo::get_flo_info(part)?;
+        println!("✓ {:.2}s -> {}", info.duration_secs, path.display());
+    }
+
+    Ok(())
+}
+
+fn concat(inputs: &[PathBuf], output: &PathBuf) -> Result<()> {
+    let files = inputs
+        .iter()
+        .map(|path| fs::read(path).with_context(|| format!("Failed to read {}", path.display())))
+        .collect::<Result<Vec<_>>>()?;
+    let parts: Vec<&[u8]> = files.iter().map(|f| f.as_slice()).collect();
+
+    let joined = reflo::concat_flo(&parts)?;
+    let info = reflo::get_flo_info(&joined)?;
+
+    fs::write(output, &joined).context("Failed to write output file")?;
+    println!(
+        "✓ Joined {} files ({:.2}s) -> {}",
+        inputs.len(),
+        info.duration_secs,
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn chapters(input: &PathBuf, cue: Option<PathBuf>, output: Option<PathBuf>) -> Result<()> {
+    let flo_data = fs::read(input).context("Failed to read flo™ file")?;
+
+    let Some(cue) = cue else {
+        let chapters = reflo::get_chapters(&flo_data)?;
+        if chapters.is_empty() {
+            println!("No chapters");
+        } else {
+            let info = reflo::get_flo_info(&flo_data)?;
+            print_chapters(&chapters, info.sample_rate);
+        }
+        return Ok(());
+    };
+
+    let cue_text = fs::read_to_string(&cue).context("Failed to read CUE sheet")?;
+    let updated = reflo::import_cue_no_reencode(&flo_data, &cue_text)?;
+    let count = reflo::get_chapters(&updated)?.len();
+
+    let output = output.unwrap_or_else(|| input.clone());
+    fs::write(&output, &updated).context("Failed to write output file")?;
+    println!("✓ Imported {} tracks -> {}", count, output.display());
+
+    Ok(())
+}
+
+fn id3(input: &PathBuf, import: Option<PathBuf>, v23: bool, output: Option<PathBuf>) -> Result<()> {
+    let flo_data = fs::read(input).context("Failed to read flo™ file")?;
+
+    let Some(import) = import else {
+        let version = if v23 {
+            reflo::Id3Version::V23
+        } else {
+            reflo::Id3Version::V24
+        };
+        let tag = reflo::export_id3(&flo_data, version)?;
+        let output = output.unwrap_or_else(|| input.with_extension("id3"));
+        fs::write(&output, &tag).context("Failed to write ID3 tag")?;
+        println!("✓ Exported {} bytes -> {}", tag.len(), output.display());
+        return Ok(());
+    };
+
+    let id3_data = fs::read(&import).context("Failed to read ID3 tag")?;
+    let updated = reflo::import_id3_no_reencode(&flo_data, &id3_data)?;
+
+    let output = output.unwrap_or_else(|| input.clone());
+    fs::write(&output, &updated).context("Failed to write output file")?;
+    println!("✓ Imported {} -> {}", import.display(), output.display());
+
+    Ok(())
+}
+
+fn replaygain(inputs: &[PathBuf], album: bool) -> Result<()> {
+    let files = inputs
+        .iter()
+        .map(|path| fs::read(path).with_context(|| format!("Failed to read {}", path.display())))
+        .collect::<Result<Vec<_>>>()?;
+
+    let updated = if album {
+        let parts: Vec<&[u8]> = files.iter().map(|f| f.as_slice()).collect();
+        reflo::apply_album_gain(&parts)?
+    } else {
+        files
+            .iter()
+            .map(|data| reflo::apply_track_gain(data))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    for (path, data) in inputs.iter().zip(&updated) {
+        fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+        let meta = reflo::get_metadata(data)?.unwrap_or_default();
+        let gain = meta.track_gain_db.unwrap_or_default();
+        println!("✓ {}  track {:+.2} dB", path.display(), gain);
+    }
+    if album {
+        let meta = reflo::get_metadata(&updated[0])?.unwrap_or_default();
+        println!("  album {:+.2} dB", meta.album_gain_db.unwrap_or_default());
+    }
+
+    Ok(())
+}
+
+fn tracks(input: &PathBuf, output_dir: Option<PathBuf>) -> Result<()> {
+    let flo_data = fs::read(input).context("Failed to read flo™ file")?;
+
+    let chapters = reflo::get_chapters(&flo_data)?;
+    if chapters.is_empty() {
+        bail!("{} has no chapters", input.display());
+    }
+
+    let dir = output_dir
+        .or_else(|| input.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default();
+
+    for (i, chapter) in chapters.iter().enumerate() {
+        let number = chapter.track_number.unwrap_or(i as u32 + 1);
+        let name = match chapter.title {
+            Some(ref title) => format!("{:02} - {}.flo", number, safe_file_name(title)),
+            None => format!("{:02}.flo", number),
+        };
+        let path = dir.join(name);
+
+        let track = reflo::extract_track(&flo_data, i)?;
+        fs::write(&path, &track).context("Failed to write output file")?;
+        let info = reflo::get_flo_info(&track)?;
+        println!("✓ {:.2}s -> {}", info.duration_secs, path.display());
+    }
+
+    Ok(())
+}
+
+fn print_chapters(chapters: &[reflo::Chapter], sample_rate: u32) {
+    for (i, chapter) in chapters.iter().enumerate() {
+        let ms = chapter.start_sample * 1000 / sample_rate.max(1) as u64;
+        let number = chapter.track_number.unwrap_or(i as u32 + 1);
+        let title = chapter.title.as_deref().unwrap_or("");
+        match chapter.artist {
+            Some(ref artist) => {
+                println!("  {:02} {} {} - {}", number, format_time(ms), artist, title)
+            }
+            None => println!("  {:02} {} {}", number, format_time(ms), title),
+        }
+    }
+}
+
+/// replace characters that aren't allowed in file names
+fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
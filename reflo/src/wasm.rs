@@ -124,6 +124,26 @@ pub fn strip_flo_metadata(flo_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
     crate::strip_metadata_no_reencode(flo_bytes).map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+/// Set the fields of an ID3 tag (or an MP3 file) on a flo™ file
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn import_flo_id3(flo_bytes: &[u8], id3_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    crate::import_id3_no_reencode(flo_bytes, id3_bytes)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Export the metadata of a flo™ file as an ID3v2.4 tag (v2.3 if `v23`)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn export_flo_id3(flo_bytes: &[u8], v23: bool) -> Result<Vec<u8>, JsValue> {
+    let version = if v23 {
+        crate::Id3Version::V23
+    } else {
+        crate::Id3Version::V24
+    };
+    crate::export_id3(flo_bytes, version).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
 /// Check if a flo™ file has metadata
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
@@ -145,6 +165,21 @@ pub fn compute_loudness_metrics_reflo(
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
+/// Compute momentary and short-term loudness over time
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn compute_loudness_series_reflo(
+    samples: &[f32],
+    channels: u8,
+    sample_rate: u32,
+    points_per_second: u32,
+) -> Result<JsValue, JsValue> {
+    use libflo_audio::core::ebu_r128::compute_loudness_series;
+    let series = compute_loudness_series(samples, channels, sample_rate, points_per_second);
+    serde_wasm_bindgen::to_value(&series)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
 /// Extract spectral fingerprint from audio samples
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
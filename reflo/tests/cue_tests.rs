@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use reflo::audio::AudioMetadata;
+    use reflo::cue::CueSheet;
+    use reflo::{
+        decode_to_samples, encode_from_samples, extract_track, get_chapters, get_metadata,
+        import_cue_no_reencode, EncodeOptions,
+    };
+
+    const CUE: &str = r#"REM GENRE "Ambient"
+REM DATE 1999
+PERFORMER "The Band"
+TITLE "The Album"
+FILE "The Album.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opening"
+    ISRC USXXX9900001
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Middle"
+    PERFORMER "The Band feat. Guest"
+    REM COMPOSER "Someone"
+    INDEX 00 00:00:60
+    INDEX 01 00:01:00
+  TRACK 03 AUDIO
+    TITLE "Closing"
+    INDEX 01 00:02:37
+"#;
+
+    fn encode_album(lossy: bool) -> Vec<u8> {
+        let sample_rate = 44100;
+        let samples: Vec<f32> = (0..sample_rate * 3)
+            .map(|i| (i as f32 * 0.03).sin() * 0.5)
+            .collect();
+        let options = if lossy {
+            EncodeOptions::lossy(0.6)
+        } else {
+            EncodeOptions::lossless()
+        };
+        encode_from_samples(&samples, sample_rate, 1, AudioMetadata::default(), options).unwrap()
+    }
+
+    #[test]
+    fn test_parse_cue() {
+        let sheet = CueSheet::parse(CUE).unwrap();
+
+        assert_eq!(sheet.title.as_deref(), Some("The Album"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.file.as_deref(), Some("The Album.wav"));
+        assert_eq!(sheet.tracks.len(), 3);
+
+        // INDEX 00 is the pregap, the track starts at INDEX 01
+        assert_eq!(sheet.tracks[1].start_frames, 75);
+        assert_eq!(sheet.tracks[2].start_frames, 2 * 75 + 37);
+
+        let chapters = sheet.to_chapters(44100);
+        let starts: Vec<u64> = chapters.iter().map(|c| c.start_sample).collect();
+        assert_eq!(starts, vec![0, 44100, 88200 + 37 * 588]);
+        assert_eq!(chapters[0].isrc.as_deref(), Some("USXXX9900001"));
+        assert_eq!(chapters[1].artist.as_deref(), Some("The Band feat. Guest"));
+        assert_eq!(chapters[1].user_text[0].description, "COMPOSER");
+        assert_eq!(chapters[2].track_number, Some(3));
+    }
+
+    #[test]
+    fn test_parse_cue_errors() {
+        assert!(CueSheet::parse("TITLE \"x\"\n").is_err());
+        assert!(CueSheet::parse("TRACK 01 AUDIO\n  TITLE \"x\"\n").is_err());
+        assert!(CueSheet::parse("TRACK 01 AUDIO\n  INDEX 01 00:61:00\n").is_err());
+        assert!(CueSheet::parse(
+            "TRACK 01 AUDIO\n INDEX 01 00:05:00\nTRACK 02 AUDIO\n INDEX 01 00:01:00\n"
+        )
+        .is_err());
+        assert!(CueSheet::parse("FILE \"a.wav\" WAVE\nFILE \"b.wav\" WAVE\n").is_err());
+    }
+
+    #[test]
+    fn test_import_cue_and_export_tracks() {
+        let flo_bytes = encode_album(false);
+        let with_cue = import_cue_no_reencode(&flo_bytes, CUE).unwrap();
+
+        let meta = get_metadata(&with_cue).unwrap().unwrap();
+        assert_eq!(meta.album.as_deref(), Some("The Album"));
+        assert_eq!(meta.artist.as_deref(), Some("The Band"));
+        assert_eq!(meta.genre.as_deref(), Some("Ambient"));
+        assert_eq!(meta.year, Some(1999));
+        assert_eq!(meta.track_total, Some(3));
+        assert_eq!(get_chapters(&with_cue).unwrap().len(), 3);
+
+        // audio is untouched
+        let (original, _, _) = decode_to_samples(&flo_bytes).unwrap();
+        let (imported, _, _) = decode_to_samples(&with_cue).unwrap();
+        assert_eq!(original, imported);
+
+        let track = extract_track(&with_cue, 1).unwrap();
+        let (samples, _, _) = decode_to_samples(&track).unwrap();
+        assert_eq!(samples, original[44100..88200 + 37 * 588]);
+
+        let meta = get_metadata(&track).unwrap().unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Middle"));
+        assert_eq!(meta.artist.as_deref(), Some("The Band feat. Guest"));
+        assert_eq!(meta.track_number, Some(2));
+        assert!(meta.chapters.is_empty());
+    }
+
+    #[test]
+    fn test_export_lossy_tracks() {
+        let with_cue = import_cue_no_reencode(&encode_album(true), CUE).unwrap();
+
+        // lossy tracks snap to the frame hop, so allow one hop either way
+        for (i, expected) in [44100usize, 44100 + 37 * 588, 44100 - 37 * 588]
+            .iter()
+            .enumerate()
+        {
+            let (samples, _, _) = decode_to_samples(&extract_track(&with_cue, i).unwrap()).unwrap();
+            let diff = samples.len().abs_diff(*expected);
+            assert!(diff <= 2 * 1024, "track {}: {} samples", i, samples.len());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use reflo::audio::{write_wav_to_bytes, AudioMetadata};
+    use reflo::{
+        encode_from_audio, encode_from_samples, export_id3, get_metadata, import_id3_no_reencode,
+        EncodeOptions, FloMetadata, Id3Version,
+    };
+
+    fn samples() -> Vec<f32> {
+        (0..22050).map(|i| (i as f32 * 0.03).sin() * 0.5).collect()
+    }
+
+    fn tagged() -> FloMetadata {
+        let mut meta = FloMetadata::with_basic(
+            Some("Tagged".to_string()),
+            Some("Someone".to_string()),
+            Some("Album".to_string()),
+        );
+        meta.mood = Some("Calm".to_string());
+        meta.track_number = Some(4);
+        meta.track_total = Some(9);
+        meta.add_lyrics("la la la", Some("eng"));
+        meta
+    }
+
+    #[test]
+    fn test_encode_keeps_id3_tag() {
+        // audio behind an ID3 tag, as in an MP3
+        let mut audio = tagged().to_id3(Id3Version::V23).unwrap();
+        audio.extend(write_wav_to_bytes(&samples(), 22050, 1).unwrap());
+
+        let flo_bytes = encode_from_audio(&audio, EncodeOptions::lossless()).unwrap();
+        let meta = get_metadata(&flo_bytes).unwrap().unwrap();
+
+        assert_eq!(meta.title.as_deref(), Some("Tagged"));
+        // fields symphonia doesn't map
+        assert_eq!(meta.mood.as_deref(), Some("Calm"));
+        assert_eq!(meta.track_total, Some(9));
+        assert_eq!(meta.lyrics[0].text, "la la la");
+        // encoding info is still filled in
+        assert!(meta.flo_encoder_version.is_some());
+        assert_eq!(meta.length_ms, Some(1000));
+    }
+
+    #[test]
+    fn test_id3_sidecar_round_trip() {
+        let flo_bytes = encode_from_samples(
+            &samples(),
+            22050,
+            1,
+            AudioMetadata::default(),
+            EncodeOptions::lossless(),
+        )
+        .unwrap();
+
+        let tag = tagged().to_id3(Id3Version::V24).unwrap();
+        let updated = import_id3_no_reencode(&flo_bytes, &tag).unwrap();
+        let meta = get_metadata(&updated).unwrap().unwrap();
+        assert_eq!(meta.artist.as_deref(), Some("Someone"));
+        // the rest of the metadata stays
+        assert!(meta.encoding_time.is_some());
+        assert!(meta.encoder_settings.is_some());
+
+        for version in [Id3Version::V23, Id3Version::V24] {
+            let exported = export_id3(&updated, version).unwrap();
+            let back = FloMetadata::from_id3(&exported).unwrap();
+            assert_eq!(back.title.as_deref(), Some("Tagged"));
+            assert_eq!(back.mood.as_deref(), Some("Calm"));
+            assert_eq!(back.encoder_settings, meta.encoder_settings);
+        }
+
+        assert!(import_id3_no_reencode(&flo_bytes, b"not a tag").is_err());
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use reflo::audio::AudioMetadata;
+    use reflo::{
+        apply_album_gain, apply_track_gain, decode_to_samples, encode_from_samples, get_metadata,
+        EncodeOptions,
+    };
+
+    fn encode(amp: f32) -> Vec<u8> {
+        let samples: Vec<f32> = (0..44100).map(|i| (i as f32 * 0.05).sin() * amp).collect();
+        encode_from_samples(
+            &samples,
+            22050,
+            2,
+            AudioMetadata::default(),
+            EncodeOptions::lossless(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_encode_sets_track_gain() {
+        let meta = get_metadata(&encode(0.5)).unwrap().unwrap();
+        let lufs = meta.integrated_loudness_lufs.unwrap();
+        assert_eq!(meta.track_gain_db, Some(-18.0 - lufs));
+        assert!(meta.true_peak_dbtp.unwrap() < 0.0);
+        assert_eq!(meta.album_gain_db, None);
+    }
+
+    #[test]
+    fn test_encode_sets_loudness_series() {
+        let meta = get_metadata(&encode(0.5)).unwrap().unwrap();
+        let series = meta.loudness_series.unwrap();
+        assert_eq!(series.points_per_second, 10);
+        assert_eq!(series.momentary.len(), 10);
+        assert_eq!(series.short_term.len(), 10);
+
+        let samples: Vec<f32> = (0..44100).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        let data = encode_from_samples(
+            &samples,
+            22050,
+            2,
+            AudioMetadata::default(),
+            EncodeOptions::lossless().with_loudness_resolution(25),
+        )
+        .unwrap();
+        let series = get_metadata(&data)
+            .unwrap()
+            .unwrap()
+            .loudness_series
+            .unwrap();
+        assert_eq!(series.points_per_second, 25);
+        assert_eq!(series.momentary.len(), 25);
+    }
+
+    #[test]
+    fn test_album_gain() {
+        let loud = encode(0.5);
+        let quiet = encode(0.25);
+
+        let updated = apply_album_gain(&[&loud, &quiet]).unwrap();
+        let loud_meta = get_metadata(&updated[0]).unwrap().unwrap();
+        let quiet_meta = get_metadata(&updated[1]).unwrap().unwrap();
+
+        let album_gain = loud_meta.album_gain_db.unwrap();
+        assert_eq!(quiet_meta.album_gain_db, Some(album_gain));
+        assert!(album_gain > loud_meta.track_gain_db.unwrap());
+        assert!(album_gain < quiet_meta.track_gain_db.unwrap());
+        assert!(loud_meta.encoder_settings.is_some());
+        assert_eq!(
+            decode_to_samples(&updated[1]).unwrap(),
+            decode_to_samples(&quiet).unwrap()
+        );
+
+        let track = apply_track_gain(&loud).unwrap();
+        let track_meta = get_metadata(&track).unwrap().unwrap();
+        assert_eq!(track_meta.track_gain_db, loud_meta.track_gain_db);
+        assert_eq!(track_meta.album_gain_db, None);
+    }
+}
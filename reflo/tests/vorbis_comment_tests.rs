@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use libflo_audio::{Picture, PictureType, UserText};
+    use reflo::{encode_from_audio, get_metadata, EncodeOptions, FloMetadata};
+
+    fn crc8(data: &[u8]) -> u8 {
+        data.iter().fold(0u8, |crc, &byte| {
+            (0..8).fold(crc ^ byte, |crc, _| {
+                if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x07
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    fn crc16(data: &[u8]) -> u16 {
+        data.iter().fold(0u16, |crc, &byte| {
+            (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+                if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x8005
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    fn block(kind: u8, last: bool, body: &[u8]) -> Vec<u8> {
+        let mut block = vec![kind | if last { 0x80 } else { 0 }];
+        block.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        block.extend_from_slice(body);
+        block
+    }
+
+    /// a mono 16 bit FLAC file, one verbatim frame of 256 samples
+    fn flac(comments: &[u8], picture: &[u8]) -> Vec<u8> {
+        let samples: Vec<i16> = (0..256)
+            .map(|i| ((i as f32 * 0.1).sin() * 8000.0) as i16)
+            .collect();
+
+        let mut info = Vec::new();
+        info.extend_from_slice(&256u16.to_be_bytes());
+        info.extend_from_slice(&256u16.to_be_bytes());
+        info.extend_from_slice(&[0; 6]);
+        let packed = (44100u64 << 44) | (15 << 36) | samples.len() as u64;
+        info.extend_from_slice(&packed.to_be_bytes());
+        info.extend_from_slice(&[0; 16]);
+
+        let mut frame = vec![0xFF, 0xF8, 0x60, 0x08, 0x00, 0xFF];
+        frame.push(crc8(&frame));
+        frame.push(0x02);
+        frame.extend(samples.iter().flat_map(|s| s.to_be_bytes()));
+        frame.extend_from_slice(&crc16(&frame).to_be_bytes());
+
+        let mut file = b"fLaC".to_vec();
+        file.extend(block(0, false, &info));
+        file.extend(block(4, false, comments));
+        file.extend(block(6, true, picture));
+        file.extend(frame);
+        file
+    }
+
+    #[test]
+    fn test_encode_keeps_vorbis_comments() {
+        let mut tags = FloMetadata::with_basic(
+            Some("Flac Song".to_string()),
+            Some("Someone".to_string()),
+            None,
+        );
+        tags.mood = Some("Calm".to_string());
+        tags.track_total = Some(9);
+        tags.user_text.push(UserText {
+            description: "MusicBrainz Album Id".to_string(),
+            value: "album-id".to_string(),
+        });
+        let picture = Picture {
+            mime_type: "image/png".to_string(),
+            picture_type: PictureType::CoverBack,
+            description: Some("back".to_string()),
+            data: vec![1, 2, 3, 4],
+        };
+
+        let file = flac(
+            &tags.to_vorbis_comment_block("reference libFLAC 1.4.3"),
+            &picture.to_flac_picture(),
+        );
+        let flo_bytes = encode_from_audio(&file, EncodeOptions::lossless()).unwrap();
+        let meta = get_metadata(&flo_bytes).unwrap().unwrap();
+
+        assert_eq!(meta.title.as_deref(), Some("Flac Song"));
+        assert_eq!(meta.artist.as_deref(), Some("Someone"));
+        // fields symphonia doesn't map
+        assert_eq!(meta.mood.as_deref(), Some("Calm"));
+        assert_eq!(meta.track_total, Some(9));
+        assert_eq!(meta.user_text.len(), 1);
+        assert_eq!(meta.user_text[0].description, "MusicBrainz Album Id");
+        assert_eq!(meta.user_text[0].value, "album-id");
+        // not only front covers
+        assert_eq!(meta.pictures.len(), 1);
+        assert_eq!(meta.pictures[0].picture_type, PictureType::CoverBack);
+        assert_eq!(meta.pictures[0].description.as_deref(), Some("back"));
+        assert_eq!(meta.pictures[0].data, [1, 2, 3, 4]);
+        assert_eq!(meta.source_format.as_deref(), Some("FLAC"));
+    }
+}